@@ -6,7 +6,8 @@
 //!    `%APPDATA%\mboxshell\config.toml` (Windows)
 //! 3. Built-in defaults
 
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -24,6 +25,9 @@ pub struct Config {
     pub export: ExportConfig,
     /// Performance tuning.
     pub performance: PerformanceConfig,
+    /// Saved searches: name → query, run as `mboxshell search FILE @name`
+    /// and listed in the TUI sidebar.
+    pub searches: BTreeMap<String, String>,
 }
 
 /// General behavior settings.
@@ -40,6 +44,9 @@ pub struct GeneralConfig {
     pub cache_dir: Option<PathBuf>,
     /// Log level: "error", "warn", "info", "debug", "trace".
     pub log_level: String,
+    /// Timezone for relative search dates (`newer:7d`, `date:today`):
+    /// "local", "utc" or an offset such as "+02:00".
+    pub timezone: String,
 }
 
 /// Display and layout settings.
@@ -104,6 +111,7 @@ impl Default for GeneralConfig {
             date_format: "%Y-%m-%d %H:%M".to_string(),
             cache_dir: None,
             log_level: "warn".to_string(),
+            timezone: "local".to_string(),
         }
     }
 }
@@ -200,6 +208,40 @@ pub fn save_config(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Save `query` under `name` in the `[searches]` table of the config file at
+/// `path`, or remove the saved search when `query` is `None`.
+///
+/// Only that table is touched: the other settings in the file are kept as
+/// they are rather than rewritten with their defaults.
+pub fn store_saved_search(path: &Path, name: &str, query: Option<&str>) -> anyhow::Result<()> {
+    let mut table = if path.exists() {
+        std::fs::read_to_string(path)?.parse::<toml::Table>()?
+    } else {
+        toml::Table::new()
+    };
+
+    let searches = table
+        .entry("searches")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("`searches` in {} is not a table", path.display()))?;
+    match query {
+        Some(query) => {
+            searches.insert(name.to_string(), toml::Value::String(query.to_string()));
+        }
+        None => {
+            searches.remove(name);
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, toml::to_string_pretty(&table)?)?;
+    tracing::info!(path = %path.display(), name, "Saved search stored");
+    Ok(())
+}
+
 /// Determine the config file path (checking env var first, then standard dirs).
 pub fn config_file_path() -> Option<PathBuf> {
     // 1. Environment variable override
@@ -238,6 +280,7 @@ mod tests {
         assert_eq!(cfg.display.theme, "dark");
         assert_eq!(cfg.performance.lru_cache_size, 50);
         assert_eq!(cfg.export.csv_separator, ',');
+        assert_eq!(cfg.general.timezone, "local");
     }
 
     #[test]
@@ -278,4 +321,20 @@ theme = "light"
         // On CI it might be None, so we just check it doesn't panic
         let _ = path;
     }
+
+    #[test]
+    fn test_store_saved_search_keeps_other_settings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "[display]\ntheme = \"light\"\n").unwrap();
+
+        store_saved_search(&path, "boss", Some("from:boss has:attachment")).unwrap();
+        store_saved_search(&path, "old", Some("before:2020-01-01")).unwrap();
+        store_saved_search(&path, "old", None).unwrap();
+
+        let cfg: Config = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(cfg.display.theme, "light");
+        assert_eq!(cfg.searches.len(), 1);
+        assert_eq!(cfg.searches["boss"], "from:boss has:attachment");
+    }
 }
@@ -52,6 +52,10 @@ pub enum MboxError {
     /// An invalid path was provided.
     #[error("Invalid path: {0}")]
     InvalidPath(String),
+
+    /// A search query that cannot be run as typed.
+    #[error("Invalid search query: {0}")]
+    InvalidQuery(String),
 }
 
 /// Convenience alias for `Result<T, MboxError>`.
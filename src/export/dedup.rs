@@ -0,0 +1,356 @@
+//! Find duplicate messages, by `Message-ID` or by what they contain.
+//!
+//! Matching on `Message-ID` misses the copies that matter most in a merged
+//! archive: messages without one, and messages sent again under a new one.
+//! The content strategies hash the message instead:
+//!
+//! - **content** — sender, To and Cc addresses, subject and date, plus the
+//!   body. Catches copies whose `Message-ID`, `Received:` trail or MIME
+//!   boundaries differ.
+//! - **body** — the body alone. Also catches a message re-sent on another
+//!   day or to other people; it is the loosest, and the one to check with a
+//!   dry run first.
+//!
+//! The body is the decoded text (the HTML when there is no text part) with
+//! line endings and trailing whitespace normalized, plus the name, type and
+//! size of every attachment.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+use crate::model::mail::MailEntry;
+use crate::store::reader::MboxStore;
+
+/// How two messages are recognized as the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupStrategy {
+    /// Same `Message-ID`. Messages without one are never duplicates.
+    #[default]
+    MessageId,
+    /// Same sender, recipients, subject, date and body.
+    Content,
+    /// Same body. Messages with an empty body and no attachments are never
+    /// duplicates.
+    Body,
+}
+
+impl DedupStrategy {
+    /// Every strategy, in the order they are listed to the user.
+    pub const ALL: [Self; 3] = [Self::MessageId, Self::Content, Self::Body];
+
+    /// The strategy's name, as `--dedup-by` takes it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MessageId => "message-id",
+            Self::Content => "content",
+            Self::Body => "body",
+        }
+    }
+}
+
+impl fmt::Display for DedupStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DedupStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|d| d.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|d| d.name()).collect();
+                format!(
+                    "unknown dedup strategy '{s}' (expected {})",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Remembers the messages seen so far and tells when one repeats an earlier
+/// one. Messages are named by an id of the caller's choosing — an index into
+/// the entries, or a running count across several mailboxes.
+#[derive(Debug, Default)]
+pub struct DuplicateFinder {
+    strategy: DedupStrategy,
+    first: HashMap<[u8; 32], usize>,
+}
+
+impl DuplicateFinder {
+    pub fn new(strategy: DedupStrategy) -> Self {
+        Self {
+            strategy,
+            first: HashMap::new(),
+        }
+    }
+
+    /// Record message `id` and return the id of the first message recorded
+    /// that it duplicates, if any.
+    pub fn check(
+        &mut self,
+        store: &mut MboxStore,
+        entry: &MailEntry,
+        id: usize,
+    ) -> anyhow::Result<Option<usize>> {
+        let Some(key) = message_key(store, entry, self.strategy)? else {
+            return Ok(None);
+        };
+        match self.first.get(&key) {
+            Some(&first) => Ok(Some(first)),
+            None => {
+                self.first.insert(key, id);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// A message and the later messages that duplicate it, as indices into the
+/// entries searched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCluster {
+    /// The first occurrence: the copy that is kept.
+    pub kept: usize,
+    /// The copies after it, in mailbox order.
+    pub duplicates: Vec<usize>,
+}
+
+/// The duplicates among `entries`, one cluster per message that has any, in
+/// the order of their first occurrence.
+///
+/// The progress callback receives `(current, total)` messages checked.
+pub fn find_duplicates(
+    store: &mut MboxStore,
+    entries: &[MailEntry],
+    strategy: DedupStrategy,
+    progress: &dyn Fn(usize, usize),
+) -> anyhow::Result<Vec<DuplicateCluster>> {
+    let mut finder = DuplicateFinder::new(strategy);
+    let mut clusters: Vec<DuplicateCluster> = Vec::new();
+    let mut cluster_of: HashMap<usize, usize> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        progress(i, entries.len());
+        let Some(first) = finder.check(store, entry, i)? else {
+            continue;
+        };
+        let cluster = *cluster_of.entry(first).or_insert_with(|| {
+            clusters.push(DuplicateCluster {
+                kept: first,
+                duplicates: Vec::new(),
+            });
+            clusters.len() - 1
+        });
+        clusters[cluster].duplicates.push(i);
+    }
+    progress(entries.len(), entries.len());
+    clusters.sort_by_key(|c| c.kept);
+    Ok(clusters)
+}
+
+/// `entries` without the duplicates of `clusters`.
+pub fn without_duplicates<'a>(
+    entries: &'a [MailEntry],
+    clusters: &[DuplicateCluster],
+) -> Vec<&'a MailEntry> {
+    let dropped: HashSet<usize> = clusters
+        .iter()
+        .flat_map(|c| c.duplicates.iter().copied())
+        .collect();
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !dropped.contains(i))
+        .map(|(_, e)| e)
+        .collect()
+}
+
+/// What `strategy` compares of `entry`, hashed; `None` when the message can
+/// match no other.
+pub(crate) fn message_key(
+    store: &mut MboxStore,
+    entry: &MailEntry,
+    strategy: DedupStrategy,
+) -> anyhow::Result<Option<[u8; 32]>> {
+    let mut hasher = Sha256::new();
+    match strategy {
+        DedupStrategy::MessageId => {
+            let id = entry.message_id.trim();
+            if id.is_empty() {
+                return Ok(None);
+            }
+            hasher.update(id.as_bytes());
+        }
+        DedupStrategy::Content => {
+            hash_headers(&mut hasher, entry);
+            hash_body(&mut hasher, store, entry)?;
+        }
+        DedupStrategy::Body => {
+            if !hash_body(&mut hasher, store, entry)? {
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(hasher.finalize().into()))
+}
+
+/// Hash the sender, To and Cc addresses, subject and date of `entry`.
+/// Bcc is left out: only the sender's copy of a message has it.
+fn hash_headers(hasher: &mut Sha256, entry: &MailEntry) {
+    let mut recipients: Vec<String> = entry
+        .to
+        .iter()
+        .chain(&entry.cc)
+        .map(|a| a.address.to_lowercase())
+        .collect();
+    recipients.sort();
+    recipients.dedup();
+
+    hasher.update(entry.from.address.to_lowercase().as_bytes());
+    hasher.update(b"\0");
+    hasher.update(recipients.join(",").as_bytes());
+    hasher.update(b"\0");
+    let subject: Vec<&str> = entry.subject.split_whitespace().collect();
+    hasher.update(subject.join(" ").as_bytes());
+    hasher.update(b"\0");
+    hasher.update(entry.date.timestamp().to_le_bytes());
+    hasher.update(b"\0");
+}
+
+/// Hash the normalized body and the attachments of `entry`, and return
+/// whether there was anything to hash.
+fn hash_body(
+    hasher: &mut Sha256,
+    store: &mut MboxStore,
+    entry: &MailEntry,
+) -> anyhow::Result<bool> {
+    let body = store.get_message(entry)?;
+    let text = body.text.as_deref().or(body.html.as_deref()).unwrap_or("");
+    let mut lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    let text = lines.join("\n");
+    hasher.update(text.trim_start_matches('\n').as_bytes());
+    for attachment in &entry.attachments {
+        hasher.update(b"\0");
+        hasher.update(attachment.filename.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(attachment.content_type.as_bytes());
+        hasher.update(attachment.size.to_le_bytes());
+    }
+    Ok(!text.trim().is_empty() || !entry.attachments.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::builder;
+    use std::path::Path;
+
+    /// A message from a@x to b@x.
+    fn message(id: Option<&str>, date: &str, subject: &str, body: &str) -> String {
+        let id = id
+            .map(|id| format!("Message-ID: {id}\n"))
+            .unwrap_or_default();
+        format!(
+            "From a@x Thu Jan 01 00:00:00 2024\nFrom: a@x\nTo: b@x\n{id}\
+             Date: {date}\nSubject: {subject}\n\n{body}\n"
+        )
+    }
+
+    fn clusters(path: &Path, strategy: DedupStrategy) -> Vec<DuplicateCluster> {
+        let entries = builder::build_index(path, false, None).unwrap();
+        let mut store = MboxStore::open(path).unwrap();
+        find_duplicates(&mut store, &entries, strategy, &|_, _| {}).unwrap()
+    }
+
+    fn cluster(kept: usize, duplicates: &[usize]) -> DuplicateCluster {
+        DuplicateCluster {
+            kept,
+            duplicates: duplicates.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_strategies_find_different_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("mail.mbox");
+        let monday = "Mon, 01 Jan 2024 10:00:00 +0000";
+        let tuesday = "Tue, 02 Jan 2024 10:00:00 +0000";
+        let mailbox = [
+            message(Some("<1@x>"), monday, "Hello", "Same body"),
+            // A copy under a new Message-ID, with trailing whitespace.
+            message(Some("<2@x>"), monday, "Hello", "Same body  \n\n"),
+            // A different message reusing the first one's Message-ID.
+            message(Some("<1@x>"), monday, "Hello", "Other body"),
+            // The same body sent again the next day, without a Message-ID.
+            message(None, tuesday, "Hello again", "Same body"),
+            // Empty bodies never match by body.
+            message(None, monday, "Empty", ""),
+            message(None, tuesday, "Empty too", ""),
+        ];
+        std::fs::write(&src, mailbox.join("\n")).unwrap();
+
+        assert_eq!(
+            clusters(&src, DedupStrategy::MessageId),
+            vec![cluster(0, &[2])]
+        );
+        assert_eq!(
+            clusters(&src, DedupStrategy::Content),
+            vec![cluster(0, &[1])]
+        );
+        assert_eq!(
+            clusters(&src, DedupStrategy::Body),
+            vec![cluster(0, &[1, 3])]
+        );
+
+        let entries = builder::build_index(&src, false, None).unwrap();
+        let kept = without_duplicates(&entries, &[cluster(0, &[1, 3])]);
+        let subjects: Vec<&str> = kept.iter().map(|e| e.subject.as_str()).collect();
+        assert_eq!(subjects, ["Hello", "Hello", "Empty", "Empty too"]);
+    }
+
+    #[test]
+    fn test_merge_dedups_by_content_across_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let date = "Mon, 01 Jan 2024 10:00:00 +0000";
+        let a = dir.path().join("a.mbox");
+        let b = dir.path().join("b.mbox");
+        std::fs::write(&a, message(Some("<1@x>"), date, "Hi", "body")).unwrap();
+        std::fs::write(
+            &b,
+            message(Some("<relay-1@y>"), date, "Hi", "body")
+                + "\n"
+                + &message(None, date, "Hi", "more"),
+        )
+        .unwrap();
+
+        let out = dir.path().join("merged.mbox");
+        let stats = crate::export::mbox::merge_mbox_files(
+            &[a, b],
+            &out,
+            Some(DedupStrategy::Content),
+            false,
+            None,
+            &|_, _, _| {},
+        )
+        .unwrap();
+        assert_eq!(stats.total_messages, 2);
+        assert_eq!(stats.duplicates_removed, 1);
+    }
+
+    #[test]
+    fn test_strategy_names() {
+        assert_eq!("Message-ID".parse(), Ok(DedupStrategy::MessageId));
+        assert_eq!("body".parse(), Ok(DedupStrategy::Body));
+        assert!("subject".parse::<DedupStrategy>().is_err());
+        assert_eq!(DedupStrategy::default().to_string(), "message-id");
+    }
+}
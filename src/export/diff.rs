@@ -0,0 +1,254 @@
+//! Compare two mailboxes — typically two exports of the same account taken
+//! at different times — and tell what changed between them.
+//!
+//! Messages are paired by `Message-ID` first. Those left over, including
+//! every message without one, are paired by the hash of their content, the
+//! one `dedup --by content` uses: sender, recipients, subject, date and
+//! body. A pair can still differ in its labels, or, when it was paired by
+//! `Message-ID`, in its content.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::export::dedup::{self, DedupStrategy};
+use crate::model::mail::MailEntry;
+use crate::store::reader::MboxStore;
+
+/// A message present in both mailboxes that is not the same in both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageChange {
+    /// Index of the message in the first mailbox.
+    pub a: usize,
+    /// Index of the message in the second mailbox.
+    pub b: usize,
+    /// Labels the message has in the second mailbox but not in the first.
+    pub labels_added: Vec<String>,
+    /// Labels the message had in the first mailbox but not in the second.
+    pub labels_removed: Vec<String>,
+    /// Whether its sender, recipients, subject, date or body changed.
+    pub content_changed: bool,
+}
+
+/// The differences between two mailboxes, as indices into their entries,
+/// each list in mailbox order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MailboxDiff {
+    pub only_in_a: Vec<usize>,
+    pub only_in_b: Vec<usize>,
+    pub changed: Vec<MessageChange>,
+    /// Messages the same in both.
+    pub unchanged: usize,
+    /// Pairs found by content rather than `Message-ID`.
+    pub matched_by_content: usize,
+}
+
+impl MailboxDiff {
+    /// Whether the two mailboxes hold the same messages, unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the messages of mailbox A (`entries_a`, read through `store_a`)
+/// with those of mailbox B.
+///
+/// Every message is read to hash its content. The progress callback
+/// receives `(current, total)` messages read, across both mailboxes.
+pub fn diff_mailboxes(
+    store_a: &mut MboxStore,
+    entries_a: &[MailEntry],
+    store_b: &mut MboxStore,
+    entries_b: &[MailEntry],
+    progress: &dyn Fn(usize, usize),
+) -> anyhow::Result<MailboxDiff> {
+    let total = entries_a.len() + entries_b.len();
+    let keys_a = content_keys(store_a, entries_a, &|i| progress(i, total))?;
+    let keys_b = content_keys(store_b, entries_b, &|i| {
+        progress(entries_a.len() + i, total)
+    })?;
+    progress(total, total);
+
+    // Pair by Message-ID; when an ID repeats, its copies pair in order.
+    let mut by_id: HashMap<&str, VecDeque<usize>> = HashMap::new();
+    for (i, entry) in entries_b.iter().enumerate() {
+        let id = entry.message_id.trim();
+        if !id.is_empty() {
+            by_id.entry(id).or_default().push_back(i);
+        }
+    }
+    let mut pair_of_a: Vec<Option<(usize, bool)>> = vec![None; entries_a.len()];
+    let mut paired_b = vec![false; entries_b.len()];
+    for (i, entry) in entries_a.iter().enumerate() {
+        let id = entry.message_id.trim();
+        if let Some(b) = by_id.get_mut(id).and_then(VecDeque::pop_front) {
+            pair_of_a[i] = Some((b, false));
+            paired_b[b] = true;
+        }
+    }
+
+    // Pair what is left by content.
+    let mut by_content: HashMap<[u8; 32], VecDeque<usize>> = HashMap::new();
+    for (i, key) in keys_b.iter().enumerate() {
+        if !paired_b[i] {
+            by_content.entry(*key).or_default().push_back(i);
+        }
+    }
+    for (i, key) in keys_a.iter().enumerate() {
+        if pair_of_a[i].is_some() {
+            continue;
+        }
+        if let Some(b) = by_content.get_mut(key).and_then(VecDeque::pop_front) {
+            pair_of_a[i] = Some((b, true));
+            paired_b[b] = true;
+        }
+    }
+
+    let mut diff = MailboxDiff {
+        only_in_b: (0..entries_b.len()).filter(|&b| !paired_b[b]).collect(),
+        ..MailboxDiff::default()
+    };
+    for (a, pair) in pair_of_a.into_iter().enumerate() {
+        let Some((b, by_content)) = pair else {
+            diff.only_in_a.push(a);
+            continue;
+        };
+        if by_content {
+            diff.matched_by_content += 1;
+        }
+        let labels_a = sorted_labels(&entries_a[a]);
+        let labels_b = sorted_labels(&entries_b[b]);
+        let change = MessageChange {
+            a,
+            b,
+            labels_added: difference(&labels_b, &labels_a),
+            labels_removed: difference(&labels_a, &labels_b),
+            content_changed: keys_a[a] != keys_b[b],
+        };
+        if change.content_changed
+            || !change.labels_added.is_empty()
+            || !change.labels_removed.is_empty()
+        {
+            diff.changed.push(change);
+        } else {
+            diff.unchanged += 1;
+        }
+    }
+    Ok(diff)
+}
+
+/// The content hash of every message of `entries`.
+fn content_keys(
+    store: &mut MboxStore,
+    entries: &[MailEntry],
+    progress: &dyn Fn(usize),
+) -> anyhow::Result<Vec<[u8; 32]>> {
+    let mut keys = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        progress(i);
+        let key = dedup::message_key(store, entry, DedupStrategy::Content)?;
+        keys.push(key.expect("every message has a content key"));
+    }
+    Ok(keys)
+}
+
+fn sorted_labels(entry: &MailEntry) -> Vec<&str> {
+    let mut labels: Vec<&str> = entry.labels.iter().map(String::as_str).collect();
+    labels.sort_unstable();
+    labels.dedup();
+    labels
+}
+
+/// The labels of `from` missing from `without`, both sorted.
+fn difference(from: &[&str], without: &[&str]) -> Vec<String> {
+    from.iter()
+        .filter(|label| without.binary_search(label).is_err())
+        .map(|label| label.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::builder;
+
+    fn message(id: Option<&str>, labels: &str, subject: &str, body: &str) -> String {
+        let id = id
+            .map(|id| format!("Message-ID: {id}\n"))
+            .unwrap_or_default();
+        format!(
+            "From a@x Mon Jan 01 10:00:00 2024\nFrom: a@x\nTo: b@x\n{id}\
+             Date: Mon, 01 Jan 2024 10:00:00 +0000\nX-Gmail-Labels: {labels}\n\
+             Subject: {subject}\n\n{body}\n"
+        )
+    }
+
+    fn diff(a: &[String], b: &[String]) -> MailboxDiff {
+        let dir = tempfile::tempdir().unwrap();
+        let (path_a, path_b) = (dir.path().join("a.mbox"), dir.path().join("b.mbox"));
+        std::fs::write(&path_a, a.join("\n")).unwrap();
+        std::fs::write(&path_b, b.join("\n")).unwrap();
+        let entries_a = builder::build_index(&path_a, false, None).unwrap();
+        let entries_b = builder::build_index(&path_b, false, None).unwrap();
+        let mut store_a = MboxStore::open(&path_a).unwrap();
+        let mut store_b = MboxStore::open(&path_b).unwrap();
+        diff_mailboxes(
+            &mut store_a,
+            &entries_a,
+            &mut store_b,
+            &entries_b,
+            &|_, _| {},
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_between_snapshots() {
+        let a = [
+            message(Some("<1@x>"), "Inbox", "Kept", "one"),
+            message(Some("<2@x>"), "Inbox", "Deleted", "two"),
+            message(Some("<3@x>"), "Inbox", "Relabeled", "three"),
+            message(None, "Inbox", "No ID", "four"),
+            message(Some("<5@x>"), "Inbox", "Edited", "five"),
+        ];
+        let b = [
+            message(Some("<1@x>"), "Inbox", "Kept", "one"),
+            message(Some("<3@x>"), "Archived,Work", "Relabeled", "three"),
+            message(None, "Inbox", "No ID", "four"),
+            message(Some("<5@x>"), "Inbox", "Edited", "five, amended"),
+            message(Some("<6@x>"), "Inbox", "New", "six"),
+        ];
+        let diff = diff(&a, &b);
+        assert_eq!(diff.only_in_a, [1]);
+        assert_eq!(diff.only_in_b, [4]);
+        assert_eq!(diff.unchanged, 2);
+        assert_eq!(diff.matched_by_content, 1);
+        assert_eq!(
+            diff.changed,
+            [
+                MessageChange {
+                    a: 2,
+                    b: 1,
+                    labels_added: vec!["Archived".into(), "Work".into()],
+                    labels_removed: vec!["Inbox".into()],
+                    content_changed: false,
+                },
+                MessageChange {
+                    a: 4,
+                    b: 3,
+                    labels_added: vec![],
+                    labels_removed: vec![],
+                    content_changed: true,
+                },
+            ]
+        );
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn test_content_pairs_messages_given_a_new_id() {
+        let a = [message(Some("<old@x>"), "Inbox", "Same", "body")];
+        let b = [message(Some("<new@y>"), "Inbox", "Same", "body")];
+        let diff = diff(&a, &b);
+        assert!(diff.is_empty(), "{diff:?}");
+        assert_eq!(diff.matched_by_content, 1);
+    }
+}
@@ -33,7 +33,7 @@ pub fn export_eml_opts(
 ) -> anyhow::Result<PathBuf> {
     let raw = store.get_raw_message(entry)?;
     let stripped = skip_from_line(&raw);
-    let mut bytes = unescape_mboxrd(stripped);
+    let mut bytes = trim_separator(stripped);
 
     if qp {
         bytes = reencode_message_as_qp(bytes);
@@ -108,47 +108,16 @@ fn skip_from_line(raw: &[u8]) -> &[u8] {
     raw
 }
 
-/// Reverse mboxrd `From `-line escaping for EML output.
-///
-/// In mboxrd, any body line starting with one or more `>` followed by `From `
-/// was escaped by prepending an extra `>`. To produce a standards-compliant
-/// RFC 5322 message we strip exactly one leading `>` from those lines.
-/// Also trims a trailing blank line that MBOX adds as a message separator.
-fn unescape_mboxrd(body: &[u8]) -> Vec<u8> {
-    let mut out = Vec::with_capacity(body.len());
-    let mut at_line_start = true;
-
-    let mut i = 0;
-    while i < body.len() {
-        if at_line_start && body[i] == b'>' {
-            // Count consecutive '>' followed by "From "
-            let mut j = i;
-            while j < body.len() && body[j] == b'>' {
-                j += 1;
-            }
-            if body[j..].starts_with(b"From ") {
-                // Drop exactly one '>'
-                out.extend_from_slice(&body[i + 1..j]);
-                out.extend_from_slice(b"From ");
-                i = j + b"From ".len();
-                at_line_start = false;
-                continue;
-            }
-        }
-        let b = body[i];
-        out.push(b);
-        at_line_start = b == b'\n';
-        i += 1;
-    }
-
-    // Trim a single trailing blank line added by MBOX as separator
+/// Trim the trailing blank line that MBOX adds as a message separator. The
+/// store has already undone the mailbox's `From ` quoting.
+fn trim_separator(message: &[u8]) -> Vec<u8> {
+    let mut out = message.to_vec();
     while out.ends_with(b"\n\n") || out.ends_with(b"\r\n\r\n") {
         out.pop();
         if out.last() == Some(&b'\r') {
             out.pop();
         }
     }
-
     out
 }
 
@@ -561,16 +530,24 @@ mod tests {
             },
             to: vec![],
             cc: vec![],
+            bcc: vec![],
+            reply_to: vec![],
+            sender: None,
+            delivered_to: vec![],
             subject: subject.to_string(),
             message_id: "<id@example.com>".to_string(),
             in_reply_to: None,
             references: vec![],
             has_attachments: false,
+            attachments: Vec::new(),
             content_type: "text/plain".to_string(),
             text_size: 0,
             labels: vec![],
+            tags: vec![],
             thread_id: None,
             sequence: 0,
+            file: None,
+            flags: Default::default(),
         }
     }
 
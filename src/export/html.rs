@@ -120,7 +120,7 @@ fn push_header(out: &mut String, label: &str, value: &str) {
     ));
 }
 
-fn join_addresses(addrs: &[crate::model::address::EmailAddress]) -> String {
+fn join_addresses(addrs: &[crate::model::address::SharedAddress]) -> String {
     addrs
         .iter()
         .map(|a| a.display())
@@ -159,6 +159,7 @@ mod tests {
     use super::*;
     use crate::model::address::EmailAddress;
     use chrono::TimeZone;
+    use std::sync::Arc;
 
     fn sample_entry() -> MailEntry {
         MailEntry {
@@ -169,21 +170,29 @@ mod tests {
                 display_name: "Alice".to_string(),
                 address: "alice@example.com".to_string(),
             },
-            to: vec![EmailAddress {
+            to: vec![Arc::new(EmailAddress {
                 display_name: String::new(),
                 address: "bob@example.com".to_string(),
-            }],
+            })],
             cc: vec![],
+            bcc: vec![],
+            reply_to: vec![],
+            sender: None,
+            delivered_to: vec![],
             subject: "Test <subject> & more".to_string(),
             message_id: "<msg@example.com>".to_string(),
             in_reply_to: None,
             references: vec![],
             has_attachments: false,
+            attachments: Vec::new(),
             content_type: "text/html".to_string(),
             text_size: 0,
             labels: vec![],
+            tags: vec![],
             thread_id: None,
             sequence: 0,
+            file: None,
+            flags: Default::default(),
         }
     }
 
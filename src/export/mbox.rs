@@ -1,12 +1,19 @@
-//! Write MBOX mailboxes: merge several into one, or export a selection as a new one.
+//! Write MBOX mailboxes: merge several into one, export a selection as a
+//! new one, or write a repaired copy of a damaged one.
+//!
+//! Either can be written in any [`MboxDialect`]: messages come out of the
+//! store with their source's quoting undone and are quoted again for the
+//! dialect asked for, with a `Content-Length:` header for mboxcl and mboxcl2.
 
-use std::collections::HashSet;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+use crate::export::dedup::{DedupStrategy, DuplicateFinder};
 use crate::index::builder;
 use crate::mailbox_naming;
 use crate::model::mail::MailEntry;
+use crate::parser::dialect::{self, MboxDialect};
+use crate::parser::verify;
 use crate::store::reader::MboxStore;
 
 /// Statistics returned by a merge operation.
@@ -22,8 +29,9 @@ pub struct MergeStats {
 
 /// Merge multiple MBOX files into a single output file.
 ///
-/// If `dedup` is true, messages with duplicate Message-IDs are skipped
-/// (the first occurrence is kept).
+/// With a `dedup` strategy, messages that duplicate an earlier one — across
+/// all the inputs — are skipped and the first occurrence is kept; see
+/// [`DedupStrategy`].
 ///
 /// If `add_source_header` is true, every message gets an
 /// `X-Mbox-Source: <mailbox name>` header injected as its first header, so
@@ -35,12 +43,17 @@ pub struct MergeStats {
 /// inner `mbox` file), disambiguated across the inputs when two of them would
 /// otherwise share a name — see [`crate::mailbox_naming`].
 ///
+/// With a `dialect`, every message is written in it; without one, a plain
+/// merge copies the inputs byte for byte and the others keep each message in
+/// the dialect of its input.
+///
 /// The progress callback receives `(current_file, total_files, mailbox_name)`.
 pub fn merge_mbox_files(
     inputs: &[PathBuf],
     output: &Path,
-    dedup: bool,
+    dedup: Option<DedupStrategy>,
     add_source_header: bool,
+    dialect: Option<MboxDialect>,
     progress: &dyn Fn(usize, usize, &str),
 ) -> anyhow::Result<MergeStats> {
     // Write to a sibling temp file and rename on success, so a mid-merge error
@@ -48,7 +61,7 @@ pub fn merge_mbox_files(
     // to avoid one syscall per message on the dedup path.
     let tmp_output = output.with_extension("mbox.tmp");
     let mut out_file = std::io::BufWriter::new(std::fs::File::create(&tmp_output)?);
-    let mut seen_ids: HashSet<String> = HashSet::new();
+    let mut finder = dedup.map(DuplicateFinder::new);
     let mut total_messages: u64 = 0;
     let mut duplicates_removed: u64 = 0;
     let mut source_header_added: u64 = 0;
@@ -62,10 +75,10 @@ pub fn merge_mbox_files(
         let filename = mailbox_names[file_idx].as_str();
         progress(file_idx, total_files, filename);
 
-        // Both dedup and source-header injection need per-message boundaries, so
-        // they share the parsing path. A plain no-dedup / no-header merge stays
-        // on the fast raw block copy below.
-        if dedup || add_source_header {
+        // Dedup, source-header injection and a change of dialect need
+        // per-message boundaries, so they share the parsing path. A plain
+        // merge stays on the fast raw block copy below.
+        if dedup.is_some() || add_source_header || dialect.is_some() {
             // The source label is the mailbox name (e.g. "Inbox.mbox"),
             // sanitized so a crafted name can't inject extra headers. A
             // disambiguated name may carry a `/`, harmless in a header value.
@@ -75,20 +88,18 @@ pub fn merge_mbox_files(
                 String::new()
             };
 
-            // Index to get Message-IDs, then copy (and optionally tag) messages.
+            // Index to find the messages, then copy (and optionally tag) them.
             let entries = builder::build_index(input_path, false, None)?;
             let mut store = crate::store::reader::MboxStore::open(input_path)?;
+            let dialect = dialect.unwrap_or(store.dialect());
 
             for entry in &entries {
-                if dedup {
-                    let id = &entry.message_id;
-                    if !id.is_empty() && seen_ids.contains(id) {
+                if let Some(finder) = &mut finder {
+                    let id = (total_messages + duplicates_removed) as usize;
+                    if finder.check(&mut store, entry, id)?.is_some() {
                         duplicates_removed += 1;
                         continue;
                     }
-                    if !id.is_empty() {
-                        seen_ids.insert(id.clone());
-                    }
                 }
 
                 let mut raw = store.get_raw_message(entry)?;
@@ -96,13 +107,7 @@ pub fn merge_mbox_files(
                     raw = inject_source_header(&raw, &source_label);
                     source_header_added += 1;
                 }
-                out_file.write_all(&raw)?;
-
-                // Ensure there's a newline separator between messages
-                if !raw.ends_with(b"\n") {
-                    out_file.write_all(b"\n")?;
-                }
-
+                out_file.write_all(&mbox_record(&raw, entry, dialect))?;
                 total_messages += 1;
             }
         } else {
@@ -150,12 +155,16 @@ pub fn merge_mbox_files(
 /// request, a mailbox someone else has to read — and produce a mailbox holding
 /// only those. The source file is never touched.
 ///
+/// The messages are written in `dialect`; pass the store's own to keep them
+/// as the source has them.
+///
 /// The progress callback receives `(current, total)` and returns the number of
 /// messages written.
 pub fn export_mbox(
     store: &mut MboxStore,
     entries: &[&MailEntry],
     output: &Path,
+    dialect: MboxDialect,
     progress: &dyn Fn(usize, usize),
 ) -> anyhow::Result<usize> {
     // Same commit discipline as the merge: write to a sibling temp file and
@@ -168,7 +177,7 @@ pub fn export_mbox(
     for (i, entry) in entries.iter().enumerate() {
         progress(i, total);
         let raw = store.get_raw_message(entry)?;
-        out_file.write_all(&mbox_record(&raw, entry))?;
+        out_file.write_all(&mbox_record(&raw, entry, dialect))?;
     }
     progress(total, total);
 
@@ -179,23 +188,173 @@ pub fn export_mbox(
     Ok(total)
 }
 
-/// One mbox record: separator line, message, trailing newline.
+/// What [`repair_mbox`] changed.
+#[derive(Debug, Default)]
+pub struct RepairStats {
+    pub messages: u64,
+    /// Messages given a new `From ` line, theirs being missing or malformed.
+    pub separators_rewritten: u64,
+    /// Messages whose `\r\n` line endings were turned into `\n`.
+    pub line_endings_fixed: u64,
+    pub output_size: u64,
+}
+
+/// Write a normalized copy of the mailbox at `input` to `output`: the
+/// messages the parser finds in it, each with a proper `From ` separator
+/// after a blank line, `\n` line endings, body `From ` lines quoted for
+/// `dialect` (the input's own by default) and any `Content-Length:` header
+/// matching the body. Content is otherwise left as it is — a message cut
+/// off stays cut off, only ended with a newline.
+///
+/// The progress callback receives `(current, total)` messages.
+pub fn repair_mbox(
+    input: &Path,
+    output: &Path,
+    dialect: Option<MboxDialect>,
+    progress: &dyn Fn(usize, usize),
+) -> anyhow::Result<RepairStats> {
+    let entries = builder::build_index(input, false, None)?;
+    let mut store = MboxStore::open(input)?;
+    let dialect = dialect.unwrap_or(store.dialect());
+
+    let tmp_output = output.with_extension("mbox.tmp");
+    let mut out_file = std::io::BufWriter::new(std::fs::File::create(&tmp_output)?);
+    let mut stats = RepairStats::default();
+    let total = entries.len();
+    for (i, entry) in entries.iter().enumerate() {
+        progress(i, total);
+        let raw = store.get_raw_message(entry)?;
+        out_file.write_all(&repaired_record(&raw, entry, dialect, &mut stats))?;
+        stats.messages += 1;
+    }
+    progress(total, total);
+
+    out_file.flush()?;
+    drop(out_file);
+    std::fs::rename(&tmp_output, output)?;
+    stats.output_size = std::fs::metadata(output)?.len();
+    Ok(stats)
+}
+
+/// The record [`repair_mbox`] writes for `raw`, a message as the store
+/// reads it (quoting undone), counting what it changed in `stats`.
+fn repaired_record(
+    raw: &[u8],
+    entry: &MailEntry,
+    dialect: MboxDialect,
+    stats: &mut RepairStats,
+) -> Vec<u8> {
+    let raw = raw.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(raw);
+    let mut message = Vec::with_capacity(raw.len());
+    for line in raw.split_inclusive(|&b| b == b'\n') {
+        match line.strip_suffix(b"\r\n") {
+            Some(text) => {
+                message.extend_from_slice(text);
+                message.push(b'\n');
+            }
+            None => message.extend_from_slice(line),
+        }
+    }
+    if message.len() < raw.len() {
+        stats.line_endings_fixed += 1;
+    }
+
+    let first_line_len = message
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(message.len(), |nl| nl + 1);
+    let (mut out, body) = match verify::separator_problem(&message[..first_line_len]) {
+        None => (
+            message[..first_line_len].to_vec(),
+            &message[first_line_len..],
+        ),
+        Some(problem) => {
+            stats.separators_rewritten += 1;
+            // A broken `From ` line goes; anything else is message content.
+            let skip = match problem {
+                verify::AnomalyKind::LeadingGarbage => 0,
+                _ => first_line_len,
+            };
+            (from_line(entry), &message[skip..])
+        }
+    };
+
+    let mut end = body.len();
+    while end > 0 && body[end - 1] == b'\n' && (end == 1 || body[end - 2] == b'\n') {
+        end -= 1;
+    }
+    let mut quoted = Vec::with_capacity(end + 32);
+    dialect.escape_into(&mut quoted, &body[..end]);
+    if quoted.last() != Some(&b'\n') {
+        quoted.push(b'\n');
+    }
+    let headers = &quoted[..dialect::body_start(&quoted).unwrap_or(quoted.len())];
+    if dialect.is_length_delimited() || dialect::content_length(headers).is_some() {
+        quoted = with_content_length(&quoted);
+    }
+    out.extend_from_slice(&quoted);
+    out.push(b'\n');
+    out
+}
+
+/// One mbox record in `dialect`: separator line, message quoted for the
+/// dialect, trailing newline.
 ///
-/// A message read out of an MBOX already carries its own `From ` line and
-/// whatever quoting the source used, so it is copied verbatim — rewriting the
-/// envelope could only corrupt an archive that was already valid. A message
-/// that came from an EML has neither, so both are synthesized.
-pub fn mbox_record(raw: &[u8], entry: &MailEntry) -> Vec<u8> {
-    let mut out = if raw.starts_with(b"From ") {
-        raw.to_vec()
+/// A message read out of an MBOX already carries its own `From ` line, which
+/// is kept verbatim — rewriting the envelope could only corrupt an archive
+/// that was already valid. A message that came from an EML has none, so one
+/// is synthesized. For mboxcl and mboxcl2 the message's `Content-Length:`
+/// is set to its body as written, and a blank line ends the record.
+pub fn mbox_record(raw: &[u8], entry: &MailEntry, dialect: MboxDialect) -> Vec<u8> {
+    let (mut out, message) = match raw.iter().position(|&b| b == b'\n') {
+        Some(nl) if raw.starts_with(b"From ") => (raw[..=nl].to_vec(), &raw[nl + 1..]),
+        _ => (from_line(entry), raw),
+    };
+    let mut quoted = Vec::with_capacity(message.len() + 32);
+    dialect.escape_into(&mut quoted, message);
+    if quoted.last() != Some(&b'\n') {
+        quoted.push(b'\n');
+    }
+    if dialect.is_length_delimited() {
+        quoted = with_content_length(&quoted);
+        quoted.push(b'\n');
+    }
+    out.extend_from_slice(&quoted);
+    out
+}
+
+/// `message` with a single `Content-Length:` header giving the size of its
+/// body, in place of any it had.
+fn with_content_length(message: &[u8]) -> Vec<u8> {
+    let (headers, body) = match dialect::body_start(message) {
+        Some(at) => {
+            let blank = if message[..at].ends_with(b"\r\n\r\n") {
+                2
+            } else {
+                1
+            };
+            (&message[..at - blank], &message[at..])
+        }
+        None => (message, &[][..]),
+    };
+    let eol: &[u8] = if headers.ends_with(b"\r\n") {
+        b"\r\n"
     } else {
-        let mut v = from_line(entry);
-        append_from_quoted(&mut v, raw);
-        v
+        b"\n"
     };
-    if out.last() != Some(&b'\n') {
-        out.push(b'\n');
+    let mut out = Vec::with_capacity(message.len() + 24);
+    for line in headers.split_inclusive(|&b| b == b'\n') {
+        let is_length = line
+            .get(..15)
+            .is_some_and(|name| name.eq_ignore_ascii_case(b"Content-Length:"));
+        if !is_length {
+            out.extend_from_slice(line);
+        }
     }
+    out.extend_from_slice(format!("Content-Length: {}", body.len()).as_bytes());
+    out.extend_from_slice(eol);
+    out.extend_from_slice(eol);
+    out.extend_from_slice(body);
     out
 }
 
@@ -223,18 +382,6 @@ fn from_line(entry: &MailEntry) -> Vec<u8> {
     format!("From {sender} {stamp}\n").into_bytes()
 }
 
-/// Append `raw`, prefixing body lines that start with `From ` with `>` so they
-/// are not read back as the start of the next message.
-fn append_from_quoted(out: &mut Vec<u8>, raw: &[u8]) {
-    out.reserve(raw.len());
-    for line in raw.split_inclusive(|&b| b == b'\n') {
-        if line.starts_with(b"From ") {
-            out.push(b'>');
-        }
-        out.extend_from_slice(line);
-    }
-}
-
 /// Insert an `X-Mbox-Source: <source>` header into a raw MBOX message.
 ///
 /// The header is placed right after the `From ` envelope line (so it becomes
@@ -316,23 +463,31 @@ mod tests {
             },
             to: vec![],
             cc: vec![],
+            bcc: vec![],
+            reply_to: vec![],
+            sender: None,
+            delivered_to: vec![],
             subject: "Hello".to_string(),
             message_id: "<msg@test>".to_string(),
             in_reply_to: None,
             references: vec![],
             has_attachments: false,
+            attachments: Vec::new(),
             content_type: "text/plain".to_string(),
             text_size: 50,
             labels: vec![],
+            tags: vec![],
             sequence: 0,
             thread_id: None,
+            file: None,
+            flags: Default::default(),
         }
     }
 
     #[test]
     fn test_mbox_record_keeps_mbox_message_verbatim() {
         let raw = b"From user@x.com Thu Jan  4 10:00:00 2024\nSubject: Hi\n\nbody\n";
-        let out = mbox_record(raw, &sample_entry());
+        let out = mbox_record(raw, &sample_entry(), MboxDialect::Mboxo);
         // A message that already came out of a mailbox is copied as-is:
         // rewriting its envelope line could only corrupt a valid archive.
         assert_eq!(out, raw.to_vec());
@@ -341,7 +496,7 @@ mod tests {
     #[test]
     fn test_mbox_record_adds_envelope_line_for_eml() {
         let raw = b"Subject: Hi\n\nbody\n";
-        let out = String::from_utf8(mbox_record(raw, &sample_entry())).unwrap();
+        let out = String::from_utf8(mbox_record(raw, &sample_entry(), MboxDialect::Mboxo)).unwrap();
         // asctime, UTC, day space-padded to two columns and locale-independent.
         assert!(
             out.starts_with("From test@example.com Thu Jan  4 09:00:00 2024\n"),
@@ -353,7 +508,7 @@ mod tests {
     #[test]
     fn test_mbox_record_quotes_from_lines_in_eml_body() {
         let raw = b"Subject: Hi\n\nFrom here it broke\nok\n";
-        let out = String::from_utf8(mbox_record(raw, &sample_entry())).unwrap();
+        let out = String::from_utf8(mbox_record(raw, &sample_entry(), MboxDialect::Mboxo)).unwrap();
         // Otherwise that body line reads back as the start of the next message.
         assert!(out.contains("\n>From here it broke\n"), "not quoted: {out}");
         assert!(out.contains("\nok\n"));
@@ -362,7 +517,7 @@ mod tests {
     #[test]
     fn test_mbox_record_ends_with_newline() {
         let raw = b"Subject: Hi\n\nno trailing newline";
-        let out = mbox_record(raw, &sample_entry());
+        let out = mbox_record(raw, &sample_entry(), MboxDialect::Mboxo);
         assert_eq!(out.last(), Some(&b'\n'));
     }
 
@@ -404,7 +559,7 @@ mod tests {
         // Export a selection — the whole point: only these go in the handover.
         let selection = vec![&entries[0], &entries[2]];
         let out = dir.path().join("selection.mbox");
-        let n = export_mbox(&mut store, &selection, &out, &|_, _| {}).unwrap();
+        let n = export_mbox(&mut store, &selection, &out, MboxDialect::Mboxo, &|_, _| {}).unwrap();
         assert_eq!(n, 2);
 
         // The result must be a mailbox the tool can read back.
@@ -422,6 +577,79 @@ mod tests {
         assert!(!dir.path().join("selection.mbox.tmp").exists());
     }
 
+    #[test]
+    fn test_export_converts_between_dialects() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("source.mbox");
+        std::fs::write(
+            &src,
+            b"From a@x Thu Jan 01 00:00:00 2024\nMessage-ID: <1@x>\nSubject: A\n\n\
+              >From the start\n>>From a quote\n\n\
+              From b@x Fri Jan 02 00:00:00 2024\nMessage-ID: <2@x>\nSubject: B\n\nhi\n",
+        )
+        .unwrap();
+        let entries = builder::build_index(&src, false, None).unwrap();
+        let mut store = MboxStore::open(&src).unwrap();
+        assert_eq!(store.dialect(), MboxDialect::Mboxrd);
+        let original = store.get_raw_message(&entries[0]).unwrap();
+        assert!(String::from_utf8_lossy(&original).contains("\nFrom the start\n>From a quote\n"));
+
+        let selection: Vec<&MailEntry> = entries.iter().collect();
+        for dialect in MboxDialect::ALL {
+            let out = dir.path().join(format!("{dialect}.mbox"));
+            export_mbox(&mut store, &selection, &out, dialect, &|_, _| {}).unwrap();
+            let reexported = builder::build_index(&out, true, None).unwrap();
+            assert_eq!(reexported.len(), 2, "{dialect}");
+            let mut converted = MboxStore::open(&out).unwrap();
+            assert_eq!(converted.dialect(), dialect);
+            if dialect != MboxDialect::Mboxo && dialect != MboxDialect::Mboxcl {
+                // Only the dialects that can tell the two lines apart give
+                // the message back exactly.
+                let raw = converted.get_raw_message(&reexported[0]).unwrap();
+                let raw = String::from_utf8_lossy(&raw);
+                assert!(
+                    raw.contains("\n\nFrom the start\n>From a quote\n"),
+                    "{dialect}: {raw}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_repair_writes_a_mailbox_that_verifies_clean() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("damaged.mbox");
+        std::fs::write(
+            &src,
+            b"\xEF\xBB\xBFFrom a@x Thu Jan 01 00:00:00 2024\nSubject: A\nContent-Length: 99\n\n\
+              body\nFrom here on\r\n\
+              From b@x Fri Jan 02 00:00:00 2024\r\nSubject: B\r\n\r\nhi\r\n\r\n\
+              From \nSubject: C\nDate: Sat, 03 Feb 2024 10:00:00 +0000\n\ncut",
+        )
+        .unwrap();
+        assert_eq!(
+            verify::verify_mbox(&src, None).unwrap().worst(),
+            Some(verify::Severity::Error)
+        );
+
+        let out = dir.path().join("repaired.mbox");
+        let stats = repair_mbox(&src, &out, None, &|_, _| {}).unwrap();
+        assert_eq!(stats.messages, 3);
+        assert_eq!(stats.separators_rewritten, 1);
+        assert_eq!(stats.line_endings_fixed, 2);
+
+        let report = verify::verify_mbox(&out, None).unwrap();
+        assert!(report.anomalies.is_empty(), "{:?}", report.anomalies);
+        assert_eq!(report.crlf_lines, 0);
+        let repaired = String::from_utf8(std::fs::read(&out).unwrap()).unwrap();
+        assert!(repaired.contains("Content-Length: 19\n\nbody\n>From here on\n\n"));
+        // The bare separator gets a sender and date from the message.
+        assert!(
+            repaired.contains("\n\nFrom MAILER-DAEMON Sat Feb  3 10:00:00 2024\nSubject: C\n"),
+            "{repaired}"
+        );
+    }
+
     #[test]
     fn test_non_dedup_merge_preserves_bytes() {
         let dir = tempfile::tempdir().unwrap();
@@ -435,7 +663,7 @@ mod tests {
         std::fs::write(&b, b_bytes).unwrap();
 
         let out = dir.path().join("out.mbox");
-        let stats = merge_mbox_files(&[a, b], &out, false, false, &|_, _, _| {}).unwrap();
+        let stats = merge_mbox_files(&[a, b], &out, None, false, None, &|_, _, _| {}).unwrap();
 
         let merged = std::fs::read(&out).unwrap();
         let mut expected = Vec::new();
@@ -456,7 +684,15 @@ mod tests {
         std::fs::write(&b, msg).unwrap();
 
         let out = dir.path().join("out.mbox");
-        let stats = merge_mbox_files(&[a, b], &out, true, false, &|_, _, _| {}).unwrap();
+        let stats = merge_mbox_files(
+            &[a, b],
+            &out,
+            Some(DedupStrategy::MessageId),
+            false,
+            None,
+            &|_, _, _| {},
+        )
+        .unwrap();
 
         assert_eq!(stats.duplicates_removed, 1);
         assert_eq!(stats.total_messages, 1);
@@ -536,7 +772,7 @@ mod tests {
 
         let out = dir.path().join("out.mbox");
         // dedup off, source header on: proves the two options are independent.
-        let stats = merge_mbox_files(&[a, b], &out, false, true, &|_, _, _| {}).unwrap();
+        let stats = merge_mbox_files(&[a, b], &out, None, true, None, &|_, _, _| {}).unwrap();
 
         assert_eq!(stats.total_messages, 2);
         assert_eq!(stats.source_header_added, 2);
@@ -570,7 +806,15 @@ mod tests {
         .unwrap();
 
         let out = dir.path().join("out.mbox");
-        let stats = merge_mbox_files(&[inbox, sent], &out, true, true, &|_, _, _| {}).unwrap();
+        let stats = merge_mbox_files(
+            &[inbox, sent],
+            &out,
+            Some(DedupStrategy::MessageId),
+            true,
+            None,
+            &|_, _, _| {},
+        )
+        .unwrap();
 
         assert_eq!(stats.source_header_added, 2);
         let merged = String::from_utf8(std::fs::read(&out).unwrap()).unwrap();
@@ -602,7 +846,15 @@ mod tests {
         std::fs::write(&b, b"From z@w Fri Jan 02 00:00:00 2024\nSubject: B\n\nhi\n").unwrap();
 
         let out = dir.path().join("out.mbox");
-        merge_mbox_files(&[a, b], &out, true, true, &|_, _, _| {}).unwrap();
+        merge_mbox_files(
+            &[a, b],
+            &out,
+            Some(DedupStrategy::MessageId),
+            true,
+            None,
+            &|_, _, _| {},
+        )
+        .unwrap();
 
         let merged = String::from_utf8(std::fs::read(&out).unwrap()).unwrap();
         assert!(merged.contains("X-Mbox-Source: Work/Inbox.mbox"));
@@ -1,8 +1,12 @@
-//! Export functionality: EML, CSV, text, attachments, and MBOX merge.
+//! Export functionality: EML, CSV, text, attachments, and MBOX merge, dedup,
+//! diff, repair and split.
 
 pub mod attachment;
 pub mod csv;
+pub mod dedup;
+pub mod diff;
 pub mod eml;
 pub mod html;
 pub mod mbox;
+pub mod split;
 pub mod text;
@@ -0,0 +1,511 @@
+//! Split a mailbox into several, each a valid MBOX of its own: by size, for
+//! platforms that cap what they accept, or by year, month, label or sender
+//! domain.
+//!
+//! Every part is written like [`export_mbox`] writes a selection, so a part
+//! reads back exactly as that selection exported on its own would.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::export::eml::sanitize_filename_part;
+use crate::export::mbox::{export_mbox, mbox_record};
+use crate::model::mail::MailEntry;
+use crate::parser::dialect::MboxDialect;
+use crate::search::query::{self, parse_byte_size};
+use crate::store::reader::MboxStore;
+
+/// Longest partition key kept in a file name.
+const MAX_NAME_LEN: usize = 100;
+
+/// How [`split_mbox`] partitions the messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    /// Consecutive parts of at most this many bytes, in mailbox order. A
+    /// message larger than that gets a part of its own, flagged
+    /// [`oversized`](SplitPart::oversized).
+    Size(u64),
+    /// Year the message was sent, in the query timezone.
+    Year,
+    /// Month the message was sent, in the query timezone, as `YYYY-MM`.
+    Month,
+    /// Label (Gmail label, folder, Google Group). A message with several is
+    /// written to the part of each.
+    Label,
+    /// Domain of the sender address.
+    SenderDomain,
+}
+
+impl fmt::Display for SplitBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Size(bytes) => write!(f, "size:{bytes}"),
+            Self::Year => f.write_str("year"),
+            Self::Month => f.write_str("month"),
+            Self::Label => f.write_str("label"),
+            Self::SenderDomain => f.write_str("sender-domain"),
+        }
+    }
+}
+
+impl FromStr for SplitBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if let Some(size) = s.strip_prefix("size:") {
+            return match parse_byte_size(size) {
+                Some(bytes) if bytes > 0 => Ok(Self::Size(bytes)),
+                _ => Err(format!("invalid size '{size}' (e.g. size:2gb, size:500mb)")),
+            };
+        }
+        match s.as_str() {
+            "year" => Ok(Self::Year),
+            "month" => Ok(Self::Month),
+            "label" => Ok(Self::Label),
+            "sender-domain" | "domain" => Ok(Self::SenderDomain),
+            _ => Err(format!(
+                "unknown split '{s}' (expected size:<n>, year, month, label, sender-domain)"
+            )),
+        }
+    }
+}
+
+/// One mailbox written by [`split_mbox`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitPart {
+    pub file: PathBuf,
+    /// What its messages share: the year, month, label or domain, or the
+    /// part number of a split by size. `None` for the part holding the
+    /// messages without a label or a sender domain.
+    pub key: Option<String>,
+    pub messages: usize,
+    pub bytes: u64,
+    /// Dates of its oldest and newest messages.
+    pub date_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// `Message-ID`s of its messages, in the order written (empty for a
+    /// message without one).
+    pub message_ids: Vec<String>,
+    /// Whether the part is larger than the size limit, because its single
+    /// message is.
+    pub oversized: bool,
+}
+
+/// Write `entries` into `output_dir` as one MBOX per partition, in
+/// `dialect`, and return the parts in the order of their keys (years and
+/// months oldest first, labels and domains alphabetically, size parts as
+/// they were filled).
+///
+/// Parts are named after their key (`2024.mbox`, `2024-03.mbox`,
+/// `Inbox.mbox`, `example.com.mbox`, `part-0001.mbox`), made safe for a file
+/// name and told apart when two keys would give the same one. Messages
+/// without a label or a sender domain go to a last part, `no-label.mbox` or
+/// `no-domain.mbox` (`no-label-2.mbox` when a label is called `no-label`).
+///
+/// The progress callback receives `(current, total)` messages written.
+pub fn split_mbox(
+    store: &mut MboxStore,
+    entries: &[&MailEntry],
+    by: SplitBy,
+    output_dir: &Path,
+    dialect: MboxDialect,
+    progress: &dyn Fn(usize, usize),
+) -> anyhow::Result<Vec<SplitPart>> {
+    std::fs::create_dir_all(output_dir)?;
+    if let SplitBy::Size(limit) = by {
+        return split_by_size(store, entries, limit, output_dir, dialect, progress);
+    }
+
+    let mut groups: BTreeMap<Option<String>, Vec<&MailEntry>> = BTreeMap::new();
+    for &entry in entries {
+        for key in partition_keys(entry, by) {
+            groups.entry(key).or_default().push(entry);
+        }
+    }
+    // The part of the messages without a key goes last.
+    let unkeyed = groups.remove(&None).map(|group| (None, group));
+
+    let total = groups
+        .values()
+        .chain(unkeyed.iter().map(|(_, g)| g))
+        .map(Vec::len)
+        .sum();
+    let mut done = 0;
+    let mut names = HashSet::new();
+    let mut parts = Vec::with_capacity(groups.len() + 1);
+    for (key, group) in groups.into_iter().chain(unkeyed) {
+        let name = match (&key, by) {
+            (Some(key), _) => key.as_str(),
+            (None, SplitBy::Label) => "no-label",
+            (None, _) => "no-domain",
+        };
+        let file = output_dir.join(unique_file_name(name, &mut names));
+        export_mbox(store, &group, &file, dialect, &|i, _| {
+            progress(done + i, total)
+        })?;
+        done += group.len();
+        parts.push(part(file, key, &group)?);
+    }
+    progress(total, total);
+    Ok(parts)
+}
+
+/// The partitions `entry` belongs to; `None` for a message without a label
+/// or a sender domain.
+fn partition_keys(entry: &MailEntry, by: SplitBy) -> Vec<Option<String>> {
+    match by {
+        SplitBy::Year => vec![Some(date_of(entry).format("%Y").to_string())],
+        SplitBy::Month => vec![Some(date_of(entry).format("%Y-%m").to_string())],
+        SplitBy::Label if entry.labels.is_empty() => vec![None],
+        SplitBy::Label => {
+            let mut labels = entry.labels.clone();
+            labels.sort();
+            labels.dedup();
+            labels.into_iter().map(Some).collect()
+        }
+        SplitBy::SenderDomain => {
+            let address = entry.from.address.to_lowercase();
+            let domain = match address.rsplit_once('@') {
+                Some((_, domain)) if !domain.is_empty() => Some(domain.to_string()),
+                _ => None,
+            };
+            vec![domain]
+        }
+        SplitBy::Size(_) => unreachable!("size splits do not group"),
+    }
+}
+
+/// The day `entry` was sent, where `date:` takes it, so a year or month part
+/// holds what `date:2024` or `date:2024-03` finds.
+fn date_of(entry: &MailEntry) -> NaiveDate {
+    query::timezone().date_of(entry.date)
+}
+
+/// Consecutive parts of at most `limit` bytes.
+fn split_by_size(
+    store: &mut MboxStore,
+    entries: &[&MailEntry],
+    limit: u64,
+    output_dir: &Path,
+    dialect: MboxDialect,
+    progress: &dyn Fn(usize, usize),
+) -> anyhow::Result<Vec<SplitPart>> {
+    let mut parts = Vec::new();
+    let mut current: Option<PartWriter> = None;
+    let total = entries.len();
+    for (i, &entry) in entries.iter().enumerate() {
+        progress(i, total);
+        let record = mbox_record(&store.get_raw_message(entry)?, entry, dialect);
+        let full = current
+            .as_ref()
+            .is_some_and(|part| part.bytes + record.len() as u64 > limit);
+        if full {
+            if let Some(part) = current.take() {
+                parts.push(part.finish()?);
+            }
+        }
+        let writer = match &mut current {
+            Some(writer) => writer,
+            None => {
+                let key = format!("{}", parts.len() + 1);
+                let file = output_dir.join(format!("part-{key:0>4}.mbox"));
+                current.insert(PartWriter::create(file, Some(key))?)
+            }
+        };
+        writer.write(&record, entry)?;
+    }
+    if let Some(part) = current {
+        parts.push(part.finish()?);
+    }
+    for part in &mut parts {
+        part.oversized = part.bytes > limit;
+    }
+    progress(total, total);
+    Ok(parts)
+}
+
+/// A size part being written: to a temporary file renamed into place when
+/// it is complete, like every other mailbox the tool writes.
+struct PartWriter {
+    file: PathBuf,
+    tmp: PathBuf,
+    out: std::io::BufWriter<std::fs::File>,
+    key: Option<String>,
+    bytes: u64,
+    entries: Vec<MailEntry>,
+}
+
+impl PartWriter {
+    fn create(file: PathBuf, key: Option<String>) -> anyhow::Result<Self> {
+        let tmp = file.with_extension("mbox.tmp");
+        let out = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        Ok(Self {
+            file,
+            tmp,
+            out,
+            key,
+            bytes: 0,
+            entries: Vec::new(),
+        })
+    }
+
+    fn write(&mut self, record: &[u8], entry: &MailEntry) -> anyhow::Result<()> {
+        self.out.write_all(record)?;
+        self.bytes += record.len() as u64;
+        self.entries.push(entry.clone());
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<SplitPart> {
+        self.out.flush()?;
+        drop(self.out);
+        std::fs::rename(&self.tmp, &self.file)?;
+        let entries: Vec<&MailEntry> = self.entries.iter().collect();
+        part(self.file, self.key, &entries)
+    }
+}
+
+/// The manifest entry of the part written to `file`.
+fn part(file: PathBuf, key: Option<String>, entries: &[&MailEntry]) -> anyhow::Result<SplitPart> {
+    let dates = entries.iter().map(|e| e.date);
+    let date_range = dates.clone().min().zip(dates.max());
+    Ok(SplitPart {
+        bytes: std::fs::metadata(&file)?.len(),
+        file,
+        key,
+        messages: entries.len(),
+        date_range,
+        message_ids: entries.iter().map(|e| e.message_id.clone()).collect(),
+        oversized: false,
+    })
+}
+
+/// `<key>.mbox` made safe for a file name, with a `-2`, `-3`… suffix when a
+/// part already took that name. Names are compared ignoring case, as
+/// Windows and macOS file systems do.
+fn unique_file_name(key: &str, taken: &mut HashSet<String>) -> String {
+    let base = sanitize_filename_part(key, MAX_NAME_LEN);
+    let mut name = format!("{base}.mbox");
+    let mut n = 1;
+    while !taken.insert(name.to_lowercase()) {
+        n += 1;
+        name = format!("{base}-{n}.mbox");
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::builder;
+
+    /// A mailbox of three messages over two years and two domains, the
+    /// first two with Gmail labels.
+    fn sample_mailbox(dir: &Path) -> PathBuf {
+        let src = dir.join("source.mbox");
+        std::fs::write(
+            &src,
+            b"From a@x Mon Dec 30 10:00:00 2024\nFrom: a@one.org\nMessage-ID: <1@x>\n\
+              Date: Mon, 30 Dec 2024 10:00:00 +0000\nX-Gmail-Labels: Inbox,Work\n\
+              Subject: A\n\nFrom the top\n\n\
+              From b@x Thu Jan 02 10:00:00 2025\nFrom: b@Two.org\nMessage-ID: <2@x>\n\
+              Date: Thu, 02 Jan 2025 10:00:00 +0000\nX-Gmail-Labels: Work\n\
+              Subject: B\n\nbody\n\n\
+              From c@x Fri Jan 03 10:00:00 2025\nFrom: c@one.org\nMessage-ID: <3@x>\n\
+              Date: Fri, 03 Jan 2025 10:00:00 +0000\nSubject: C\n\nbody\n",
+        )
+        .unwrap();
+        src
+    }
+
+    /// The `Message-ID`s each part reads back with, by file name.
+    fn read_back(parts: &[SplitPart]) -> Vec<(String, Vec<String>)> {
+        parts
+            .iter()
+            .map(|part| {
+                let entries = builder::build_index(&part.file, true, None).unwrap();
+                let name = part.file.file_name().unwrap().to_string_lossy().into();
+                (name, entries.into_iter().map(|e| e.message_id).collect())
+            })
+            .collect()
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_by_year_label_and_domain() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = sample_mailbox(dir.path());
+        let entries = builder::build_index(&src, false, None).unwrap();
+        let mut store = MboxStore::open(&src).unwrap();
+        let selection: Vec<&MailEntry> = entries.iter().collect();
+        let mut split = |by: SplitBy| {
+            let out = dir.path().join(by.to_string());
+            let parts = split_mbox(
+                &mut store,
+                &selection,
+                by,
+                &out,
+                MboxDialect::Mboxrd,
+                &|_, _| {},
+            )
+            .unwrap();
+            read_back(&parts)
+        };
+
+        assert_eq!(
+            split(SplitBy::Year),
+            vec![
+                ("2024.mbox".into(), ids(&["<1@x>"])),
+                ("2025.mbox".into(), ids(&["<2@x>", "<3@x>"])),
+            ]
+        );
+        // A message with two labels is written to both parts.
+        assert_eq!(
+            split(SplitBy::Label),
+            vec![
+                ("Inbox.mbox".into(), ids(&["<1@x>"])),
+                ("Work.mbox".into(), ids(&["<1@x>", "<2@x>"])),
+                ("no-label.mbox".into(), ids(&["<3@x>"])),
+            ]
+        );
+        assert_eq!(
+            split(SplitBy::SenderDomain),
+            vec![
+                ("one.org.mbox".into(), ids(&["<1@x>", "<3@x>"])),
+                ("two.org.mbox".into(), ids(&["<2@x>"])),
+            ]
+        );
+
+        // Parts keep the quoting of the dialect they were written in.
+        let part = std::fs::read_to_string(dir.path().join("year/2024.mbox")).unwrap();
+        assert!(part.contains("\n\n>From the top\n"), "{part}");
+    }
+
+    #[test]
+    fn test_messages_without_a_key_do_not_join_a_part_named_like_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("source.mbox");
+        std::fs::write(
+            &src,
+            b"From a@x Mon Dec 30 10:00:00 2024\nFrom: a@no-domain\nMessage-ID: <1@x>\n\
+              X-Gmail-Labels: no-label\nSubject: A\n\nbody\n\n\
+              From b@x Mon Dec 30 10:00:00 2024\nFrom: nobody\nMessage-ID: <2@x>\n\
+              Subject: B\n\nbody\n",
+        )
+        .unwrap();
+        let entries = builder::build_index(&src, false, None).unwrap();
+        let mut store = MboxStore::open(&src).unwrap();
+        let selection: Vec<&MailEntry> = entries.iter().collect();
+
+        for (by, name) in [
+            (SplitBy::Label, "no-label"),
+            (SplitBy::SenderDomain, "no-domain"),
+        ] {
+            let out = dir.path().join(by.to_string());
+            let parts = split_mbox(
+                &mut store,
+                &selection,
+                by,
+                &out,
+                MboxDialect::Mboxrd,
+                &|_, _| {},
+            )
+            .unwrap();
+            let keys: Vec<Option<&str>> = parts.iter().map(|p| p.key.as_deref()).collect();
+            assert_eq!(keys, [Some(name), None]);
+            assert_eq!(
+                read_back(&parts),
+                vec![
+                    (format!("{name}.mbox"), ids(&["<1@x>"])),
+                    (format!("{name}-2.mbox"), ids(&["<2@x>"])),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_by_size_fills_parts_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = sample_mailbox(dir.path());
+        let entries = builder::build_index(&src, false, None).unwrap();
+        let mut store = MboxStore::open(&src).unwrap();
+        let selection: Vec<&MailEntry> = entries.iter().collect();
+
+        // Room for the last two messages, but not for the first and the
+        // second together.
+        let sizes: Vec<u64> = selection
+            .iter()
+            .map(|&e| {
+                let raw = store.get_raw_message(e).unwrap();
+                mbox_record(&raw, e, MboxDialect::Mboxo).len() as u64
+            })
+            .collect();
+        let limit = sizes[1] + sizes[2];
+        assert!(sizes[0] + sizes[1] > limit);
+        let out = dir.path().join("parts");
+        let parts = split_mbox(
+            &mut store,
+            &selection,
+            SplitBy::Size(limit),
+            &out,
+            MboxDialect::Mboxo,
+            &|_, _| {},
+        )
+        .unwrap();
+        assert_eq!(
+            read_back(&parts),
+            vec![
+                ("part-0001.mbox".into(), ids(&["<1@x>"])),
+                ("part-0002.mbox".into(), ids(&["<2@x>", "<3@x>"])),
+            ]
+        );
+        assert!(parts
+            .iter()
+            .all(|part| part.bytes <= limit && !part.oversized));
+        assert_eq!(parts[1].messages, 2);
+
+        // A limit smaller than any message still writes each one, alone.
+        let out = dir.path().join("tiny");
+        let parts = split_mbox(
+            &mut store,
+            &selection,
+            SplitBy::Size(10),
+            &out,
+            MboxDialect::Mboxo,
+            &|_, _| {},
+        )
+        .unwrap();
+        assert_eq!(parts.len(), 3);
+        assert!(parts
+            .iter()
+            .all(|part| part.messages == 1 && part.oversized));
+        assert!(!out.join("part-0001.mbox.tmp").exists());
+    }
+
+    #[test]
+    fn test_parse_split_by() {
+        assert_eq!("size:2gb".parse(), Ok(SplitBy::Size(2 << 30)));
+        assert_eq!("SIZE:500MB".parse(), Ok(SplitBy::Size(500 << 20)));
+        assert_eq!("month".parse(), Ok(SplitBy::Month));
+        assert_eq!("sender-domain".parse(), Ok(SplitBy::SenderDomain));
+        assert!("size:0".parse::<SplitBy>().is_err());
+        assert!("size:lots".parse::<SplitBy>().is_err());
+        assert!("week".parse::<SplitBy>().is_err());
+    }
+
+    #[test]
+    fn test_unique_file_name() {
+        let mut taken = HashSet::new();
+        assert_eq!(unique_file_name("Work", &mut taken), "Work.mbox");
+        assert_eq!(unique_file_name("work", &mut taken), "work-2.mbox");
+        assert_eq!(unique_file_name("Work", &mut taken), "Work-3.mbox");
+    }
+}
@@ -137,6 +137,31 @@ msg!(
     "Merge multiple MBOX files",
     "Combinar varios ficheros MBOX"
 );
+msg!(
+    help_cmd_dedup,
+    "Remove duplicate messages from an MBOX file",
+    "Eliminar los mensajes duplicados de un fichero MBOX"
+);
+msg!(
+    help_cmd_diff,
+    "Compare two MBOX files",
+    "Comparar dos ficheros MBOX"
+);
+msg!(
+    help_cmd_verify,
+    "Check an MBOX file for damage the parser reads past",
+    "Comprobar los da\u{f1}os de un fichero MBOX que el parser tolera"
+);
+msg!(
+    help_cmd_repair,
+    "Write a repaired copy of an MBOX file",
+    "Escribir una copia reparada de un fichero MBOX"
+);
+msg!(
+    help_cmd_split,
+    "Split an MBOX file by size, year, month, label or sender domain",
+    "Dividir un fichero MBOX por tama\u{f1}o, a\u{f1}o, mes, etiqueta o dominio del remitente"
+);
 msg!(
     help_cmd_attachments,
     "Extract all attachments",
@@ -173,6 +198,12 @@ msg!(
     "Construyendo \u{ed}ndice..."
 );
 msg!(msg_index_built, "Index built", "Índice construido");
+msg!(msg_indexing_fulltext, "Indexing text", "Indexando texto");
+msg!(
+    msg_fulltext_index_size,
+    "Full-text index",
+    "\u{cd}ndice de texto"
+);
 msg!(msg_file, "File", "Fichero");
 msg!(msg_file_size, "File size", "Tama\u{f1}o del fichero");
 msg!(msg_message_count, "Messages", "Mensajes");
@@ -265,6 +296,7 @@ msg!(tui_header_from, "From:    ", "De:      ");
 msg!(tui_header_to, "To:      ", "Para:    ");
 msg!(tui_header_cc, "Cc:      ", "Cc:      ");
 msg!(tui_header_subject, "Subject: ", "Asunto:  ");
+msg!(tui_header_tags, "Tags:    ", "Etiq.:   ");
 
 // ── Header bar ──────────────────────────────────────────────────
 
@@ -320,6 +352,13 @@ msg!(
 );
 msg!(tui_help_mark_unmark, "Mark / unmark", "Marcar / desmarcar");
 msg!(tui_help_mark_all, "Mark all", "Marcar todos");
+msg!(
+    tui_help_toggle_read,
+    "Read / unread",
+    "Le\u{ed}do / no le\u{ed}do"
+);
+msg!(tui_help_toggle_star, "Star / unstar", "Destacar / quitar");
+msg!(tui_help_tag, "Tag / untag", "Etiquetar / quitar");
 msg!(tui_help_cycle_sort, "Cycle sort col", "Cambiar columna");
 msg!(
     tui_help_sort_direction,
@@ -329,6 +368,7 @@ msg!(
 msg!(tui_help_thread_view, "Thread view", "Vista hilos");
 msg!(tui_help_search_bar, "Search bar", "Barra b\u{fa}squeda");
 msg!(tui_help_filter_popup, "Filter popup", "Popup filtros");
+msg!(tui_help_save_search, "Save search", "Guardar b\u{fa}squeda");
 msg!(
     tui_help_next_prev_result,
     "Next / prev result",
@@ -344,6 +384,11 @@ msg!(
     "Up/Down in search bar: navigate history",
     "Arriba/Abajo en barra b\u{fa}squeda: navegar historial"
 );
+msg!(
+    tui_help_saved_searches,
+    "Saved searches (@name) are listed in the sidebar; d deletes one",
+    "Las b\u{fa}squedas guardadas (@nombre) aparecen en el panel; d borra una"
+);
 
 // ── Status bar hints ────────────────────────────────────────────
 
@@ -367,6 +412,8 @@ msg!(tui_hint_scroll_body, "Scroll body", "Scroll cuerpo");
 msg!(tui_hint_headers, "Headers", "Cabeceras");
 msg!(tui_hint_raw, "Raw", "Raw");
 msg!(tui_hint_cancel, "Cancel", "Cancelar");
+msg!(tui_hint_delete, "Delete", "Borrar");
+msg!(tui_hint_save, "Save", "Guardar");
 
 // ── Body scroll indicator ───────────────────────────────────────
 
@@ -389,6 +436,56 @@ msg!(
 );
 msg!(tui_hint_find, "Find", "Buscar");
 
+// ── Saved searches ──────────────────────────────────────────────
+
+msg!(
+    tui_save_search_prompt,
+    "Save search as @",
+    "Guardar b\u{fa}squeda como @"
+);
+msg!(tui_search_saved, "Saved search", "B\u{fa}squeda guardada");
+msg!(
+    tui_search_deleted,
+    "Deleted search",
+    "B\u{fa}squeda borrada"
+);
+msg!(
+    tui_no_search_to_save,
+    "No search to save",
+    "No hay b\u{fa}squeda que guardar"
+);
+msg!(
+    tui_saved_search_error,
+    "Error saving search",
+    "Error guardando la b\u{fa}squeda"
+);
+
+// ── Message states ──────────────────────────────────────────────
+
+msg!(tui_unread, "Unread", "No le\u{ed}dos");
+msg!(tui_starred_title, "Starred", "Destacados");
+msg!(tui_marked_read, "Marked read", "Marcado como le\u{ed}do");
+msg!(
+    tui_marked_unread,
+    "Marked unread",
+    "Marcado como no le\u{ed}do"
+);
+msg!(tui_starred, "Starred", "Destacado");
+msg!(tui_unstarred, "Unstarred", "Sin destacar");
+msg!(tui_tag_added, "Tagged", "Etiquetado");
+msg!(tui_tag_removed, "Untagged", "Etiqueta quitada");
+msg!(tui_tag_prompt, "Tag: #", "Etiqueta: #");
+msg!(
+    tui_state_error,
+    "Error with message states",
+    "Error con los estados de los mensajes"
+);
+msg!(
+    tui_state_unavailable,
+    "Message states are unavailable: their file could not be read",
+    "Los estados de los mensajes no est\u{e1}n disponibles: no se pudo leer su fichero"
+);
+
 // ── Search filter popup labels ──────────────────────────────────
 
 msg!(tui_filter_text, "Text:", "Texto:");
@@ -577,6 +674,19 @@ msg!(
 // ── CLI strings ─────────────────────────────────────────────────
 
 msg!(cli_searching, "Searching", "Buscando");
+msg!(cli_col_score, "Score", "Punt.");
+msg!(
+    cli_explain_empty,
+    "Empty query: matches every message",
+    "Consulta vac\u{ed}a: coinciden todos los mensajes"
+);
+msg!(
+    cli_explain_reads_bodies,
+    "Reads message bodies",
+    "Lee el cuerpo de los mensajes"
+);
+msg!(cli_yes, "yes", "s\u{ed}");
+msg!(cli_no, "no", "no");
 msg!(cli_exporting, "Exporting", "Exportando");
 msg!(cli_merging, "Merging", "Combinando");
 msg!(cli_extracting, "Extracting", "Extrayendo");
@@ -623,6 +733,163 @@ msg!(
 );
 msg!(cli_output_size, "Output size", "Tama\u{f1}o de salida");
 msg!(cli_output_file, "Output file", "Fichero de salida");
+msg!(cli_verifying, "Verifying", "Verificando");
+msg!(cli_dialect, "Dialect", "Dialecto");
+msg!(cli_line_endings, "Line endings", "Fines de l\u{ed}nea");
+msg!(
+    cli_no_anomalies,
+    "No anomalies found.",
+    "No se encontraron anomal\u{ed}as."
+);
+msg!(cli_offsets, "offsets", "offsets");
+msg!(cli_more, "more", "m\u{e1}s");
+msg!(cli_severity_info, "info", "info");
+msg!(cli_severity_warning, "warning", "aviso");
+msg!(cli_severity_error, "error", "error");
+msg!(
+    anomaly_bom,
+    "UTF-8 byte order mark at the start",
+    "Marca de orden de bytes UTF-8 al principio"
+);
+msg!(
+    anomaly_bare_separator,
+    "Bare 'From ' separators, without sender or date",
+    "Separadores 'From ' vac\u{ed}os, sin remitente ni fecha"
+);
+msg!(
+    anomaly_malformed_separator,
+    "First line is not a valid 'From ' separator",
+    "La primera l\u{ed}nea no es un separador 'From ' v\u{e1}lido"
+);
+msg!(
+    anomaly_leading_garbage,
+    "Content before the first 'From ' separator",
+    "Contenido antes del primer separador 'From '"
+);
+msg!(
+    anomaly_missing_blank_line,
+    "Separators without a blank line before them",
+    "Separadores sin l\u{ed}nea en blanco antes"
+);
+msg!(
+    anomaly_missing_header_end,
+    "Messages without a blank line after the headers",
+    "Mensajes sin l\u{ed}nea en blanco tras las cabeceras"
+);
+msg!(
+    anomaly_unquoted_from,
+    "Body lines starting with 'From ' left unquoted",
+    "L\u{ed}neas del cuerpo que empiezan por 'From ' sin escapar"
+);
+msg!(
+    anomaly_mixed_line_endings,
+    "Mixed LF and CRLF line endings",
+    "Fines de l\u{ed}nea LF y CRLF mezclados"
+);
+msg!(
+    anomaly_nul_bytes,
+    "NUL bytes in messages",
+    "Bytes NUL en mensajes"
+);
+msg!(
+    anomaly_content_length_mismatch,
+    "Content-Length headers that do not match the body",
+    "Cabeceras Content-Length que no coinciden con el cuerpo"
+);
+msg!(
+    anomaly_truncated,
+    "Truncated messages",
+    "Mensajes truncados"
+);
+msg!(
+    anomaly_oversized,
+    "Messages larger than the 256 MB the parser reads",
+    "Mensajes mayores que los 256 MB que lee el parser"
+);
+msg!(cli_repairing, "Repairing", "Reparando");
+msg!(
+    cli_repair_complete,
+    "Repair complete:",
+    "Reparaci\u{f3}n completa:"
+);
+msg!(
+    cli_separators_rewritten,
+    "Separators rewritten",
+    "Separadores reescritos"
+);
+msg!(
+    cli_line_endings_fixed,
+    "Line endings fixed",
+    "Fines de l\u{ed}nea corregidos"
+);
+msg!(
+    cli_finding_duplicates,
+    "Finding duplicates",
+    "Buscando duplicados"
+);
+msg!(
+    cli_duplicate_clusters,
+    "duplicate cluster(s),",
+    "grupo(s) de duplicados,"
+);
+msg!(
+    cli_duplicates_found,
+    "message(s) to remove",
+    "mensaje(s) a eliminar"
+);
+msg!(cli_dedup_by, "by", "por");
+msg!(cli_dedup_kept, "kept", "se queda");
+msg!(cli_dedup_duplicate, "duplicate", "duplicado");
+msg!(
+    cli_dedup_complete,
+    "Deduplication complete:",
+    "Eliminaci\u{f3}n de duplicados completa:"
+);
+msg!(cli_comparing, "Comparing", "Comparando");
+msg!(cli_diff_only_in_a, "Only in A", "Solo en A");
+msg!(cli_diff_only_in_b, "Only in B", "Solo en B");
+msg!(cli_diff_changed, "Changed", "Cambiados");
+msg!(
+    cli_diff_labels_changed,
+    "Labels changed",
+    "Etiquetas cambiadas"
+);
+msg!(
+    cli_diff_content_changed,
+    "Content changed",
+    "Contenido cambiado"
+);
+msg!(cli_diff_content, "content", "contenido");
+msg!(cli_diff_unchanged, "Unchanged", "Sin cambios");
+msg!(
+    cli_diff_identical,
+    "Both mailboxes hold the same messages.",
+    "Los dos buzones tienen los mismos mensajes."
+);
+msg!(
+    cli_diff_matched_by_content,
+    "Matched by content",
+    "Emparejados por contenido"
+);
+msg!(cli_splitting, "Splitting", "Dividiendo");
+msg!(
+    cli_split_complete,
+    "Split complete:",
+    "Divisi\u{f3}n completa:"
+);
+msg!(cli_split_parts, "part(s) in", "parte(s) en");
+msg!(cli_col_file, "File", "Fichero");
+msg!(cli_col_messages, "Messages", "Mensajes");
+msg!(
+    cli_split_duplicated,
+    "Messages with several labels are written to the part of each.",
+    "Los mensajes con varias etiquetas se escriben en la parte de cada una."
+);
+msg!(
+    cli_split_oversized,
+    "Warning: over the size limit, each holding one message larger than it",
+    "Aviso: superan el l\u{ed}mite de tama\u{f1}o, cada una con un mensaje mayor que \u{e9}l"
+);
 msg!(
     cli_no_attachments_found,
     "No messages with attachments found.",
@@ -636,6 +903,46 @@ msg!(
 msg!(cli_extracted, "Extracted", "Extra\u{ed}do");
 msg!(cli_attachments_to, "attachment(s) to", "adjunto(s) en");
 
+// ── Search facets ───────────────────────────────────────────────
+
+msg!(facet_senders, "Senders", "Remitentes");
+msg!(facet_domains, "Sender domains", "Dominios del remitente");
+msg!(facet_labels, "Labels", "Etiquetas");
+msg!(facet_years, "Years", "A\u{f1}os");
+msg!(facet_months, "Months", "Meses");
+msg!(facet_threads, "Threads", "Hilos");
+msg!(
+    facet_attachment_types,
+    "Attachment types",
+    "Tipos de adjunto"
+);
+msg!(facet_more, "more", "m\u{e1}s");
+msg!(tui_facets_title, " Facets ", " Facetas ");
+msg!(
+    tui_facets_footer,
+    "j/k:Navigate  Enter:Narrow  Esc:Close",
+    "j/k:Navegar  Enter:Acotar  Esc:Cerrar"
+);
+msg!(
+    tui_help_facets,
+    "Result facets",
+    "Facetas de los resultados"
+);
+
+/// Localized heading of a search facet.
+pub fn facet_title(kind: crate::search::facets::FacetKind) -> &'static str {
+    use crate::search::facets::FacetKind;
+    match kind {
+        FacetKind::Sender => facet_senders(),
+        FacetKind::Domain => facet_domains(),
+        FacetKind::Label => facet_labels(),
+        FacetKind::Year => facet_years(),
+        FacetKind::Month => facet_months(),
+        FacetKind::Thread => facet_threads(),
+        FacetKind::AttachmentType => facet_attachment_types(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,23 +1,40 @@
 //! Index construction, validation, and persistence.
 
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 use std::time::SystemTime;
 
 use sha2::{Digest, Sha256};
 use tracing::{debug, info, warn};
 
 use crate::error::MboxError;
-use crate::index::format::{IndexHeader, HASH_PREFIX_LEN, HEADER_SIZE, MAGIC, VERSION};
+use crate::index::format::{
+    CheckpointHeader, FulltextHeader, IndexHeader, CHECKPOINT_MAGIC, CHECKPOINT_VERSION,
+    FULLTEXT_MAGIC, FULLTEXT_VERSION, HASH_PREFIX_LEN, HEADER_SIZE, MAGIC, VERSION,
+};
+use crate::index::state::StateOverlay;
+use crate::model::address::AddressPool;
+use crate::model::attachment::AttachmentSummary;
 use crate::model::mail::MailEntry;
+use crate::parser::archive;
 use crate::parser::header;
 use crate::parser::mbox::MboxParser;
+use crate::parser::message_dir;
+use crate::parser::mime;
+use crate::search::inverted::InvertedIndex;
+use crate::source::{CheckpointTable, Codec};
+use crate::store::reader::MboxStore;
 
 /// Build (or load) the index for an MBOX file.
 ///
 /// 1. If a valid index already exists and `force_rebuild` is false, load it.
-/// 2. Otherwise, parse headers of all messages and write a new index file.
+/// 2. Otherwise, parse headers of all messages, read the ones with
+///    attachments to summarize them, and write a new index file.
+/// 3. Either way, lay the user's message states (see [`super::state`]) over
+///    the entries.
 ///
 /// Returns the list of [`MailEntry`] for every message in the MBOX.
 pub fn build_index(
@@ -35,6 +52,24 @@ pub fn build_index_cancelable(
     force_rebuild: bool,
     progress: Option<&dyn Fn(u64, u64)>,
     should_cancel: &dyn Fn() -> bool,
+) -> anyhow::Result<Vec<MailEntry>> {
+    let mut entries = load_or_build_index(mbox_path, force_rebuild, progress, should_cancel)?;
+    // Parsed and deserialized entries carry a copy of every address.
+    AddressPool::share(&mut entries);
+    match StateOverlay::load(mbox_path) {
+        Ok(overlay) => overlay.apply(&mut entries),
+        Err(e) => warn!(error = %e, "Could not read message states; showing the mailbox's own"),
+    }
+    Ok(entries)
+}
+
+/// The entries of [`build_index_cancelable`], before their addresses are
+/// shared.
+fn load_or_build_index(
+    mbox_path: &Path,
+    force_rebuild: bool,
+    progress: Option<&dyn Fn(u64, u64)>,
+    should_cancel: &dyn Fn() -> bool,
 ) -> anyhow::Result<Vec<MailEntry>> {
     if !force_rebuild {
         if let Some(entries) = load_index(mbox_path)? {
@@ -45,15 +80,65 @@ pub fn build_index_cancelable(
             );
             return Ok(entries);
         }
+        if let Some(entries) = append_to_index(mbox_path, progress, should_cancel)? {
+            return Ok(entries);
+        }
     }
 
     info!(path = %mbox_path.display(), "Building index");
 
+    let (mut entries, checkpoints) = if let Some(format) = message_dir::detect(mbox_path) {
+        let entries = message_dir::index_messages(mbox_path, format, progress, should_cancel)?;
+        (entries, None)
+    } else if let Some(kind) = archive::detect(mbox_path)? {
+        archive::index_archive(mbox_path, kind, progress, should_cancel)?
+    } else {
+        index_mbox(mbox_path, progress, should_cancel)?
+    };
+
+    if should_cancel() {
+        anyhow::bail!("indexing cancelled");
+    }
+
+    // A compressed mailbox's index is only valid with its checkpoints.
+    if let Some(table) = &checkpoints {
+        if let Err(e) = write_checkpoints(mbox_path, table) {
+            warn!(error = %e, "Could not write checkpoint file; continuing without persistence");
+        }
+    }
+
+    summarize_attachments(mbox_path, &mut entries, progress, should_cancel)?;
+    if should_cancel() {
+        anyhow::bail!("indexing cancelled");
+    }
+
+    // Write the index file
+    if let Err(e) = write_index(mbox_path, &entries) {
+        warn!(error = %e, "Could not write index file; continuing without persistence");
+    }
+
+    Ok(entries)
+}
+
+/// Parse the headers of every message in an MBOX file into entries, plus the
+/// checkpoint table when the file is compressed.
+fn index_mbox(
+    mbox_path: &Path,
+    progress: Option<&dyn Fn(u64, u64)>,
+    should_cancel: &dyn Fn() -> bool,
+) -> anyhow::Result<(Vec<MailEntry>, Option<CheckpointTable>)> {
     let parser = MboxParser::new(mbox_path)?;
+    if parser.codec().is_none() && parser.file_size() >= PARALLEL_MIN_SIZE {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if threads > 1 {
+            let entries = index_mbox_parallel(&parser, threads, progress, should_cancel)?;
+            return Ok((entries, None));
+        }
+    }
     let mut entries: Vec<MailEntry> = Vec::new();
     let mut sequence: u64 = 0;
 
-    parser.parse_headers_only(
+    let (_, checkpoints) = parser.parse_headers_with_checkpoints(
         &mut |offset, length, header_bytes| {
             if should_cancel() {
                 return false; // detiene el parseo
@@ -72,16 +157,170 @@ pub fn build_index_cancelable(
         progress,
     )?;
 
-    if should_cancel() {
-        anyhow::bail!("indexing cancelled");
+    Ok((entries, checkpoints))
+}
+
+/// Smallest plain MBOX indexed on several threads; below this, starting the
+/// workers costs more than it saves.
+const PARALLEL_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// [`index_mbox`] for a plain MBOX on up to `threads` threads: the file is
+/// split at message separators, each chunk's headers are parsed on a thread
+/// of its own and the entries are stitched back in file order, numbered as
+/// the serial scan numbers them.
+fn index_mbox_parallel(
+    parser: &MboxParser,
+    threads: usize,
+    progress: Option<&dyn Fn(u64, u64)>,
+    should_cancel: &dyn Fn() -> bool,
+) -> anyhow::Result<Vec<MailEntry>> {
+    let chunks = parser.split_at_separators(threads)?;
+    debug!(chunks = chunks.len(), "Indexing in parallel");
+    let cancelled = AtomicBool::new(false);
+    let (progress_tx, progress_rx) = mpsc::channel::<u64>();
+
+    let results = std::thread::scope(|scope| {
+        let workers: Vec<_> = chunks
+            .iter()
+            .map(|chunk| {
+                let progress_tx = progress_tx.clone();
+                let cancelled = &cancelled;
+                scope.spawn(move || {
+                    let mut entries: Vec<MailEntry> = Vec::new();
+                    parser.parse_chunk_headers(
+                        chunk,
+                        &mut |offset, length, header_bytes| {
+                            if cancelled.load(Ordering::Relaxed) {
+                                return false;
+                            }
+                            match header::parse_headers_to_entry(header_bytes, offset, length, 0) {
+                                Ok(entry) => entries.push(entry),
+                                Err(e) => {
+                                    warn!(offset = offset, error = %e, "Skipping unparseable message");
+                                }
+                            }
+                            true
+                        },
+                        &mut |bytes| {
+                            let _ = progress_tx.send(bytes);
+                        },
+                    )?;
+                    Ok::<_, MboxError>(entries)
+                })
+            })
+            .collect();
+        drop(progress_tx);
+
+        // The callbacks are not `Sync`: report progress and poll for
+        // cancellation here, until every worker has dropped its sender.
+        let mut done: u64 = 0;
+        for bytes in progress_rx {
+            done += bytes;
+            if should_cancel() {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            if let Some(cb) = progress {
+                cb(done, parser.file_size());
+            }
+        }
+        workers
+            .into_iter()
+            .map(|w| w.join().unwrap_or_else(|p| std::panic::resume_unwind(p)))
+            .collect::<Vec<_>>()
+    });
+
+    let mut entries: Vec<MailEntry> = Vec::new();
+    for chunk_entries in results {
+        entries.extend(chunk_entries?);
+    }
+    for (sequence, entry) in entries.iter_mut().enumerate() {
+        entry.sequence = sequence as u64;
     }
+    Ok(entries)
+}
 
-    // Write the index file
-    if let Err(e) = write_index(mbox_path, &entries) {
-        warn!(error = %e, "Could not write index file; continuing without persistence");
+/// Fill in [`MailEntry::attachments`] for the entries with
+/// `has_attachments` set, the only messages read in full while indexing.
+/// They are read on up to one thread per core, each with a store of its own
+/// over a contiguous run of them; `progress` receives the bytes read out of
+/// the total length of those messages. A message whose attachments cannot
+/// be listed keeps an empty list.
+fn summarize_attachments(
+    mbox_path: &Path,
+    entries: &mut [MailEntry],
+    progress: Option<&dyn Fn(u64, u64)>,
+    should_cancel: &dyn Fn() -> bool,
+) -> anyhow::Result<()> {
+    let mut pending: Vec<&mut MailEntry> =
+        entries.iter_mut().filter(|e| e.has_attachments).collect();
+    if pending.is_empty() {
+        return Ok(());
     }
+    let total: u64 = pending.iter().map(|e| e.length).sum();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let run = pending.len().div_ceil(threads);
+    debug!(messages = pending.len(), "Summarizing attachments");
+    let cancelled = AtomicBool::new(false);
+    let (progress_tx, progress_rx) = mpsc::channel::<u64>();
 
-    Ok(entries)
+    let results = std::thread::scope(|scope| {
+        let workers: Vec<_> = pending
+            .chunks_mut(run)
+            .map(|chunk| {
+                let progress_tx = progress_tx.clone();
+                let cancelled = &cancelled;
+                scope.spawn(move || {
+                    let mut store = MboxStore::open(mbox_path)?;
+                    for entry in chunk {
+                        if cancelled.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        entry.attachments = attachment_summaries(&mut store, entry);
+                        let _ = progress_tx.send(entry.length);
+                    }
+                    Ok::<_, MboxError>(())
+                })
+            })
+            .collect();
+        drop(progress_tx);
+
+        let mut done: u64 = 0;
+        for bytes in progress_rx {
+            done += bytes;
+            if should_cancel() {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            if let Some(cb) = progress {
+                cb(done, total);
+            }
+        }
+        workers
+            .into_iter()
+            .map(|w| w.join().unwrap_or_else(|p| std::panic::resume_unwind(p)))
+            .collect::<Vec<_>>()
+    });
+    for result in results {
+        result?;
+    }
+    Ok(())
+}
+
+/// The summaries of a message's attachments, inline parts left out.
+fn attachment_summaries(store: &mut MboxStore, entry: &MailEntry) -> Vec<AttachmentSummary> {
+    match store
+        .get_raw_message(entry)
+        .and_then(|raw| mime::list_attachments(&raw))
+    {
+        Ok(attachments) => attachments
+            .iter()
+            .filter(|a| !a.is_inline)
+            .map(AttachmentSummary::from)
+            .collect(),
+        Err(e) => {
+            warn!(offset = entry.offset, error = %e, "Could not list attachments");
+            Vec::new()
+        }
+    }
 }
 
 /// Attempt to load an existing index. Returns `None` if the index is missing or invalid.
@@ -116,52 +355,75 @@ fn load_index_from_file(
     let idx_len = std::fs::metadata(idx_path)
         .map_err(|e| MboxError::io(idx_path, e))?
         .len();
-    let mbox_len = std::fs::metadata(mbox_path).map(|m| m.len()).unwrap_or(0);
-    if !index_size_acceptable(idx_len, mbox_len) {
+    let source = source_fingerprint(mbox_path)?;
+    let Some(content_size) = content_size(mbox_path, &source)? else {
+        debug!("Checkpoint file of compressed mailbox missing or stale");
+        return Ok(None);
+    };
+    if !index_size_acceptable(idx_len, content_size) {
         debug!("Index file implausibly large; ignoring");
         return Ok(None);
     }
 
     let data = std::fs::read(idx_path).map_err(|e| MboxError::io(idx_path, e))?;
+    let Some(header) = read_index_header(idx_path, &data)? else {
+        return Ok(None);
+    };
 
-    if data.len() < HEADER_SIZE {
-        debug!("Index file too small");
+    // Validate against current MBOX file
+    if header.mbox_file_size != source.size {
+        debug!("MBOX file size changed");
         return Ok(None);
     }
 
-    let header: IndexHeader =
-        bincode::deserialize(&data[..HEADER_SIZE]).map_err(|e| MboxError::InvalidIndex {
-            path: idx_path.to_path_buf(),
-            reason: format!("Header deserialization failed: {e}"),
-        })?;
-
-    if let Err(reason) = header.validate() {
-        debug!(reason = %reason, "Index header invalid");
+    if header.mbox_modified_time != source.modified {
+        debug!("MBOX modification time changed");
         return Ok(None);
     }
 
-    // Validate against current MBOX file
-    let mbox_meta = std::fs::metadata(mbox_path).map_err(|e| MboxError::io(mbox_path, e))?;
+    // Verify SHA-256 of first 4 KB (or of the directory listing)
+    if header.sha256_first_4kb != source.hash {
+        debug!("MBOX content hash changed");
+        return Ok(None);
+    }
 
-    if header.mbox_file_size != mbox_meta.len() {
-        debug!("MBOX file size changed");
+    let Some(entries) = read_index_entries(idx_path, &data, &header)? else {
+        return Ok(None);
+    };
+    if !entries_in_bounds(&entries, content_size) {
+        debug!("Index contains entries beyond the MBOX bounds");
         return Ok(None);
     }
 
-    let mbox_mtime = mtime_nanos(&mbox_meta);
+    Ok(Some(entries))
+}
 
-    if header.mbox_modified_time != mbox_mtime {
-        debug!("MBOX modification time changed");
+/// Parse and validate the header of an index file read into `data`.
+fn read_index_header(idx_path: &Path, data: &[u8]) -> anyhow::Result<Option<IndexHeader>> {
+    if data.len() < HEADER_SIZE {
+        debug!("Index file too small");
         return Ok(None);
     }
 
-    // Verify SHA-256 of first 4 KB
-    let current_hash = sha256_first_n(mbox_path, HASH_PREFIX_LEN)?;
-    if header.sha256_first_4kb != current_hash {
-        debug!("MBOX content hash changed");
+    let header: IndexHeader =
+        bincode::deserialize(&data[..HEADER_SIZE]).map_err(|e| MboxError::InvalidIndex {
+            path: idx_path.to_path_buf(),
+            reason: format!("Header deserialization failed: {e}"),
+        })?;
+
+    if let Err(reason) = header.validate() {
+        debug!(reason = %reason, "Index header invalid");
         return Ok(None);
     }
+    Ok(Some(header))
+}
 
+/// Deserialize the entries of an index file whose `header` has been read.
+fn read_index_entries(
+    idx_path: &Path,
+    data: &[u8],
+    header: &IndexHeader,
+) -> anyhow::Result<Option<Vec<MailEntry>>> {
     let entries: Vec<MailEntry> =
         bincode::deserialize(&data[HEADER_SIZE..]).map_err(|e| MboxError::InvalidIndex {
             path: idx_path.to_path_buf(),
@@ -172,83 +434,482 @@ fn load_index_from_file(
         debug!("Message count mismatch");
         return Ok(None);
     }
+    Ok(Some(entries))
+}
 
-    // A corrupt or crafted index could carry offsets/lengths pointing outside
-    // the MBOX; reading such an entry would attempt an arbitrarily large
-    // allocation before the read fails. Treat it as invalid and rebuild.
-    let mbox_len = mbox_meta.len();
-    let in_bounds = entries.iter().all(|e| {
+/// Whether every entry lies within `content_size` bytes.
+///
+/// A corrupt or crafted index could carry offsets/lengths pointing outside
+/// the MBOX; reading such an entry would attempt an arbitrarily large
+/// allocation before the read fails. Such an index is treated as invalid.
+fn entries_in_bounds(entries: &[MailEntry], content_size: u64) -> bool {
+    entries.iter().all(|e| {
         e.offset
             .checked_add(e.length)
-            .is_some_and(|end| end <= mbox_len)
-    });
-    if !in_bounds {
-        debug!("Index contains entries beyond the MBOX bounds");
-        return Ok(None);
-    }
-
-    Ok(Some(entries))
+            .is_some_and(|end| end <= content_size)
+    })
 }
 
 /// Write the index to disk.
 fn write_index(mbox_path: &Path, entries: &[MailEntry]) -> anyhow::Result<()> {
-    let mbox_meta = std::fs::metadata(mbox_path).map_err(|e| MboxError::io(mbox_path, e))?;
-
-    let mbox_mtime = mtime_nanos(&mbox_meta);
+    let source = source_fingerprint(mbox_path)?;
+    let padded_header = index_header_bytes(&source, entries.len() as u64)?;
+    let entries_bytes = bincode::serialize(entries)?;
 
-    let hash = sha256_first_n(mbox_path, HASH_PREFIX_LEN)?;
+    let path = write_sidecar(
+        &index_path_for(mbox_path),
+        &cache_index_path_for(mbox_path),
+        &padded_header,
+        &entries_bytes,
+    )?;
+    info!(path = %path.display(), "Index written");
+    Ok(())
+}
 
+/// The index header for `source` and `message_count` entries, padded to
+/// [`HEADER_SIZE`].
+fn index_header_bytes(source: &SourceFingerprint, message_count: u64) -> anyhow::Result<Vec<u8>> {
     let header = IndexHeader {
         magic: *MAGIC,
         version: VERSION,
         flags: 0,
-        message_count: entries.len() as u64,
-        mbox_file_size: mbox_meta.len(),
-        mbox_modified_time: mbox_mtime,
-        sha256_first_4kb: hash,
+        message_count,
+        mbox_file_size: source.size,
+        mbox_modified_time: source.modified,
+        sha256_first_4kb: source.hash,
     };
-
     let header_bytes = bincode::serialize(&header)?;
-    let entries_bytes = bincode::serialize(entries)?;
 
     // Pad header to HEADER_SIZE
     let mut padded_header = vec![0u8; HEADER_SIZE];
     let copy_len = header_bytes.len().min(HEADER_SIZE);
     padded_header[..copy_len].copy_from_slice(&header_bytes[..copy_len]);
+    Ok(padded_header)
+}
 
-    // Try writing next to the MBOX file first
-    let idx_path = index_path_for(mbox_path);
-    match write_index_to_file(&idx_path, &padded_header, &entries_bytes) {
-        Ok(()) => {
-            info!(path = %idx_path.display(), "Index written");
-            return Ok(());
+/// Bring the index of a plain MBOX that has only grown since it was indexed
+/// up to date by parsing just the appended tail.
+///
+/// Applies when the first 4 KB are unchanged, the file is larger than when
+/// indexed, and the indexed messages are still where the index says: every
+/// entry within the old size, every one still starting with a `From `
+/// separator, and the last one still the same message. The tail is parsed
+/// from the start of the last indexed message rather than from its end: that
+/// message ran to the old end of the file, and a client that was still
+/// writing it may have appended more of it. Returns `None` when the mailbox
+/// is not in that state (compressed, directory and archive sources never are)
+/// and a full rebuild is needed.
+fn append_to_index(
+    mbox_path: &Path,
+    progress: Option<&dyn Fn(u64, u64)>,
+    should_cancel: &dyn Fn() -> bool,
+) -> anyhow::Result<Option<Vec<MailEntry>>> {
+    if message_dir::detect(mbox_path).is_some()
+        || Codec::detect(mbox_path)?.is_some()
+        || archive::detect(mbox_path)?.is_some()
+    {
+        return Ok(None);
+    }
+    let Some(idx_path) = [index_path_for(mbox_path), cache_index_path_for(mbox_path)]
+        .into_iter()
+        .find(|p| p.exists())
+    else {
+        return Ok(None);
+    };
+    let source = source_fingerprint(mbox_path)?;
+    let idx_len = std::fs::metadata(&idx_path)
+        .map_err(|e| MboxError::io(&idx_path, e))?
+        .len();
+    if !index_size_acceptable(idx_len, source.size) {
+        return Ok(None);
+    }
+
+    let data = std::fs::read(&idx_path).map_err(|e| MboxError::io(&idx_path, e))?;
+    let Some(header) = read_index_header(&idx_path, &data)? else {
+        return Ok(None);
+    };
+    if header.sha256_first_4kb != source.hash || header.mbox_file_size >= source.size {
+        return Ok(None);
+    }
+    let Some(mut entries) = read_index_entries(&idx_path, &data, &header)? else {
+        return Ok(None);
+    };
+    drop(data);
+    if !entries_in_bounds(&entries, header.mbox_file_size)
+        || !separators_intact(mbox_path, &entries)?
+    {
+        return Ok(None);
+    }
+    let Some(last) = entries.pop() else {
+        return Ok(None);
+    };
+
+    info!(
+        path = %mbox_path.display(),
+        from = last.offset,
+        "Mailbox grew; indexing appended messages"
+    );
+    let parser = MboxParser::new(mbox_path)?;
+    let mut added: Vec<MailEntry> = Vec::new();
+    let mut sequence = entries.len() as u64;
+    parser.parse_headers_from(
+        last.offset,
+        &mut |offset, length, header_bytes| {
+            if should_cancel() {
+                return false;
+            }
+            match header::parse_headers_to_entry(header_bytes, offset, length, sequence) {
+                Ok(entry) => {
+                    added.push(entry);
+                    sequence += 1;
+                }
+                Err(e) => {
+                    warn!(offset = offset, error = %e, "Skipping unparseable message");
+                }
+            }
+            true
+        },
+        progress,
+    )?;
+    if should_cancel() {
+        anyhow::bail!("indexing cancelled");
+    }
+    // The re-parsed last message must still be the one that was indexed.
+    if !added
+        .first()
+        .is_some_and(|e| e.offset == last.offset && e.message_id == last.message_id)
+    {
+        debug!("Last indexed message changed; rebuilding");
+        return Ok(None);
+    }
+    summarize_attachments(mbox_path, &mut added, progress, should_cancel)?;
+    if should_cancel() {
+        anyhow::bail!("indexing cancelled");
+    }
+
+    let count = entries.len() as u64 + added.len() as u64;
+    if let Err(e) = append_index_file(&idx_path, &source, count, &last, &added) {
+        debug!(error = %e, "Cannot extend index in place, rewriting it");
+        entries.extend(added);
+        if let Err(e) = write_index(mbox_path, &entries) {
+            warn!(error = %e, "Could not write index file; continuing without persistence");
+        }
+        return Ok(Some(entries));
+    }
+    info!(path = %idx_path.display(), added = count - entries.len() as u64, "Index extended");
+    entries.extend(added);
+    Ok(Some(entries))
+}
+
+/// Whether every one of `entries` still starts with a `From ` separator in
+/// the mailbox as it is now. Catches a mailbox rewritten with the same first
+/// 4 KB, such as one compacted by its client. One 5-byte read per message,
+/// little next to parsing what was appended.
+fn separators_intact(mbox_path: &Path, entries: &[MailEntry]) -> anyhow::Result<bool> {
+    let mut file = File::open(mbox_path).map_err(|e| MboxError::io(mbox_path, e))?;
+    for entry in entries {
+        let mut start = [0u8; 5];
+        file.seek(SeekFrom::Start(entry.offset))
+            .and_then(|_| file.read_exact(&mut start))
+            .map_err(|e| MboxError::io(mbox_path, e))?;
+        if &start != b"From " {
+            debug!(offset = entry.offset, "Indexed message moved; rebuilding");
+            return Ok(false);
         }
+    }
+    Ok(true)
+}
+
+/// Extend an index file in place: drop its last entry (`replaced`), append
+/// `added` and rewrite the entry count and the header, so an index of a
+/// million messages is not rewritten for a handful of new ones.
+fn append_index_file(
+    idx_path: &Path,
+    source: &SourceFingerprint,
+    count: u64,
+    replaced: &MailEntry,
+    added: &[MailEntry],
+) -> anyhow::Result<()> {
+    let map_err = |e| MboxError::io(idx_path, e);
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(idx_path)
+        .map_err(map_err)?;
+
+    // The last entry is the tail of the file; make sure it serializes to
+    // exactly those bytes before cutting them off.
+    let replaced_bytes = bincode::serialize(replaced)?;
+    let len = file.metadata().map_err(map_err)?.len();
+    let cut = len
+        .checked_sub(replaced_bytes.len() as u64)
+        .filter(|&cut| cut >= (HEADER_SIZE + 8) as u64)
+        .ok_or_else(|| anyhow::anyhow!("index file too short"))?;
+    let mut tail = vec![0u8; replaced_bytes.len()];
+    file.seek(SeekFrom::Start(cut)).map_err(map_err)?;
+    file.read_exact(&mut tail).map_err(map_err)?;
+    if tail != replaced_bytes {
+        anyhow::bail!("last index entry does not match");
+    }
+
+    // Entries first and the header last: an interrupted update leaves a
+    // header that no longer matches the mailbox, and the index is rebuilt.
+    file.set_len(cut).map_err(map_err)?;
+    file.seek(SeekFrom::End(0)).map_err(map_err)?;
+    let mut out = std::io::BufWriter::new(&mut file);
+    for entry in added {
+        bincode::serialize_into(&mut out, entry)?;
+    }
+    out.flush().map_err(map_err)?;
+    drop(out);
+    // bincode prefixes the entry vector with its length as a u64.
+    file.seek(SeekFrom::Start(HEADER_SIZE as u64))
+        .map_err(map_err)?;
+    file.write_all(&count.to_le_bytes()).map_err(map_err)?;
+    file.seek(SeekFrom::Start(0)).map_err(map_err)?;
+    file.write_all(&index_header_bytes(source, count)?)
+        .map_err(map_err)?;
+    file.flush().map_err(map_err)?;
+    Ok(())
+}
+
+/// Write the checkpoint table of a compressed mailbox to disk.
+fn write_checkpoints(mbox_path: &Path, table: &CheckpointTable) -> anyhow::Result<()> {
+    let source = source_fingerprint(mbox_path)?;
+    let header = CheckpointHeader {
+        magic: *CHECKPOINT_MAGIC,
+        version: CHECKPOINT_VERSION,
+        mbox_file_size: source.size,
+        mbox_modified_time: source.modified,
+        sha256_first_4kb: source.hash,
+    };
+    let path = write_sidecar(
+        &checkpoint_path_for(mbox_path),
+        &cache_checkpoint_path_for(mbox_path),
+        &bincode::serialize(&header)?,
+        &bincode::serialize(table)?,
+    )?;
+    info!(path = %path.display(), count = table.checkpoints.len(), "Checkpoints written");
+    Ok(())
+}
+
+/// Write a sidecar file next to the mailbox, falling back to `cache_path`
+/// when the mailbox's directory is not writable. Returns where it went.
+fn write_sidecar(
+    path: &Path,
+    cache_path: &Path,
+    header: &[u8],
+    body: &[u8],
+) -> anyhow::Result<PathBuf> {
+    // Try writing next to the MBOX file first
+    match write_sidecar_file(path, header, body) {
+        Ok(()) => return Ok(path.to_path_buf()),
         Err(e) => {
-            debug!(error = %e, "Cannot write index next to MBOX, trying cache dir");
+            debug!(error = %e, "Cannot write next to MBOX, trying cache dir");
         }
     }
 
     // Fallback: write to cache directory
-    let cache_path = cache_index_path_for(mbox_path);
     if let Some(parent) = cache_path.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    write_index_to_file(&cache_path, &padded_header, &entries_bytes)?;
-    info!(path = %cache_path.display(), "Index written to cache");
-    Ok(())
+    write_sidecar_file(cache_path, header, body)?;
+    Ok(cache_path.to_path_buf())
 }
 
-/// Write header + entries to a file.
-fn write_index_to_file(path: &Path, header: &[u8], entries: &[u8]) -> anyhow::Result<()> {
+/// Write header + body to a file.
+fn write_sidecar_file(path: &Path, header: &[u8], body: &[u8]) -> anyhow::Result<()> {
     let mut file = File::create(path).map_err(|e| MboxError::io(path, e))?;
     file.write_all(header).map_err(|e| MboxError::io(path, e))?;
-    file.write_all(entries)
-        .map_err(|e| MboxError::io(path, e))?;
+    file.write_all(body).map_err(|e| MboxError::io(path, e))?;
     file.flush().map_err(|e| MboxError::io(path, e))?;
     Ok(())
 }
 
-/// Compute SHA-256 of the first `n` bytes of a file.
+/// Load the checkpoint table of a compressed mailbox, if one was recorded
+/// for the file as it is now. `None` for a plain mailbox.
+pub fn load_checkpoints(mbox_path: &Path) -> Option<CheckpointTable> {
+    let source = source_fingerprint(mbox_path).ok()?;
+    load_checkpoints_for(mbox_path, &source)
+}
+
+fn load_checkpoints_for(mbox_path: &Path, source: &SourceFingerprint) -> Option<CheckpointTable> {
+    let codec = Codec::detect(mbox_path).ok()??;
+    [
+        checkpoint_path_for(mbox_path),
+        cache_checkpoint_path_for(mbox_path),
+    ]
+    .iter()
+    .find(|p| p.exists())
+    .and_then(|path| match read_checkpoints(path, codec, source) {
+        Ok(table) => table,
+        Err(e) => {
+            debug!(path = %path.display(), error = %e, "Unreadable checkpoint file");
+            None
+        }
+    })
+}
+
+/// Read a checkpoint file and validate it against the mailbox.
+fn read_checkpoints(
+    path: &Path,
+    codec: Codec,
+    source: &SourceFingerprint,
+) -> anyhow::Result<Option<CheckpointTable>> {
+    let data = std::fs::read(path).map_err(|e| MboxError::io(path, e))?;
+    let mut cursor = data.as_slice();
+    let header: CheckpointHeader = bincode::deserialize_from(&mut cursor)?;
+    if let Err(reason) = header.validate() {
+        debug!(reason = %reason, "Checkpoint header invalid");
+        return Ok(None);
+    }
+    if header.mbox_file_size != source.size
+        || header.mbox_modified_time != source.modified
+        || header.sha256_first_4kb != source.hash
+    {
+        debug!("Compressed mailbox changed since its checkpoints were recorded");
+        return Ok(None);
+    }
+    let table: CheckpointTable = bincode::deserialize_from(&mut cursor)?;
+    if table.codec != codec || !table.is_well_formed() {
+        debug!("Checkpoint table does not match the mailbox");
+        return Ok(None);
+    }
+    Ok(Some(table))
+}
+
+/// Write the full-text index of a mailbox to disk, next to its index.
+pub fn write_fulltext(mbox_path: &Path, index: &InvertedIndex) -> anyhow::Result<()> {
+    let source = source_fingerprint(mbox_path)?;
+    let header = FulltextHeader {
+        magic: *FULLTEXT_MAGIC,
+        version: FULLTEXT_VERSION,
+        index_version: VERSION,
+        mbox_file_size: source.size,
+        mbox_modified_time: source.modified,
+        sha256_first_4kb: source.hash,
+    };
+    let path = write_sidecar(
+        &fulltext_path_for(mbox_path),
+        &cache_fulltext_path_for(mbox_path),
+        &bincode::serialize(&header)?,
+        &bincode::serialize(index)?,
+    )?;
+    info!(path = %path.display(), "Full-text index written");
+    Ok(())
+}
+
+/// Load the full-text index of a mailbox, if one was built for the file as
+/// it is now and for an index of `message_count` entries.
+pub fn load_fulltext(mbox_path: &Path, message_count: usize) -> Option<InvertedIndex> {
+    let source = source_fingerprint(mbox_path).ok()?;
+    [
+        fulltext_path_for(mbox_path),
+        cache_fulltext_path_for(mbox_path),
+    ]
+    .iter()
+    .find(|p| p.exists())
+    .and_then(|path| match read_fulltext(path, &source) {
+        Ok(index) => index,
+        Err(e) => {
+            debug!(path = %path.display(), error = %e, "Unreadable full-text index");
+            None
+        }
+    })
+    .filter(|index| index.message_count() == message_count as u64)
+}
+
+/// Read a full-text index file and validate it against the mailbox.
+fn read_fulltext(path: &Path, source: &SourceFingerprint) -> anyhow::Result<Option<InvertedIndex>> {
+    let data = std::fs::read(path).map_err(|e| MboxError::io(path, e))?;
+    let mut cursor = data.as_slice();
+    let header: FulltextHeader = bincode::deserialize_from(&mut cursor)?;
+    if let Err(reason) = header.validate() {
+        debug!(reason = %reason, "Full-text index header invalid");
+        return Ok(None);
+    }
+    if header.mbox_file_size != source.size
+        || header.mbox_modified_time != source.modified
+        || header.sha256_first_4kb != source.hash
+    {
+        debug!("Mailbox changed since its full-text index was built");
+        return Ok(None);
+    }
+    Ok(Some(bincode::deserialize_from(&mut cursor)?))
+}
+
+/// The size index offsets are bounded by: the file size for a plain MBOX, a
+/// plain tar or a directory, the content size from the checkpoint table for a
+/// compressed file or a zip archive. `None` when such a file has no valid
+/// table.
+fn content_size(mbox_path: &Path, source: &SourceFingerprint) -> anyhow::Result<Option<u64>> {
+    if message_dir::detect(mbox_path).is_some() || Codec::detect(mbox_path)?.is_none() {
+        return Ok(Some(source.size));
+    }
+    Ok(load_checkpoints_for(mbox_path, source).map(|t| t.content_size))
+}
+
+/// What an index is validated against: the size, modification time and a
+/// content hash of the mailbox it was built from.
+struct SourceFingerprint {
+    size: u64,
+    modified: i64,
+    hash: [u8; 32],
+}
+
+/// Fingerprint the mailbox at `mbox_path`.
+///
+/// For an MBOX file: its length, mtime and the SHA-256 of its first 4 KB.
+/// For a directory (Maildir or `.eml` folder): the total size of its message
+/// files, the newest mtime among them, and a SHA-256 over the sorted listing
+/// of relative paths and sizes. Flags live in Maildir file names, so a
+/// delivered, deleted or re-flagged message changes the listing and the index
+/// is rebuilt.
+fn source_fingerprint(mbox_path: &Path) -> anyhow::Result<SourceFingerprint> {
+    if let Some(format) = message_dir::detect(mbox_path) {
+        let files = message_dir::list_messages(mbox_path, format)?;
+        let mut hasher = Sha256::new();
+        for f in &files {
+            hasher.update(f.rel_path.as_bytes());
+            hasher.update([0]);
+            hasher.update(f.size.to_le_bytes());
+        }
+        return Ok(SourceFingerprint {
+            size: files.iter().map(|f| f.size).sum(),
+            modified: files
+                .iter()
+                .map(|f| nanos_since_epoch(f.modified))
+                .max()
+                .unwrap_or(0),
+            hash: hasher.finalize().into(),
+        });
+    }
+
+    let meta = std::fs::metadata(mbox_path).map_err(|e| MboxError::io(mbox_path, e))?;
+    Ok(SourceFingerprint {
+        size: meta.len(),
+        modified: mtime_nanos(&meta),
+        hash: sha256_first_n(mbox_path, HASH_PREFIX_LEN)?,
+    })
+}
+
+/// Size in bytes of the mailbox at `mbox_path`: the file length for an MBOX
+/// (compressed size for a compressed one, which is what indexing progress is
+/// measured in), the total of all message files for a directory mailbox. Used
+/// for progress bars and statistics, where the length of a directory entry
+/// would be meaningless.
+pub fn mailbox_size(mbox_path: &Path) -> anyhow::Result<u64> {
+    if let Some(format) = message_dir::detect(mbox_path) {
+        return Ok(message_dir::list_messages(mbox_path, format)?
+            .iter()
+            .map(|f| f.size)
+            .sum());
+    }
+    Ok(std::fs::metadata(mbox_path)
+        .map_err(|e| MboxError::io(mbox_path, e))?
+        .len())
+}
+
 /// Modification time of a file as nanoseconds since the Unix epoch.
 ///
 /// Nanoseconds rather than seconds: a mailbox rewritten in the same second the
@@ -257,13 +918,17 @@ fn write_index_to_file(path: &Path, header: &[u8], entries: &[u8]) -> anyhow::Re
 /// epoch, or beyond what an `i64` of nanoseconds can hold (year 2262), fall
 /// back to `0` — the index is then simply rebuilt.
 fn mtime_nanos(meta: &std::fs::Metadata) -> i64 {
-    meta.modified()
-        .ok()
-        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+    nanos_since_epoch(meta.modified().ok())
+}
+
+/// [`mtime_nanos`] for a bare timestamp.
+fn nanos_since_epoch(time: Option<SystemTime>) -> i64 {
+    time.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
         .and_then(|d| i64::try_from(d.as_nanos()).ok())
         .unwrap_or(0)
 }
 
+/// Compute SHA-256 of the first `n` bytes of a file.
 fn sha256_first_n(path: &Path, n: usize) -> anyhow::Result<[u8; 32]> {
     let file = File::open(path).map_err(|e| MboxError::io(path, e))?;
     // Read exactly the first `n` bytes (or the whole file if shorter) with a
@@ -292,15 +957,69 @@ pub fn index_path_for(mbox_path: &Path) -> PathBuf {
 ///
 /// Example: `~/.cache/mboxshell/<sha256_of_path>.idx`
 pub fn cache_index_path_for(mbox_path: &Path) -> PathBuf {
+    cache_path_for(mbox_path, "idx")
+}
+
+/// Checkpoint file of a compressed mailbox, next to its index.
+///
+/// Example: `/data/mail.mbox.gz` → `/data/.mail.mbox.gz.mboxshell.ckpt`
+pub fn checkpoint_path_for(mbox_path: &Path) -> PathBuf {
+    let filename = mbox_path.file_name().unwrap_or_default().to_string_lossy();
+    mbox_path.with_file_name(format!(".{filename}.mboxshell.ckpt"))
+}
+
+/// Fallback checkpoint path inside the user cache directory.
+pub fn cache_checkpoint_path_for(mbox_path: &Path) -> PathBuf {
+    cache_path_for(mbox_path, "ckpt")
+}
+
+/// Full-text index file, next to the index.
+///
+/// Example: `/data/mail.mbox` → `/data/.mail.mbox.mboxshell.fts`
+pub fn fulltext_path_for(mbox_path: &Path) -> PathBuf {
+    let filename = mbox_path.file_name().unwrap_or_default().to_string_lossy();
+    mbox_path.with_file_name(format!(".{filename}.mboxshell.fts"))
+}
+
+/// Fallback full-text index path inside the user cache directory.
+pub fn cache_fulltext_path_for(mbox_path: &Path) -> PathBuf {
+    cache_path_for(mbox_path, "fts")
+}
+
+/// Message-state overlay (read, starred, tags), next to the index.
+///
+/// Example: `/data/mail.mbox` → `/data/.mail.mbox.mboxshell.state`
+pub fn state_path_for(mbox_path: &Path) -> PathBuf {
+    let filename = mbox_path.file_name().unwrap_or_default().to_string_lossy();
+    mbox_path.with_file_name(format!(".{filename}.mboxshell.state"))
+}
+
+/// Fallback state overlay path inside the user data directory rather than
+/// the cache: unlike an index, the overlay cannot be rebuilt.
+///
+/// Example: `~/.local/share/mboxshell/<sha256_of_path>.state`
+pub fn data_state_path_for(mbox_path: &Path) -> PathBuf {
+    let data_dir = dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from(".local/share"))
+        .join("mboxshell");
+    hashed_path_for(&data_dir, mbox_path, "state")
+}
+
+/// `~/.cache/mboxshell/<sha256_of_path>.<extension>`
+fn cache_path_for(mbox_path: &Path, extension: &str) -> PathBuf {
     let cache_dir = dirs::cache_dir()
         .unwrap_or_else(|| PathBuf::from(".cache"))
         .join("mboxshell");
+    hashed_path_for(&cache_dir, mbox_path, extension)
+}
 
+/// `<dir>/<sha256_of_path>.<extension>`
+fn hashed_path_for(dir: &Path, mbox_path: &Path, extension: &str) -> PathBuf {
     let mut hasher = Sha256::new();
     hasher.update(mbox_path.to_string_lossy().as_bytes());
     let hash = format!("{:x}", hasher.finalize());
 
-    cache_dir.join(format!("{hash}.idx"))
+    dir.join(format!("{hash}.{extension}"))
 }
 
 /// Return the size in bytes of the index file for the given MBOX (0 if missing).
@@ -312,6 +1031,15 @@ pub fn index_file_size(mbox_path: &Path) -> u64 {
         .unwrap_or(0)
 }
 
+/// Return the size in bytes of the full-text index of the given MBOX (0 if
+/// missing).
+pub fn fulltext_file_size(mbox_path: &Path) -> u64 {
+    std::fs::metadata(fulltext_path_for(mbox_path))
+        .or_else(|_| std::fs::metadata(cache_fulltext_path_for(mbox_path)))
+        .map(|m| m.len())
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -358,4 +1086,190 @@ mod tests {
         write_index(&mbox_path, &entries).expect("write valid index");
         assert!(load_index(&mbox_path).expect("load").is_some());
     }
+
+    #[test]
+    fn test_parallel_index_matches_serial() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("parallel.mbox");
+        let mut text = String::new();
+        for i in 0..40 {
+            text.push_str(&message(i, i % 7));
+            match i % 5 {
+                // A quoted separator-looking line in a body does not split.
+                1 => text.push_str("From here on, all is well\n\n"),
+                // A separator without a blank line before it still splits.
+                2 => text.push_str("trailing line"),
+                3 => text.push_str("From abc123 Mon Sep 17 00:00:00 2001\n\n"),
+                _ => {}
+            }
+            if i % 5 == 2 {
+                text.push('\n');
+            }
+        }
+        std::fs::write(&path, &text).expect("write");
+
+        let (serial, _) = index_mbox(&path, None, &|| false).expect("serial");
+        let serial_bytes = bincode::serialize(&serial).expect("serialize");
+        assert_eq!(serial.len(), 40);
+        let parser = MboxParser::new(&path).expect("parser");
+        assert!(parser.split_at_separators(8).expect("split").len() > 4);
+        for threads in [2, 3, 8, 64] {
+            let parallel =
+                index_mbox_parallel(&parser, threads, None, &|| false).expect("parallel");
+            assert_eq!(
+                bincode::serialize(&parallel).expect("serialize"),
+                serial_bytes,
+                "{threads} threads"
+            );
+        }
+    }
+
+    #[test]
+    fn test_fulltext_index_roundtrip_and_invalidation() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mbox_path = dir.path().join("fts.mbox");
+        std::fs::copy("tests/fixtures/simple.mbox", &mbox_path).expect("copy fixture");
+        let entries = build_index(&mbox_path, true, None).expect("build index");
+        assert!(load_fulltext(&mbox_path, entries.len()).is_none());
+
+        let index = InvertedIndex::build(&mbox_path, &entries, &|_, _| true).expect("build");
+        write_fulltext(&mbox_path, &index).expect("write");
+        assert!(fulltext_path_for(&mbox_path).exists());
+        let loaded = load_fulltext(&mbox_path, entries.len()).expect("valid full-text index");
+        assert_eq!(loaded.body_term_count(), index.body_term_count());
+        // Built for a different set of entries.
+        assert!(load_fulltext(&mbox_path, entries.len() + 1).is_none());
+
+        let (_, with_index) =
+            crate::search::execute(&mbox_path, &entries, "perspective", None).expect("search");
+        assert_eq!(with_index.len(), 1);
+
+        // Any change to the mailbox makes it stale.
+        append(&mbox_path, &message(99, 1));
+        assert!(load_fulltext(&mbox_path, entries.len()).is_none());
+    }
+
+    fn message(i: usize, body_lines: usize) -> String {
+        let mut msg = format!(
+            "From a{i}@example.com Mon Jan  1 10:00:00 2024\n\
+             From: a{i}@example.com\n\
+             Subject: message {i}\n\
+             Message-ID: <m{i}@example.com>\n\n"
+        );
+        for line in 0..body_lines {
+            msg.push_str(&format!("line {line}\n"));
+        }
+        msg.push('\n');
+        msg
+    }
+
+    fn append(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .expect("open for append");
+        file.write_all(text.as_bytes()).expect("append");
+    }
+
+    fn summary(entries: &[MailEntry]) -> Vec<(u64, u64, u64, String)> {
+        entries
+            .iter()
+            .map(|e| (e.offset, e.length, e.sequence, e.subject.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_appended_messages_extend_the_index() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("grow.mbox");
+        // Past the 4 KB hashed prefix, so growing keeps the same hash.
+        let initial: String = (0..20).map(|i| message(i, 30)).collect();
+        std::fs::write(&path, &initial).expect("write");
+        assert_eq!(build_index(&path, true, None).expect("build").len(), 20);
+
+        append(&path, &(20..25).map(|i| message(i, 5)).collect::<String>());
+        let appended = append_to_index(&path, None, &|| false)
+            .expect("append")
+            .expect("append-only change is detected");
+        assert_eq!(appended.len(), 25);
+        let rebuilt = build_index(&path, true, None).expect("rebuild");
+        assert_eq!(summary(&appended), summary(&rebuilt));
+
+        // The index extended in place loads as is.
+        append(&path, &message(25, 5));
+        assert_eq!(build_index(&path, false, None).expect("build").len(), 26);
+        let loaded = load_index(&path).expect("load").expect("valid index");
+        assert_eq!(
+            summary(&loaded),
+            summary(&build_index(&path, true, None).expect("rebuild"))
+        );
+    }
+
+    #[test]
+    fn test_append_extends_a_partially_written_last_message() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("partial.mbox");
+        let mut text: String = (0..20).map(|i| message(i, 30)).collect();
+        text.push_str("From late@example.com Mon Jan  1 10:00:00 2024\nSubject: late\n");
+        std::fs::write(&path, &text).expect("write");
+        assert_eq!(build_index(&path, true, None).expect("build").len(), 21);
+
+        // The client finishes writing the last message, then adds another.
+        append(&path, &format!("\nbody of late\n\n{}", message(21, 3)));
+        let entries = build_index(&path, false, None).expect("build");
+        assert_eq!(
+            summary(&entries),
+            summary(&build_index(&path, true, None).expect("rebuild"))
+        );
+        assert_eq!(entries[20].subject, "late");
+        assert_eq!(entries.len(), 22);
+    }
+
+    #[test]
+    fn test_rewritten_mailbox_is_not_treated_as_append() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("rewrite.mbox");
+        let initial: String = (0..20).map(|i| message(i, 30)).collect();
+        std::fs::write(&path, &initial).expect("write");
+        build_index(&path, true, None).expect("build");
+
+        // Same first 4 KB, but a middle message deleted and others added: the
+        // indexed offsets no longer point at separators.
+        let rewritten: String = (0..20)
+            .filter(|&i| i != 15)
+            .map(|i| message(i, 30))
+            .chain((20..30).map(|i| message(i, 30)))
+            .collect();
+        std::fs::write(&path, &rewritten).expect("rewrite");
+        assert!(append_to_index(&path, None, &|| false)
+            .expect("check")
+            .is_none());
+        assert_eq!(build_index(&path, false, None).expect("build").len(), 29);
+
+        // A shrunk mailbox is never an append.
+        std::fs::write(&path, &initial[..initial.len() / 2]).expect("truncate");
+        assert!(append_to_index(&path, None, &|| false)
+            .expect("check")
+            .is_none());
+    }
+
+    #[test]
+    fn test_every_indexed_separator_is_checked_before_appending() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("edited.mbox");
+        let initial: String = (0..300).map(|i| message(i, 3)).collect();
+        std::fs::write(&path, &initial).expect("write");
+        let entries = build_index(&path, true, None).expect("build");
+
+        // One separator in the middle overwritten in place, then the
+        // mailbox grows: every other offset is still right.
+        let mut edited = initial.into_bytes();
+        let start = entries[149].offset as usize;
+        edited[start..start + 5].copy_from_slice(b"XXXXX");
+        std::fs::write(&path, &edited).expect("edit");
+        append(&path, &message(300, 3));
+        assert!(append_to_index(&path, None, &|| false)
+            .expect("check")
+            .is_none());
+    }
 }
@@ -16,6 +16,18 @@
 //! │  bincode-serialized Vec<MailEntry>   │
 //! └──────────────────────────────────────┘
 //! ```
+//!
+//! A compressed mailbox also gets a checkpoint file next to its index
+//! (`.mail.mbox.gz.mboxshell.ckpt`): a bincode [`CheckpointHeader`] followed
+//! by the bincode [`CheckpointTable`](crate::source::CheckpointTable). The
+//! index of a compressed mailbox is only used while its checkpoint file is
+//! valid too, since index offsets are checked against the decompressed size
+//! recorded there.
+//!
+//! `mboxshell index --fulltext` also writes a full-text index next to it
+//! (`.mail.mbox.mboxshell.fts`): a bincode [`FulltextHeader`] followed by the
+//! bincode [`InvertedIndex`](crate::search::inverted::InvertedIndex). It is
+//! only used while it matches both the mailbox and the index format.
 
 /// Magic bytes identifying an mboxShell index file.
 pub const MAGIC: &[u8; 8] = b"MBOXTUI\0";
@@ -31,7 +43,15 @@ pub const MAGIC: &[u8; 8] = b"MBOXTUI\0";
 /// v4: `MailEntry` gained `thread_id` (`X-GM-THRID`), and
 /// `mbox_modified_time` switched from seconds to nanoseconds — both change
 /// what an index means, so v3 files are rebuilt.
-pub const VERSION: u32 = 4;
+/// v5: `MailEntry` gained `file` and `flags` for Maildir sources.
+/// v6: `MailEntry` gained `attachments`, a summary of each attachment.
+/// v7: `to` and `cc` are no longer truncated to 5 addresses, and `MailEntry`
+/// gained `bcc`, `reply_to`, `sender` and `delivered_to`.
+/// v8: layout unchanged; mboxcl and mboxcl2 messages are now delimited by
+/// their `Content-Length:`, so v7 indexes of those mailboxes are rebuilt.
+/// v9: layout unchanged; MBOX `flags` now come from the `X-Gmail-Labels`,
+/// `Status:` and `X-Status:` headers instead of being all unset.
+pub const VERSION: u32 = 9;
 
 /// Fixed header size in bytes.
 pub const HEADER_SIZE: usize = 128;
@@ -77,3 +97,82 @@ impl IndexHeader {
         Ok(())
     }
 }
+
+/// Magic bytes identifying an mboxShell checkpoint file.
+pub const CHECKPOINT_MAGIC: &[u8; 8] = b"MBOXCKP\0";
+
+/// Current checkpoint file format version.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Header of a checkpoint file: the same source fingerprint as the index.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CheckpointHeader {
+    /// Magic bytes (must equal [`CHECKPOINT_MAGIC`]).
+    pub magic: [u8; 8],
+    /// Format version (must equal [`CHECKPOINT_VERSION`]).
+    pub version: u32,
+    /// Size of the compressed file when the table was recorded.
+    pub mbox_file_size: u64,
+    /// Modification time of the compressed file (nanoseconds).
+    pub mbox_modified_time: i64,
+    /// SHA-256 of the first 4 KB of the compressed file.
+    pub sha256_first_4kb: [u8; 32],
+}
+
+impl CheckpointHeader {
+    /// Validate that the header is well-formed and matches the current format.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.magic != *CHECKPOINT_MAGIC {
+            return Err("Invalid magic bytes".into());
+        }
+        if self.version != CHECKPOINT_VERSION {
+            return Err(format!(
+                "Incompatible version: expected {CHECKPOINT_VERSION}, found {}",
+                self.version
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Magic bytes identifying an mboxShell full-text index file.
+pub const FULLTEXT_MAGIC: &[u8; 8] = b"MBOXFTS\0";
+
+/// Current full-text index file format version. Version 2 stores folded
+/// tokens (see [`crate::search::normalize`]).
+pub const FULLTEXT_VERSION: u32 = 2;
+
+/// Header of a full-text index file: the same source fingerprint as the
+/// index, plus the index format version its message numbers refer to.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct FulltextHeader {
+    /// Magic bytes (must equal [`FULLTEXT_MAGIC`]).
+    pub magic: [u8; 8],
+    /// Format version (must equal [`FULLTEXT_VERSION`]).
+    pub version: u32,
+    /// Index format version (must equal [`VERSION`]): a new one may split
+    /// the mailbox differently, renumbering its messages.
+    pub index_version: u32,
+    /// Size of the mailbox when the full-text index was built.
+    pub mbox_file_size: u64,
+    /// Modification time of the mailbox (nanoseconds).
+    pub mbox_modified_time: i64,
+    /// SHA-256 of the first 4 KB of the mailbox.
+    pub sha256_first_4kb: [u8; 32],
+}
+
+impl FulltextHeader {
+    /// Validate that the header is well-formed and matches the current format.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.magic != *FULLTEXT_MAGIC {
+            return Err("Invalid magic bytes".into());
+        }
+        if self.version != FULLTEXT_VERSION || self.index_version != VERSION {
+            return Err(format!(
+                "Incompatible version: expected {FULLTEXT_VERSION}/{VERSION}, found {}/{}",
+                self.version, self.index_version
+            ));
+        }
+        Ok(())
+    }
+}
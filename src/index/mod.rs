@@ -3,3 +3,4 @@
 pub mod builder;
 pub mod format;
 pub mod reader;
+pub mod state;
@@ -0,0 +1,301 @@
+//! Message states the user sets: read or unread, starred, and free-form tags.
+//!
+//! The mailbox itself is never written to, so these live in an overlay file
+//! next to it (`.<name>.mboxshell.state`, falling back to the user data
+//! directory, see [`builder::state_path_for`]). It is JSON, keyed by a
+//! message identity that survives a rebuilt or re-exported index — the
+//! `Message-ID`, see [`message_key`] — so marks are not lost when the
+//! mailbox grows or the index format changes.
+//!
+//! A state only records what the user changed: a message nobody touched
+//! keeps the flags its mailbox gave it ([`MailEntry::flags`]: Maildir file
+//! names, or the `X-Gmail-Labels`, `Status:` and `X-Status:` headers of an
+//! MBOX message, which is read when it has none of them).
+//! [`StateOverlay::apply`] lays the recorded states over the entries, so
+//! search (`is:unread`, `is:starred`, `tag:`) and the TUI read them from
+//! [`MailEntry::flags`] and [`MailEntry::tags`] like any other metadata.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::MboxError;
+use crate::index::builder;
+use crate::model::mail::MailEntry;
+
+/// Version of the overlay file layout.
+const STATE_VERSION: u32 = 1;
+
+/// What the user set on one message. `None` leaves the mailbox's own flag.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub starred: Option<bool>,
+    /// Sorted, without duplicates.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl MessageState {
+    /// Whether the state records nothing.
+    fn is_empty(&self) -> bool {
+        self.read.is_none() && self.starred.is_none() && self.tags.is_empty()
+    }
+}
+
+/// The message states of one mailbox.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateOverlay {
+    version: u32,
+    /// [`message_key`] → state.
+    messages: BTreeMap<String, MessageState>,
+}
+
+/// The identity a message's state is recorded under: its `Message-ID`, or,
+/// for a message without one, its date, sender and subject.
+pub fn message_key(entry: &MailEntry) -> String {
+    if entry.message_id.is_empty() {
+        format!(
+            "{} {} {}",
+            entry.date.timestamp(),
+            entry.from.address,
+            entry.subject
+        )
+    } else {
+        entry.message_id.clone()
+    }
+}
+
+impl StateOverlay {
+    /// The overlay of the mailbox at `mbox_path`: empty when none was saved,
+    /// an error when the file cannot be read or parsed (rather than starting
+    /// over and overwriting it on the next change).
+    pub fn load(mbox_path: &Path) -> anyhow::Result<Self> {
+        let Some(path) = Self::existing_path(mbox_path) else {
+            return Ok(Self::default());
+        };
+        let data = std::fs::read(&path).map_err(|e| MboxError::io(&path, e))?;
+        let overlay: Self = serde_json::from_slice(&data)
+            .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+        if overlay.version > STATE_VERSION {
+            anyhow::bail!(
+                "{}: message states written by a newer version (v{})",
+                path.display(),
+                overlay.version
+            );
+        }
+        Ok(overlay)
+    }
+
+    /// Write the overlay for the mailbox at `mbox_path`, replacing the
+    /// previous one in a single rename: next to the mailbox when possible,
+    /// in the user data directory otherwise (or when it was already there).
+    /// Returns the path written.
+    pub fn save(&self, mbox_path: &Path) -> anyhow::Result<PathBuf> {
+        let primary = builder::state_path_for(mbox_path);
+        let fallback = builder::data_state_path_for(mbox_path);
+        let json = serde_json::to_vec_pretty(&Self {
+            version: STATE_VERSION,
+            messages: self.messages.clone(),
+        })?;
+
+        if primary.exists() || !fallback.exists() {
+            match write_replacing(&primary, &json) {
+                Ok(()) => return Ok(primary),
+                Err(e) => tracing::debug!(error = %e, "Cannot write next to the mailbox"),
+            }
+        }
+        if let Some(parent) = fallback.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| MboxError::io(parent, e))?;
+        }
+        write_replacing(&fallback, &json)?;
+        Ok(fallback)
+    }
+
+    /// The overlay file of `mbox_path`, if one was saved.
+    fn existing_path(mbox_path: &Path) -> Option<PathBuf> {
+        [
+            builder::state_path_for(mbox_path),
+            builder::data_state_path_for(mbox_path),
+        ]
+        .into_iter()
+        .find(|p| p.exists())
+    }
+
+    /// Number of messages with a recorded state.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Whether no message has a recorded state.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// The recorded state of `entry`, if any.
+    pub fn get(&self, entry: &MailEntry) -> Option<&MessageState> {
+        self.messages.get(&message_key(entry))
+    }
+
+    /// Lay the recorded states over `entries`.
+    pub fn apply(&self, entries: &mut [MailEntry]) {
+        if self.messages.is_empty() {
+            return;
+        }
+        for entry in entries {
+            if let Some(state) = self.get(entry) {
+                apply_state(entry, state);
+            }
+        }
+    }
+
+    /// Mark `entry` read or unread.
+    pub fn set_read(&mut self, entry: &mut MailEntry, read: bool) {
+        self.update(entry, |state| state.read = Some(read));
+    }
+
+    /// Star or unstar `entry`.
+    pub fn set_starred(&mut self, entry: &mut MailEntry, starred: bool) {
+        self.update(entry, |state| state.starred = Some(starred));
+    }
+
+    /// Add `tag` to `entry`, or remove it. Tags are trimmed; an empty one is
+    /// ignored.
+    pub fn set_tag(&mut self, entry: &mut MailEntry, tag: &str, present: bool) {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return;
+        }
+        self.update(entry, |state| {
+            match (
+                state.tags.binary_search_by(|t| t.as_str().cmp(tag)),
+                present,
+            ) {
+                (Err(at), true) => state.tags.insert(at, tag.to_string()),
+                (Ok(at), false) => {
+                    state.tags.remove(at);
+                }
+                _ => {}
+            }
+        });
+    }
+
+    /// Change the recorded state of `entry` with `change` and apply it to
+    /// the entry. A state left recording nothing is dropped.
+    fn update(&mut self, entry: &mut MailEntry, change: impl FnOnce(&mut MessageState)) {
+        let key = message_key(entry);
+        let mut state = self.messages.remove(&key).unwrap_or_else(|| MessageState {
+            tags: entry.tags.clone(),
+            ..MessageState::default()
+        });
+        change(&mut state);
+        entry.tags.clear();
+        apply_state(entry, &state);
+        if !state.is_empty() {
+            self.messages.insert(key, state);
+        }
+    }
+}
+
+/// Set the flags and tags of `entry` from `state`.
+fn apply_state(entry: &mut MailEntry, state: &MessageState) {
+    if let Some(read) = state.read {
+        entry.flags.seen = read;
+    }
+    if let Some(starred) = state.starred {
+        entry.flags.flagged = starred;
+    }
+    entry.tags.clone_from(&state.tags);
+}
+
+/// Write `data` to a temporary file beside `path` and rename it over
+/// `path`, so a crash never leaves a half-written overlay.
+fn write_replacing(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    std::fs::write(&tmp, data).map_err(|e| MboxError::io(&tmp, e))?;
+    if let Err(e) = std::fs::rename(&tmp, path) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(MboxError::io(path, e).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::header::parse_headers_to_entry;
+
+    /// An unread message.
+    fn entry(message_id: &str) -> MailEntry {
+        let raw = format!("Message-ID: {message_id}\nSubject: Hi\nStatus: O\n");
+        parse_headers_to_entry(raw.as_bytes(), 0, 10, 0).unwrap()
+    }
+
+    #[test]
+    fn test_states_survive_a_save_and_apply_to_new_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let mbox = dir.path().join("mail.mbox");
+        std::fs::write(&mbox, b"").unwrap();
+
+        let mut overlay = StateOverlay::load(&mbox).unwrap();
+        assert!(overlay.is_empty());
+        let mut a = entry("<a@x>");
+        let mut b = entry("<b@x>");
+        overlay.set_read(&mut a, true);
+        overlay.set_starred(&mut b, true);
+        overlay.set_tag(&mut b, "review", true);
+        overlay.set_tag(&mut b, " later ", true);
+        assert!(a.flags.seen);
+        assert_eq!(b.tags, ["later", "review"]);
+        assert_eq!(overlay.save(&mbox).unwrap(), builder::state_path_for(&mbox));
+
+        // A fresh parse of the same messages, as after a rebuilt index.
+        let mut entries = vec![entry("<a@x>"), entry("<b@x>"), entry("<c@x>")];
+        let loaded = StateOverlay::load(&mbox).unwrap();
+        assert_eq!(loaded.messages, overlay.messages);
+        loaded.apply(&mut entries);
+        assert!(entries[0].flags.seen && !entries[0].flags.flagged);
+        assert!(!entries[1].flags.seen && entries[1].flags.flagged);
+        assert_eq!(entries[1].tags, ["later", "review"]);
+        assert!(entries[2].tags.is_empty() && !entries[2].flags.seen);
+    }
+
+    #[test]
+    fn test_untouched_flags_and_cleared_states() {
+        let mut overlay = StateOverlay::default();
+        let mut maildir = entry("<m@x>");
+        maildir.flags.seen = true;
+        overlay.set_tag(&mut maildir, "todo", true);
+        // Tagging does not touch the mailbox's own read flag.
+        assert!(maildir.flags.seen);
+
+        overlay.set_tag(&mut maildir, "todo", false);
+        assert!(overlay.is_empty(), "a state recording nothing is dropped");
+
+        overlay.set_tag(&mut maildir, "  ", true);
+        assert!(overlay.is_empty());
+    }
+
+    #[test]
+    fn test_messages_without_message_id_are_keyed_by_their_headers() {
+        let mut overlay = StateOverlay::default();
+        let mut first = entry("");
+        overlay.set_starred(&mut first, true);
+        let mut again = entry("");
+        overlay.apply(std::slice::from_mut(&mut again));
+        assert!(again.flags.flagged);
+    }
+
+    #[test]
+    fn test_unreadable_overlay_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mbox = dir.path().join("mail.mbox");
+        std::fs::write(builder::state_path_for(&mbox), b"{ not json").unwrap();
+        assert!(StateOverlay::load(&mbox).is_err());
+    }
+}
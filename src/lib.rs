@@ -12,5 +12,8 @@ pub mod mailbox_naming;
 pub mod model;
 pub mod parser;
 pub mod search;
+pub mod source;
 pub mod store;
+#[cfg(test)]
+pub(crate) mod testing;
 pub mod tui;
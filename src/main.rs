@@ -1,13 +1,22 @@
 //! CLI entry point for `mboxShell`.
 
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::time::Instant;
 
 use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
 
+use mboxshell::export::dedup::{DedupStrategy, DuplicateCluster};
+use mboxshell::export::diff::MailboxDiff;
+use mboxshell::export::split::{SplitBy, SplitPart};
 use mboxshell::i18n;
 use mboxshell::index::{builder, reader as index_reader};
+use mboxshell::parser::dialect::MboxDialect;
+use mboxshell::parser::verify;
+use mboxshell::search;
+use mboxshell::search::inverted::InvertedIndex;
+use mboxshell::search::query::{self, QueryTimezone};
 
 #[derive(Parser)]
 #[command(name = "mboxshell", version)]
@@ -55,6 +64,10 @@ enum Commands {
     /// Index an MBOX file
     Index {
         path: PathBuf,
+        /// Also build a full-text index of bodies, attachment filenames and
+        /// headers, so body and free-text searches need not read every message
+        #[arg(long)]
+        fulltext: bool,
         #[command(flatten)]
         force: ForceArg,
     },
@@ -69,9 +82,32 @@ enum Commands {
     /// Search messages
     Search {
         path: PathBuf,
-        query: String,
+        /// Search query, or `@name` to run the search saved as `name` in
+        /// the config file
+        #[arg(required_unless_present = "query_json")]
+        query: Option<String>,
+        /// Read the query from a JSON document (`-` for stdin) instead, in
+        /// the form `--explain --json` prints
+        #[arg(long, value_name = "FILE", conflicts_with = "query")]
+        query_json: Option<PathBuf>,
+        /// Print how the query was understood, as a tree (or as JSON with
+        /// --json), instead of searching
+        #[arg(long, conflicts_with_all = ["facets", "rank"])]
+        explain: bool,
         #[arg(long)]
         json: bool,
+        /// Instead of listing the results, summarize them: counts by sender,
+        /// sender domain, label, year, month, thread and attachment type
+        #[arg(long)]
+        facets: bool,
+        /// Values shown per facet (0 = all)
+        #[arg(long, default_value_t = 10, requires = "facets")]
+        facet_limit: usize,
+        /// Order the results by relevance (term frequency, subject and
+        /// sender hits first, recent messages first) and show the passages
+        /// of the body that matched
+        #[arg(long, conflicts_with = "facets")]
+        rank: bool,
         #[command(flatten)]
         force: ForceArg,
     },
@@ -87,6 +123,7 @@ enum Commands {
         /// (a folder gets `export.csv` / `export.mbox` written inside it).
         #[arg(short, long)]
         output: PathBuf,
+        /// Only export the messages matching this query (or `@name`)
         #[arg(long)]
         query: Option<String>,
         /// Re-encode 8-bit text bodies as quoted-printable so the EML is
@@ -99,6 +136,10 @@ enum Commands {
         /// these files. Only affects --format=html.
         #[arg(long)]
         raw_html: bool,
+        /// MBOX dialect to write: mboxo, mboxrd, mboxcl or mboxcl2.
+        /// Defaults to the source's own. Only affects --format=mbox.
+        #[arg(long)]
+        dialect: Option<MboxDialect>,
         /// Force rebuild index even if one already exists
         #[arg(long)]
         force: bool,
@@ -108,16 +149,109 @@ enum Commands {
         inputs: Vec<PathBuf>,
         #[arg(short, long)]
         output: PathBuf,
-        /// Skip duplicate-Message-ID detection and concatenate the inputs
+        /// Skip duplicate detection and concatenate the inputs
         /// byte-for-byte (preserves original bytes and line endings).
         #[arg(long)]
         no_dedup: bool,
+        /// How duplicates are recognized: message-id, content (sender,
+        /// recipients, subject, date and body) or body
+        #[arg(
+            long,
+            value_name = "STRATEGY",
+            default_value_t,
+            conflicts_with = "no_dedup"
+        )]
+        dedup_by: DedupStrategy,
         /// Inject an `X-Mbox-Source: <mailbox name>` header into every message
         /// so the merged archive stays traceable to its source mailbox. The
         /// name is the one you see (`Inbox.mbox`, not Apple Mail's inner
         /// `mbox` file).
         #[arg(long)]
         source_header: bool,
+        /// Write every message in this MBOX dialect: mboxo, mboxrd, mboxcl
+        /// or mboxcl2. By default each keeps the dialect of its input.
+        #[arg(long)]
+        dialect: Option<MboxDialect>,
+    },
+    /// Remove duplicate messages from an MBOX file
+    Dedup {
+        path: PathBuf,
+        /// Where to write the mailbox without its duplicates
+        #[arg(short, long, required_unless_present = "dry_run")]
+        output: Option<PathBuf>,
+        /// How duplicates are recognized: message-id, content (sender,
+        /// recipients, subject, date and body) or body
+        #[arg(long, value_name = "STRATEGY", default_value_t)]
+        by: DedupStrategy,
+        /// List the duplicate clusters without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Print the duplicate clusters as JSON
+        #[arg(long)]
+        json: bool,
+        /// MBOX dialect to write: mboxo, mboxrd, mboxcl or mboxcl2.
+        /// Defaults to the input's own.
+        #[arg(long)]
+        dialect: Option<MboxDialect>,
+        #[command(flatten)]
+        force: ForceArg,
+    },
+    /// Compare two MBOX files
+    Diff {
+        /// The earlier mailbox
+        a: PathBuf,
+        /// The later mailbox
+        b: PathBuf,
+        /// Write the messages only in B to this new MBOX file
+        #[arg(long, value_name = "FILE")]
+        only_in_b: Option<PathBuf>,
+        /// MBOX dialect to write: mboxo, mboxrd, mboxcl or mboxcl2.
+        /// Defaults to B's own. Only affects --only-in-b.
+        #[arg(long)]
+        dialect: Option<MboxDialect>,
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        force: ForceArg,
+    },
+    /// Check an MBOX file for damage the parser reads past
+    Verify {
+        path: PathBuf,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write a repaired copy of an MBOX file
+    Repair {
+        path: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// MBOX dialect to write: mboxo, mboxrd, mboxcl or mboxcl2.
+        /// Defaults to the input's own.
+        #[arg(long)]
+        dialect: Option<MboxDialect>,
+    },
+    /// Split an MBOX file into several
+    Split {
+        path: PathBuf,
+        /// How to split: size:<n> (parts of at most n bytes, in units of
+        /// 1024: size:2gb is 2 GiB), year, month, label or sender-domain
+        #[arg(long, value_name = "SPLIT")]
+        by: SplitBy,
+        /// Folder the parts are written to
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Only split the messages matching this query (or `@name`)
+        #[arg(long)]
+        query: Option<String>,
+        /// MBOX dialect to write: mboxo, mboxrd, mboxcl or mboxcl2.
+        /// Defaults to the source's own.
+        #[arg(long)]
+        dialect: Option<MboxDialect>,
+        /// Print the manifest as JSON, with the Message-IDs in every part
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        force: ForceArg,
     },
     /// Extract all attachments
     Attachments {
@@ -189,6 +323,21 @@ fn build_localized_command() -> clap::Command {
                 "merge" => {
                     s = s.about(i18n::help_cmd_merge());
                 }
+                "dedup" => {
+                    s = s.about(i18n::help_cmd_dedup());
+                }
+                "diff" => {
+                    s = s.about(i18n::help_cmd_diff());
+                }
+                "verify" => {
+                    s = s.about(i18n::help_cmd_verify());
+                }
+                "repair" => {
+                    s = s.about(i18n::help_cmd_repair());
+                }
+                "split" => {
+                    s = s.about(i18n::help_cmd_split());
+                }
                 "attachments" => {
                     s = s.about(i18n::help_cmd_attachments());
                 }
@@ -212,7 +361,7 @@ fn build_localized_command() -> clap::Command {
     cmd
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<ExitCode> {
     // Detect language BEFORE clap parsing so --help is localized
     let lang = detect_lang_early();
     i18n::set_lang(lang);
@@ -234,12 +383,24 @@ fn main() -> anyhow::Result<()> {
     };
     setup_logging(log_level, &config);
 
+    match QueryTimezone::parse(&config.general.timezone) {
+        Some(timezone) => query::set_timezone(timezone),
+        None => tracing::warn!(
+            timezone = %config.general.timezone,
+            "Unrecognized timezone in config, using the local one"
+        ),
+    }
+
     // `-f` before the subcommand (`mboxshell -f index x.mbox`) and after it
     // (`mboxshell index x.mbox -f`) both mean the same thing.
     let root_force = cli.force;
 
-    match cli.command {
-        Some(Commands::Index { path, force }) => cmd_index(&path, root_force || force.force),
+    let result = match cli.command {
+        Some(Commands::Index {
+            path,
+            fulltext,
+            force,
+        }) => cmd_index(&path, fulltext, root_force || force.force),
         Some(Commands::Stats { path, json, force }) => {
             cmd_stats(&path, json, root_force || force.force)
         }
@@ -254,9 +415,34 @@ fn main() -> anyhow::Result<()> {
         Some(Commands::Search {
             path,
             query,
+            query_json,
+            explain,
             json,
+            facets,
+            facet_limit,
+            rank,
             force,
-        }) => cmd_search(&path, &query, json, root_force || force.force),
+        }) => {
+            let query = match (query, query_json) {
+                (_, Some(file)) => read_query_json(&file)?,
+                (Some(query), None) => {
+                    query::parse_query(search::resolve_saved(&query, &config.searches)?)
+                }
+                (None, None) => unreachable!("clap requires a query"),
+            };
+            if explain {
+                cmd_explain(&query, json)
+            } else {
+                cmd_search(
+                    &path,
+                    query,
+                    json,
+                    facets.then_some(facet_limit),
+                    rank,
+                    root_force || force.force,
+                )
+            }
+        }
         Some(Commands::Export {
             path,
             format,
@@ -264,22 +450,92 @@ fn main() -> anyhow::Result<()> {
             query,
             qp,
             raw_html,
+            dialect,
             force,
         }) => cmd_export(
             &path,
             &format,
             &output,
-            query.as_deref(),
+            query
+                .as_deref()
+                .map(|q| search::resolve_saved(q, &config.searches))
+                .transpose()?,
             root_force || force,
             qp,
             raw_html,
+            dialect,
         ),
         Some(Commands::Merge {
             inputs,
             output,
             no_dedup,
+            dedup_by,
+            source_header,
+            dialect,
+        }) => cmd_merge(
+            &inputs,
+            &output,
+            (!no_dedup).then_some(dedup_by),
             source_header,
-        }) => cmd_merge(&inputs, &output, !no_dedup, source_header),
+            dialect,
+        ),
+        Some(Commands::Dedup {
+            path,
+            output,
+            by,
+            dry_run,
+            json,
+            dialect,
+            force,
+        }) => cmd_dedup(
+            &path,
+            output.as_deref().filter(|_| !dry_run),
+            by,
+            json,
+            dialect,
+            root_force || force.force,
+        ),
+        Some(Commands::Diff {
+            a,
+            b,
+            only_in_b,
+            dialect,
+            json,
+            force,
+        }) => cmd_diff(
+            &a,
+            &b,
+            only_in_b.as_deref(),
+            dialect,
+            json,
+            root_force || force.force,
+        ),
+        Some(Commands::Verify { path, json }) => cmd_verify(&path, json),
+        Some(Commands::Repair {
+            path,
+            output,
+            dialect,
+        }) => cmd_repair(&path, &output, dialect),
+        Some(Commands::Split {
+            path,
+            by,
+            output,
+            query,
+            dialect,
+            json,
+            force,
+        }) => cmd_split(
+            &path,
+            by,
+            &output,
+            query
+                .as_deref()
+                .map(|q| search::resolve_saved(q, &config.searches))
+                .transpose()?,
+            dialect,
+            json,
+            root_force || force.force,
+        ),
         Some(Commands::Attachments {
             path,
             output,
@@ -287,9 +543,26 @@ fn main() -> anyhow::Result<()> {
         }) => cmd_attachments(&path, &output, root_force || force.force),
         Some(Commands::Completions { shell }) => cmd_completions(shell),
         Some(Commands::Manpage) => cmd_manpage(),
+    };
+    match result {
+        Err(e) if e.is::<ProblemsFound>() => Ok(ExitCode::FAILURE),
+        result => result.map(|()| ExitCode::SUCCESS),
     }
 }
 
+/// Returned by `verify` when the mailbox has problems. The report is already
+/// printed, so `main` only exits with status 1.
+#[derive(Debug)]
+struct ProblemsFound;
+
+impl std::fmt::Display for ProblemsFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the mailbox has problems")
+    }
+}
+
+impl std::error::Error for ProblemsFound {}
+
 /// Set up tracing with stderr output and optional file logging.
 fn setup_logging(level: &str, config: &mboxshell::config::Config) {
     use tracing_subscriber::layer::SubscriberExt;
@@ -339,13 +612,13 @@ fn cmd_manpage() -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Index an MBOX file and print statistics.
-fn cmd_index(path: &Path, force: bool) -> anyhow::Result<()> {
+/// Index an MBOX file (and, with `fulltext`, its text) and print statistics.
+fn cmd_index(path: &Path, fulltext: bool, force: bool) -> anyhow::Result<()> {
     if !path.exists() {
         anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
     }
 
-    let file_size = std::fs::metadata(path)?.len();
+    let file_size = builder::mailbox_size(path)?;
     let pb = ProgressBar::new(file_size);
     pb.set_style(
         ProgressStyle::default_bar()
@@ -370,6 +643,10 @@ fn cmd_index(path: &Path, force: bool) -> anyhow::Result<()> {
 
     pb.finish_and_clear();
 
+    if fulltext {
+        build_fulltext(path, &entries, force)?;
+    }
+
     let elapsed = start.elapsed();
     let idx_size = builder::index_file_size(path);
 
@@ -378,13 +655,44 @@ fn cmd_index(path: &Path, force: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Build and write the full-text index of a mailbox, unless a valid one
+/// already exists and `force` is not set.
+fn build_fulltext(
+    path: &Path,
+    entries: &[mboxshell::model::mail::MailEntry],
+    force: bool,
+) -> anyhow::Result<()> {
+    if !force && builder::load_fulltext(path, entries.len()).is_some() {
+        return Ok(());
+    }
+
+    let pb = ProgressBar::new(entries.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(&format!(
+                "{{spinner:.green}} {} [{{bar:40.cyan/blue}}] {{pos}}/{{len}} ({{eta}})",
+                i18n::msg_indexing_fulltext()
+            ))
+            .expect("valid template")
+            .progress_chars("#>-"),
+    );
+    let index = InvertedIndex::build(path, entries, &|current, total| {
+        pb.set_length(total as u64);
+        pb.set_position(current as u64);
+        true
+    })?;
+    pb.finish_and_clear();
+
+    builder::write_fulltext(path, &index)
+}
+
 /// Show statistics for an MBOX file.
 fn cmd_stats(path: &Path, json: bool, force: bool) -> anyhow::Result<()> {
     if !path.exists() {
         anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
     }
 
-    let file_size = std::fs::metadata(path)?.len();
+    let file_size = builder::mailbox_size(path)?;
 
     let pb = ProgressBar::new(file_size);
     pb.set_style(
@@ -431,8 +739,47 @@ fn cmd_open_interactive() -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Search messages in an MBOX file and print results.
-fn cmd_search(path: &Path, query: &str, json: bool, force: bool) -> anyhow::Result<()> {
+/// Read a JSON query document from `file`, or stdin for `-`.
+fn read_query_json(file: &Path) -> anyhow::Result<query::SearchQuery> {
+    let json = if file.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(file).map_err(|e| anyhow::anyhow!("{}: {e}", file.display()))?
+    };
+    Ok(query::SearchQuery::from_json(&json)?)
+}
+
+/// Print how `query` was understood: its expression tree and whether it
+/// reads message bodies, or the query as JSON. A query with an invalid
+/// regular expression fails instead, as the search would.
+fn cmd_explain(query: &query::SearchQuery, json: bool) -> anyhow::Result<()> {
+    query.check()?;
+    if json {
+        println!("{}", query.to_json());
+    } else if query.is_empty() {
+        println!("{}", i18n::cli_explain_empty());
+    } else {
+        print!("{}", query.explain());
+        let reads = if search::needs_body_scan(query) {
+            i18n::cli_yes()
+        } else {
+            i18n::cli_no()
+        };
+        println!("\n{}: {reads}", i18n::cli_explain_reads_bodies());
+    }
+    Ok(())
+}
+
+/// Search messages in an MBOX file and print results, or with `facets`
+/// (values shown per facet) a summary of them.
+fn cmd_search(
+    path: &Path,
+    query: query::SearchQuery,
+    json: bool,
+    facets: Option<usize>,
+    rank: bool,
+    force: bool,
+) -> anyhow::Result<()> {
     if !path.exists() {
         anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
     }
@@ -451,7 +798,7 @@ fn cmd_search(path: &Path, query: &str, json: bool, force: bool) -> anyhow::Resu
             .progress_chars("#>-"),
     );
 
-    let (_parsed_query, results) = mboxshell::search::execute(
+    let (parsed_query, results) = mboxshell::search::execute_query(
         path,
         &entries,
         query,
@@ -464,16 +811,45 @@ fn cmd_search(path: &Path, query: &str, json: bool, force: bool) -> anyhow::Resu
 
     pb.finish_and_clear();
 
-    if json {
-        print_search_results_json(&entries, &results)?;
+    if let Some(limit) = facets {
+        let facets = search::facets::compute(&entries, &results);
+        if json {
+            print_facets_json(&facets, limit)?;
+        } else {
+            print_facets_table(&facets, limit);
+        }
+    } else if rank {
+        // Bodies are read to count their matches and cut snippets.
+        pb.reset();
+        let hits = search::rank::rank(
+            path,
+            &entries,
+            &parsed_query,
+            &results,
+            &|current, total| {
+                pb.set_length(total as u64);
+                pb.set_position(current as u64);
+                true
+            },
+        )?;
+        pb.finish_and_clear();
+        let order: Vec<usize> = hits.iter().map(|hit| hit.index).collect();
+        if json {
+            print_search_results_json(&entries, &order, Some(&hits))?;
+        } else {
+            print_search_results_table(&entries, &order, Some(&hits));
+        }
+    } else if json {
+        print_search_results_json(&entries, &results, None)?;
     } else {
-        print_search_results_table(&entries, &results);
+        print_search_results_table(&entries, &results, None);
     }
 
     Ok(())
 }
 
 /// Export messages from an MBOX file.
+#[allow(clippy::too_many_arguments)]
 fn cmd_export(
     path: &Path,
     format: &str,
@@ -482,6 +858,7 @@ fn cmd_export(
     force: bool,
     qp: bool,
     raw_html: bool,
+    dialect: Option<MboxDialect>,
 ) -> anyhow::Result<()> {
     if !path.exists() {
         anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
@@ -599,10 +976,12 @@ fn cmd_export(
             if let Some(parent) = mbox_path.parent() {
                 std::fs::create_dir_all(parent)?;
             }
+            let dialect = dialect.unwrap_or(store.dialect());
             let count = mboxshell::export::mbox::export_mbox(
                 &mut store,
                 &selected,
                 &mbox_path,
+                dialect,
                 &|current, _total| {
                     pb.set_position(current as u64);
                 },
@@ -633,8 +1012,9 @@ fn cmd_export(
 fn cmd_merge(
     inputs: &[PathBuf],
     output: &Path,
-    dedup: bool,
+    dedup: Option<DedupStrategy>,
     add_source_header: bool,
+    dialect: Option<MboxDialect>,
 ) -> anyhow::Result<()> {
     for input in inputs {
         if !input.exists() {
@@ -658,6 +1038,7 @@ fn cmd_merge(
         output,
         dedup,
         add_source_header,
+        dialect,
         &|current, _total, _name| {
             pb.set_position(current as u64);
         },
@@ -674,7 +1055,7 @@ fn cmd_merge(
         i18n::cli_total_messages(),
         stats.total_messages
     );
-    if dedup {
+    if dedup.is_some() {
         println!(
             "  {:<25} {}",
             i18n::cli_duplicates_removed(),
@@ -699,8 +1080,16 @@ fn cmd_merge(
     Ok(())
 }
 
-/// Extract all attachments from an MBOX file.
-fn cmd_attachments(path: &Path, output: &Path, force: bool) -> anyhow::Result<()> {
+/// Find the duplicate messages of an MBOX file, and write it without them
+/// unless this is a dry run (`output` is `None`).
+fn cmd_dedup(
+    path: &Path,
+    output: Option<&Path>,
+    by: DedupStrategy,
+    json: bool,
+    dialect: Option<MboxDialect>,
+    force: bool,
+) -> anyhow::Result<()> {
     if !path.exists() {
         anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
     }
@@ -708,73 +1097,343 @@ fn cmd_attachments(path: &Path, output: &Path, force: bool) -> anyhow::Result<()
     let entries = builder::build_index(path, force, None)?;
     let mut store = mboxshell::store::reader::MboxStore::open(path)?;
 
-    let with_att: Vec<&mboxshell::model::mail::MailEntry> =
-        entries.iter().filter(|e| e.has_attachments).collect();
+    let pb = ProgressBar::new(entries.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(&format!(
+                "{{spinner:.green}} {} [{{bar:40.cyan/blue}}] {{pos}}/{{len}}",
+                i18n::cli_finding_duplicates()
+            ))
+            .expect("valid template")
+            .progress_chars("#>-"),
+    );
+    let clusters =
+        mboxshell::export::dedup::find_duplicates(&mut store, &entries, by, &|current, _total| {
+            pb.set_position(current as u64);
+        })?;
+    pb.finish_and_clear();
 
-    if with_att.is_empty() {
-        println!("  {}", i18n::cli_no_attachments_found());
-        return Ok(());
+    let written = match output {
+        Some(output) => {
+            let kept = mboxshell::export::dedup::without_duplicates(&entries, &clusters);
+            let dialect = dialect.unwrap_or(store.dialect());
+            mboxshell::export::mbox::export_mbox(&mut store, &kept, output, dialect, &|_, _| {})?;
+            Some((output, kept.len()))
+        }
+        None => None,
+    };
+
+    if json {
+        return print_dedup_json(path, &entries, by, &clusters, written);
+    }
+    print_dedup_table(&entries, by, &clusters, written.is_none());
+    if let Some((output, kept)) = written {
+        use humansize::{format_size, BINARY};
+        println!("  {}", i18n::cli_dedup_complete());
+        println!("  {:<25} {}", i18n::cli_total_messages(), kept);
+        println!(
+            "  {:<25} {}",
+            i18n::cli_duplicates_removed(),
+            entries.len() - kept
+        );
+        println!(
+            "  {:<25} {}",
+            i18n::cli_output_size(),
+            format_size(std::fs::metadata(output)?.len(), BINARY)
+        );
+        println!("  {:<25} {}", i18n::cli_output_file(), output.display());
+        println!();
     }
+    Ok(())
+}
 
-    println!(
-        "  {} {} message(s)",
-        i18n::cli_extracting_from(),
-        with_att.len()
-    );
+/// Compare two MBOX files, and write the messages only in the second to
+/// `only_in_b` when given.
+fn cmd_diff(
+    a: &Path,
+    b: &Path,
+    only_in_b: Option<&Path>,
+    dialect: Option<MboxDialect>,
+    json: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    for path in [a, b] {
+        if !path.exists() {
+            anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
+        }
+    }
 
-    let pb = ProgressBar::new(with_att.len() as u64);
+    let entries_a = builder::build_index(a, force, None)?;
+    let entries_b = builder::build_index(b, force, None)?;
+    let mut store_a = mboxshell::store::reader::MboxStore::open(a)?;
+    let mut store_b = mboxshell::store::reader::MboxStore::open(b)?;
+
+    let pb = ProgressBar::new((entries_a.len() + entries_b.len()) as u64);
     pb.set_style(
         ProgressStyle::default_bar()
             .template(&format!(
                 "{{spinner:.green}} {} [{{bar:40.cyan/blue}}] {{pos}}/{{len}}",
-                i18n::cli_extracting()
+                i18n::cli_comparing()
             ))
             .expect("valid template")
             .progress_chars("#>-"),
     );
-
-    let paths = mboxshell::export::attachment::export_bulk_attachments(
-        &mut store,
-        &with_att,
-        output,
+    let diff = mboxshell::export::diff::diff_mailboxes(
+        &mut store_a,
+        &entries_a,
+        &mut store_b,
+        &entries_b,
         &|current, _total| {
             pb.set_position(current as u64);
         },
     )?;
-
     pb.finish_and_clear();
-    println!(
-        "  {} {} {} {}",
-        i18n::cli_extracted(),
-        paths.len(),
-        i18n::cli_attachments_to(),
-        output.display()
+
+    if let Some(output) = only_in_b {
+        let selected: Vec<&mboxshell::model::mail::MailEntry> =
+            diff.only_in_b.iter().map(|&i| &entries_b[i]).collect();
+        let dialect = dialect.unwrap_or(store_b.dialect());
+        mboxshell::export::mbox::export_mbox(&mut store_b, &selected, output, dialect, &|_, _| {})?;
+    }
+
+    if json {
+        print_diff_json((a, &entries_a), (b, &entries_b), &diff, only_in_b)
+    } else {
+        print_diff_table((a, &entries_a), (b, &entries_b), &diff, only_in_b);
+        Ok(())
+    }
+}
+
+/// Check an MBOX file and report every anomaly the parser tolerates. Exits
+/// with status 1 when one is more than informational, for scripts.
+fn cmd_verify(path: &Path, json: bool) -> anyhow::Result<()> {
+    if !path.exists() {
+        anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
+    }
+
+    let file_size = builder::mailbox_size(path)?;
+    let pb = ProgressBar::new(file_size);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(&format!(
+                "{{spinner:.green}} {} [{{bar:40.cyan/blue}}] {{bytes}}/{{total_bytes}}",
+                i18n::cli_verifying()
+            ))
+            .expect("valid template")
+            .progress_chars("#>-"),
     );
+    let report = verify::verify_mbox(
+        path,
+        Some(&|current, total| {
+            pb.set_length(total);
+            pb.set_position(current);
+        }),
+    )?;
+    pb.finish_and_clear();
 
+    if json {
+        print_verify_json(path, &report)?;
+    } else {
+        print_verify_table(path, &report);
+    }
+    if report.worst() > Some(verify::Severity::Info) {
+        return Err(ProblemsFound.into());
+    }
     Ok(())
 }
 
-/// Print search results as a human-readable table.
-fn print_search_results_table(entries: &[mboxshell::model::mail::MailEntry], results: &[usize]) {
-    use humansize::{format_size, BINARY};
+/// Write a repaired copy of an MBOX file.
+fn cmd_repair(path: &Path, output: &Path, dialect: Option<MboxDialect>) -> anyhow::Result<()> {
+    if !path.exists() {
+        anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
+    }
+
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(&format!(
+                "{{spinner:.green}} {} [{{bar:40.cyan/blue}}] {{pos}}/{{len}}",
+                i18n::cli_repairing()
+            ))
+            .expect("valid template")
+            .progress_chars("#>-"),
+    );
+    let stats = mboxshell::export::mbox::repair_mbox(path, output, dialect, &|current, total| {
+        pb.set_length(total as u64);
+        pb.set_position(current as u64);
+    })?;
+    pb.finish_and_clear();
 
+    use humansize::{format_size, BINARY};
     println!();
-    println!("  {} {}", results.len(), i18n::tui_results());
+    println!("  {}", i18n::cli_repair_complete());
+    println!("  {:<25} {}", i18n::cli_total_messages(), stats.messages);
+    println!(
+        "  {:<25} {}",
+        i18n::cli_separators_rewritten(),
+        stats.separators_rewritten
+    );
+    println!(
+        "  {:<25} {}",
+        i18n::cli_line_endings_fixed(),
+        stats.line_endings_fixed
+    );
+    println!(
+        "  {:<25} {}",
+        i18n::cli_output_size(),
+        format_size(stats.output_size, BINARY)
+    );
+    println!("  {:<25} {}", i18n::cli_output_file(), output.display());
     println!();
 
-    if results.is_empty() {
-        return;
-    }
+    Ok(())
+}
 
-    println!(
-        "  {:<4} {:<17} {:<25} {:<40} {:>8}",
-        "#",
-        i18n::tui_col_date(),
-        i18n::tui_col_from(),
+/// Split an MBOX file into one mailbox per partition and print the manifest.
+fn cmd_split(
+    path: &Path,
+    by: SplitBy,
+    output: &Path,
+    query: Option<&str>,
+    dialect: Option<MboxDialect>,
+    json: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    if !path.exists() {
+        anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
+    }
+
+    let entries = builder::build_index(path, force, None)?;
+    let mut store = mboxshell::store::reader::MboxStore::open(path)?;
+
+    let indices: Vec<usize> = if let Some(q) = query {
+        let (_, results) = mboxshell::search::execute(path, &entries, q, None)?;
+        results
+    } else {
+        (0..entries.len()).collect()
+    };
+    let selected: Vec<&mboxshell::model::mail::MailEntry> =
+        indices.iter().map(|&i| &entries[i]).collect();
+
+    let pb = ProgressBar::new(selected.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(&format!(
+                "{{spinner:.green}} {} [{{bar:40.cyan/blue}}] {{pos}}/{{len}}",
+                i18n::cli_splitting()
+            ))
+            .expect("valid template")
+            .progress_chars("#>-"),
+    );
+    let dialect = dialect.unwrap_or(store.dialect());
+    let parts = mboxshell::export::split::split_mbox(
+        &mut store,
+        &selected,
+        by,
+        output,
+        dialect,
+        &|current, total| {
+            pb.set_length(total as u64);
+            pb.set_position(current as u64);
+        },
+    )?;
+    pb.finish_and_clear();
+
+    if json {
+        print_split_json(path, by, dialect, &parts)
+    } else {
+        print_split_table(output, by, &parts);
+        Ok(())
+    }
+}
+
+/// Extract all attachments from an MBOX file.
+fn cmd_attachments(path: &Path, output: &Path, force: bool) -> anyhow::Result<()> {
+    if !path.exists() {
+        anyhow::bail!("{}: {}", i18n::err_file_not_found(), path.display());
+    }
+
+    let entries = builder::build_index(path, force, None)?;
+    let mut store = mboxshell::store::reader::MboxStore::open(path)?;
+
+    let with_att: Vec<&mboxshell::model::mail::MailEntry> =
+        entries.iter().filter(|e| e.has_attachments).collect();
+
+    if with_att.is_empty() {
+        println!("  {}", i18n::cli_no_attachments_found());
+        return Ok(());
+    }
+
+    println!(
+        "  {} {} message(s)",
+        i18n::cli_extracting_from(),
+        with_att.len()
+    );
+
+    let pb = ProgressBar::new(with_att.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(&format!(
+                "{{spinner:.green}} {} [{{bar:40.cyan/blue}}] {{pos}}/{{len}}",
+                i18n::cli_extracting()
+            ))
+            .expect("valid template")
+            .progress_chars("#>-"),
+    );
+
+    let paths = mboxshell::export::attachment::export_bulk_attachments(
+        &mut store,
+        &with_att,
+        output,
+        &|current, _total| {
+            pb.set_position(current as u64);
+        },
+    )?;
+
+    pb.finish_and_clear();
+    println!(
+        "  {} {} {} {}",
+        i18n::cli_extracted(),
+        paths.len(),
+        i18n::cli_attachments_to(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+/// Print search results as a human-readable table.
+fn print_search_results_table(
+    entries: &[mboxshell::model::mail::MailEntry],
+    results: &[usize],
+    ranked: Option<&[search::rank::RankedHit]>,
+) {
+    use humansize::{format_size, BINARY};
+    use std::io::IsTerminal;
+
+    println!();
+    println!("  {} {}", results.len(), i18n::tui_results());
+    println!();
+
+    if results.is_empty() {
+        return;
+    }
+
+    let score_col = match ranked {
+        Some(_) => format!(" {:>6}", i18n::cli_col_score()),
+        None => String::new(),
+    };
+    println!(
+        "  {:<4} {:<17} {:<25} {:<40} {:>8}{score_col}",
+        "#",
+        i18n::tui_col_date(),
+        i18n::tui_col_from(),
         i18n::tui_col_subject(),
         i18n::tui_col_size()
     );
-    println!("  {}", "-".repeat(98));
+    println!("  {}", "-".repeat(98 + score_col.len()));
+    // Matches are shown in bold on a terminal, and left plain in a pipe.
+    let bold = std::io::stdout().is_terminal();
 
     for (i, &idx) in results.iter().enumerate() {
         let entry = &entries[idx];
@@ -795,29 +1454,61 @@ fn print_search_results_table(entries: &[mboxshell::model::mail::MailEntry], res
         )
         .into_owned();
         let size = format_size(entry.length, BINARY);
+        let hit = ranked.map(|hits| &hits[i]);
+        let score = match hit {
+            Some(hit) => format!(" {:>6.2}", hit.score),
+            None => String::new(),
+        };
 
         println!(
-            "  {:<4} {:<17} {:<25} {:<40} {:>8}",
+            "  {:<4} {:<17} {:<25} {:<40} {:>8}{score}",
             i + 1,
             date,
             from_trunc,
             subj_trunc,
             size
         );
+        for snippet in hit.map(|hit| &hit.snippets[..]).unwrap_or_default() {
+            println!("       {}", render_snippet(snippet, bold));
+        }
     }
     println!();
 }
 
+/// A snippet on one line, its matches in bold when `bold` is set. Snippets
+/// are body text, so each piece is sanitized like the result table.
+fn render_snippet(snippet: &search::rank::Snippet, bold: bool) -> String {
+    let sanitize = |s: &str| mboxshell::tui::text::sanitize_line(s).into_owned();
+    let (on, off) = if bold {
+        ("\x1b[1m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+    let mut out = String::new();
+    let mut pos = 0;
+    for range in &snippet.highlights {
+        out.push_str(&sanitize(&snippet.text[pos..range.start]));
+        out.push_str(on);
+        out.push_str(&sanitize(&snippet.text[range.clone()]));
+        out.push_str(off);
+        pos = range.end;
+    }
+    out.push_str(&sanitize(&snippet.text[pos..]));
+    out
+}
+
 /// Print search results as JSON.
 fn print_search_results_json(
     entries: &[mboxshell::model::mail::MailEntry],
     results: &[usize],
+    ranked: Option<&[search::rank::RankedHit]>,
 ) -> anyhow::Result<()> {
     let items: Vec<serde_json::Value> = results
         .iter()
-        .map(|&idx| {
+        .enumerate()
+        .map(|(i, &idx)| {
             let e = &entries[idx];
-            serde_json::json!({
+            let mut item = serde_json::json!({
                 "index": idx,
                 "date": e.date.to_rfc3339(),
                 "from": {
@@ -832,8 +1523,29 @@ fn print_search_results_json(
                 "message_id": e.message_id,
                 "size": e.length,
                 "has_attachments": e.has_attachments,
+                "attachments": e.attachments,
                 "labels": e.labels,
-            })
+            });
+            if let Some(hit) = ranked.map(|hits| &hits[i]) {
+                // Highlights are character offsets into the snippet text.
+                let snippets: Vec<serde_json::Value> = hit
+                    .snippets
+                    .iter()
+                    .map(|s| {
+                        serde_json::json!({
+                            "text": s.text,
+                            "highlights": s
+                                .char_highlights()
+                                .iter()
+                                .map(|r| [r.start, r.end])
+                                .collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect();
+                item["score"] = serde_json::json!(hit.score);
+                item["snippets"] = serde_json::json!(snippets);
+            }
+            item
         })
         .collect();
 
@@ -846,6 +1558,74 @@ fn print_search_results_json(
     Ok(())
 }
 
+/// Print the facets of a search, `limit` values per facet (0 = all).
+fn print_facets_table(facets: &search::facets::Facets, limit: usize) {
+    println!();
+    println!("  {} {}", facets.total, i18n::tui_results());
+    for facet in &facets.facets {
+        if facet.values.is_empty() {
+            continue;
+        }
+        println!();
+        println!("  {}:", i18n::facet_title(facet.kind));
+        let shown = if limit == 0 {
+            facet.values.len()
+        } else {
+            limit
+        };
+        for value in facet.values.iter().take(shown) {
+            // Sanitized like the result table: values come from raw headers.
+            let text = mboxshell::tui::text::sanitize_line(&value.value).into_owned();
+            println!("    {:>6}  {text}", value.count);
+        }
+        if facet.values.len() > shown {
+            let rest = facet.values.len() - shown;
+            println!("            … {rest} {}", i18n::facet_more());
+        }
+    }
+    println!();
+}
+
+/// Print the facets of a search as JSON, `limit` values per facet (0 = all).
+/// Each value carries the query term that narrows the search to it, when
+/// the query syntax has one.
+fn print_facets_json(facets: &search::facets::Facets, limit: usize) -> anyhow::Result<()> {
+    let mut by_kind = serde_json::Map::new();
+    for facet in &facets.facets {
+        let shown = if limit == 0 {
+            facet.values.len()
+        } else {
+            limit
+        };
+        let values: Vec<serde_json::Value> = facet
+            .values
+            .iter()
+            .take(shown)
+            .map(|v| {
+                serde_json::json!({
+                    "value": v.value,
+                    "count": v.count,
+                    "query": facet.kind.narrowing_term(&v.value),
+                })
+            })
+            .collect();
+        by_kind.insert(
+            facet.kind.name().to_string(),
+            serde_json::json!({
+                "distinct": facet.values.len(),
+                "values": values,
+            }),
+        );
+    }
+
+    let output = serde_json::json!({
+        "result_count": facets.total,
+        "facets": by_kind,
+    });
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
 /// Print statistics in a human-readable table.
 fn print_stats_table(
     path: &Path,
@@ -882,6 +1662,15 @@ fn print_stats_table(
         );
     }
 
+    let fulltext_size = builder::fulltext_file_size(path);
+    if fulltext_size > 0 {
+        println!(
+            "  {:<20} {}",
+            i18n::msg_fulltext_index_size(),
+            format_size(fulltext_size, BINARY)
+        );
+    }
+
     println!("  {:<20} {:.2?}", i18n::msg_indexing_time(), elapsed);
 
     let with_att = index_reader::count_with_attachments(entries);
@@ -916,6 +1705,522 @@ fn print_stats_table(
     println!();
 }
 
+/// Offsets listed per anomaly kind in the `verify` table.
+const VERIFY_OFFSETS_SHOWN: usize = 5;
+
+/// Print a verification report as a human-readable table: one line per kind
+/// of anomaly found, with the first offsets where it occurs.
+fn print_verify_table(path: &Path, report: &verify::VerifyReport) {
+    println!();
+    println!("  {:<20} {}", i18n::msg_file(), path.display());
+    println!("  {:<20} {}", i18n::cli_dialect(), report.dialect);
+    println!("  {:<20} {}", i18n::msg_message_count(), report.messages);
+    println!(
+        "  {:<20} {} LF, {} CRLF",
+        i18n::cli_line_endings(),
+        report.lf_lines,
+        report.crlf_lines
+    );
+    println!();
+
+    if report.anomalies.is_empty() {
+        println!("  {}", i18n::cli_no_anomalies());
+        println!();
+        return;
+    }
+    for kind in verify::AnomalyKind::ALL {
+        let found: Vec<&verify::Anomaly> =
+            report.anomalies.iter().filter(|a| a.kind == kind).collect();
+        if found.is_empty() {
+            continue;
+        }
+        let severity = match kind.severity() {
+            verify::Severity::Info => i18n::cli_severity_info(),
+            verify::Severity::Warning => i18n::cli_severity_warning(),
+            verify::Severity::Error => i18n::cli_severity_error(),
+        };
+        let occurrences: u64 = found.iter().map(|a| a.count).sum();
+        println!(
+            "  {severity:<8} {occurrences:>8}  {}",
+            anomaly_description(kind)
+        );
+        let mut offsets: Vec<String> = found
+            .iter()
+            .take(VERIFY_OFFSETS_SHOWN)
+            .map(|a| a.offset.to_string())
+            .collect();
+        if found.len() > VERIFY_OFFSETS_SHOWN {
+            offsets.push(format!(
+                "… (+{} {})",
+                found.len() - VERIFY_OFFSETS_SHOWN,
+                i18n::cli_more()
+            ));
+        }
+        println!(
+            "  {:<18} {}: {}",
+            "",
+            i18n::cli_offsets(),
+            offsets.join(", ")
+        );
+    }
+    println!();
+}
+
+/// The localized description of an anomaly kind.
+fn anomaly_description(kind: verify::AnomalyKind) -> &'static str {
+    use verify::AnomalyKind::*;
+    match kind {
+        Bom => i18n::anomaly_bom(),
+        BareSeparator => i18n::anomaly_bare_separator(),
+        MalformedSeparator => i18n::anomaly_malformed_separator(),
+        LeadingGarbage => i18n::anomaly_leading_garbage(),
+        MissingBlankLine => i18n::anomaly_missing_blank_line(),
+        MissingHeaderEnd => i18n::anomaly_missing_header_end(),
+        UnquotedFrom => i18n::anomaly_unquoted_from(),
+        MixedLineEndings => i18n::anomaly_mixed_line_endings(),
+        NulBytes => i18n::anomaly_nul_bytes(),
+        ContentLengthMismatch => i18n::anomaly_content_length_mismatch(),
+        Truncated => i18n::anomaly_truncated(),
+        Oversized => i18n::anomaly_oversized(),
+    }
+}
+
+/// Print a verification report as JSON: a summary per kind of anomaly and
+/// every anomaly with its offsets.
+fn print_verify_json(path: &Path, report: &verify::VerifyReport) -> anyhow::Result<()> {
+    let summary: Vec<serde_json::Value> = verify::AnomalyKind::ALL
+        .into_iter()
+        .filter(|&kind| report.count(kind) > 0)
+        .map(|kind| {
+            let found = report.anomalies.iter().filter(|a| a.kind == kind);
+            serde_json::json!({
+                "kind": kind,
+                "severity": kind.severity(),
+                "anomalies": report.count(kind),
+                "occurrences": found.map(|a| a.count).sum::<u64>(),
+            })
+        })
+        .collect();
+
+    let json = serde_json::json!({
+        "file": path.to_string_lossy(),
+        "dialect": report.dialect.name(),
+        "messages": report.messages,
+        "bytes": report.bytes,
+        "line_endings": {
+            "lf": report.lf_lines,
+            "crlf": report.crlf_lines,
+        },
+        "worst": report.worst(),
+        "summary": summary,
+        "anomalies": report.anomalies,
+    });
+
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
+
+/// Print the manifest of a split: one row per part with its messages, size
+/// and date range.
+fn print_split_table(output: &Path, by: SplitBy, parts: &[SplitPart]) {
+    use humansize::{format_size, BINARY};
+
+    println!();
+    println!("  {}", i18n::cli_split_complete());
+    println!(
+        "  {} {} {}",
+        parts.len(),
+        i18n::cli_split_parts(),
+        output.display()
+    );
+    println!();
+    if parts.is_empty() {
+        return;
+    }
+
+    println!(
+        "  {:<30} {:>9} {:>10}  {}",
+        i18n::cli_col_file(),
+        i18n::cli_col_messages(),
+        i18n::tui_col_size(),
+        i18n::msg_date_range()
+    );
+    println!("  {}", "-".repeat(75));
+    for part in parts {
+        let name = part
+            .file
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let dates = part
+            .date_range
+            .map(|(oldest, newest)| {
+                format!(
+                    "{} — {}",
+                    oldest.format("%Y-%m-%d"),
+                    newest.format("%Y-%m-%d")
+                )
+            })
+            .unwrap_or_default();
+        println!(
+            "  {:<30} {:>9} {:>10}  {}",
+            name,
+            part.messages,
+            format_size(part.bytes, BINARY),
+            dates
+        );
+    }
+    if by == SplitBy::Label {
+        println!();
+        println!("  {}", i18n::cli_split_duplicated());
+    }
+    let oversized: Vec<String> = parts
+        .iter()
+        .filter(|part| part.oversized)
+        .filter_map(|part| part.file.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    if !oversized.is_empty() {
+        println!();
+        println!(
+            "  {}: {}",
+            i18n::cli_split_oversized(),
+            oversized.join(", ")
+        );
+    }
+    println!();
+}
+
+/// Print the manifest of a split as JSON, with the `Message-ID`s of every
+/// part.
+fn print_split_json(
+    path: &Path,
+    by: SplitBy,
+    dialect: MboxDialect,
+    parts: &[SplitPart],
+) -> anyhow::Result<()> {
+    let parts_json: Vec<serde_json::Value> = parts
+        .iter()
+        .map(|part| {
+            let date_range = part.date_range.map(|(oldest, newest)| {
+                serde_json::json!({
+                    "oldest": oldest.to_rfc3339(),
+                    "newest": newest.to_rfc3339(),
+                })
+            });
+            serde_json::json!({
+                "file": part.file.to_string_lossy(),
+                "key": part.key,
+                "messages": part.messages,
+                "bytes": part.bytes,
+                "date_range": date_range,
+                "message_ids": part.message_ids,
+                "oversized": part.oversized,
+            })
+        })
+        .collect();
+
+    let json = serde_json::json!({
+        "file": path.to_string_lossy(),
+        "by": by.to_string(),
+        "dialect": dialect.name(),
+        "parts": parts_json,
+    });
+
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
+
+/// Messages listed per section in the `diff` table.
+const DIFF_MESSAGES_SHOWN: usize = 20;
+
+/// Print the differences between two mailboxes: the counts, then the first
+/// messages only in A, only in B and changed.
+fn print_diff_table(
+    (a, entries_a): (&Path, &[mboxshell::model::mail::MailEntry]),
+    (b, entries_b): (&Path, &[mboxshell::model::mail::MailEntry]),
+    diff: &MailboxDiff,
+    only_in_b: Option<&Path>,
+) {
+    println!();
+    println!(
+        "  A: {} ({} {})",
+        a.display(),
+        entries_a.len(),
+        i18n::cli_mbox_messages()
+    );
+    println!(
+        "  B: {} ({} {})",
+        b.display(),
+        entries_b.len(),
+        i18n::cli_mbox_messages()
+    );
+    println!();
+    let labels_changed = diff
+        .changed
+        .iter()
+        .filter(|c| !c.labels_added.is_empty() || !c.labels_removed.is_empty())
+        .count();
+    let content_changed = diff.changed.iter().filter(|c| c.content_changed).count();
+    println!(
+        "  {:<25} {}",
+        i18n::cli_diff_only_in_a(),
+        diff.only_in_a.len()
+    );
+    println!(
+        "  {:<25} {}",
+        i18n::cli_diff_only_in_b(),
+        diff.only_in_b.len()
+    );
+    println!(
+        "  {:<25} {}",
+        i18n::cli_diff_labels_changed(),
+        labels_changed
+    );
+    println!(
+        "  {:<25} {}",
+        i18n::cli_diff_content_changed(),
+        content_changed
+    );
+    println!("  {:<25} {}", i18n::cli_diff_unchanged(), diff.unchanged);
+    println!(
+        "  {:<25} {}",
+        i18n::cli_diff_matched_by_content(),
+        diff.matched_by_content
+    );
+
+    if diff.is_empty() {
+        println!();
+        println!("  {}", i18n::cli_diff_identical());
+    }
+
+    let sections = [
+        (i18n::cli_diff_only_in_a(), entries_a, &diff.only_in_a),
+        (i18n::cli_diff_only_in_b(), entries_b, &diff.only_in_b),
+    ];
+    for (title, entries, indices) in sections {
+        if indices.is_empty() {
+            continue;
+        }
+        println!();
+        println!("  {title}:");
+        for &i in indices.iter().take(DIFF_MESSAGES_SHOWN) {
+            println!("    {}", diff_message_line(&entries[i]));
+        }
+        print_diff_more(indices.len());
+    }
+    if !diff.changed.is_empty() {
+        println!();
+        println!("  {}:", i18n::cli_diff_changed());
+        for change in diff.changed.iter().take(DIFF_MESSAGES_SHOWN) {
+            let mut what: Vec<String> = Vec::new();
+            what.extend(change.labels_added.iter().map(|l| format!("+{l}")));
+            what.extend(change.labels_removed.iter().map(|l| format!("-{l}")));
+            if change.content_changed {
+                what.push(i18n::cli_diff_content().to_string());
+            }
+            let what = mboxshell::tui::text::sanitize_line(&what.join(" ")).into_owned();
+            println!(
+                "    {}  [{}]",
+                diff_message_line(&entries_b[change.b]),
+                what
+            );
+        }
+        print_diff_more(diff.changed.len());
+    }
+
+    if let Some(output) = only_in_b {
+        println!();
+        println!(
+            "  {} {} ({} {})",
+            i18n::cli_exported_mbox(),
+            output.display(),
+            diff.only_in_b.len(),
+            i18n::cli_mbox_messages()
+        );
+    }
+    println!();
+}
+
+/// One message of a `diff` section: date, sender and subject.
+fn diff_message_line(entry: &mboxshell::model::mail::MailEntry) -> String {
+    let from = if entry.from.display_name.is_empty() {
+        &entry.from.address
+    } else {
+        &entry.from.display_name
+    };
+    let from = mboxshell::tui::text::sanitize_line(&from.chars().take(24).collect::<String>())
+        .into_owned();
+    let subject =
+        mboxshell::tui::text::sanitize_line(&entry.subject.chars().take(50).collect::<String>())
+            .into_owned();
+    format!(
+        "{:<17} {:<25} {}",
+        entry.date.format("%Y-%m-%d %H:%M"),
+        from,
+        subject
+    )
+}
+
+/// The `… (+N more)` line after a `diff` section cut short.
+fn print_diff_more(listed: usize) {
+    if listed > DIFF_MESSAGES_SHOWN {
+        println!(
+            "    … (+{} {})",
+            listed - DIFF_MESSAGES_SHOWN,
+            i18n::cli_more()
+        );
+    }
+}
+
+/// Print the differences between two mailboxes as JSON, listing every
+/// message.
+fn print_diff_json(
+    (a, entries_a): (&Path, &[mboxshell::model::mail::MailEntry]),
+    (b, entries_b): (&Path, &[mboxshell::model::mail::MailEntry]),
+    diff: &MailboxDiff,
+    only_in_b: Option<&Path>,
+) -> anyhow::Result<()> {
+    let message = |entry: &mboxshell::model::mail::MailEntry| {
+        serde_json::json!({
+            "offset": entry.offset,
+            "message_id": entry.message_id,
+            "date": entry.date.to_rfc3339(),
+            "from": entry.from.to_string(),
+            "subject": entry.subject,
+        })
+    };
+    let changed: Vec<serde_json::Value> = diff
+        .changed
+        .iter()
+        .map(|change| {
+            serde_json::json!({
+                "a_offset": entries_a[change.a].offset,
+                "b": message(&entries_b[change.b]),
+                "labels_added": change.labels_added,
+                "labels_removed": change.labels_removed,
+                "content_changed": change.content_changed,
+            })
+        })
+        .collect();
+
+    let json = serde_json::json!({
+        "a": { "file": a.to_string_lossy(), "messages": entries_a.len() },
+        "b": { "file": b.to_string_lossy(), "messages": entries_b.len() },
+        "only_in_a": diff.only_in_a.iter().map(|&i| message(&entries_a[i])).collect::<Vec<_>>(),
+        "only_in_b": diff.only_in_b.iter().map(|&i| message(&entries_b[i])).collect::<Vec<_>>(),
+        "changed": changed,
+        "unchanged": diff.unchanged,
+        "matched_by_content": diff.matched_by_content,
+        "only_in_b_file": only_in_b.map(|p| p.to_string_lossy()),
+    });
+
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
+
+/// Print the duplicate clusters: each kept message followed by its copies.
+/// A dry run lists every cluster; otherwise only the summary is printed.
+fn print_dedup_table(
+    entries: &[mboxshell::model::mail::MailEntry],
+    by: DedupStrategy,
+    clusters: &[DuplicateCluster],
+    list: bool,
+) {
+    let duplicates: usize = clusters.iter().map(|c| c.duplicates.len()).sum();
+    println!();
+    println!(
+        "  {} {} {} {} ({} {})",
+        clusters.len(),
+        i18n::cli_duplicate_clusters(),
+        duplicates,
+        i18n::cli_duplicates_found(),
+        i18n::cli_dedup_by(),
+        by
+    );
+    println!();
+    if !list || clusters.is_empty() {
+        return;
+    }
+
+    for (n, cluster) in clusters.iter().enumerate() {
+        let kept = &entries[cluster.kept];
+        let subject =
+            mboxshell::tui::text::sanitize_line(&kept.subject.chars().take(70).collect::<String>())
+                .into_owned();
+        println!("  [{}] {}", n + 1, subject);
+        let rows = std::iter::once((i18n::cli_dedup_kept(), cluster.kept)).chain(
+            cluster
+                .duplicates
+                .iter()
+                .map(|&i| (i18n::cli_dedup_duplicate(), i)),
+        );
+        for (label, i) in rows {
+            let entry = &entries[i];
+            let from = if entry.from.display_name.is_empty() {
+                &entry.from.address
+            } else {
+                &entry.from.display_name
+            };
+            let from =
+                mboxshell::tui::text::sanitize_line(&from.chars().take(24).collect::<String>())
+                    .into_owned();
+            let id = mboxshell::tui::text::sanitize_line(&entry.message_id).into_owned();
+            println!(
+                "      {:<10} {:>12}  {:<17} {:<25} {}",
+                label,
+                entry.offset,
+                entry.date.format("%Y-%m-%d %H:%M"),
+                from,
+                id
+            );
+        }
+    }
+    println!();
+}
+
+/// Print the duplicate clusters as JSON, with the output written if any.
+fn print_dedup_json(
+    path: &Path,
+    entries: &[mboxshell::model::mail::MailEntry],
+    by: DedupStrategy,
+    clusters: &[DuplicateCluster],
+    written: Option<(&Path, usize)>,
+) -> anyhow::Result<()> {
+    let message = |i: usize| {
+        let entry = &entries[i];
+        serde_json::json!({
+            "offset": entry.offset,
+            "message_id": entry.message_id,
+            "date": entry.date.to_rfc3339(),
+            "from": entry.from.to_string(),
+            "subject": entry.subject,
+        })
+    };
+    let clusters_json: Vec<serde_json::Value> = clusters
+        .iter()
+        .map(|cluster| {
+            serde_json::json!({
+                "kept": message(cluster.kept),
+                "duplicates": cluster.duplicates.iter().map(|&i| message(i)).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    let json = serde_json::json!({
+        "file": path.to_string_lossy(),
+        "strategy": by.name(),
+        "messages": entries.len(),
+        "duplicates": clusters.iter().map(|c| c.duplicates.len()).sum::<usize>(),
+        "clusters": clusters_json,
+        "output": written.map(|(output, _)| output.to_string_lossy()),
+        "kept": written.map(|(_, kept)| kept),
+    });
+
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
+
 /// Print statistics as JSON.
 fn print_stats_json(
     path: &Path,
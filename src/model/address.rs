@@ -1,11 +1,17 @@
-//! Email address parsing (RFC 5322 §3.4).
+//! Email address parsing (RFC 5322 §3.4), and the shared addresses the
+//! index keeps recipient lists as.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::mail::MailEntry;
 
 /// A parsed email address.
 ///
 /// # Examples
 /// - `"Juan García <juan@ejemplo.com>"` → `display_name = "Juan García"`, `address = "juan@ejemplo.com"`
 /// - `"user@example.com"` → `display_name = ""`, `address = "user@example.com"`
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
 pub struct EmailAddress {
     /// Human-readable display name (may be empty).
     pub display_name: String,
@@ -115,6 +121,41 @@ impl EmailAddress {
     }
 }
 
+/// An address as the index keeps recipients: one copy per distinct address,
+/// shared by every entry that names it once [`AddressPool::share`] has run
+/// over them. A mailing list, or the owner's own address in `Delivered-To`,
+/// is then stored once however many messages carry it.
+pub type SharedAddress = Arc<EmailAddress>;
+
+/// Hands out one [`SharedAddress`] per distinct address.
+#[derive(Debug, Default)]
+pub struct AddressPool {
+    addresses: HashSet<SharedAddress>,
+}
+
+impl AddressPool {
+    /// The pool's copy of `address`, added if it is new.
+    pub fn intern(&mut self, address: &SharedAddress) -> SharedAddress {
+        if let Some(shared) = self.addresses.get(address) {
+            return Arc::clone(shared);
+        }
+        self.addresses.insert(Arc::clone(address));
+        Arc::clone(address)
+    }
+
+    /// Make the entries share one copy of each recipient address. Entries
+    /// are parsed (on several threads) and read back from the index with a
+    /// copy per occurrence; this runs once over all of them afterwards.
+    pub fn share(entries: &mut [MailEntry]) {
+        let mut pool = Self::default();
+        for entry in entries {
+            for address in entry.recipients_mut() {
+                *address = pool.intern(address);
+            }
+        }
+    }
+}
+
 /// Strip surrounding double-quotes and trim whitespace.
 fn strip_quotes(s: &str) -> String {
     let trimmed = s.trim();
@@ -199,6 +240,32 @@ mod tests {
         assert_eq!(addr.display(), "alice@example.com");
     }
 
+    #[test]
+    fn test_share_interns_repeated_addresses() {
+        use crate::parser::header::parse_headers_to_entry;
+
+        let mut entries = vec![
+            parse_headers_to_entry(b"To: Alice <alice@example.com>\n", 0, 10, 0).unwrap(),
+            parse_headers_to_entry(
+                b"Cc: alice@example.com, bob@example.com\nSender: Alice <alice@example.com>\n",
+                0,
+                10,
+                1,
+            )
+            .unwrap(),
+        ];
+        assert!(!Arc::ptr_eq(
+            &entries[0].to[0],
+            &entries[1].sender.clone().unwrap()
+        ));
+
+        AddressPool::share(&mut entries);
+        let alice = &entries[0].to[0];
+        assert!(Arc::ptr_eq(alice, entries[1].sender.as_ref().unwrap()));
+        // The same address under another name is another entry.
+        assert!(!Arc::ptr_eq(alice, &entries[1].cc[0]));
+    }
+
     #[test]
     fn test_parse_empty() {
         let addr = EmailAddress::parse("");
@@ -2,6 +2,9 @@
 //!
 //! The actual content is NOT loaded until export time.
 //! Only offsets and metadata are stored.
+//!
+//! The index keeps an [`AttachmentSummary`] of each attachment, so they can
+//! be searched by type, size and count without reading the messages.
 
 /// Metadata about an email attachment.
 ///
@@ -39,3 +42,36 @@ pub struct AttachmentMeta {
     #[serde(default)]
     pub part_index: usize,
 }
+
+/// What the index records about an attachment: enough to search by type and
+/// size (`attachment-type:`, `attachment-size:`) without reading the message.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AttachmentSummary {
+    /// Filename of the attachment, as in [`AttachmentMeta::filename`].
+    pub filename: String,
+
+    /// Lowercase MIME content type, without parameters (`"application/pdf"`).
+    pub content_type: String,
+
+    /// Decoded size in bytes.
+    pub size: u64,
+}
+
+impl From<&AttachmentMeta> for AttachmentSummary {
+    fn from(meta: &AttachmentMeta) -> Self {
+        let content_type = meta.content_type.split(';').next().unwrap_or("");
+        Self {
+            filename: meta.filename.clone(),
+            content_type: content_type.trim().to_lowercase(),
+            size: meta.size,
+        }
+    }
+}
+
+impl AttachmentSummary {
+    /// The lowercase extension of the filename (`"pdf"`), if it has one.
+    pub fn extension(&self) -> Option<String> {
+        let (stem, extension) = self.filename.rsplit_once('.')?;
+        (!stem.is_empty() && !extension.is_empty()).then(|| extension.to_lowercase())
+    }
+}
@@ -2,17 +2,24 @@
 
 use chrono::{DateTime, Utc};
 
-use super::address::EmailAddress;
-use super::attachment::AttachmentMeta;
+use super::address::{EmailAddress, SharedAddress};
+use super::attachment::{AttachmentMeta, AttachmentSummary};
 
 /// Compact metadata for a single email message, stored in the binary index.
 ///
 /// All indexed messages are kept in memory as `Vec<MailEntry>`.
-/// At ~500 bytes per entry, 1 million messages ≈ 500 MB of RAM.
+/// At ~500 bytes per entry, 1 million messages ≈ 500 MB of RAM. Recipient
+/// lists are complete but cost a pointer per address, the addresses
+/// themselves being shared between entries (see [`SharedAddress`]).
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct MailEntry {
     /// Byte offset of the message start inside the MBOX file
     /// (points to the `From ` separator line).
+    ///
+    /// For sources that keep one message per file (Maildir) this is a
+    /// virtual offset: the running total of the sizes of the files before
+    /// it. It stays unique per message, which the body cache and the TUI's
+    /// marks rely on.
     pub offset: u64,
 
     /// Total byte length of the message (from `From ` to next separator or EOF).
@@ -25,11 +32,25 @@ pub struct MailEntry {
     /// Sender (first `From:` header).
     pub from: EmailAddress,
 
-    /// Primary recipients (`To:`), truncated to the first 5.
-    pub to: Vec<EmailAddress>,
+    /// Primary recipients (`To:`).
+    pub to: Vec<SharedAddress>,
+
+    /// Carbon-copy recipients (`CC:`).
+    pub cc: Vec<SharedAddress>,
+
+    /// Blind-copy recipients (`Bcc:`), kept by the sender's copy of a message.
+    pub bcc: Vec<SharedAddress>,
+
+    /// Where replies should go (`Reply-To:`).
+    pub reply_to: Vec<SharedAddress>,
 
-    /// Carbon-copy recipients (`CC:`), truncated to the first 5.
-    pub cc: Vec<EmailAddress>,
+    /// The mailbox that actually sent the message on behalf of `from`
+    /// (`Sender:`), if it says so.
+    pub sender: Option<SharedAddress>,
+
+    /// The mailboxes the message was delivered to, one per `Delivered-To:`
+    /// header.
+    pub delivered_to: Vec<SharedAddress>,
 
     /// Decoded subject line (RFC 2047 encoded-words resolved).
     pub subject: String,
@@ -47,6 +68,11 @@ pub struct MailEntry {
     /// (detected via `Content-Type: multipart/mixed` or similar).
     pub has_attachments: bool,
 
+    /// The message's attachments, inline parts (images embedded in the HTML)
+    /// left out. Filled in by the indexer, which reads the messages with
+    /// [`has_attachments`](Self::has_attachments) set; empty for the others.
+    pub attachments: Vec<AttachmentSummary>,
+
     /// Top-level `Content-Type` of the message.
     pub content_type: String,
 
@@ -60,6 +86,11 @@ pub struct MailEntry {
     /// the sidebar works for a whole Takeout archive, not just its Gmail half.
     pub labels: Vec<String>,
 
+    /// Tags the user gave the message, from the state overlay
+    /// ([`crate::index::state`]). Not stored in the index.
+    #[serde(skip)]
+    pub tags: Vec<String>,
+
     /// Explicit conversation id when the mailbox provides one (`X-GM-THRID`,
     /// written by Gmail and Google Groups exports).
     ///
@@ -70,6 +101,71 @@ pub struct MailEntry {
 
     /// Sequential index within the MBOX (0, 1, 2, …).
     pub sequence: u64,
+
+    /// Path of the message file relative to the mailbox root, with `/`
+    /// separators, for sources that keep one message per file (Maildir).
+    /// For a Takeout archive, the mailbox member the message came from; the
+    /// message itself is still read at `offset`/`length`.
+    /// `None` for MBOX, where `offset`/`length` address the message directly.
+    pub file: Option<String>,
+
+    /// Status flags recorded by the mailbox itself (Maildir `:2,` info, MBOX
+    /// status headers), with the read and starred states the user set laid
+    /// over them (see [`crate::index::state`]).
+    pub flags: MailFlags,
+}
+
+impl MailEntry {
+    /// Every recipient-side address: To, Cc, Bcc, Reply-To, Sender and
+    /// Delivered-To.
+    pub fn recipients(&self) -> impl Iterator<Item = &SharedAddress> {
+        self.to
+            .iter()
+            .chain(&self.cc)
+            .chain(&self.bcc)
+            .chain(&self.reply_to)
+            .chain(&self.sender)
+            .chain(&self.delivered_to)
+    }
+
+    /// [`recipients`](Self::recipients), mutably.
+    pub(crate) fn recipients_mut(&mut self) -> impl Iterator<Item = &mut SharedAddress> {
+        self.to
+            .iter_mut()
+            .chain(&mut self.cc)
+            .chain(&mut self.bcc)
+            .chain(&mut self.reply_to)
+            .chain(&mut self.sender)
+            .chain(&mut self.delivered_to)
+    }
+
+    /// Everyone the message involves: the sender (`From:`) and every
+    /// [recipient](Self::recipients).
+    pub fn participants(&self) -> impl Iterator<Item = &EmailAddress> {
+        std::iter::once(&self.from).chain(self.recipients().map(|a| &**a))
+    }
+}
+
+/// Per-message status flags stored by the source mailbox.
+///
+/// Maildir encodes them as letters after `:2,` in the file name. MBOX has no
+/// portable equivalent; MBOX entries take the read and starred states from
+/// the `X-Gmail-Labels`, `Status:` and `X-Status:` headers, and are read
+/// when they have none of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MailFlags {
+    /// `S`: the message has been read.
+    pub seen: bool,
+    /// `R`: the message has been replied to.
+    pub replied: bool,
+    /// `F`: the message is flagged (starred).
+    pub flagged: bool,
+    /// `P`: the message has been forwarded ("passed").
+    pub passed: bool,
+    /// `D`: the message is a draft.
+    pub draft: bool,
+    /// `T`: the message is marked for deletion.
+    pub trashed: bool,
 }
 
 /// Full body of a message, loaded on demand.
@@ -0,0 +1,228 @@
+//! Google Takeout archives — `.zip`, `.tgz` (or any compressed `.tar`) —
+//! indexed in place, without extracting them first.
+//!
+//! Every mailbox member of the archive ([`source::is_mailbox_member`]) is
+//! scanned as an MBOX of its own and the results are indexed as one virtual
+//! mailbox. Offsets address the content the store reads back:
+//!
+//! - **tar** — the (decompressed) tar stream itself, so a plain `.tar` is
+//!   read like an MBOX file and a `.tgz` like an `.mbox.gz`, checkpoints
+//!   included. Other members are skipped (by seeking, in a plain `.tar`).
+//! - **zip** — the concatenation of the mailbox members, which is what
+//!   [`crate::source::zip`] reads.
+//!
+//! Each entry records the member it came from in [`MailEntry::file`] and
+//! gets the member's display name as a label, so the sidebar lists the
+//! mailboxes of the archive.
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::error::{MboxError, Result};
+use crate::mailbox_naming;
+use crate::model::mail::MailEntry;
+use crate::parser::{header, mbox};
+use crate::source::{self, tar, zip, CheckpointTable, Codec, SourceReader};
+
+/// Read buffer over the archive's content, as for an MBOX.
+const READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Container format of an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    /// A tar stream, possibly gzip/zstd/xz compressed.
+    Tar,
+}
+
+/// Which kind of archive `path` is, or `None` for anything else (an MBOX,
+/// compressed or not, or a directory).
+pub fn detect(path: &Path) -> Result<Option<ArchiveKind>> {
+    match Codec::detect(path)? {
+        Some(Codec::Zip) => return Ok(Some(ArchiveKind::Zip)),
+        _ if !path.is_file() => return Ok(None),
+        _ => {}
+    }
+    let mut block = Vec::with_capacity(tar::BLOCK_SIZE as usize);
+    let read = SourceReader::open(path)?
+        .take(tar::BLOCK_SIZE)
+        .read_to_end(&mut block);
+    // A damaged compressed MBOX is reported by the MBOX parser.
+    Ok((read.is_ok() && tar::is_tar(&block)).then_some(ArchiveKind::Tar))
+}
+
+/// Index every mailbox in the archive at `path`, returning the entries and
+/// the checkpoint table of the archive's content (`None` for a plain tar).
+///
+/// `should_cancel` is checked before each message; when it returns `true`
+/// the scan stops early and the entries read so far are returned.
+pub fn index_archive(
+    path: &Path,
+    kind: ArchiveKind,
+    progress: Option<&dyn Fn(u64, u64)>,
+    should_cancel: &dyn Fn() -> bool,
+) -> Result<(Vec<MailEntry>, Option<CheckpointTable>)> {
+    let map_err = |e| MboxError::io(path, e);
+    let file_size = std::fs::metadata(path).map_err(map_err)?.len();
+    let mut reader =
+        BufReader::with_capacity(READ_BUFFER_SIZE, SourceReader::open_recording(path)?);
+    let mut scan = Scan {
+        path,
+        file_size,
+        progress,
+        should_cancel,
+        entries: Vec::new(),
+        mailboxes: Vec::new(),
+        cancelled: false,
+    };
+
+    match kind {
+        ArchiveKind::Zip => {
+            for member in zip::list_mailboxes(path).map_err(map_err)? {
+                scan.member(&mut reader, &member.name, member.size)?;
+                if scan.cancelled {
+                    break;
+                }
+            }
+        }
+        ArchiveKind::Tar => {
+            while let Some(member) = tar::next_member(&mut reader).map_err(map_err)? {
+                if member.is_file && member.size > 0 && source::is_mailbox_member(&member.name) {
+                    scan.member(&mut reader, &member.name, member.size)?;
+                    if scan.cancelled {
+                        break;
+                    }
+                } else {
+                    skip(&mut reader, member.size).map_err(map_err)?;
+                }
+                skip(&mut reader, tar::padding(member.size)).map_err(map_err)?;
+            }
+        }
+    }
+
+    let checkpoints = if scan.cancelled {
+        None
+    } else {
+        // Read past the end-of-archive marker so the table is complete.
+        io::copy(&mut reader, &mut io::sink()).map_err(map_err)?;
+        reader.get_mut().take_checkpoints()
+    };
+    if let Some(cb) = progress {
+        cb(file_size, file_size);
+    }
+
+    let Scan {
+        mut entries,
+        mailboxes,
+        ..
+    } = scan;
+    let paths: Vec<PathBuf> = mailboxes
+        .iter()
+        .map(|(name, _)| PathBuf::from(name))
+        .collect();
+    let names = mailbox_naming::unique_display_names(&paths);
+    for ((_, range), name) in mailboxes.into_iter().zip(names) {
+        for entry in &mut entries[range] {
+            if !entry.labels.contains(&name) {
+                entry.labels.push(name.clone());
+            }
+        }
+    }
+    Ok((entries, checkpoints))
+}
+
+/// State of an archive scan.
+struct Scan<'a> {
+    path: &'a Path,
+    file_size: u64,
+    progress: Option<&'a dyn Fn(u64, u64)>,
+    should_cancel: &'a dyn Fn() -> bool,
+    entries: Vec<MailEntry>,
+    /// Each mailbox member scanned, with the range of its entries.
+    mailboxes: Vec<(String, Range<usize>)>,
+    cancelled: bool,
+}
+
+impl Scan<'_> {
+    /// Scan the next `size` bytes of `reader`, a mailbox member called `name`.
+    fn member(
+        &mut self,
+        reader: &mut BufReader<SourceReader>,
+        name: &str,
+        size: u64,
+    ) -> Result<()> {
+        let base = content_position(reader);
+        let first = self.entries.len();
+        let mut member = reader.take(size);
+        let (entries, cancelled) = (&mut self.entries, &mut self.cancelled);
+        let (should_cancel, progress, file_size) =
+            (self.should_cancel, self.progress, self.file_size);
+
+        mbox::scan_headers(
+            &mut member,
+            self.path,
+            &mut |offset, length, header_bytes| {
+                if should_cancel() {
+                    *cancelled = true;
+                    return false;
+                }
+                let sequence = entries.len() as u64;
+                match header::parse_headers_to_entry(header_bytes, base + offset, length, sequence)
+                {
+                    Ok(mut entry) => {
+                        entry.file = Some(name.to_string());
+                        entries.push(entry);
+                    }
+                    Err(e) => {
+                        warn!(
+                            member = name,
+                            offset = base + offset,
+                            error = %e,
+                            "Skipping unparseable message"
+                        );
+                    }
+                }
+                true
+            },
+            &mut |_, member| {
+                if let Some(cb) = progress {
+                    cb(member.get_ref().get_ref().source_position(), file_size);
+                }
+            },
+        )?;
+        if !self.cancelled && member.limit() > 0 {
+            return Err(MboxError::io(
+                self.path,
+                io::Error::new(io::ErrorKind::UnexpectedEof, "archive is truncated"),
+            ));
+        }
+        self.mailboxes
+            .push((name.to_string(), first..self.entries.len()));
+        Ok(())
+    }
+}
+
+/// Offset in the content of the next byte `reader` will return.
+fn content_position(reader: &BufReader<SourceReader>) -> u64 {
+    reader.get_ref().position() - reader.buffer().len() as u64
+}
+
+/// Skip `n` bytes of content, seeking when the source allows it.
+fn skip(reader: &mut BufReader<SourceReader>, n: u64) -> io::Result<()> {
+    let buffered = reader
+        .buffer()
+        .len()
+        .min(usize::try_from(n).unwrap_or(usize::MAX));
+    reader.consume(buffered);
+    let rest = n - buffered as u64;
+    if rest > 0 && reader.get_mut().skip(rest)? < rest {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "archive is truncated",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,469 @@
+//! Plain text of attachments, for `attachment:` search.
+//!
+//! Text-based attachments (plain text, CSV, JSON, XML, calendars, HTML
+//! rendered to text) are read as they are; PDFs through `lopdf`; OOXML
+//! (DOCX, XLSX, PPTX) and ODF (ODT, ODS, ODP) documents by reading the XML
+//! parts that hold their text out of the zip container. Anything else —
+//! images, archives, encrypted or broken documents — has no text.
+
+use std::borrow::Cow;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::source::zip;
+
+use super::mime::html_to_text;
+
+/// Attachments larger than this (decoded, or uncompressed for the parts of
+/// a document) are not read.
+pub const MAX_ATTACHMENT_LEN: usize = 32 * 1024 * 1024;
+
+/// Extracted text is cut at this many bytes.
+pub const MAX_TEXT_LEN: usize = 1024 * 1024;
+
+/// Width HTML attachments are rendered at.
+const HTML_WIDTH: usize = 120;
+
+/// How an attachment's text is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Html,
+    Pdf,
+    Ooxml,
+    Odf,
+}
+
+/// The text of an attachment, by its `filename` and `content_type`, or
+/// `None` when it has none that can be read.
+pub fn extract_text(filename: &str, content_type: &str, data: &[u8]) -> Option<String> {
+    if data.len() > MAX_ATTACHMENT_LEN {
+        return None;
+    }
+    let mut text = match kind(filename, content_type)? {
+        Kind::Text => decode_text(data)?,
+        Kind::Html => html_to_text(&decode_text(data)?, HTML_WIDTH),
+        Kind::Pdf => pdf_text(data)?,
+        Kind::Ooxml => ooxml_text(data)?,
+        Kind::Odf => odf_text(data)?,
+    };
+    if text.len() > MAX_TEXT_LEN {
+        let mut end = MAX_TEXT_LEN;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// The kind of an attachment. The content type decides when it is specific;
+/// mail clients label many documents `application/octet-stream`, so the
+/// filename extension decides otherwise.
+fn kind(filename: &str, content_type: &str) -> Option<Kind> {
+    let content_type = content_type.to_ascii_lowercase();
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    let by_type = match content_type.as_str() {
+        "text/html" | "application/xhtml+xml" => Some(Kind::Html),
+        "application/pdf" | "application/x-pdf" => Some(Kind::Pdf),
+        ct if ct.starts_with("application/vnd.openxmlformats-officedocument.") => Some(Kind::Ooxml),
+        ct if ct.starts_with("application/vnd.oasis.opendocument.") => Some(Kind::Odf),
+        ct if ct.starts_with("text/") => Some(Kind::Text),
+        "application/json" | "application/xml" | "application/csv" | "application/x-yaml"
+        | "application/ics" => Some(Kind::Text),
+        _ => None,
+    };
+    by_type.or(match extension.as_str() {
+        "html" | "htm" | "xhtml" => Some(Kind::Html),
+        "pdf" => Some(Kind::Pdf),
+        "docx" | "docm" | "xlsx" | "xlsm" | "pptx" | "pptm" => Some(Kind::Ooxml),
+        "odt" | "ods" | "odp" => Some(Kind::Odf),
+        "txt" | "text" | "csv" | "tsv" | "md" | "json" | "xml" | "log" | "ics" | "vcf" | "yaml"
+        | "yml" => Some(Kind::Text),
+        _ => None,
+    })
+}
+
+/// Text attachment bytes as a string. Text parts arrive decoded to UTF-8;
+/// the rest is UTF-8 or, failing that, Windows-1252. Bytes with a NUL are
+/// binary data with a misleading label.
+fn decode_text(data: &[u8]) -> Option<String> {
+    if data.contains(&0) {
+        return None;
+    }
+    Some(match std::str::from_utf8(data) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1252.decode(data).0.into_owned(),
+    })
+}
+
+/// The text of every page of a PDF that can be read.
+fn pdf_text(data: &[u8]) -> Option<String> {
+    let document = lopdf::Document::load_mem(data).ok()?;
+    let pages: Vec<u32> = document.get_pages().into_keys().collect();
+    let text: String = document
+        .extract_text_chunks(&pages)
+        .into_iter()
+        .filter_map(|chunk| chunk.ok())
+        .collect();
+    Some(text)
+}
+
+/// The text of a DOCX, PPTX or XLSX document: the paragraphs of the body,
+/// of every slide, or the cells of every sheet.
+fn ooxml_text(data: &[u8]) -> Option<String> {
+    let parts =
+        zip::read_members(data, |name| ooxml_part(name).is_some(), MAX_ATTACHMENT_LEN).ok()?;
+    let mut parts: Vec<_> = parts
+        .into_iter()
+        .filter_map(|(name, xml)| Some((ooxml_part(&name)?, xml)))
+        .collect();
+    // Slides and sheets in their order, not the archive's; the shared
+    // strings before the sheets that refer to them.
+    parts.sort_by_key(|&(part, _)| part);
+
+    let mut text = String::new();
+    let mut shared = Vec::new();
+    for (part, xml) in &parts {
+        match part {
+            OoxmlPart::Document | OoxmlPart::Slide(_) => {
+                let (run, paragraph) = match part {
+                    OoxmlPart::Document => (&b"w:t"[..], &b"w:p"[..]),
+                    _ => (&b"a:t"[..], &b"a:p"[..]),
+                };
+                let mut in_run = false;
+                walk_xml(xml, |node| match node {
+                    Node::Open(e) => in_run = e.name().as_ref() == run,
+                    Node::Close(name) if name == paragraph => text.push('\n'),
+                    Node::Close(b"w:tab") => text.push('\t'),
+                    Node::Close(b"w:br" | b"a:br") => text.push('\n'),
+                    Node::Close(_) => in_run = false,
+                    Node::Text(t) if in_run => text.push_str(&t),
+                    Node::Text(_) => {}
+                })?;
+            }
+            OoxmlPart::SharedStrings => {
+                let mut current = String::new();
+                let mut in_text = false;
+                walk_xml(xml, |node| match node {
+                    Node::Open(e) => in_text = e.name().as_ref() == b"t",
+                    Node::Close(b"si") => shared.push(std::mem::take(&mut current)),
+                    Node::Close(_) => in_text = false,
+                    Node::Text(t) if in_text => current.push_str(&t),
+                    Node::Text(_) => {}
+                })?;
+            }
+            OoxmlPart::Sheet(_) => sheet_text(xml, &shared, &mut text)?,
+        }
+    }
+    Some(text)
+}
+
+/// The parts of an OOXML document that hold its text, in reading order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum OoxmlPart {
+    Document,
+    Slide(u32),
+    SharedStrings,
+    Sheet(u32),
+}
+
+fn ooxml_part(name: &str) -> Option<OoxmlPart> {
+    let numbered = |prefix: &str| {
+        name.strip_prefix(prefix)?
+            .strip_suffix(".xml")?
+            .parse::<u32>()
+            .ok()
+    };
+    match name {
+        "word/document.xml" => Some(OoxmlPart::Document),
+        "xl/sharedStrings.xml" => Some(OoxmlPart::SharedStrings),
+        _ => numbered("ppt/slides/slide")
+            .map(OoxmlPart::Slide)
+            .or_else(|| numbered("xl/worksheets/sheet").map(OoxmlPart::Sheet)),
+    }
+}
+
+/// The cells of an XLSX sheet, tab-separated, one row per line. Text cells
+/// refer to `shared` strings by index; numbers and inline strings are in
+/// the cell.
+fn sheet_text(xml: &[u8], shared: &[String], text: &mut String) -> Option<()> {
+    let mut shared_cell = false;
+    let mut in_value = false;
+    walk_xml(xml, |node| match node {
+        Node::Open(e) => match e.name().as_ref() {
+            b"c" => {
+                shared_cell = e
+                    .try_get_attribute("t")
+                    .ok()
+                    .flatten()
+                    .is_some_and(|t| t.value.as_ref() == b"s");
+            }
+            b"v" | b"t" => in_value = true,
+            _ => {}
+        },
+        Node::Close(name) => {
+            in_value = false;
+            match name {
+                b"c" => text.push('\t'),
+                b"row" => text.push('\n'),
+                _ => {}
+            }
+        }
+        Node::Text(t) if in_value && shared_cell => {
+            if let Some(s) = t.trim().parse::<usize>().ok().and_then(|i| shared.get(i)) {
+                text.push_str(s);
+            }
+        }
+        Node::Text(t) if in_value => text.push_str(&t),
+        Node::Text(_) => {}
+    })
+}
+
+/// The text of an ODT, ODS or ODP document: its paragraphs and headings,
+/// which also hold the text of table cells and slides.
+fn odf_text(data: &[u8]) -> Option<String> {
+    let (_, xml) = zip::read_members(data, |name| name == "content.xml", MAX_ATTACHMENT_LEN)
+        .ok()?
+        .pop()?;
+    let mut text = String::new();
+    let mut depth = 0usize;
+    walk_xml(&xml, |node| match node {
+        Node::Open(e) if matches!(e.name().as_ref(), b"text:p" | b"text:h") => depth += 1,
+        Node::Open(_) => {}
+        Node::Close(b"text:p" | b"text:h") => {
+            depth = depth.saturating_sub(1);
+            text.push('\n');
+        }
+        Node::Close(b"text:s") => text.push(' '),
+        Node::Close(b"text:tab") => text.push('\t'),
+        Node::Close(b"text:line-break") => text.push('\n'),
+        Node::Close(_) => {}
+        Node::Text(t) if depth > 0 => text.push_str(&t),
+        Node::Text(_) => {}
+    })?;
+    Some(text)
+}
+
+/// An XML node, as [`walk_xml`] visits it.
+enum Node<'a, 'x> {
+    Open(&'a BytesStart<'x>),
+    /// The end of an element, by qualified name. Empty elements are opened
+    /// and closed.
+    Close(&'a [u8]),
+    /// Character data, unescaped.
+    Text(Cow<'a, str>),
+}
+
+/// Visit the nodes of `xml` in document order; `None` when it is not
+/// well-formed.
+fn walk_xml(xml: &[u8], mut visit: impl FnMut(Node)) -> Option<()> {
+    let mut reader = Reader::from_reader(xml);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) => visit(Node::Open(&e)),
+            Event::Empty(e) => {
+                visit(Node::Open(&e));
+                visit(Node::Close(e.name().as_ref()));
+            }
+            Event::End(e) => visit(Node::Close(e.name().as_ref())),
+            Event::Text(t) => visit(Node::Text(t.unescape().ok()?)),
+            Event::CData(t) => visit(Node::Text(String::from_utf8_lossy(&t))),
+            Event::Eof => return Some(()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A zip archive storing `(name, data)` members uncompressed.
+    fn zip_archive(members: &[(&str, &str)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for &(name, data) in members {
+            let mut fields = vec![0, 0, 0, 0, 0, 0]; // method, time, date
+            fields.extend(crc32fast::hash(data.as_bytes()).to_le_bytes());
+            fields.extend((data.len() as u32).to_le_bytes());
+            fields.extend((data.len() as u32).to_le_bytes());
+            fields.extend((name.len() as u16).to_le_bytes());
+
+            central.extend([0x50, 0x4b, 1, 2, 20, 0, 20, 0, 0, 0]);
+            central.extend(&fields);
+            central.extend([0; 12]); // extra, comment, disk, attributes
+            central.extend((out.len() as u32).to_le_bytes());
+            central.extend(name.as_bytes());
+
+            out.extend([0x50, 0x4b, 3, 4, 20, 0, 0, 0]);
+            out.extend(&fields);
+            out.extend([0, 0]);
+            out.extend(name.as_bytes());
+            out.extend(data.as_bytes());
+        }
+        let cd_offset = out.len() as u32;
+        out.extend(&central);
+        out.extend([0x50, 0x4b, 5, 6, 0, 0, 0, 0]);
+        out.extend((members.len() as u16).to_le_bytes());
+        out.extend((members.len() as u16).to_le_bytes());
+        out.extend((central.len() as u32).to_le_bytes());
+        out.extend(cd_offset.to_le_bytes());
+        out.extend([0, 0]);
+        out
+    }
+
+    /// A one-page PDF showing `line`.
+    fn pdf(line: &str) -> Vec<u8> {
+        use lopdf::content::{Content, Operation};
+        use lopdf::{dictionary, Document, Object, Stream};
+
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Td", vec![100.into(), 700.into()]),
+                Operation::new("Tj", vec![Object::string_literal(line)]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        let mut out = Vec::new();
+        doc.save_to(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_text_attachments() {
+        assert_eq!(
+            extract_text(
+                "data.csv",
+                "text/csv",
+                "Fase,Importe\n1,800000\n".as_bytes()
+            )
+            .as_deref(),
+            Some("Fase,Importe\n1,800000\n")
+        );
+        // Labelled by extension only, and not UTF-8.
+        assert_eq!(
+            extract_text("notas.txt", "application/octet-stream", b"se\xf1al").as_deref(),
+            Some("se\u{f1}al")
+        );
+        let html = extract_text("page.html", "text/html", b"<p>Hello <b>there</b></p>").unwrap();
+        assert!(html.contains("Hello there"), "{html:?}");
+
+        assert_eq!(
+            extract_text("photo.jpg", "image/jpeg", b"\xff\xd8\xff"),
+            None
+        );
+        assert_eq!(extract_text("data.bin", "text/plain", b"a\0b"), None);
+        assert_eq!(extract_text("empty.txt", "text/plain", b"  \n"), None);
+    }
+
+    #[test]
+    fn test_pdf_text() {
+        let data = pdf("Invoice INV-2024-001 total 1,250.00 EUR");
+        let text = extract_text("factura.pdf", "application/octet-stream", &data).unwrap();
+        assert!(text.contains("Invoice INV-2024-001 total"), "{text:?}");
+
+        assert_eq!(
+            extract_text("broken.pdf", "application/pdf", b"%PDF-1.4 garbage"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_office_documents() {
+        let docx = zip_archive(&[
+            ("[Content_Types].xml", "<Types/>"),
+            (
+                "word/document.xml",
+                r#"<w:document><w:body><w:p><w:r><w:t>Contrato de </w:t></w:r><w:r><w:t>servicios</w:t></w:r></w:p><w:p><w:r><w:t>Tom &amp; Jerry</w:t><w:tab/><w:t>S.L.</w:t></w:r></w:p></w:body></w:document>"#,
+            ),
+        ]);
+        assert_eq!(
+            extract_text("contrato.docx", "application/octet-stream", &docx).as_deref(),
+            Some("Contrato de servicios\nTom & Jerry\tS.L.\n")
+        );
+
+        let xlsx = zip_archive(&[
+            (
+                "xl/worksheets/sheet1.xml",
+                r#"<worksheet><sheetData><row><c t="s"><v>1</v></c><c><v>1250.5</v></c></row><row><c t="inlineStr"><is><t>Total</t></is></c></row></sheetData></worksheet>"#,
+            ),
+            (
+                "xl/sharedStrings.xml",
+                r#"<sst><si><t>Concepto</t></si><si><r><t>Licen</t></r><r><t>cias</t></r></si></sst>"#,
+            ),
+        ]);
+        assert_eq!(
+            extract_text(
+                "presupuesto.xlsx",
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                &xlsx
+            )
+            .as_deref(),
+            Some("Licencias\t1250.5\t\nTotal\t\n")
+        );
+
+        let pptx = zip_archive(&[
+            (
+                "ppt/slides/slide10.xml",
+                "<p:sld><a:p><a:r><a:t>Last</a:t></a:r></a:p></p:sld>",
+            ),
+            (
+                "ppt/slides/slide2.xml",
+                "<p:sld><a:p><a:r><a:t>First</a:t></a:r></a:p></p:sld>",
+            ),
+        ]);
+        assert_eq!(
+            extract_text("deck.pptx", "", &pptx).as_deref(),
+            Some("First\nLast\n")
+        );
+
+        let odt = zip_archive(&[
+            ("mimetype", "application/vnd.oasis.opendocument.text"),
+            (
+                "content.xml",
+                r#"<office:document-content><office:body><office:text><text:h>Acta</text:h><text:p>Reuni<text:span>ón</text:span><text:s/>anual</text:p></office:text></office:body></office:document-content>"#,
+            ),
+        ]);
+        assert_eq!(
+            extract_text("acta.odt", "application/vnd.oasis.opendocument.text", &odt).as_deref(),
+            Some("Acta\nReunión anual\n")
+        );
+
+        assert_eq!(extract_text("bad.docx", "", b"PK\x03\x04 truncated"), None);
+    }
+}
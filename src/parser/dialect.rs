@@ -0,0 +1,301 @@
+//! MBOX dialects: how a mailbox marks where a message ends, and how it
+//! quotes body lines that would otherwise read as the start of the next one.
+//!
+//! - **mboxo** — messages are delimited by `From ` lines, and a body line
+//!   starting with `From ` is written `>From `. Nothing else is quoted, so a
+//!   `>From ` line read back may have been either.
+//! - **mboxrd** — like mboxo, but every line matching `>*From ` gets one more
+//!   `>`, so reading removes exactly one and the original always comes back.
+//! - **mboxcl** — mboxo quoting, plus a `Content-Length:` header with the
+//!   size of the body, which delimits the message.
+//! - **mboxcl2** — delimited by `Content-Length:`, and nothing is quoted.
+//!
+//! A mailbox does not say which one it is. [`MboxDialect::detect`] looks at
+//! its first messages: when their `Content-Length:` headers all land on the
+//! next `From ` line it is mboxcl or mboxcl2, told apart by whether their
+//! bodies quote `From `; otherwise it is mboxrd when a `>>From ` line shows
+//! up, mboxo when not.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Messages of the sample [`MboxDialect::detect`] checks at most.
+const DETECT_MESSAGES: usize = 16;
+
+/// An MBOX dialect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MboxDialect {
+    /// `From `-delimited, `From ` quoted as `>From `.
+    #[default]
+    Mboxo,
+    /// `From `-delimited, `>*From ` quoted with one more `>`.
+    Mboxrd,
+    /// `Content-Length:`-delimited, `From ` quoted as `>From `.
+    Mboxcl,
+    /// `Content-Length:`-delimited, nothing quoted.
+    Mboxcl2,
+}
+
+impl MboxDialect {
+    /// Every dialect, in the order they are listed to the user.
+    pub const ALL: [Self; 4] = [Self::Mboxo, Self::Mboxrd, Self::Mboxcl, Self::Mboxcl2];
+
+    /// The dialect's name, as `--dialect` takes it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mboxo => "mboxo",
+            Self::Mboxrd => "mboxrd",
+            Self::Mboxcl => "mboxcl",
+            Self::Mboxcl2 => "mboxcl2",
+        }
+    }
+
+    /// Whether messages end where their `Content-Length:` header says
+    /// rather than at the next `From ` line.
+    pub fn is_length_delimited(self) -> bool {
+        matches!(self, Self::Mboxcl | Self::Mboxcl2)
+    }
+
+    /// The dialect of a mailbox whose content starts with `sample`;
+    /// `complete` says whether the sample is the whole mailbox.
+    pub fn detect(sample: &[u8], complete: bool) -> Self {
+        let sample = sample.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(sample);
+        if let Some(bodies) = length_delimited_bodies(sample, complete) {
+            let quoted = bodies.iter().flat_map(|body| lines(body)).find_map(|line| {
+                if line.starts_with(b"From ") {
+                    Some(false)
+                } else {
+                    line.starts_with(b">From ").then_some(true)
+                }
+            });
+            return match quoted {
+                Some(true) => Self::Mboxcl,
+                _ => Self::Mboxcl2,
+            };
+        }
+        let rd_quoted = lines(sample).any(|line| {
+            let unquoted = line.strip_prefix(b">").unwrap_or(line);
+            unquoted.starts_with(b">") && quoted_from(unquoted).is_some()
+        });
+        if rd_quoted {
+            Self::Mboxrd
+        } else {
+            Self::Mboxo
+        }
+    }
+
+    /// `message` (starting with its `From ` line) with this dialect's
+    /// quoting of body lines undone.
+    pub fn unescape(self, message: &[u8]) -> Cow<'_, [u8]> {
+        if self == Self::Mboxcl2 || !message.windows(2).any(|w| w == b"\n>") {
+            return Cow::Borrowed(message);
+        }
+        let mut out = Vec::with_capacity(message.len());
+        for (i, line) in lines(message).enumerate() {
+            let unquote = i > 0
+                && match self {
+                    Self::Mboxrd => quoted_from(line).is_some(),
+                    _ => line.starts_with(b">From "),
+                };
+            out.extend_from_slice(if unquote { &line[1..] } else { line });
+        }
+        Cow::Owned(out)
+    }
+
+    /// Append `message` (the lines after the `From ` line) to `out`, quoting
+    /// the lines this dialect quotes.
+    pub fn escape_into(self, out: &mut Vec<u8>, message: &[u8]) {
+        out.reserve(message.len());
+        for line in lines(message) {
+            let quote = match self {
+                Self::Mboxo | Self::Mboxcl => line.starts_with(b"From "),
+                Self::Mboxrd => line.starts_with(b"From ") || quoted_from(line).is_some(),
+                Self::Mboxcl2 => false,
+            };
+            if quote {
+                out.push(b'>');
+            }
+            out.extend_from_slice(line);
+        }
+    }
+}
+
+impl fmt::Display for MboxDialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for MboxDialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|d| d.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|d| d.name()).collect();
+                format!("unknown dialect '{s}' (expected {})", names.join(", "))
+            })
+    }
+}
+
+/// The value of the `Content-Length:` header in a message's header block,
+/// if it has a valid one.
+pub fn content_length(headers: &[u8]) -> Option<u64> {
+    lines(headers).find_map(|line| {
+        let (name, value) = line.split_at(line.iter().position(|&b| b == b':')?);
+        if !name.eq_ignore_ascii_case(b"Content-Length") {
+            return None;
+        }
+        std::str::from_utf8(&value[1..]).ok()?.trim().parse().ok()
+    })
+}
+
+/// Where the header block of `message` ends: the offset of its body, after
+/// the blank line. `None` when the message has no blank line.
+pub fn body_start(message: &[u8]) -> Option<usize> {
+    let mut at = 0;
+    for line in lines(message) {
+        at += line.len();
+        if line == b"\n" || line == b"\r\n" {
+            return Some(at);
+        }
+    }
+    None
+}
+
+/// `line` without the `>` quoting of a `>+From ` line, or `None` for any
+/// other line.
+fn quoted_from(line: &[u8]) -> Option<&[u8]> {
+    let unquoted = &line[line.iter().position(|&b| b != b'>')?..];
+    (unquoted.len() < line.len() && unquoted.starts_with(b"From ")).then_some(unquoted)
+}
+
+/// The bodies of the first messages of `sample` when every one of them ends
+/// exactly where its `Content-Length:` says — at the end of the mailbox or
+/// (after blank lines) at the next `From ` line — or `None` when one does
+/// not, or has no such header.
+fn length_delimited_bodies(sample: &[u8], complete: bool) -> Option<Vec<&[u8]>> {
+    let mut bodies = Vec::new();
+    let mut rest = sample;
+    while rest.starts_with(b"From ") && bodies.len() < DETECT_MESSAGES {
+        let Some(start) = body_start(rest) else {
+            break;
+        };
+        let length = usize::try_from(content_length(&rest[..start])?).ok()?;
+        let Some(body) = rest.get(start..start + length) else {
+            // The message runs past the sample: it cannot be checked.
+            if complete {
+                return None;
+            }
+            break;
+        };
+        bodies.push(body);
+        rest = &rest[start + length..];
+        while let Some(after) = rest
+            .strip_prefix(b"\n")
+            .or_else(|| rest.strip_prefix(b"\r\n"))
+        {
+            rest = after;
+        }
+        if !rest.is_empty() && !rest.starts_with(b"From ") {
+            return None;
+        }
+    }
+    (!bodies.is_empty()).then_some(bodies)
+}
+
+/// The lines of `data`, each with its line terminator.
+fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    data.split_inclusive(|&b| b == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mailbox of `bodies`, each message with a correct `Content-Length:`.
+    fn with_lengths(bodies: &[&str]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for body in bodies {
+            out.extend_from_slice(b"From a@x Thu Jan 01 00:00:00 2024\nSubject: Hi\n");
+            out.extend_from_slice(format!("Content-Length: {}\n\n", body.len()).as_bytes());
+            out.extend_from_slice(body.as_bytes());
+            out.extend_from_slice(b"\n");
+        }
+        out
+    }
+
+    #[test]
+    fn test_detect() {
+        let plain = b"From a@x Thu Jan 01 00:00:00 2024\nSubject: Hi\n\n>From here\n";
+        assert_eq!(MboxDialect::detect(plain, true), MboxDialect::Mboxo);
+        let rd = b"From a@x Thu Jan 01 00:00:00 2024\nSubject: Hi\n\n>>From here\n";
+        assert_eq!(MboxDialect::detect(rd, true), MboxDialect::Mboxrd);
+
+        let cl2 = with_lengths(&["From the start\nbody\n", "two\n"]);
+        assert_eq!(MboxDialect::detect(&cl2, true), MboxDialect::Mboxcl2);
+        let cl = with_lengths(&[">From the start\n"]);
+        assert_eq!(MboxDialect::detect(&cl, true), MboxDialect::Mboxcl);
+        // A sample cut inside a message still counts the messages before it.
+        assert_eq!(
+            MboxDialect::detect(&cl2[..cl2.len() - 2], false),
+            MboxDialect::Mboxcl2
+        );
+
+        // A Content-Length that does not land on the next message is ignored.
+        let mut wrong = with_lengths(&["body\n", "two\n"]);
+        wrong.splice(
+            0..0,
+            b"From a@x Thu Jan 01 00:00:00 2024\nContent-Length: 3\n\nbody\n".to_vec(),
+        );
+        assert_eq!(MboxDialect::detect(&wrong, true), MboxDialect::Mboxo);
+    }
+
+    #[test]
+    fn test_unescape() {
+        let message = b"From a@x Thu Jan 01 00:00:00 2024\n\n>From a\n>>From b\nFrom c\n";
+        assert_eq!(
+            &*MboxDialect::Mboxo.unescape(message),
+            b"From a@x Thu Jan 01 00:00:00 2024\n\nFrom a\n>>From b\nFrom c\n"
+        );
+        assert_eq!(
+            &*MboxDialect::Mboxrd.unescape(message),
+            b"From a@x Thu Jan 01 00:00:00 2024\n\nFrom a\n>From b\nFrom c\n"
+        );
+        assert_eq!(&*MboxDialect::Mboxcl2.unescape(message), message);
+    }
+
+    #[test]
+    fn test_escape_round_trips() {
+        let body = b"Subject: Hi\n\nFrom a\n>From b\n>>From c\nplain\n";
+        for dialect in MboxDialect::ALL {
+            let mut message = b"From a@x Thu Jan 01 00:00:00 2024\n".to_vec();
+            dialect.escape_into(&mut message, body);
+            let back = dialect.unescape(&message);
+            let expected_back = match dialect {
+                // mboxo cannot tell a quoted `From ` from a `>From `.
+                MboxDialect::Mboxo | MboxDialect::Mboxcl => {
+                    b"Subject: Hi\n\nFrom a\nFrom b\n>>From c\nplain\n".as_slice()
+                }
+                _ => body,
+            };
+            assert_eq!(&back[34..], expected_back, "{dialect}");
+        }
+    }
+
+    #[test]
+    fn test_names_and_headers() {
+        assert_eq!("MBOXRD".parse(), Ok(MboxDialect::Mboxrd));
+        assert!("mboxz".parse::<MboxDialect>().is_err());
+        assert_eq!(MboxDialect::Mboxcl2.to_string(), "mboxcl2");
+
+        let headers = b"From a@x\nSubject: Hi\ncontent-length:  42 \n\n";
+        assert_eq!(content_length(headers), Some(42));
+        assert_eq!(content_length(b"Content-Length: many\n"), None);
+        assert_eq!(body_start(b"A: b\r\n\r\nbody"), Some(8));
+        assert_eq!(body_start(b"A: b\n"), None);
+    }
+}
@@ -1,31 +1,117 @@
-//! Parser for individual `.eml` files (RFC 5322 messages without MBOX framing).
+//! Parser for individual `.eml` files (RFC 5322 messages without MBOX framing),
+//! and the listing of a folder of them opened as one mailbox.
 
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
 
+use tracing::warn;
+
 use crate::error::{MboxError, Result};
 use crate::model::mail::MailEntry;
 use crate::parser::header;
+use crate::parser::message_dir::{relative_path, MessageFile};
 
 /// Parse a single `.eml` file and return its [`MailEntry`].
 ///
 /// An EML file is a bare RFC 5322 message (no `From ` separator).
 pub fn parse_eml(path: impl AsRef<Path>, sequence: u64) -> Result<MailEntry> {
     let path = path.as_ref();
-    let data = std::fs::read(path).map_err(|e| {
+    let (header_bytes, length) = read_header_block(path)?;
+    header::parse_headers_to_entry(&header_bytes, 0, length, sequence)
+}
+
+/// Size of each read while looking for the end of the header block.
+const HEADER_READ_CHUNK: usize = 64 * 1024;
+
+/// Upper bound on a header block, matching the MBOX parser's retain cap.
+/// A file with no blank line in this span is indexed from its first 16 MB.
+const MAX_HEADER_BLOCK: usize = 16 * 1024 * 1024;
+
+/// Read only the header block of a one-message file, plus the file's length.
+///
+/// Indexing a Maildir or an `.eml` folder must not pull every attachment into
+/// memory, so the file is read in chunks until the first blank line.
+pub(crate) fn read_header_block(path: &Path) -> Result<(Vec<u8>, u64)> {
+    let map_err = |e: std::io::Error| {
         if e.kind() == std::io::ErrorKind::NotFound {
             MboxError::FileNotFound(path.to_path_buf())
         } else {
             MboxError::io(path, e)
         }
-    })?;
+    };
+    let mut file = File::open(path).map_err(map_err)?;
+    let length = file.metadata().map_err(map_err)?.len();
 
-    let length = data.len() as u64;
+    let mut data = Vec::new();
+    let mut chunk = vec![0u8; HEADER_READ_CHUNK];
+    loop {
+        let n = file.read(&mut chunk).map_err(map_err)?;
+        if n == 0 {
+            break;
+        }
+        // Re-scan the last three bytes too: a `\r\n\r\n` may straddle reads.
+        let scan_from = data.len().saturating_sub(3);
+        data.extend_from_slice(&chunk[..n]);
+        if let Some(end) = find_header_end(&data[scan_from..]) {
+            data.truncate(scan_from + end);
+            return Ok((data, length));
+        }
+        if data.len() >= MAX_HEADER_BLOCK {
+            data.truncate(MAX_HEADER_BLOCK);
+            break;
+        }
+    }
+    Ok((data, length))
+}
 
-    // Find end of headers (first blank line)
-    let header_end = find_header_end(&data).unwrap_or(data.len());
-    let header_bytes = &data[..header_end];
+/// List every `.eml` file below `root`, recursively, sorted by relative path.
+///
+/// Hidden files and directories are skipped, and symlinks are not followed
+/// (a linked directory could loop back on itself). A file in a subfolder gets the
+/// subfolder's relative path as its label, so an evidence folder organised
+/// by custodian or date can be filtered from the sidebar.
+pub fn list_eml_files(root: &Path) -> Result<Vec<MessageFile>> {
+    let mut files = Vec::new();
+    collect_eml_files(root, root, &mut files)?;
+    files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok(files)
+}
 
-    header::parse_headers_to_entry(header_bytes, 0, length, sequence)
+fn collect_eml_files(root: &Path, dir: &Path, out: &mut Vec<MessageFile>) -> Result<()> {
+    for item in std::fs::read_dir(dir).map_err(|e| MboxError::io(dir, e))? {
+        let item = item.map_err(|e| MboxError::io(dir, e))?;
+        let name = item.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let meta = match item.metadata() {
+            Ok(m) => m,
+            Err(e) => {
+                warn!(path = %item.path().display(), error = %e, "Skipping unreadable entry");
+                continue;
+            }
+        };
+        if meta.is_dir() {
+            collect_eml_files(root, &item.path(), out)?;
+        } else if meta.is_file() && is_eml_name(&name) {
+            let folder = (dir != root).then(|| relative_path(root, dir));
+            out.push(MessageFile {
+                rel_path: relative_path(root, &item.path()),
+                size: meta.len(),
+                modified: meta.modified().ok(),
+                folder,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether a file name has the `.eml` extension (any case).
+fn is_eml_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("eml"))
 }
 
 /// Find the byte offset where headers end (position of the first blank line).
@@ -66,4 +152,40 @@ mod tests {
         let data = b"From: a@b.com\r\nSubject: Hi\r\n\r\nBody\r\n";
         assert_eq!(find_header_end(data), Some(26));
     }
+
+    #[test]
+    fn test_list_eml_files_recurses_and_labels_subfolders() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("alice/2023")).unwrap();
+        std::fs::create_dir_all(root.join(".hidden")).unwrap();
+        for rel in [
+            "b.eml",
+            "a.EML",
+            "alice/2023/c.eml",
+            ".hidden/d.eml",
+            "notes.txt",
+        ] {
+            std::fs::write(root.join(rel), b"Subject: x\n\nbody\n").unwrap();
+        }
+
+        let files = list_eml_files(root).unwrap();
+        let paths: Vec<&str> = files.iter().map(|f| f.rel_path.as_str()).collect();
+        assert_eq!(paths, vec!["a.EML", "alice/2023/c.eml", "b.eml"]);
+        assert_eq!(files[0].folder, None);
+        assert_eq!(files[1].folder.as_deref(), Some("alice/2023"));
+    }
+
+    #[test]
+    fn test_read_header_block_stops_at_blank_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("msg.eml");
+        let mut data = b"From: a@b.com\r\nSubject: Hi\r\n\r\n".to_vec();
+        data.extend(std::iter::repeat_n(b'x', 3 * HEADER_READ_CHUNK));
+        std::fs::write(&path, &data).unwrap();
+
+        let (headers, length) = read_header_block(&path).unwrap();
+        assert_eq!(headers, b"From: a@b.com\r\nSubject: Hi");
+        assert_eq!(length, data.len() as u64);
+    }
 }
@@ -1,10 +1,12 @@
 //! RFC 5322 header parsing: folding, encoded-words (RFC 2047), and date parsing.
 
+use std::sync::Arc;
+
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use tracing::warn;
 
-use crate::model::address::EmailAddress;
-use crate::model::mail::MailEntry;
+use crate::model::address::{EmailAddress, SharedAddress};
+use crate::model::mail::{MailEntry, MailFlags};
 
 /// Build a [`MailEntry`] from raw header bytes.
 ///
@@ -30,13 +32,15 @@ pub fn parse_headers_to_entry(
     let from_raw = get_header(&headers, "from").unwrap_or_default();
     let from = EmailAddress::parse(&decode_encoded_words(&from_raw));
 
-    let to_raw = get_header(&headers, "to").unwrap_or_default();
-    let mut to = EmailAddress::parse_list(&decode_encoded_words(&to_raw));
-    to.truncate(5);
-
-    let cc_raw = get_header(&headers, "cc").unwrap_or_default();
-    let mut cc = EmailAddress::parse_list(&decode_encoded_words(&cc_raw));
-    cc.truncate(5);
+    let to = address_list(&headers, "to");
+    let cc = address_list(&headers, "cc");
+    let bcc = address_list(&headers, "bcc");
+    let reply_to = address_list(&headers, "reply-to");
+    let delivered_to = address_list(&headers, "delivered-to");
+    let sender = get_header(&headers, "sender")
+        .map(|raw| EmailAddress::parse(&decode_encoded_words(&raw)))
+        .filter(|a| !a.address.is_empty())
+        .map(Arc::new);
 
     let subject_raw = get_header(&headers, "subject").unwrap_or_default();
     let subject = decode_encoded_words(&subject_raw);
@@ -71,6 +75,8 @@ pub fn parse_headers_to_entry(
         })
         .unwrap_or_default();
 
+    let flags = header_flags(&headers, &gmail_labels);
+
     // Google Groups exports carry no `X-Gmail-Labels`, so the sidebar would be
     // empty for them. Surface the group itself as a virtual label instead.
     let mut labels = gmail_labels;
@@ -93,19 +99,49 @@ pub fn parse_headers_to_entry(
         from,
         to,
         cc,
+        bcc,
+        reply_to,
+        sender,
+        delivered_to,
         subject,
         message_id,
         in_reply_to,
         references,
         has_attachments,
+        attachments: Vec::new(),
         content_type,
         text_size: 0,
         labels,
+        tags: Vec::new(),
         thread_id,
         sequence,
+        file: None,
+        flags,
     })
 }
 
+/// The read and starred states the message carries in its headers.
+///
+/// A Takeout export lists `Unread` and `Starred` among the `X-Gmail-Labels`;
+/// mail clients write `Status:` (`R` read) and `X-Status:` (`F` flagged).
+/// A message with none of them has no record of being unread, so it counts
+/// as read.
+fn header_flags(headers: &[(String, String)], gmail_labels: &[String]) -> MailFlags {
+    let has_label = |name: &str| gmail_labels.iter().any(|l| l.eq_ignore_ascii_case(name));
+    let status = get_header(headers, "status");
+    let x_status = get_header(headers, "x-status").unwrap_or_default();
+    let seen = if get_header(headers, "x-gmail-labels").is_some() {
+        !has_label("Unread")
+    } else {
+        status.is_none_or(|s| s.contains('R'))
+    };
+    MailFlags {
+        seen,
+        flagged: has_label("Starred") || x_status.contains('F'),
+        ..MailFlags::default()
+    }
+}
+
 /// Decode raw header bytes to a string.
 ///
 /// Tries UTF-8 first, then falls back to ISO-8859-1 (which accepts every byte).
@@ -129,7 +165,7 @@ fn decode_header_bytes(bytes: &[u8]) -> String {
 /// Unfold headers: join continuation lines (starting with space or tab) with the previous header.
 ///
 /// Returns a list of `(lowercase_name, raw_value)` pairs.
-fn unfold_headers(text: &str) -> Vec<(String, String)> {
+pub(crate) fn unfold_headers(text: &str) -> Vec<(String, String)> {
     let mut result: Vec<(String, String)> = Vec::new();
 
     for line in text.lines() {
@@ -158,6 +194,17 @@ fn get_header(headers: &[(String, String)], name: &str) -> Option<String> {
         .map(|(_, v)| v.clone())
 }
 
+/// Every address of every `name` header, in order. Lists are kept whole:
+/// a header repeated (one `Delivered-To:` per hop) adds its addresses.
+fn address_list(headers: &[(String, String)], name: &str) -> Vec<SharedAddress> {
+    headers
+        .iter()
+        .filter(|(k, _)| k == name)
+        .flat_map(|(_, v)| EmailAddress::parse_list(&decode_encoded_words(v)))
+        .map(Arc::new)
+        .collect()
+}
+
 /// Virtual label for a Google Groups message, from its Groups-specific headers.
 ///
 /// `X-Google-Groups` carries the bare group name and is present on virtually
@@ -706,6 +753,30 @@ mod tests {
         assert_eq!(entry2.date.month(), 6);
     }
 
+    #[test]
+    fn test_recipient_headers_are_kept_whole() {
+        let raw = b"From: a@example.com\n\
+To: t1@example.com, t2@example.com, t3@example.com,\n t4@example.com, t5@example.com, t6@example.com\n\
+Bcc: =?UTF-8?Q?Jos=C3=A9?= <jose@example.com>\n\
+Reply-To: list@example.com\n\
+Sender: bounces@example.com\n\
+Delivered-To: me@example.com\n\
+Delivered-To: me+inbox@example.com\n\
+Subject: Many\n";
+        let entry = parse_headers_to_entry(raw, 0, 100, 0).unwrap();
+        assert_eq!(entry.to.len(), 6);
+        assert_eq!(entry.to[5].address, "t6@example.com");
+        assert_eq!(entry.bcc[0].display_name, "José");
+        assert_eq!(entry.reply_to[0].address, "list@example.com");
+        assert_eq!(
+            entry.sender.as_ref().map(|s| s.address.as_str()),
+            Some("bounces@example.com")
+        );
+        // Every occurrence of a repeated header counts.
+        assert_eq!(entry.delivered_to.len(), 2);
+        assert_eq!(entry.participants().count(), 12);
+    }
+
     #[test]
     fn test_parse_date_iso8601() {
         let dt = parse_date("2024-01-04T10:00:00Z");
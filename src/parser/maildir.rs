@@ -0,0 +1,265 @@
+//! Maildir source: one message per file under `cur/` and `new/`.
+//!
+//! Listing, flags and file lookup specific to Maildir; the indexing itself is
+//! shared with `.eml` folders in [`super::message_dir`]. Maildir unique names
+//! start with the delivery timestamp, so sorting by path is close to arrival
+//! order.
+//!
+//! `tmp/` is skipped: it holds deliveries that are still being written.
+//! Subfolders (Maildir++ `.Sent`, `.Archive.2019`, or nested directories as
+//! written by `mbsync`) are indexed too, and their messages get the folder
+//! name as a label so the sidebar can filter by folder.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::error::{MboxError, Result};
+use crate::model::mail::MailFlags;
+use crate::parser::message_dir::{relative_path, MessageFile};
+
+/// Subdirectories that hold messages, in the order they are listed.
+const MESSAGE_DIRS: [&str; 2] = ["cur", "new"];
+
+/// Whether `path` is a Maildir: a directory with a `cur/` or `new/` inside.
+pub fn is_maildir(path: &Path) -> bool {
+    path.is_dir() && MESSAGE_DIRS.iter().any(|d| path.join(d).is_dir())
+}
+
+/// List every message file of the Maildir at `root`, sorted by relative path.
+pub fn list_messages(root: &Path) -> Result<Vec<MessageFile>> {
+    let mut files = Vec::new();
+    collect_folder(root, root, None, &mut files)?;
+    files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok(files)
+}
+
+/// Collect the messages of one Maildir folder, then recurse into subfolders.
+fn collect_folder(
+    root: &Path,
+    dir: &Path,
+    folder: Option<&str>,
+    out: &mut Vec<MessageFile>,
+) -> Result<()> {
+    for sub in MESSAGE_DIRS {
+        let msg_dir = dir.join(sub);
+        if !msg_dir.is_dir() {
+            continue;
+        }
+        for item in std::fs::read_dir(&msg_dir).map_err(|e| MboxError::io(&msg_dir, e))? {
+            let item = item.map_err(|e| MboxError::io(&msg_dir, e))?;
+            let name = item.file_name().to_string_lossy().into_owned();
+            // Dotfiles are not messages (some servers keep metadata there).
+            if name.starts_with('.') {
+                continue;
+            }
+            let meta = match item.metadata() {
+                Ok(m) if m.is_file() => m,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!(path = %item.path().display(), error = %e, "Skipping unreadable Maildir entry");
+                    continue;
+                }
+            };
+            out.push(MessageFile {
+                rel_path: relative_path(root, &item.path()),
+                size: meta.len(),
+                modified: meta.modified().ok(),
+                folder: folder.map(str::to_string),
+            });
+        }
+    }
+
+    for item in std::fs::read_dir(dir).map_err(|e| MboxError::io(dir, e))? {
+        let item = item.map_err(|e| MboxError::io(dir, e))?;
+        let name = item.file_name().to_string_lossy().into_owned();
+        if name == "cur" || name == "new" || name == "tmp" {
+            continue;
+        }
+        let path = item.path();
+        if !item.file_type().is_ok_and(|t| t.is_dir()) || !is_maildir(&path) {
+            continue;
+        }
+        let label = folder_label(folder, &name);
+        collect_folder(root, &path, Some(&label), out)?;
+    }
+    Ok(())
+}
+
+/// Label for a subfolder named `name` inside `parent`.
+///
+/// Maildir++ folders are dot-prefixed with `.` as the hierarchy separator
+/// (`.Archive.2019`); they are shown as `Archive/2019`, like Gmail labels.
+fn folder_label(parent: Option<&str>, name: &str) -> String {
+    let own = match name.strip_prefix('.') {
+        Some(rest) => rest.replace('.', "/"),
+        None => name.to_string(),
+    };
+    match parent {
+        Some(p) => format!("{p}/{own}"),
+        None => own,
+    }
+}
+
+/// Parse the flags from a Maildir file name (`<unique>:2,<flags>`).
+///
+/// `!` is accepted in place of `:` — tools that run on Windows, where `:` is
+/// not allowed in file names, write it that way. Unknown letters (including
+/// lowercase keyword flags) are ignored.
+pub fn parse_flags(file_name: &str) -> MailFlags {
+    let mut flags = MailFlags::default();
+    let Some(info) = file_name
+        .rsplit_once(":2,")
+        .or_else(|| file_name.rsplit_once("!2,"))
+        .map(|(_, f)| f)
+    else {
+        return flags;
+    };
+    for c in info.chars() {
+        match c {
+            'S' => flags.seen = true,
+            'R' => flags.replied = true,
+            'F' => flags.flagged = true,
+            'P' => flags.passed = true,
+            'D' => flags.draft = true,
+            'T' => flags.trashed = true,
+            _ => {}
+        }
+    }
+    flags
+}
+
+/// The unique part of a Maildir file name: everything before the info.
+fn unique_name(file_name: &str) -> &str {
+    file_name
+        .find([':', '!'])
+        .map_or(file_name, |pos| &file_name[..pos])
+}
+
+/// Delivery time encoded at the start of a Maildir unique name
+/// (`1700000000.M1P2.host`), used when a message has no usable `Date:`.
+pub(crate) fn delivery_time(file_name: &str) -> Option<DateTime<Utc>> {
+    let secs: i64 = file_name.split('.').next()?.parse().ok()?;
+    DateTime::from_timestamp(secs, 0)
+}
+
+/// Resolve the on-disk path of a Maildir message recorded as `rel_path`.
+///
+/// Other mail clients rename a message when its flags change (and move it
+/// from `new/` to `cur/` once seen), so an index entry can outlive its exact
+/// file name. When the recorded path is gone, the file with the same unique
+/// name is looked up in the folder's `cur/` and `new/`.
+pub fn locate_message(root: &Path, rel_path: &str) -> Option<PathBuf> {
+    let path = root.join(rel_path);
+    if path.is_file() {
+        return Some(path);
+    }
+    let (folder, file_name) = match rel_path.rsplit_once('/') {
+        Some((dir, name)) => (dir.rsplit_once('/').map_or("", |(f, _)| f), name),
+        None => ("", rel_path),
+    };
+    let unique = unique_name(file_name);
+    let folder_dir = root.join(folder);
+    MESSAGE_DIRS.iter().find_map(|sub| {
+        std::fs::read_dir(folder_dir.join(sub))
+            .ok()?
+            .filter_map(|item| item.ok())
+            .find(|item| unique_name(&item.file_name().to_string_lossy()) == unique)
+            .map(|item| item.path())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::message_dir::{index_messages, DirFormat};
+
+    /// `rel` as written on disk: Windows does not allow `:` in file names,
+    /// so the tests use the `!` form there.
+    fn on_disk(rel: &str) -> String {
+        if cfg!(windows) {
+            rel.replace(':', "!")
+        } else {
+            rel.to_string()
+        }
+    }
+
+    fn write_message(root: &Path, rel: &str, subject: &str) {
+        let path = root.join(on_disk(rel));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let body = format!(
+            "From: sender@example.com\nSubject: {subject}\nMessage-ID: <{subject}@example.com>\n\nBody of {subject}\n"
+        );
+        std::fs::write(path, body).unwrap();
+    }
+
+    fn make_maildir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for sub in ["cur", "new", "tmp"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        write_message(root, "cur/1700000000.M1P1.host:2,S", "first");
+        write_message(root, "cur/1700000100.M2P1.host:2,FRS", "second");
+        write_message(root, "new/1700000200.M3P1.host", "third");
+        write_message(root, "tmp/1700000300.M4P1.host", "in-flight");
+        write_message(root, ".Sent/cur/1700000400.M5P1.host:2,S", "sent");
+        dir
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let f = parse_flags("1700000000.M1P1.host:2,FRS");
+        assert!(f.flagged && f.replied && f.seen);
+        assert!(!f.draft && !f.passed && !f.trashed);
+        assert!(parse_flags("1700000000.M1P1.host!2,T").trashed);
+        assert_eq!(parse_flags("1700000000.M1P1.host"), MailFlags::default());
+        // Lowercase keyword flags are not standard flags.
+        assert_eq!(parse_flags("x:2,ab"), MailFlags::default());
+    }
+
+    #[test]
+    fn test_folder_label() {
+        assert_eq!(folder_label(None, ".Archive.2019"), "Archive/2019");
+        assert_eq!(folder_label(None, "Sent"), "Sent");
+        assert_eq!(folder_label(Some("Work"), "Clients"), "Work/Clients");
+    }
+
+    #[test]
+    fn test_index_maildir_skips_tmp_and_keeps_flags() {
+        let dir = make_maildir();
+        let entries = index_messages(dir.path(), DirFormat::Maildir, None, &|| false).unwrap();
+
+        let subjects: Vec<&str> = entries.iter().map(|e| e.subject.as_str()).collect();
+        assert_eq!(subjects, vec!["sent", "first", "second", "third"]);
+
+        let second = &entries[2];
+        assert!(second.flags.flagged && second.flags.replied && second.flags.seen);
+        assert!(!entries[3].flags.seen, "new/ messages are unread");
+        assert_eq!(entries[0].labels, vec!["Sent".to_string()]);
+        assert!(entries[1].labels.is_empty());
+
+        // Virtual offsets are contiguous and sequences follow the order.
+        for (i, pair) in entries.windows(2).enumerate() {
+            assert_eq!(pair[0].offset + pair[0].length, pair[1].offset);
+            assert_eq!(pair[1].sequence, i as u64 + 1);
+        }
+        // No Date: header, so the delivery time from the name is used.
+        assert_eq!(entries[1].date.timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_locate_message_after_flag_rename() {
+        let dir = make_maildir();
+        let root = dir.path();
+        std::fs::rename(
+            root.join("new/1700000200.M3P1.host"),
+            root.join(on_disk("cur/1700000200.M3P1.host:2,S")),
+        )
+        .unwrap();
+        let found = locate_message(root, "new/1700000200.M3P1.host").unwrap();
+        assert_eq!(found, root.join(on_disk("cur/1700000200.M3P1.host:2,S")));
+        assert!(locate_message(root, "cur/1699999999.gone:2,S").is_none());
+    }
+}
@@ -2,14 +2,21 @@
 //!
 //! Reads MBOX files line-by-line with a 1 MB buffer.
 //! Never loads the entire file into memory. Tolerant of malformed input.
+//! gzip, zstd and xz files are decompressed on the fly (see [`crate::source`]);
+//! offsets then refer to the decompressed content. The mailbox's dialect
+//! (see [`MboxDialect`]) is detected from its first bytes, and decides
+//! whether messages end at the next `From ` line or where their
+//! `Content-Length:` says.
 
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
-use tracing::warn;
+use tracing::{debug, warn};
 
 use crate::error::{MboxError, Result};
+use crate::parser::dialect::{self, MboxDialect};
+use crate::source::{CheckpointTable, Codec, SourceReader};
 
 /// Size of the internal read buffer (1 MB for fast sequential reads on modern SSDs).
 const READ_BUFFER_SIZE: usize = 1024 * 1024;
@@ -28,6 +35,9 @@ const MAX_LINE_RETAIN: usize = 8 * 1024 * 1024;
 /// line) stops accumulating; offset accounting is unaffected.
 const MAX_HEADER_RETAIN: usize = 16 * 1024 * 1024;
 
+/// Bytes at the start of a mailbox its dialect is detected from.
+const DIALECT_SAMPLE_SIZE: u64 = 256 * 1024;
+
 /// Streaming MBOX parser.
 ///
 /// Reads through the file sequentially, invoking a caller-supplied callback for
@@ -42,17 +52,23 @@ const MAX_HEADER_RETAIN: usize = 16 * 1024 * 1024;
 /// - Truncated messages at EOF
 /// - NUL bytes and other binary content in the body
 /// - UTF-8 BOM at the start of the file
+///
+/// In an mboxcl or mboxcl2 mailbox, the body a message's `Content-Length:`
+/// covers is taken whole, so `From ` lines inside it never split it.
 pub struct MboxParser {
     path: PathBuf,
     file_size: u64,
+    codec: Option<Codec>,
+    dialect: MboxDialect,
     max_message_size: usize,
 }
 
 impl MboxParser {
     /// Create a parser for the given MBOX file.
     ///
-    /// Verifies that the file exists and is readable, but does NOT validate
-    /// that it is actually an MBOX.
+    /// Verifies that the file exists and is readable, and detects whether it
+    /// is compressed and its dialect, but does NOT validate that it is
+    /// actually an MBOX.
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
         let metadata = std::fs::metadata(&path).map_err(|e| {
@@ -62,23 +78,42 @@ impl MboxParser {
                 MboxError::io(&path, e)
             }
         })?;
+        let codec = Codec::detect(&path)?;
+        let dialect = if metadata.len() == 0 {
+            MboxDialect::default()
+        } else {
+            detect_dialect(&path)
+        };
         Ok(Self {
             path,
             file_size: metadata.len(),
+            codec,
+            dialect,
             max_message_size: MAX_MESSAGE_SIZE,
         })
     }
 
-    /// Total size of the underlying file in bytes.
+    /// Total size of the underlying file in bytes (compressed, for a
+    /// compressed mailbox).
     pub fn file_size(&self) -> u64 {
         self.file_size
     }
 
+    /// Compression of the file, or `None` for a plain MBOX.
+    pub fn codec(&self) -> Option<Codec> {
+        self.codec
+    }
+
     /// Path to the MBOX file.
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    /// Dialect of the mailbox, detected when the parser was created.
+    pub fn dialect(&self) -> MboxDialect {
+        self.dialect
+    }
+
     /// Parse the full MBOX, calling `message_callback` for each message found.
     ///
     /// The callback receives `(offset, raw_bytes)` and returns `true` to
@@ -94,8 +129,8 @@ impl MboxParser {
             return Ok(0);
         }
 
-        let file = File::open(&self.path).map_err(|e| MboxError::io(&self.path, e))?;
-        let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, file);
+        let source = SourceReader::open(&self.path)?;
+        let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, source);
 
         let mut count: u64 = 0;
         let mut current_offset: u64 = 0;
@@ -106,6 +141,9 @@ impl MboxParser {
         let mut first_line = true;
         let mut git_patch_mbox = false;
         let mut last_progress: u64 = 0;
+        let mut in_headers = false;
+        // Bytes of a `Content-Length:` body still to be read.
+        let mut body_remaining: u64 = 0;
 
         // Reusable line buffer
         let mut line_buf: Vec<u8> = Vec::with_capacity(4096);
@@ -128,7 +166,13 @@ impl MboxParser {
                 consumed as u64
             };
 
-            let kind = classify_from_line(&line_buf);
+            let in_body = body_remaining > 0;
+            body_remaining = body_remaining.saturating_sub(line_len);
+            let kind = if in_body {
+                FromLineKind::Content
+            } else {
+                classify_from_line(&line_buf)
+            };
             if first_line && kind == FromLineKind::GitPatchMarker {
                 git_patch_mbox = true;
             }
@@ -151,6 +195,7 @@ impl MboxParser {
                 message_start = current_offset;
                 message_buf.clear();
                 message_buf.extend_from_slice(&line_buf);
+                in_headers = true;
             } else if is_from_line && !prev_line_was_empty && !first_line {
                 warn!(
                     offset = current_offset,
@@ -165,6 +210,7 @@ impl MboxParser {
                 message_start = current_offset;
                 message_buf.clear();
                 message_buf.extend_from_slice(&line_buf);
+                in_headers = true;
             } else if message_buf.len() + line_buf.len() <= self.max_message_size {
                 message_buf.extend_from_slice(&line_buf);
             } else if message_buf.len() <= self.max_message_size {
@@ -176,14 +222,21 @@ impl MboxParser {
                 );
             }
 
-            prev_line_was_empty = is_blank_line(&line_buf);
+            if in_headers && !is_from_line && is_blank_line(&line_buf) {
+                in_headers = false;
+                if self.dialect.is_length_delimited() {
+                    body_remaining = dialect::content_length(&message_buf).unwrap_or(0);
+                }
+            }
+            // A `Content-Length:` body ends where the next message starts.
+            prev_line_was_empty = is_blank_line(&line_buf) || (in_body && body_remaining == 0);
             first_line = false;
             current_offset += line_len;
             bytes_read += line_len;
 
             if let Some(cb) = progress_callback {
                 if bytes_read - last_progress >= PROGRESS_INTERVAL {
-                    cb(bytes_read, self.file_size);
+                    cb(self.progress_position(&reader, bytes_read), self.file_size);
                     last_progress = bytes_read;
                 }
             }
@@ -216,158 +269,447 @@ impl MboxParser {
         if self.file_size == 0 {
             return Ok(0);
         }
+        let source = SourceReader::open(&self.path)?;
+        let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, source);
+        self.scan_headers(&mut reader, 0, header_callback, progress_callback)
+    }
 
-        let file = File::open(&self.path).map_err(|e| MboxError::io(&self.path, e))?;
-        let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, file);
-
-        let mut count: u64 = 0;
-        let mut current_offset: u64 = 0;
-        let mut header_buf: Vec<u8> = Vec::with_capacity(16 * 1024);
-        let mut in_headers = false;
-        let mut prev_line_was_empty = true;
-        let mut first_line = true;
-        let mut git_patch_mbox = false;
-        let mut bytes_read: u64 = 0;
-        let mut last_progress: u64 = 0;
-        let mut prev_message_start: Option<u64> = None;
-        let mut prev_headers: Option<Vec<u8>> = None;
-
-        // Reusable line buffer — avoids allocation per line
-        let mut line_buf: Vec<u8> = Vec::with_capacity(4096);
+    /// [`parse_headers_only`](Self::parse_headers_only) starting at byte
+    /// `start`, which must be the beginning of a message. Offsets passed to
+    /// the callback are still counted from the start of the file.
+    ///
+    /// Used to index only what was appended to a mailbox since it was last
+    /// indexed.
+    pub fn parse_headers_from(
+        &self,
+        start: u64,
+        header_callback: &mut dyn FnMut(u64, u64, &[u8]) -> bool,
+        progress_callback: Option<&dyn Fn(u64, u64)>,
+    ) -> Result<u64> {
+        let mut source = SourceReader::open(&self.path)?;
+        let skipped = source
+            .skip(start)
+            .map_err(|e| MboxError::io(&self.path, e))?;
+        if skipped < start {
+            return Err(MboxError::ParseError {
+                offset: start,
+                reason: "start offset is past the end of the file".to_string(),
+            });
+        }
+        let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, source);
+        self.scan_headers(&mut reader, start, header_callback, progress_callback)
+    }
 
-        // Progress every 4 MB (less overhead on large files)
-        const PROGRESS_INTERVAL: u64 = 4 * 1024 * 1024;
+    /// [`parse_headers_only`](Self::parse_headers_only) that also records the
+    /// checkpoint table of a compressed mailbox, for random access later.
+    ///
+    /// The table is `None` for a plain MBOX, and when the callback stopped the
+    /// scan before the end of the file.
+    pub fn parse_headers_with_checkpoints(
+        &self,
+        header_callback: &mut dyn FnMut(u64, u64, &[u8]) -> bool,
+        progress_callback: Option<&dyn Fn(u64, u64)>,
+    ) -> Result<(u64, Option<CheckpointTable>)> {
+        if self.file_size == 0 {
+            return Ok((0, None));
+        }
+        let source = SourceReader::open_recording(&self.path)?;
+        let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, source);
+        let count = self.scan_headers(&mut reader, 0, header_callback, progress_callback)?;
+        Ok((count, reader.get_mut().take_checkpoints()))
+    }
 
-        loop {
-            // Read a line into the reusable buffer (zero-alloc in the common case)
-            line_buf.clear();
-            let line_len = {
-                // Read a full physical line, accumulating across buffer
-                // boundaries. Using `fill_buf` + manual consume here would
-                // split long lines (e.g. folded `Received:` headers) when they
-                // straddle the read buffer, leaving a stray `\r\n` tail that
-                // `is_blank_line` misreads as the end of the headers.
-                let consumed = reader
-                    .read_until(b'\n', &mut line_buf)
-                    .map_err(|e| MboxError::io(&self.path, e))?;
-                if consumed == 0 {
-                    break; // EOF
-                }
-                consumed as u64
-            };
-            // Cap what we RETAIN, never what we consumed: `line_len` above is
-            // the true byte count read from the file, so offsets stay exact
-            // while a pathological newline-free run can't blow up memory.
-            if line_buf.len() > MAX_LINE_RETAIN {
-                warn!(
-                    offset = current_offset,
-                    retained = MAX_LINE_RETAIN,
-                    "Oversized line while indexing; truncating retained bytes"
-                );
-                line_buf.truncate(MAX_LINE_RETAIN);
-            }
+    /// Split a plain MBOX into at most `parts` chunks of roughly equal size,
+    /// each starting at a message separator, so their headers can be scanned
+    /// concurrently with [`parse_chunk_headers`](Self::parse_chunk_headers).
+    ///
+    /// A boundary is the first line at or after the nominal split point that
+    /// [`scan_headers`] would itself treat as a separator, so scanning the
+    /// chunks one after the other finds exactly the messages a scan of the
+    /// whole file does. Returns a single chunk for a compressed or empty file,
+    /// which can only be read from the start, and for an mboxcl or mboxcl2
+    /// one, whose separators only the previous message's length tells from
+    /// body lines.
+    pub fn split_at_separators(&self, parts: usize) -> Result<Vec<MboxChunk>> {
+        let whole = |git_patch_mbox| {
+            vec![MboxChunk {
+                start: 0,
+                end: self.file_size,
+                git_patch_mbox,
+            }]
+        };
+        if self.codec.is_some() || self.file_size == 0 || self.dialect.is_length_delimited() {
+            return Ok(whole(false));
+        }
+        let io_err = |e| MboxError::io(&self.path, e);
+        let file = File::open(&self.path).map_err(io_err)?;
+        let mut reader = BufReader::with_capacity(64 * 1024, file);
+        let mut line = Vec::with_capacity(4096);
+
+        reader.read_until(b'\n', &mut line).map_err(io_err)?;
+        line.truncate(MAX_LINE_RETAIN);
+        let git_patch_mbox = classify_from_line(&line) == FromLineKind::GitPatchMarker;
+        if parts <= 1 {
+            return Ok(whole(git_patch_mbox));
+        }
 
-            let kind = classify_from_line(&line_buf);
-            if first_line && kind == FromLineKind::GitPatchMarker {
-                git_patch_mbox = true;
+        let mut starts = vec![0u64];
+        for part in 1..parts as u64 {
+            let target = self.file_size / parts as u64 * part;
+            let limit = self.file_size / parts as u64 * (part + 1);
+            let last = *starts.last().unwrap_or(&0);
+            if target <= last {
+                continue;
             }
-            let is_from_line = match kind {
-                FromLineKind::Separator => true,
-                FromLineKind::GitPatchMarker => git_patch_mbox,
-                // See `parse()`: lenient on the very first line of the file.
-                FromLineKind::Content => first_line && starts_with_from(&line_buf),
-            };
-
-            if is_from_line {
-                if !first_line && !prev_line_was_empty {
-                    warn!(
-                        offset = current_offset,
-                        "Found 'From ' separator without preceding blank line"
-                    );
+            // Line starts are the bytes after a `\n`: resume scanning at the
+            // line containing `target - 1`, whose end is a line start >= target.
+            reader.seek(SeekFrom::Start(target - 1)).map_err(io_err)?;
+            line.clear();
+            let mut pos = target - 1 + reader.read_until(b'\n', &mut line).map_err(io_err)? as u64;
+            while pos < limit {
+                line.clear();
+                let len = reader.read_until(b'\n', &mut line).map_err(io_err)? as u64;
+                if len == 0 {
+                    break;
                 }
-
-                // Emit the *previous* message. Use its saved headers, or fall
-                // back to the still-accumulating buffer when the message had no
-                // blank line before this separator — otherwise that message
-                // (its headers never got swapped out) would be silently dropped.
-                if let Some(pstart) = prev_message_start {
-                    let pheaders = prev_headers
-                        .take()
-                        .unwrap_or_else(|| std::mem::take(&mut header_buf));
-                    let msg_length = current_offset - pstart;
-                    if !header_callback(pstart, msg_length, &pheaders) {
-                        return Ok(count);
-                    }
-                    count += 1;
-                }
-
-                header_buf.clear();
-                header_buf.extend_from_slice(&line_buf);
-                in_headers = true;
-                prev_message_start = Some(current_offset);
-            } else if in_headers {
-                if is_blank_line(&line_buf) {
-                    // End of headers — save without cloning (swap trick)
-                    in_headers = false;
-                    let mut saved = Vec::with_capacity(header_buf.len());
-                    std::mem::swap(&mut saved, &mut header_buf);
-                    prev_headers = Some(saved);
-                } else if header_buf.len() < MAX_HEADER_RETAIN {
-                    header_buf.extend_from_slice(&line_buf);
+                line.truncate(MAX_LINE_RETAIN);
+                let is_separator = match classify_from_line(&line) {
+                    FromLineKind::Separator => true,
+                    FromLineKind::GitPatchMarker => git_patch_mbox,
+                    FromLineKind::Content => false,
+                };
+                if is_separator {
+                    starts.push(pos);
+                    break;
                 }
+                pos += len;
             }
+        }
 
-            prev_line_was_empty = is_blank_line(&line_buf);
-            first_line = false;
-            current_offset += line_len;
-            bytes_read += line_len;
+        let ends = starts.iter().skip(1).copied().chain([self.file_size]);
+        Ok(starts
+            .iter()
+            .zip(ends)
+            .map(|(&start, end)| MboxChunk {
+                start,
+                end,
+                git_patch_mbox,
+            })
+            .collect())
+    }
 
-            if let Some(cb) = progress_callback {
-                if bytes_read - last_progress >= PROGRESS_INTERVAL {
-                    cb(bytes_read, self.file_size);
-                    last_progress = bytes_read;
-                }
-            }
+    /// [`parse_headers_only`](Self::parse_headers_only) over one chunk from
+    /// [`split_at_separators`](Self::split_at_separators). Offsets passed to
+    /// the callback are counted from the start of the file; `progress`
+    /// receives the bytes of the chunk read since its previous call.
+    pub fn parse_chunk_headers(
+        &self,
+        chunk: &MboxChunk,
+        header_callback: &mut dyn FnMut(u64, u64, &[u8]) -> bool,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<u64> {
+        if chunk.start == chunk.end {
+            return Ok(0);
         }
-
-        // Flush last message
-        if let Some(pstart) = prev_message_start {
-            let hdrs = prev_headers.unwrap_or(header_buf);
-            let msg_length = current_offset - pstart;
-            if header_callback(pstart, msg_length, &hdrs) {
-                count += 1;
-            }
+        let mut source = SourceReader::open(&self.path)?;
+        let skipped = source
+            .skip(chunk.start)
+            .map_err(|e| MboxError::io(&self.path, e))?;
+        if skipped < chunk.start {
+            return Err(MboxError::ParseError {
+                offset: chunk.start,
+                reason: "chunk starts past the end of the file".to_string(),
+            });
         }
+        let mut reader =
+            BufReader::with_capacity(READ_BUFFER_SIZE, source.take(chunk.end - chunk.start));
+        let mut reported = 0;
+        let count = scan_headers_in(
+            &mut reader,
+            &self.path,
+            Some(chunk.git_patch_mbox),
+            self.dialect.is_length_delimited(),
+            &mut |offset, length, headers| header_callback(chunk.start + offset, length, headers),
+            &mut |bytes_read, _| {
+                progress(bytes_read - reported);
+                reported = bytes_read;
+            },
+        )?;
+        progress(chunk.end - chunk.start - reported);
+        Ok(count)
+    }
 
+    /// The header scan shared by the `parse_headers_*` entry points, over a
+    /// reader positioned at content offset `start`.
+    fn scan_headers(
+        &self,
+        reader: &mut BufReader<SourceReader>,
+        start: u64,
+        header_callback: &mut dyn FnMut(u64, u64, &[u8]) -> bool,
+        progress_callback: Option<&dyn Fn(u64, u64)>,
+    ) -> Result<u64> {
+        let count = scan_headers_in(
+            reader,
+            &self.path,
+            None,
+            self.dialect.is_length_delimited(),
+            &mut |offset, length, headers| header_callback(start + offset, length, headers),
+            &mut |bytes_read, reader| {
+                if let Some(cb) = progress_callback {
+                    cb(
+                        self.progress_position(reader, start + bytes_read),
+                        self.file_size,
+                    );
+                }
+            },
+        )?;
         if let Some(cb) = progress_callback {
             cb(self.file_size, self.file_size);
         }
-
         Ok(count)
     }
 
+    /// Progress to report after `bytes_read` bytes of content: the content
+    /// position for a plain file, the position in the file on disk for a
+    /// compressed one (progress is measured against the file size).
+    fn progress_position(&self, reader: &BufReader<SourceReader>, bytes_read: u64) -> u64 {
+        match self.codec {
+            None => bytes_read,
+            Some(_) => reader.get_ref().source_position(),
+        }
+    }
+
     /// Read a single message at the given offset and length.
     ///
     /// Uses `seek` to jump directly to the message without scanning the file.
+    /// A compressed mailbox is decoded from the start up to the message; use
+    /// `MboxStore`, which keeps a checkpoint table, for repeated reads.
     pub fn read_message_at(path: impl AsRef<Path>, offset: u64, length: u64) -> Result<Vec<u8>> {
         let path = path.as_ref();
-        let mut file = File::open(path).map_err(|e| MboxError::io(path, e))?;
-        file.seek(SeekFrom::Start(offset))
-            .map_err(|e| MboxError::io(path, e))?;
         // Convert with a checked cast instead of `as usize`, which would
         // truncate on a 32-bit target and under-allocate the buffer.
         let len = usize::try_from(length).map_err(|_| {
             MboxError::io(path, std::io::Error::from(std::io::ErrorKind::InvalidData))
         })?;
         let mut buffer = vec![0u8; len];
+        if let Some(codec) = Codec::detect(path)? {
+            let mut source = crate::source::SeekableSource::new(path, codec, Vec::new());
+            source.read_at(offset, &mut buffer)?;
+            return Ok(buffer);
+        }
+        let mut file = File::open(path).map_err(|e| MboxError::io(path, e))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| MboxError::io(path, e))?;
         file.read_exact(&mut buffer)
             .map_err(|e| MboxError::io(path, e))?;
         Ok(buffer)
     }
 }
 
+/// A byte range of a plain MBOX starting at a message separator, from
+/// [`MboxParser::split_at_separators`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MboxChunk {
+    /// Offset of the chunk's first separator line.
+    pub start: u64,
+    /// End of the chunk: the next chunk's start, or the file size.
+    pub end: u64,
+    /// Whether the file is a git patch series (see [`scan_headers`]), which
+    /// a scan starting mid-file cannot tell from its own first line.
+    git_patch_mbox: bool,
+}
+
+/// Scan an MBOX stream for message boundaries, calling `header_callback`
+/// with `(offset, message_length, header_bytes)` for each message, offsets
+/// counted from the start of `reader`. Stops early when the callback returns
+/// `false`.
+///
+/// `progress` is called every few megabytes with the bytes read so far and
+/// the reader, so the caller can translate that into its own units. This is
+/// the loop behind [`MboxParser::parse_headers_only`], exposed for mailboxes
+/// that are one member of a larger stream (a Takeout archive); `path` is only
+/// used in error messages. Messages are delimited by `From ` lines.
+pub(crate) fn scan_headers<R: BufRead>(
+    reader: &mut R,
+    path: &Path,
+    header_callback: &mut dyn FnMut(u64, u64, &[u8]) -> bool,
+    progress: &mut dyn FnMut(u64, &R),
+) -> Result<u64> {
+    scan_headers_in(reader, path, None, false, header_callback, progress)
+}
+
+/// [`scan_headers`] with the git patch-series mode either detected from the
+/// first line (`None`) or fixed by the caller, for a reader positioned in the
+/// middle of a mailbox whose first line it has not seen. With
+/// `length_delimited`, the body a message's `Content-Length:` covers is
+/// skipped without looking for separators in it.
+fn scan_headers_in<R: BufRead>(
+    reader: &mut R,
+    path: &Path,
+    git_patch_mbox: Option<bool>,
+    length_delimited: bool,
+    header_callback: &mut dyn FnMut(u64, u64, &[u8]) -> bool,
+    progress: &mut dyn FnMut(u64, &R),
+) -> Result<u64> {
+    let mut count: u64 = 0;
+    let mut current_offset: u64 = 0;
+    let mut header_buf: Vec<u8> = Vec::with_capacity(16 * 1024);
+    let mut in_headers = false;
+    let mut prev_line_was_empty = true;
+    let mut first_line = true;
+    let detect_git_patch = git_patch_mbox.is_none();
+    let mut git_patch_mbox = git_patch_mbox.unwrap_or(false);
+    let mut bytes_read: u64 = 0;
+    let mut last_progress: u64 = 0;
+    let mut prev_message_start: Option<u64> = None;
+    let mut prev_headers: Option<Vec<u8>> = None;
+    // Bytes of a `Content-Length:` body still to be skipped.
+    let mut body_remaining: u64 = 0;
+
+    // Reusable line buffer — avoids allocation per line
+    let mut line_buf: Vec<u8> = Vec::with_capacity(4096);
+
+    // Progress every 4 MB (less overhead on large files)
+    const PROGRESS_INTERVAL: u64 = 4 * 1024 * 1024;
+
+    loop {
+        // Read a line into the reusable buffer (zero-alloc in the common case)
+        line_buf.clear();
+        let line_len = {
+            // Read a full physical line, accumulating across buffer
+            // boundaries. Using `fill_buf` + manual consume here would
+            // split long lines (e.g. folded `Received:` headers) when they
+            // straddle the read buffer, leaving a stray `\r\n` tail that
+            // `is_blank_line` misreads as the end of the headers.
+            let consumed = reader
+                .read_until(b'\n', &mut line_buf)
+                .map_err(|e| MboxError::io(path, e))?;
+            if consumed == 0 {
+                break; // EOF
+            }
+            consumed as u64
+        };
+        // Cap what we RETAIN, never what we consumed: `line_len` above is
+        // the true byte count read from the file, so offsets stay exact
+        // while a pathological newline-free run can't blow up memory.
+        if line_buf.len() > MAX_LINE_RETAIN {
+            warn!(
+                offset = current_offset,
+                retained = MAX_LINE_RETAIN,
+                "Oversized line while indexing; truncating retained bytes"
+            );
+            line_buf.truncate(MAX_LINE_RETAIN);
+        }
+
+        let in_body = body_remaining > 0;
+        body_remaining = body_remaining.saturating_sub(line_len);
+        let kind = if in_body {
+            FromLineKind::Content
+        } else {
+            classify_from_line(&line_buf)
+        };
+        if detect_git_patch && first_line && kind == FromLineKind::GitPatchMarker {
+            git_patch_mbox = true;
+        }
+        let is_from_line = match kind {
+            FromLineKind::Separator => true,
+            FromLineKind::GitPatchMarker => git_patch_mbox,
+            // See `parse()`: lenient on the very first line of the file.
+            FromLineKind::Content => first_line && starts_with_from(&line_buf),
+        };
+
+        if is_from_line {
+            if !first_line && !prev_line_was_empty {
+                warn!(
+                    offset = current_offset,
+                    "Found 'From ' separator without preceding blank line"
+                );
+            }
+
+            // Emit the *previous* message. Use its saved headers, or fall
+            // back to the still-accumulating buffer when the message had no
+            // blank line before this separator — otherwise that message
+            // (its headers never got swapped out) would be silently dropped.
+            if let Some(pstart) = prev_message_start {
+                let pheaders = prev_headers
+                    .take()
+                    .unwrap_or_else(|| std::mem::take(&mut header_buf));
+                let msg_length = current_offset - pstart;
+                if !header_callback(pstart, msg_length, &pheaders) {
+                    return Ok(count);
+                }
+                count += 1;
+            }
+
+            header_buf.clear();
+            header_buf.extend_from_slice(&line_buf);
+            in_headers = true;
+            prev_message_start = Some(current_offset);
+        } else if in_headers {
+            if is_blank_line(&line_buf) {
+                // End of headers — save without cloning (swap trick)
+                in_headers = false;
+                let mut saved = Vec::with_capacity(header_buf.len());
+                std::mem::swap(&mut saved, &mut header_buf);
+                if length_delimited {
+                    body_remaining = dialect::content_length(&saved).unwrap_or(0);
+                }
+                prev_headers = Some(saved);
+            } else if header_buf.len() < MAX_HEADER_RETAIN {
+                header_buf.extend_from_slice(&line_buf);
+            }
+        }
+
+        // A `Content-Length:` body ends where the next message starts.
+        prev_line_was_empty = is_blank_line(&line_buf) || (in_body && body_remaining == 0);
+        first_line = false;
+        current_offset += line_len;
+        bytes_read += line_len;
+
+        if bytes_read - last_progress >= PROGRESS_INTERVAL {
+            progress(bytes_read, reader);
+            last_progress = bytes_read;
+        }
+    }
+
+    // Flush last message
+    if let Some(pstart) = prev_message_start {
+        let hdrs = prev_headers.unwrap_or(header_buf);
+        let msg_length = current_offset - pstart;
+        if header_callback(pstart, msg_length, &hdrs) {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// The dialect of the mailbox at `path`, from its first
+/// [`DIALECT_SAMPLE_SIZE`] bytes of content. One that cannot be read is
+/// taken as mboxo; the error comes up when it is parsed.
+fn detect_dialect(path: &Path) -> MboxDialect {
+    let mut sample = Vec::new();
+    let read = SourceReader::open(path).and_then(|source| {
+        source
+            .take(DIALECT_SAMPLE_SIZE)
+            .read_to_end(&mut sample)
+            .map_err(|e| MboxError::io(path, e))
+    });
+    match read {
+        Ok(len) => {
+            let dialect = MboxDialect::detect(&sample, (len as u64) < DIALECT_SAMPLE_SIZE);
+            debug!(path = %path.display(), %dialect, "Detected mailbox dialect");
+            dialect
+        }
+        Err(e) => {
+            debug!(error = %e, "Cannot read the start of the mailbox to detect its dialect");
+            MboxDialect::default()
+        }
+    }
+}
+
 /// BOM-tolerant check for a `From `-prefixed line.
-fn starts_with_from(line: &[u8]) -> bool {
+pub(crate) fn starts_with_from(line: &[u8]) -> bool {
     let line = if line.starts_with(&[0xEF, 0xBB, 0xBF]) {
         &line[3..]
     } else {
@@ -378,7 +720,7 @@ fn starts_with_from(line: &[u8]) -> bool {
 
 /// Classification of a line with respect to MBOX message separation.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum FromLineKind {
+pub(crate) enum FromLineKind {
     /// Regular content — including `From `-prefixed lines that do not have
     /// the structure of a real separator (e.g. quoted email headers inside
     /// a message body, issue #16).
@@ -401,7 +743,7 @@ enum FromLineKind {
 /// quotes an email verbatim — disqualifies the line. A bare `From ` line
 /// with nothing after it is also a separator: some writers (e.g.
 /// Thunderbird exporting a Gmail account) emit exactly that (issue #16).
-fn classify_from_line(line: &[u8]) -> FromLineKind {
+pub(crate) fn classify_from_line(line: &[u8]) -> FromLineKind {
     // Skip BOM if present at very start
     let line = if line.starts_with(&[0xEF, 0xBB, 0xBF]) {
         &line[3..]
@@ -500,7 +842,7 @@ fn is_timezone(s: &str) -> bool {
 }
 
 /// Check whether a line is blank (empty or only whitespace / CR / LF).
-fn is_blank_line(line: &[u8]) -> bool {
+pub(crate) fn is_blank_line(line: &[u8]) -> bool {
     line.iter()
         .all(|&b| b == b'\n' || b == b'\r' || b == b' ' || b == b'\t')
 }
@@ -723,6 +1065,57 @@ mod tests {
         assert_eq!(count, 3, "a patch-series mbox must split on every patch");
     }
 
+    /// Chunks of a patch series split on the magic-date separators even
+    /// though their scan does not start at the file's first line.
+    #[test]
+    fn test_git_patch_series_chunks_split() {
+        use std::io::Write;
+
+        let mut data = Vec::new();
+        for i in 1..=12 {
+            data.extend_from_slice(b"From 8f3b1c4d5e6f Mon Sep 17 00:00:00 2001\n");
+            data.extend_from_slice(format!("Subject: [PATCH {i}/12] change\n").as_bytes());
+            data.extend_from_slice(b"\n");
+            data.extend_from_slice(b"diff --git a/x b/x\n");
+            data.extend_from_slice(b"\n");
+        }
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+        file.flush().unwrap();
+
+        let parser = MboxParser::new(file.path()).unwrap();
+        let chunks = parser.split_at_separators(4).unwrap();
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks.last().unwrap().end, data.len() as u64);
+        let mut offsets = Vec::new();
+        for chunk in &chunks {
+            assert!(data[chunk.start as usize..].starts_with(b"From "));
+            parser
+                .parse_chunk_headers(
+                    chunk,
+                    &mut |off, _, _| {
+                        offsets.push(off);
+                        true
+                    },
+                    &mut |_| {},
+                )
+                .unwrap();
+        }
+        let mut serial = Vec::new();
+        parser
+            .parse_headers_only(
+                &mut |off, _, _| {
+                    serial.push(off);
+                    true
+                },
+                None,
+            )
+            .unwrap();
+        assert_eq!(offsets.len(), 12);
+        assert_eq!(offsets, serial);
+    }
+
     /// Regression test for issue #15: a header line whose trailing CRLF lands
     /// exactly on the read-buffer boundary used to be split into a separate
     /// blank line, prematurely ending the header section and dropping every
@@ -900,4 +1293,37 @@ mod tests {
         assert!(headers[0].contains("Subject: First"));
         assert!(headers[1].contains("Subject: Second"));
     }
+
+    #[test]
+    fn test_content_length_delimits_mboxcl2_messages() {
+        use std::io::Write;
+        // An unquoted `From ` line inside a body is content when the
+        // Content-Length says the body goes on past it.
+        let body = "From a@b.com Sat Jan 03 00:00:00 2024\nstill the first body\n";
+        let mut data = Vec::new();
+        data.extend_from_slice(b"From a@b.com Thu Jan 01 00:00:00 2024\n");
+        data.extend_from_slice(format!("Content-Length: {}\n\n", body.len()).as_bytes());
+        data.extend_from_slice(body.as_bytes());
+        data.extend_from_slice(b"\nFrom c@d.com Fri Jan 02 00:00:00 2024\n");
+        data.extend_from_slice(b"Subject: Second\nContent-Length: 5\n\nBody\n\n");
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+        file.flush().unwrap();
+
+        let parser = MboxParser::new(file.path()).unwrap();
+        assert_eq!(parser.dialect(), MboxDialect::Mboxcl2);
+        let mut headers: Vec<String> = Vec::new();
+        let count = parser
+            .parse_headers_only(
+                &mut |_off, _len, h| {
+                    headers.push(String::from_utf8_lossy(h).into_owned());
+                    true
+                },
+                None,
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        assert!(headers[1].contains("Subject: Second"));
+    }
 }
@@ -0,0 +1,181 @@
+//! Mailboxes stored as one file per message: Maildir trees and folders of
+//! loose `.eml` files.
+//!
+//! Either kind is indexed as one virtual mailbox. Message files are ordered
+//! by their path relative to the root and given virtual offsets — the running
+//! total of the sizes of the files before them. That keeps
+//! [`MailEntry::offset`] unique per message, and the index bounds check works
+//! against the total size exactly as it does for an MBOX. Each entry records
+//! its own file in [`MailEntry::file`], which is what `MboxStore` reads.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::error::Result;
+use crate::model::mail::MailEntry;
+use crate::parser::{eml, header, maildir};
+
+/// Report progress at most once per this many bytes of message files.
+const PROGRESS_INTERVAL: u64 = 4 * 1024 * 1024;
+
+/// Layout of a one-file-per-message directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirFormat {
+    /// `cur/`, `new/`, `tmp/`, with flags in the file names.
+    Maildir,
+    /// Any other directory: every `.eml` file below it, recursively.
+    Eml,
+}
+
+/// A message file found while listing a directory mailbox.
+#[derive(Debug, Clone)]
+pub struct MessageFile {
+    /// Path relative to the mailbox root, with `/` separators.
+    pub rel_path: String,
+    /// File size in bytes.
+    pub size: u64,
+    /// Modification time, if the filesystem reports one.
+    pub modified: Option<SystemTime>,
+    /// Label for the subfolder the file lives in (`None` for the root).
+    pub folder: Option<String>,
+}
+
+/// Which kind of directory mailbox `path` is, or `None` if it is not a
+/// directory at all (an MBOX file).
+pub fn detect(path: &Path) -> Option<DirFormat> {
+    if !path.is_dir() {
+        return None;
+    }
+    if maildir::is_maildir(path) {
+        Some(DirFormat::Maildir)
+    } else {
+        Some(DirFormat::Eml)
+    }
+}
+
+/// List every message file of the mailbox at `root`, sorted by relative path.
+pub fn list_messages(root: &Path, format: DirFormat) -> Result<Vec<MessageFile>> {
+    match format {
+        DirFormat::Maildir => maildir::list_messages(root),
+        DirFormat::Eml => eml::list_eml_files(root),
+    }
+}
+
+/// Index every message of the directory mailbox at `root`.
+///
+/// `should_cancel` is checked before each file; when it returns `true` the
+/// scan stops early and the entries read so far are returned.
+pub fn index_messages(
+    root: &Path,
+    format: DirFormat,
+    progress: Option<&dyn Fn(u64, u64)>,
+    should_cancel: &dyn Fn() -> bool,
+) -> Result<Vec<MailEntry>> {
+    let files = list_messages(root, format)?;
+    let total: u64 = files.iter().map(|f| f.size).sum();
+
+    let mut entries = Vec::with_capacity(files.len());
+    let mut offset: u64 = 0;
+    let mut last_progress: u64 = 0;
+
+    for file in &files {
+        if should_cancel() {
+            break;
+        }
+        let path = root.join(&file.rel_path);
+        let file_name = file.rel_path.rsplit('/').next().unwrap_or(&file.rel_path);
+
+        // A message file can vanish or change size between listing and
+        // reading (an MUA moving it from new/ to cur/). Its slot keeps the
+        // listed size so offsets stay consistent with the fingerprint.
+        let parsed = eml::read_header_block(&path).and_then(|(headers, _)| {
+            header::parse_headers_to_entry(&headers, offset, file.size, entries.len() as u64)
+        });
+        match parsed {
+            Ok(mut entry) => {
+                if entry.date == DateTime::UNIX_EPOCH {
+                    let delivered = match format {
+                        DirFormat::Maildir => maildir::delivery_time(file_name),
+                        DirFormat::Eml => None,
+                    };
+                    entry.date = delivered
+                        .or_else(|| file.modified.map(DateTime::<Utc>::from))
+                        .unwrap_or(DateTime::UNIX_EPOCH);
+                }
+                if let Some(folder) = &file.folder {
+                    if !entry.labels.iter().any(|l| l == folder) {
+                        entry.labels.push(folder.clone());
+                    }
+                }
+                if format == DirFormat::Maildir {
+                    entry.flags = maildir::parse_flags(file_name);
+                }
+                entry.file = Some(file.rel_path.clone());
+                entries.push(entry);
+            }
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Skipping unparseable message");
+            }
+        }
+
+        offset += file.size;
+        if let Some(cb) = progress {
+            if offset - last_progress >= PROGRESS_INTERVAL {
+                cb(offset, total);
+                last_progress = offset;
+            }
+        }
+    }
+
+    if let Some(cb) = progress {
+        cb(offset, total);
+    }
+    Ok(entries)
+}
+
+/// Resolve the on-disk path of a message recorded as `rel_path`.
+///
+/// A Maildir message may have been renamed since it was indexed (see
+/// [`maildir::locate_message`]); an `.eml` file is expected where it was.
+pub fn locate_message(root: &Path, format: DirFormat, rel_path: &str) -> Option<PathBuf> {
+    match format {
+        DirFormat::Maildir => maildir::locate_message(root, rel_path),
+        DirFormat::Eml => Some(root.join(rel_path)).filter(|p| p.is_file()),
+    }
+}
+
+/// `path` relative to `root`, joined with `/` regardless of platform.
+pub(crate) fn relative_path(root: &Path, path: &Path) -> String {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(detect(dir.path()), Some(DirFormat::Eml));
+        std::fs::create_dir(dir.path().join("cur")).unwrap();
+        assert_eq!(detect(dir.path()), Some(DirFormat::Maildir));
+
+        let file = dir.path().join("mail.mbox");
+        std::fs::write(&file, b"From a@b Mon Jan  1 00:00:00 2024\n\n").unwrap();
+        assert_eq!(detect(&file), None);
+    }
+
+    #[test]
+    fn test_relative_path_uses_forward_slashes() {
+        let root = Path::new("root");
+        let path = root.join("a").join("b").join("c.eml");
+        assert_eq!(relative_path(root, &path), "a/b/c.eml");
+    }
+}
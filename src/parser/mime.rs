@@ -6,6 +6,8 @@ use crate::error::{MboxError, Result};
 use crate::model::attachment::AttachmentMeta;
 use crate::model::mail::MailBody;
 
+use super::attachment_text;
+
 /// Parse a complete raw message (headers + body) and extract its content.
 ///
 /// Uses `mail-parser` internally, with extra fallbacks for malformed messages.
@@ -81,6 +83,23 @@ pub fn extract_attachment(raw_message: &[u8], attachment: &AttachmentMeta) -> Re
     )))
 }
 
+/// Extract the text of every attachment that has any (see
+/// [`super::attachment_text`]), in attachment order.
+pub fn extract_attachment_texts(raw_message: &[u8]) -> Result<Vec<String>> {
+    let message_bytes = skip_from_line(raw_message);
+    let parser = MessageParser::default();
+    let Some(msg) = parser.parse(message_bytes) else {
+        return Ok(Vec::new());
+    };
+    Ok(list_attachments_from_parsed(&msg)
+        .iter()
+        .zip(msg.attachments())
+        .filter_map(|(meta, part)| {
+            attachment_text::extract_text(&meta.filename, &meta.content_type, part.contents())
+        })
+        .collect())
+}
+
 /// Build attachment metadata from a parsed `mail_parser::Message`.
 fn list_attachments_from_parsed(msg: &mail_parser::Message<'_>) -> Vec<AttachmentMeta> {
     use mail_parser::MimeHeaders;
@@ -1,6 +1,12 @@
-//! Email parsing: MBOX streaming parser, EML parser, header decoding, and MIME handling.
+//! Email parsing: MBOX streaming parser and dialects, Takeout archives, EML and Maildir parsers, header decoding, MIME handling, and attachment text extraction, and integrity checks of an MBOX.
 
+pub mod archive;
+pub mod attachment_text;
+pub mod dialect;
 pub mod eml;
 pub mod header;
+pub mod maildir;
 pub mod mbox;
+pub mod message_dir;
 pub mod mime;
+pub mod verify;
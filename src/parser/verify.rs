@@ -0,0 +1,429 @@
+//! Integrity check of an MBOX: every irregularity the parser reads past.
+//!
+//! The parser is deliberately tolerant — a separator without a blank line
+//! before it, a message cut off at the end of the file, NUL bytes in a body
+//! all still index — and only logs what it tolerated. [`verify_mbox`] reads
+//! the mailbox into the same messages the parser finds and reports each of
+//! those irregularities instead, with where it is and how serious it is, so
+//! the damage in an export can be measured before it is trusted, or repaired
+//! with [`repair_mbox`](crate::export::mbox::repair_mbox).
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::{MboxError, Result};
+use crate::parser::dialect::{self, MboxDialect};
+use crate::parser::mbox::{self, FromLineKind, MboxParser};
+use crate::parser::{archive, message_dir};
+
+/// How much an anomaly matters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Unusual but read correctly by every MBOX reader.
+    Info,
+    /// Read correctly here, but other readers may split or show the
+    /// message differently.
+    Warning,
+    /// Content is missing or cannot be read as a message.
+    Error,
+}
+
+/// An irregularity the parser tolerates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// A UTF-8 byte order mark before the first line.
+    Bom,
+    /// A bare `From ` separator line, without sender or date.
+    BareSeparator,
+    /// The file starts with a `From ` line that is not shaped like a
+    /// separator.
+    MalformedSeparator,
+    /// The file does not start with a `From ` line: what comes before the
+    /// first separator is not a message.
+    LeadingGarbage,
+    /// A separator that does not follow a blank line.
+    MissingBlankLine,
+    /// A message without the blank line that ends its headers.
+    MissingHeaderEnd,
+    /// A body line starting with `From ` that the dialect should have
+    /// quoted.
+    UnquotedFrom,
+    /// Lines ending in `\n` and lines ending in `\r\n` in the same file.
+    MixedLineEndings,
+    /// NUL bytes in a message.
+    NulBytes,
+    /// A `Content-Length:` header that does not match the body.
+    ContentLengthMismatch,
+    /// A message cut off: the file ends mid-line, or before the end of the
+    /// body its `Content-Length:` announces.
+    Truncated,
+    /// A message larger than the parser keeps in memory, whose end is cut
+    /// when it is read whole.
+    Oversized,
+}
+
+impl AnomalyKind {
+    /// Every kind, in the order they are reported.
+    pub const ALL: [Self; 12] = [
+        Self::Truncated,
+        Self::Oversized,
+        Self::LeadingGarbage,
+        Self::MalformedSeparator,
+        Self::MissingBlankLine,
+        Self::MissingHeaderEnd,
+        Self::UnquotedFrom,
+        Self::ContentLengthMismatch,
+        Self::MixedLineEndings,
+        Self::NulBytes,
+        Self::BareSeparator,
+        Self::Bom,
+    ];
+
+    /// How much an anomaly of this kind matters.
+    pub fn severity(self) -> Severity {
+        match self {
+            Self::Bom | Self::BareSeparator => Severity::Info,
+            Self::MalformedSeparator
+            | Self::MissingBlankLine
+            | Self::MissingHeaderEnd
+            | Self::UnquotedFrom
+            | Self::MixedLineEndings
+            | Self::NulBytes
+            | Self::ContentLengthMismatch => Severity::Warning,
+            Self::LeadingGarbage | Self::Truncated | Self::Oversized => Severity::Error,
+        }
+    }
+}
+
+/// One anomaly found in a mailbox.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub severity: Severity,
+    /// Content offset of the (first) offending byte or line.
+    pub offset: u64,
+    /// Offset of the message it was found in.
+    pub message_offset: u64,
+    /// Occurrences it stands for: NUL bytes or unquoted lines in the
+    /// message, lines with the less common ending in the file; 1 otherwise.
+    pub count: u64,
+}
+
+/// What [`verify_mbox`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Messages the parser finds.
+    pub messages: u64,
+    /// Bytes of content read (decompressed, for a compressed mailbox).
+    pub bytes: u64,
+    pub dialect: MboxDialect,
+    /// Lines ending in `\n` alone.
+    pub lf_lines: u64,
+    /// Lines ending in `\r\n`.
+    pub crlf_lines: u64,
+    /// In the order of their offsets.
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl VerifyReport {
+    /// Number of anomalies of `kind`.
+    pub fn count(&self, kind: AnomalyKind) -> usize {
+        self.anomalies.iter().filter(|a| a.kind == kind).count()
+    }
+
+    /// The most serious anomaly found, `None` for a mailbox without any.
+    pub fn worst(&self) -> Option<Severity> {
+        self.anomalies.iter().map(|a| a.severity).max()
+    }
+}
+
+/// Check the MBOX at `path` (plain or compressed) for everything the parser
+/// tolerates. `progress` receives `(bytes_read, total_bytes)` as for
+/// [`MboxParser::parse`].
+pub fn verify_mbox(path: &Path, progress: Option<&dyn Fn(u64, u64)>) -> Result<VerifyReport> {
+    if message_dir::detect(path).is_some() || archive::detect(path)?.is_some() {
+        return Err(MboxError::InvalidMbox(path.to_path_buf()));
+    }
+    let parser = MboxParser::new(path)?;
+    let mut checker = Checker::new(parser.dialect());
+    parser.parse(
+        &mut |offset, raw| {
+            checker.check_message(offset, raw);
+            true
+        },
+        progress,
+    )?;
+    let content_size = parser.codec().is_none().then_some(parser.file_size());
+    Ok(checker.finish(content_size))
+}
+
+/// Why `line`, the first line of a message, is not a proper separator, or
+/// `None` when it is one: `From <sender> <date>`, or a `git format-patch`
+/// marker where the parser took it as a separator.
+pub(crate) fn separator_problem(line: &[u8]) -> Option<AnomalyKind> {
+    match mbox::classify_from_line(line) {
+        FromLineKind::Separator => {
+            let line = line.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(line);
+            line.trim_ascii_end()
+                .eq(b"From")
+                .then_some(AnomalyKind::BareSeparator)
+        }
+        FromLineKind::GitPatchMarker => None,
+        FromLineKind::Content if mbox::starts_with_from(line) => {
+            Some(AnomalyKind::MalformedSeparator)
+        }
+        FromLineKind::Content => Some(AnomalyKind::LeadingGarbage),
+    }
+}
+
+/// The checks, run over each message as the parser yields it.
+struct Checker {
+    dialect: MboxDialect,
+    report: VerifyReport,
+    /// Offset, length and whether it ended with a blank line (or where its
+    /// `Content-Length:` says), of the previous message.
+    previous: Option<(u64, usize, bool)>,
+    /// Whether the previous message ended with a newline.
+    ended_with_newline: bool,
+    /// Ending of the file's first line (`true` for `\r\n`), and the offsets
+    /// of the first line ending otherwise and of its message.
+    first_ending: Option<bool>,
+    other_ending_at: Option<(u64, u64)>,
+}
+
+impl Checker {
+    fn new(dialect: MboxDialect) -> Self {
+        Self {
+            dialect,
+            report: VerifyReport {
+                messages: 0,
+                bytes: 0,
+                dialect,
+                lf_lines: 0,
+                crlf_lines: 0,
+                anomalies: Vec::new(),
+            },
+            previous: None,
+            ended_with_newline: true,
+            first_ending: None,
+            other_ending_at: None,
+        }
+    }
+
+    fn push(&mut self, kind: AnomalyKind, offset: u64, message_offset: u64, count: u64) {
+        self.report.anomalies.push(Anomaly {
+            kind,
+            severity: kind.severity(),
+            offset,
+            message_offset,
+            count,
+        });
+    }
+
+    fn check_message(&mut self, offset: u64, raw: &[u8]) {
+        self.report.messages += 1;
+        self.report.bytes = offset + raw.len() as u64;
+
+        if let Some((prev_offset, prev_len, prev_ended_blank)) = self.previous {
+            if offset - prev_offset > prev_len as u64 {
+                self.push(AnomalyKind::Oversized, prev_offset, prev_offset, 1);
+            }
+            if !prev_ended_blank {
+                self.push(AnomalyKind::MissingBlankLine, offset, offset, 1);
+            }
+        }
+        if offset == 0 && raw.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            self.push(AnomalyKind::Bom, 0, 0, 1);
+        }
+        let first_line_len = raw
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(raw.len(), |nl| nl + 1);
+        if let Some(kind) = separator_problem(&raw[..first_line_len]) {
+            self.push(kind, offset, offset, 1);
+        }
+
+        let mut at = offset;
+        let mut unquoted = (0, 0);
+        let mut last_line_blank = false;
+        for (i, line) in raw.split_inclusive(|&b| b == b'\n').enumerate() {
+            if line.ends_with(b"\n") {
+                let crlf = line.ends_with(b"\r\n");
+                if crlf {
+                    self.report.crlf_lines += 1;
+                } else {
+                    self.report.lf_lines += 1;
+                }
+                match self.first_ending {
+                    None => self.first_ending = Some(crlf),
+                    Some(first) if first != crlf && self.other_ending_at.is_none() => {
+                        self.other_ending_at = Some((at, offset));
+                    }
+                    _ => {}
+                }
+            }
+            if i > 0 && self.dialect != MboxDialect::Mboxcl2 && line.starts_with(b"From ") {
+                if unquoted.0 == 0 {
+                    unquoted.1 = at;
+                }
+                unquoted.0 += 1;
+            }
+            last_line_blank = i > 0 && mbox::is_blank_line(line);
+            at += line.len() as u64;
+        }
+        if unquoted.0 > 0 {
+            self.push(AnomalyKind::UnquotedFrom, unquoted.1, offset, unquoted.0);
+        }
+
+        let nuls = raw.iter().filter(|&&b| b == 0).count() as u64;
+        if let Some(first) = raw.iter().position(|&b| b == 0) {
+            self.push(AnomalyKind::NulBytes, offset + first as u64, offset, nuls);
+        }
+
+        let mut ended_at_length = false;
+        match dialect::body_start(raw) {
+            None => self.push(AnomalyKind::MissingHeaderEnd, offset, offset, 1),
+            Some(start) => {
+                if let Some(length) = dialect::content_length(&raw[..start]) {
+                    let body = &raw[start..];
+                    let separator = if body.ends_with(b"\r\n\r\n") {
+                        2
+                    } else {
+                        usize::from(body.ends_with(b"\n\n"))
+                    };
+                    let fits = |len: usize| len as u64 == length;
+                    ended_at_length = fits(body.len());
+                    if !fits(body.len()) && !fits(body.len() - separator) {
+                        let kind =
+                            if self.dialect.is_length_delimited() && length > body.len() as u64 {
+                                AnomalyKind::Truncated
+                            } else {
+                                AnomalyKind::ContentLengthMismatch
+                            };
+                        self.push(kind, offset + start as u64, offset, 1);
+                    }
+                }
+            }
+        }
+
+        let ended_blank =
+            last_line_blank || (self.dialect.is_length_delimited() && ended_at_length);
+        self.previous = Some((offset, raw.len(), ended_blank));
+        // Only the last message can end mid-line; `finish` reports it.
+        self.ended_with_newline = raw.ends_with(b"\n");
+    }
+
+    /// The report, once every message was checked. `content_size` is the
+    /// size of the content when known up front (a plain file), to tell
+    /// whether the last message was cut at the parser's size limit.
+    fn finish(mut self, content_size: Option<u64>) -> VerifyReport {
+        if let Some((offset, len, _)) = self.previous {
+            let end = offset + len as u64;
+            if content_size.is_some_and(|size| size > end) {
+                self.push(AnomalyKind::Oversized, offset, offset, 1);
+                self.report.bytes = content_size.unwrap_or(end);
+            } else if !self.ended_with_newline {
+                self.push(AnomalyKind::Truncated, end, offset, 1);
+            }
+        }
+        if let Some((at, message_offset)) = self.other_ending_at {
+            let count = self.report.lf_lines.min(self.report.crlf_lines);
+            self.push(AnomalyKind::MixedLineEndings, at, message_offset, count);
+        }
+        self.report.anomalies.sort_by_key(|a| a.offset);
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verify(data: &[u8]) -> VerifyReport {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mail.mbox");
+        std::fs::write(&path, data).unwrap();
+        verify_mbox(&path, None).unwrap()
+    }
+
+    fn kinds(report: &VerifyReport) -> Vec<AnomalyKind> {
+        report.anomalies.iter().map(|a| a.kind).collect()
+    }
+
+    #[test]
+    fn test_clean_mailbox_has_no_anomalies() {
+        let report = verify(
+            b"From a@x Thu Jan 01 00:00:00 2024\nSubject: A\n\n>From quoted\n\n\
+              From b@x Fri Jan 02 00:00:00 2024\nSubject: B\n\nhi\n",
+        );
+        assert_eq!(report.messages, 2);
+        assert_eq!((report.lf_lines, report.crlf_lines), (9, 0));
+        assert!(report.anomalies.is_empty(), "{:?}", report.anomalies);
+        assert_eq!(report.worst(), None);
+    }
+
+    #[test]
+    fn test_tolerated_damage_is_reported_with_offsets() {
+        let data = b"\xEF\xBB\xBFFrom a@x Thu Jan 01 00:00:00 2024\nSubject: A\n\n\
+                     body\nFrom here on\n\0\0\r\n\
+                     From b@x Fri Jan 02 00:00:00 2024\nSubject: B\n\nhi\n\n\
+                     From \nSubject: C\n\ncut";
+        let report = verify(data);
+        assert_eq!(report.messages, 3);
+        assert_eq!(
+            kinds(&report),
+            [
+                AnomalyKind::Bom,
+                AnomalyKind::UnquotedFrom,
+                AnomalyKind::NulBytes,
+                AnomalyKind::MixedLineEndings,
+                AnomalyKind::MissingBlankLine,
+                AnomalyKind::BareSeparator,
+                AnomalyKind::Truncated,
+            ]
+        );
+        let find = |kind| report.anomalies.iter().find(|a| a.kind == kind).unwrap();
+        assert_eq!(find(AnomalyKind::UnquotedFrom).offset, 54);
+        assert_eq!(find(AnomalyKind::NulBytes).count, 2);
+        let second = find(AnomalyKind::MissingBlankLine);
+        assert_eq!(second.offset, 71);
+        assert_eq!(&data[71..76], b"From ");
+        assert_eq!(find(AnomalyKind::Truncated).offset, data.len() as u64);
+        assert_eq!(report.worst(), Some(Severity::Error));
+    }
+
+    #[test]
+    fn test_content_before_the_first_separator() {
+        let report = verify(b"garbage\n\nFrom a@x Thu Jan 01 00:00:00 2024\nSubject: A\n\nx\n");
+        assert_eq!(report.messages, 2);
+        assert_eq!(kinds(&report), [AnomalyKind::LeadingGarbage]);
+    }
+
+    #[test]
+    fn test_content_length_is_checked_against_the_body() {
+        // mboxcl2: a body ending exactly where its length says needs no blank
+        // line before the next separator.
+        let report = verify(
+            b"From a@x Thu Jan 01 00:00:00 2024\nContent-Length: 5\n\nbody\n\
+              From b@x Fri Jan 02 00:00:00 2024\nContent-Length: 3\n\nhi\n\n",
+        );
+        assert_eq!(report.dialect, MboxDialect::Mboxcl2);
+        assert!(report.anomalies.is_empty(), "{:?}", report.anomalies);
+
+        let report = verify(
+            b"From a@x Thu Jan 01 00:00:00 2024\nContent-Length: 50\n\nbody\n\n\
+              From b@x Fri Jan 02 00:00:00 2024\nSubject: B\n\nhi\n",
+        );
+        assert_eq!(kinds(&report), [AnomalyKind::ContentLengthMismatch]);
+    }
+
+    #[test]
+    fn test_directories_are_not_verified() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.eml"), b"Subject: A\n\nx\n").unwrap();
+        assert!(verify_mbox(dir.path(), None).is_err());
+    }
+}
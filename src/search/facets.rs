@@ -0,0 +1,385 @@
+//! Facet aggregation over a set of search results.
+//!
+//! Breaks the results down by sender, sender domain, label, year, month,
+//! conversation thread and attachment content type, so a search returning
+//! tens of thousands of messages can be understood — and narrowed — before it
+//! is exported.
+//!
+//! Everything comes from the index, attachment types included (see
+//! [`MailEntry::attachments`]), so facets never read the mailbox.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::model::mail::MailEntry;
+use crate::search::query;
+use crate::tui::threading;
+
+/// What a facet groups the results by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FacetKind {
+    /// Sender address.
+    Sender,
+    /// Domain of the sender address.
+    Domain,
+    /// Label (Gmail label, folder, Google Group).
+    Label,
+    /// Year the message was sent, in the query timezone.
+    Year,
+    /// Month the message was sent, in the query timezone, as `YYYY-MM`.
+    Month,
+    /// Conversation thread, shown by the subject of its first message.
+    Thread,
+    /// Content type of an attachment (`application/pdf`).
+    AttachmentType,
+}
+
+impl FacetKind {
+    /// Every kind, in the order facets are listed.
+    pub const ALL: [Self; 7] = [
+        Self::Sender,
+        Self::Domain,
+        Self::Label,
+        Self::Year,
+        Self::Month,
+        Self::Thread,
+        Self::AttachmentType,
+    ];
+
+    /// Stable identifier, used as the key in JSON output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sender => "sender",
+            Self::Domain => "domain",
+            Self::Label => "label",
+            Self::Year => "year",
+            Self::Month => "month",
+            Self::Thread => "thread",
+            Self::AttachmentType => "attachment_type",
+        }
+    }
+
+    /// The query term that narrows a search down to `value`, for the kinds
+    /// the query syntax can express. Threads have no search field.
+    pub fn narrowing_term(self, value: &str) -> Option<String> {
+        if value.contains('"') {
+            return None;
+        }
+        match self {
+            Self::Sender => Some(format!("from:{}", quoted(value))),
+            Self::Domain => Some(format!("from:{}", quoted(&format!("@{value}")))),
+            Self::Label => Some(format!("label:{}", quoted(value))),
+            Self::Year | Self::Month => Some(format!("date:{value}")),
+            Self::AttachmentType => Some(format!("attachment-type:{}", quoted(value))),
+            Self::Thread => None,
+        }
+    }
+}
+
+/// Quote `value` when it would not survive tokenization as one word.
+fn quoted(value: &str) -> String {
+    if value
+        .chars()
+        .any(|c| c.is_whitespace() || c == '(' || c == ')')
+    {
+        format!("\"{value}\"")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Counts of one [`FacetKind`] over the results.
+#[derive(Debug, Clone)]
+pub struct Facet {
+    pub kind: FacetKind,
+    /// Values by descending count, ties by value.
+    pub values: Vec<FacetValue>,
+}
+
+/// One value of a facet and the results holding it.
+#[derive(Debug, Clone)]
+pub struct FacetValue {
+    pub value: String,
+    /// Number of results with this value.
+    pub count: usize,
+    /// Those results, as ascending indices into the entries.
+    pub messages: Vec<usize>,
+}
+
+/// Every facet of a result set.
+#[derive(Debug, Clone)]
+pub struct Facets {
+    /// Number of results summarized.
+    pub total: usize,
+    /// One facet per [`FacetKind`], in [`FacetKind::ALL`] order.
+    pub facets: Vec<Facet>,
+}
+
+impl Facets {
+    /// The facet of `kind`.
+    pub fn get(&self, kind: FacetKind) -> Option<&Facet> {
+        self.facets.iter().find(|f| f.kind == kind)
+    }
+}
+
+/// Break `results` (indices into `entries`) down by every [`FacetKind`].
+pub fn compute(entries: &[MailEntry], results: &[usize]) -> Facets {
+    let mut results = results.to_vec();
+    results.sort_unstable();
+
+    let mut senders = Tally::default();
+    let mut domains = Tally::default();
+    let mut labels = Tally::default();
+    let mut years = Tally::default();
+    let mut months = Tally::default();
+    let mut attachment_types = Tally::default();
+    // Days are taken where `date:` takes them, so a value's narrowing term
+    // finds the messages it counts.
+    let timezone = query::timezone();
+    for &i in &results {
+        let entry = &entries[i];
+        let address = entry.from.address.to_lowercase();
+        if let Some((_, domain)) = address.rsplit_once('@') {
+            if !domain.is_empty() {
+                domains.add(domain.to_string(), i);
+            }
+        }
+        if !address.is_empty() {
+            senders.add(address, i);
+        } else if !entry.from.display_name.is_empty() {
+            senders.add(entry.from.display_name.clone(), i);
+        }
+        for label in &entry.labels {
+            labels.add(label.clone(), i);
+        }
+        let date = timezone.date_of(entry.date);
+        years.add(date.format("%Y").to_string(), i);
+        months.add(date.format("%Y-%m").to_string(), i);
+        for attachment in &entry.attachments {
+            attachment_types.add(attachment.content_type.clone(), i);
+        }
+    }
+
+    // Threads are built over the whole mailbox, so a thread counts the
+    // results it holds even when its root did not match.
+    let threads = threading::build_threads(entries);
+    let mut thread_of = vec![0; entries.len()];
+    for (t, thread) in threads.iter().enumerate() {
+        for &(i, _) in &thread.nodes {
+            if let Some(slot) = thread_of.get_mut(i) {
+                *slot = t;
+            }
+        }
+    }
+    let mut by_thread = Tally::default();
+    for &i in &results {
+        by_thread.add(thread_of[i], i);
+    }
+
+    Facets {
+        total: results.len(),
+        facets: vec![
+            senders.finish(FacetKind::Sender),
+            domains.finish(FacetKind::Domain),
+            labels.finish(FacetKind::Label),
+            years.finish(FacetKind::Year),
+            months.finish(FacetKind::Month),
+            by_thread.finish_with(FacetKind::Thread, |&t| {
+                let thread = &threads[t];
+                thread.nodes.first().map_or_else(
+                    || thread.subject.clone(),
+                    |&(i, _)| entries[i].subject.clone(),
+                )
+            }),
+            attachment_types.finish(FacetKind::AttachmentType),
+        ],
+    }
+}
+
+/// Messages per value, built in ascending message order.
+struct Tally<K> {
+    values: HashMap<K, Vec<usize>>,
+}
+
+impl<K> Default for Tally<K> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq> Tally<K> {
+    /// Record that message `i` has `key`; a message is counted once per key.
+    fn add(&mut self, key: K, i: usize) {
+        let messages = self.values.entry(key).or_default();
+        if messages.last() != Some(&i) {
+            messages.push(i);
+        }
+    }
+
+    /// The facet of `kind`, each key shown as `display(key)`.
+    fn finish_with(self, kind: FacetKind, display: impl Fn(&K) -> String) -> Facet {
+        let mut values: Vec<FacetValue> = self
+            .values
+            .into_iter()
+            .map(|(key, messages)| FacetValue {
+                value: display(&key),
+                count: messages.len(),
+                messages,
+            })
+            .collect();
+        values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        Facet { kind, values }
+    }
+}
+
+impl Tally<String> {
+    fn finish(self, kind: FacetKind) -> Facet {
+        self.finish_with(kind, String::clone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::builder;
+    use crate::testing::temp_fixture;
+
+    #[test]
+    fn test_facets_over_fixture() {
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
+        let entries = builder::build_index(&mbox_path, true, None).unwrap();
+        let all: Vec<usize> = (0..entries.len()).collect();
+        let facets = compute(&entries, &all);
+        assert_eq!(facets.total, entries.len());
+        assert_eq!(facets.facets.len(), FacetKind::ALL.len());
+
+        // Every message was sent in January 2024.
+        let months = facets.get(FacetKind::Month).unwrap();
+        assert_eq!(months.values.len(), 1);
+        assert_eq!(months.values[0].value, "2024-01");
+        assert_eq!(months.values[0].count, entries.len());
+
+        // Counts are sorted, and each value lists exactly its messages.
+        for facet in &facets.facets {
+            assert!(facet.values.windows(2).all(|w| w[0].count >= w[1].count));
+            for value in &facet.values {
+                assert_eq!(value.count, value.messages.len());
+            }
+        }
+
+        // "Hello World" and its reply form one thread.
+        let threads = facets.get(FacetKind::Thread).unwrap();
+        assert_eq!(threads.values[0].count, 2);
+        assert_eq!(threads.values.len(), entries.len() - 1);
+    }
+
+    #[test]
+    fn test_facets_count_only_the_results() {
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
+        let entries = builder::build_index(&mbox_path, true, None).unwrap();
+        let facets = compute(&entries, &[1]);
+        assert_eq!(facets.total, 1);
+        let senders = facets.get(FacetKind::Sender).unwrap();
+        assert_eq!(senders.values.len(), 1);
+        assert_eq!(senders.values[0].messages, vec![1]);
+        assert_eq!(
+            facets.get(FacetKind::Thread).unwrap().values[0].count,
+            1,
+            "a thread counts only its messages among the results"
+        );
+    }
+
+    #[test]
+    fn test_attachment_types_come_from_the_index() {
+        let (_dir, mbox_path) = temp_fixture("cloud_infra.mbox");
+        let entries = builder::build_index(&mbox_path, true, None).unwrap();
+        let all: Vec<usize> = (0..entries.len()).collect();
+
+        let facets = compute(&entries, &all);
+        let types = facets.get(FacetKind::AttachmentType).unwrap();
+        let pdf = types
+            .values
+            .iter()
+            .find(|v| v.value == "application/pdf")
+            .expect("the fixture has a PDF attachment");
+        assert!(pdf.messages.iter().all(|&i| entries[i].has_attachments));
+
+        // Its narrowing term selects the same messages.
+        let term = FacetKind::AttachmentType
+            .narrowing_term(&pdf.value)
+            .unwrap();
+        let query = crate::search::query::parse_query(&term);
+        assert_eq!(
+            crate::search::metadata::search_metadata(&entries, &query),
+            pdf.messages
+        );
+    }
+
+    #[test]
+    fn test_month_facets_count_what_their_date_term_finds() {
+        // Messages within an hour of midnight at month and year ends, so
+        // the day shifts with the timezone.
+        let dates = [
+            "Sun, 31 Dec 2023 23:30:00 +0000",
+            "Mon, 01 Jan 2024 00:30:00 +0000",
+            "Wed, 31 Jan 2024 23:30:00 +0000",
+            "Thu, 01 Feb 2024 00:30:00 +0000",
+            "Thu, 29 Feb 2024 23:59:00 +0000",
+        ];
+        let mailbox: Vec<String> = dates
+            .iter()
+            .enumerate()
+            .map(|(i, date)| {
+                format!(
+                    "From a@x Mon Jan 01 00:00:00 2024\nFrom: a@x\nDate: {date}\n\
+                     Subject: {i}\n\nbody\n"
+                )
+            })
+            .collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("midnight.mbox");
+        std::fs::write(&path, mailbox.join("\n")).unwrap();
+        let entries = builder::build_index(&path, false, None).unwrap();
+        let all: Vec<usize> = (0..entries.len()).collect();
+
+        let facets = compute(&entries, &all);
+        for kind in [FacetKind::Month, FacetKind::Year] {
+            for value in &facets.get(kind).unwrap().values {
+                let term = kind.narrowing_term(&value.value).unwrap();
+                let query = query::parse_query(&term);
+                let found = crate::search::metadata::search_metadata(&entries, &query);
+                assert_eq!(found, value.messages, "{term}");
+                assert_eq!(found.len(), value.count, "{term}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_narrowing_terms() {
+        assert_eq!(
+            FacetKind::Sender.narrowing_term("a@b.com").as_deref(),
+            Some("from:a@b.com")
+        );
+        assert_eq!(
+            FacetKind::Domain.narrowing_term("b.com").as_deref(),
+            Some("from:@b.com")
+        );
+        assert_eq!(
+            FacetKind::Label.narrowing_term("Sent Mail").as_deref(),
+            Some("label:\"Sent Mail\"")
+        );
+        assert_eq!(
+            FacetKind::Month.narrowing_term("2024-03").as_deref(),
+            Some("date:2024-03")
+        );
+        assert_eq!(
+            FacetKind::AttachmentType
+                .narrowing_term("image/png")
+                .as_deref(),
+            Some("attachment-type:image/png")
+        );
+        assert_eq!(FacetKind::Thread.narrowing_term("Hello"), None);
+    }
+}
@@ -2,17 +2,26 @@
 //!
 //! This is the slow path: for each candidate message, it reads and decodes
 //! the MIME body, then searches the plain text. Use metadata search first
-//! to reduce the candidate set.
+//! to reduce the candidate set. With a full-text index
+//! ([`super::inverted`]), [`search_indexed`] only reads the messages the
+//! index cannot settle.
+//!
+//! `attachment:` terms also extract the text of the message's attachments
+//! ([`crate::parser::attachment_text`]); only queries that have one pay for
+//! it, and the index does not hold that text.
 
 use std::path::Path;
 
 use tracing::debug;
 
-use crate::model::mail::MailEntry;
+use crate::model::mail::{MailBody, MailEntry};
+use crate::parser::header;
 use crate::store::reader::MboxStore;
 
-use super::metadata::{all_matches_metadata, term_matches_entry};
-use super::query::{SearchField, SearchOperator, SearchQuery, SearchTerm, TermGroup};
+use super::inverted::{InvertedIndex, Lookup};
+use super::metadata::{matches_text, metadata_verdict};
+use super::normalize::{self, fold};
+use super::query::{Condition, QueryExpr, SearchField, SearchOperator, SearchQuery, SearchTerm};
 
 /// Search inside message bodies by reading from the MBOX file.
 ///
@@ -28,17 +37,12 @@ pub fn search_fulltext(
     query: &SearchQuery,
     progress: &dyn Fn(usize, usize) -> bool,
 ) -> crate::error::Result<Vec<usize>> {
-    // Groups the metadata pass could not settle: those holding a body:,
-    // filename:, or free-text term (the last matches metadata *or* body).
-    // Every one of them still has to hold, so they are AND-ed, while the terms
-    // inside each are OR-ed — the same shape the metadata pass applies.
-    let scan_groups: Vec<&TermGroup> = query.groups.iter().filter(|g| g.needs_body()).collect();
-
-    if scan_groups.is_empty() {
+    let Some(expr) = query.expr.as_ref().filter(|_| query.needs_body()) else {
         // Nothing to scan — all candidates pass
         return Ok(candidates.to_vec());
-    }
+    };
 
+    let attachments = reads_attachments(query);
     let mut store = MboxStore::open(mbox_path)?;
     let mut results = Vec::new();
     let total = candidates.len();
@@ -51,12 +55,14 @@ pub fn search_fulltext(
         }
 
         let entry = &entries[idx];
-        let matches = match check_body_match(&mut store, entry, &scan_groups) {
-            Ok(m) => m,
-            Err(e) => {
+        // Metadata may settle the message on its own (`subject:x OR body:y`
+        // with a matching subject); only read the body when it does not.
+        let matches = match expr.evaluate(&mut |c| metadata_verdict(entry, c)) {
+            Some(matches) => matches,
+            None => check_body_match(&mut store, entry, expr, attachments).unwrap_or_else(|e| {
                 debug!(offset = entry.offset, error = %e, "Skipping message in fulltext search");
                 false
-            }
+            }),
         };
 
         if matches {
@@ -70,67 +76,209 @@ pub fn search_fulltext(
     Ok(results)
 }
 
-/// Check whether a single message satisfies the deferred groups by reading its
-/// body.
+/// [`search_fulltext`] answered from a full-text index.
 ///
-/// Handles `body:`, `filename:`, and free-text `All` terms. `All` terms match
-/// if the needle is found in the entry's metadata (subject/from/to) **or** the
-/// decoded body text — the "search everywhere" semantics users expect. Every
-/// group must hold (AND); within a group, any term suffices (OR).
-fn check_body_match(
-    store: &mut MboxStore,
-    entry: &MailEntry,
-    scan_groups: &[&TermGroup],
-) -> crate::error::Result<bool> {
-    let body = store.get_message(entry)?;
-    let text = body.text.as_deref().unwrap_or("");
-
-    let text_lower = text.to_lowercase();
-
-    let check_term = |term: &SearchTerm| -> bool {
-        let raw_match = match term.field {
-            SearchField::Body => match &term.operator {
-                SearchOperator::Contains(needle) => text_lower.contains(needle),
-                SearchOperator::Exact(phrase) => text_lower.contains(phrase),
-            },
-            SearchField::Filename => {
-                // Search in attachment filenames
-                body.attachments.iter().any(|att| {
-                    let fname = att.filename.to_lowercase();
-                    match &term.operator {
-                        SearchOperator::Contains(needle) => fname.contains(needle),
-                        SearchOperator::Exact(phrase) => fname == *phrase,
-                    }
-                })
+/// Each term that needs the body is looked up once; a message is only read
+/// from the MBOX when the expression cannot be settled without it (a phrase
+/// the index only narrows down, a message that was unreadable when
+/// indexed). The results are the ones the scan returns.
+pub fn search_indexed(
+    mbox_path: &Path,
+    entries: &[MailEntry],
+    candidates: &[usize],
+    query: &SearchQuery,
+    index: &InvertedIndex,
+    progress: &dyn Fn(usize, usize) -> bool,
+) -> crate::error::Result<Vec<usize>> {
+    let Some(expr) = query.expr.as_ref().filter(|_| query.needs_body()) else {
+        return Ok(candidates.to_vec());
+    };
+    // Per term, the index's answer for the body side of the term. Regular
+    // expressions and attachment text are not looked up: their messages are
+    // read.
+    let mut lookups: Vec<(&SearchTerm, Lookup)> = Vec::new();
+    for term in query.all_terms() {
+        let lookup = match (&term.field, &term.operator) {
+            (SearchField::Filename, op) => index.filename_lookup(op),
+            (
+                SearchField::Header(name),
+                SearchOperator::Contains(needle) | SearchOperator::Exact(needle),
+            ) => index.header_lookup(name, needle),
+            (SearchField::Header(name), SearchOperator::Fuzzy { term, max_distance }) => {
+                index.header_fuzzy_lookup(name, term, *max_distance)
             }
-            // Free-text term: match metadata or body.
-            SearchField::All => {
-                all_matches_metadata(entry, &term.operator)
-                    || match &term.operator {
-                        SearchOperator::Contains(needle) | SearchOperator::Exact(needle) => {
-                            text_lower.contains(needle)
-                        }
-                    }
+            (
+                SearchField::Body | SearchField::All,
+                SearchOperator::Contains(needle) | SearchOperator::Exact(needle),
+            ) => index.body_lookup(needle),
+            (
+                SearchField::Body | SearchField::All,
+                SearchOperator::Fuzzy { term, max_distance },
+            ) => index.body_fuzzy_lookup(term, *max_distance),
+            _ => continue,
+        };
+        lookups.push((term, lookup));
+    }
+
+    let attachments = reads_attachments(query);
+    let mut store: Option<MboxStore> = None;
+    let mut results = Vec::new();
+    let total = candidates.len();
+
+    for (i, &idx) in candidates.iter().enumerate() {
+        if !progress(i, total) {
+            debug!("Full-text search cancelled at {i}/{total}");
+            break;
+        }
+
+        let entry = &entries[idx];
+        let n = u32::try_from(idx).unwrap_or(u32::MAX);
+        let verdict = expr.evaluate(&mut |condition| {
+            metadata_verdict(entry, condition).or_else(|| match condition {
+                Condition::Term(term) if !index.is_unreadable(n) => lookups
+                    .iter()
+                    .find(|(t, _)| *t == term)
+                    .and_then(|(_, lookup)| lookup.decide(n)),
+                _ => None,
+            })
+        });
+        let matches = match verdict {
+            Some(matches) => matches,
+            None => {
+                let store = match &mut store {
+                    Some(store) => store,
+                    None => store.insert(MboxStore::open(mbox_path)?),
+                };
+                check_body_match(store, entry, expr, attachments).unwrap_or_else(|e| {
+                    debug!(offset = entry.offset, error = %e, "Skipping message in fulltext search");
+                    false
+                })
             }
-            // A deferred group can hold metadata terms beside the body ones
-            // (`body:x OR subject:y`); those are judged exactly as the
-            // metadata pass would. `term_matches_entry` applies negation
-            // itself, so return its verdict directly.
-            _ => return term_matches_entry(entry, term),
         };
 
-        if term.negated {
-            !raw_match
-        } else {
-            raw_match
+        if matches {
+            results.push(idx);
         }
+    }
+
+    let _ = progress(total, total);
+
+    Ok(results)
+}
+
+/// Whether `query` has `attachment:` terms, so the messages read have their
+/// attachments' text extracted.
+fn reads_attachments(query: &SearchQuery) -> bool {
+    query
+        .all_terms()
+        .any(|term| term.field == SearchField::Attachment)
+}
+
+/// Check whether a single message satisfies the query expression by reading
+/// its body, and the text of its attachments when `attachments` is set.
+///
+/// Metadata conditions are judged exactly as the metadata pass judges them;
+/// the message settles the rest — see [`term_matches_body`].
+fn check_body_match(
+    store: &mut MboxStore,
+    entry: &MailEntry,
+    expr: &QueryExpr,
+    attachments: bool,
+) -> crate::error::Result<bool> {
+    let body = store.get_message(entry)?;
+    let attachment_texts = if attachments && !body.attachments.is_empty() {
+        store.get_attachment_texts(entry)?
+    } else {
+        Vec::new()
     };
+    let message = ReadMessage::new(&body, attachment_texts);
 
-    let result = scan_groups
-        .iter()
-        .all(|group| group.terms.iter().any(&check_term));
+    let verdict = expr.evaluate(&mut |condition| {
+        metadata_verdict(entry, condition).or_else(|| match condition {
+            Condition::Term(term) => Some(term_matches_body(term, &message)),
+            // Filters are always settled by the metadata.
+            _ => None,
+        })
+    });
 
-    Ok(result)
+    Ok(verdict == Some(true))
+}
+
+/// The parts of a read message the terms are matched against.
+struct ReadMessage<'a> {
+    body: &'a MailBody,
+    /// The body text folded (see [`fold`]).
+    text_folded: String,
+    /// `(lowercase name, decoded value)` of every header, unfolded.
+    headers: Vec<(String, String)>,
+    /// `(text, folded text)` of every attachment that has text.
+    attachments: Vec<(String, String)>,
+}
+
+impl<'a> ReadMessage<'a> {
+    fn new(body: &'a MailBody, attachment_texts: Vec<String>) -> Self {
+        let headers = header::unfold_headers(&body.raw_headers)
+            .into_iter()
+            .map(|(name, value)| (name, header::decode_encoded_words(&value)))
+            .collect();
+        Self {
+            body,
+            text_folded: fold(body.text.as_deref().unwrap_or("")),
+            headers,
+            attachments: attachment_texts
+                .into_iter()
+                .map(|text| {
+                    let folded = fold(&text);
+                    (text, folded)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Whether a term the metadata left open matches the read message.
+///
+/// `body:` and free-text `All` terms look for the needle in the decoded body
+/// text (an `All` term whose needle is in the subject/from/to never gets
+/// here); `filename:` looks at the attachment filenames, `attachment:` at
+/// the text of each attachment and `header:` at every occurrence of its
+/// header. Substring needles and fuzzy words are matched against the folded
+/// text, regular expressions against the original.
+fn term_matches_body(term: &SearchTerm, message: &ReadMessage) -> bool {
+    match (&term.field, &term.operator) {
+        (SearchField::Filename, op) => message.body.attachments.iter().any(|att| match op {
+            SearchOperator::Contains(needle) => fold(&att.filename).contains(needle),
+            SearchOperator::Exact(phrase) => fold(&att.filename) == *phrase,
+            SearchOperator::Fuzzy { term, max_distance } => {
+                normalize::fuzzy_matches(&fold(&att.filename), term, *max_distance)
+            }
+            SearchOperator::Regex(pattern) => pattern.is_match(&att.filename),
+        }),
+        (SearchField::Attachment, op) => {
+            message.attachments.iter().any(|(text, folded)| match op {
+                SearchOperator::Contains(needle) | SearchOperator::Exact(needle) => {
+                    folded.contains(needle)
+                }
+                SearchOperator::Fuzzy { term, max_distance } => {
+                    normalize::fuzzy_matches(folded, term, *max_distance)
+                }
+                SearchOperator::Regex(pattern) => pattern.is_match(text),
+            })
+        }
+        (SearchField::Header(name), op) => message
+            .headers
+            .iter()
+            .any(|(n, value)| n == name && matches_text(value, op)),
+        (_, SearchOperator::Contains(needle) | SearchOperator::Exact(needle)) => {
+            message.text_folded.contains(needle)
+        }
+        (_, SearchOperator::Fuzzy { term, max_distance }) => {
+            normalize::fuzzy_matches(&message.text_folded, term, *max_distance)
+        }
+        (_, SearchOperator::Regex(pattern)) => {
+            pattern.is_match(message.body.text.as_deref().unwrap_or(""))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -138,18 +286,11 @@ mod tests {
     use super::*;
     use crate::index::builder;
     use crate::search::query::parse_query;
-    use std::path::Path;
-
-    fn fixture(name: &str) -> std::path::PathBuf {
-        Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("tests")
-            .join("fixtures")
-            .join(name)
-    }
+    use crate::testing::temp_fixture;
 
     #[test]
     fn test_fulltext_body_search() {
-        let mbox_path = fixture("simple.mbox");
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
         let entries = builder::build_index(&mbox_path, true, None).unwrap();
         let candidates: Vec<usize> = (0..entries.len()).collect();
 
@@ -164,9 +305,107 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_indexed_search_matches_scan() {
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
+        let entries = builder::build_index(&mbox_path, true, None).unwrap();
+        let candidates: Vec<usize> = (0..entries.len()).collect();
+        let index = InvertedIndex::build(&mbox_path, &entries, &|_, _| true).unwrap();
+
+        for q in [
+            "body:especiales",
+            "body:\"caracteres especiales\"",
+            "-body:especiales",
+            "perspective",
+            "perspective OR Meeting",
+            "-perspective",
+            "body:perspective OR subject:\"Meeting tomorrow\" from:user5",
+            "body:zzzznotpresent",
+            "filename:pdf",
+            "-filename:pdf",
+            "hello world",
+            "body:...",
+            "(body:perspective OR body:especiales) -subject:meeting",
+            "-(perspective OR especiales)",
+            "date:2024 OR body:especiales",
+            r"body:/caracteres\s+ESPECIALES/i",
+            r"body:/caracteres\s+ESPECIALES/",
+            "/perspect(ive|ion)/ -subject:/^Re:/",
+            "filename:/\\.PDF$/i",
+            "header:Message-ID:msg002",
+            "header:in-reply-to:\"<msg001@example.com>\"",
+            "header:References:",
+            "-header:cc:user3 header:content-type:/charset=UTF-8/",
+            "header:From:garcíka",
+            "header:From:\"José Garcíka <\"",
+            "header:From:garcika",
+            "body:ESPECÍALES",
+            "~especilaes",
+            "body:~perspectve -~tricky~0",
+            "~\"perspectve usr\"",
+            "filename:~pfd",
+            "header:subject:~wrold",
+            "header:from:~\"jose garcia\"",
+        ] {
+            let query = parse_query(q);
+            let scanned =
+                search_fulltext(&mbox_path, &entries, &candidates, &query, &|_, _| true).unwrap();
+            let indexed = search_indexed(
+                &mbox_path,
+                &entries,
+                &candidates,
+                &query,
+                &index,
+                &|_, _| true,
+            )
+            .unwrap();
+            assert_eq!(indexed, scanned, "query {q}");
+        }
+    }
+
+    #[test]
+    fn test_attachment_text_search() {
+        let (_dir, mbox_path) = temp_fixture("cloud_infra.mbox");
+        let entries = builder::build_index(&mbox_path, true, None).unwrap();
+        let candidates: Vec<usize> = (0..entries.len()).collect();
+        let index = InvertedIndex::build(&mbox_path, &entries, &|_, _| true).unwrap();
+
+        // Only the budget CSV attached to one message has an "Importe_EUR"
+        // column; it lists "Interconexión con Espanix", which other
+        // messages mention in their bodies.
+        for (q, expected) in [
+            ("attachment:importe", 1),
+            ("importe", 0),
+            ("body:importe", 0),
+            ("attachment:\"interconexion con espanix\"", 1),
+            (r"attachment:/Espanix,\d+/", 1),
+            ("attachment:~inporte", 1),
+            ("-attachment:importe", entries.len() - 1),
+            ("attachment:importe OR subject:zzzznotpresent", 1),
+        ] {
+            let query = parse_query(q);
+            let scanned =
+                search_fulltext(&mbox_path, &entries, &candidates, &query, &|_, _| true).unwrap();
+            assert_eq!(scanned.len(), expected, "query {q}");
+            if expected == 1 {
+                assert!(entries[scanned[0]].subject.starts_with("Presupuesto"));
+            }
+            let indexed = search_indexed(
+                &mbox_path,
+                &entries,
+                &candidates,
+                &query,
+                &index,
+                &|_, _| true,
+            )
+            .unwrap();
+            assert_eq!(indexed, scanned, "query {q}");
+        }
+    }
+
     #[test]
     fn test_fulltext_cancellation() {
-        let mbox_path = fixture("simple.mbox");
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
         let entries = builder::build_index(&mbox_path, true, None).unwrap();
         let candidates: Vec<usize> = (0..entries.len()).collect();
 
@@ -180,7 +419,7 @@ mod tests {
 
     #[test]
     fn test_fulltext_no_body_terms_passes_all() {
-        let mbox_path = fixture("simple.mbox");
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
         let entries = builder::build_index(&mbox_path, true, None).unwrap();
         let candidates: Vec<usize> = (0..entries.len()).collect();
 
@@ -0,0 +1,385 @@
+//! Persistent inverted index of message text.
+//!
+//! Built by `mboxshell index --fulltext` and stored next to the index (see
+//! [`crate::index::builder::write_fulltext`]). It maps every token of the decoded body
+//! text, every attachment filename and every token of every decoded header
+//! to the messages holding it, so `body:`, `filename:` and free-text queries
+//! are answered without reading each message from disk.
+//!
+//! Search semantics are those of the scan — an accent- and case-insensitive
+//! substring match — so the index answers a lookup in one of three ways
+//! (see [`Lookup`]). A token is a maximal run of alphanumeric characters of
+//! the folded text (see [`super::normalize`]), so a needle made only of
+//! alphanumerics occurs in a text exactly when it occurs inside one of its
+//! tokens: the index answers that exactly, and fuzzy words too. A needle with punctuation or spaces in it (`"two words"`,
+//! `a.b@c`) is narrowed down to the messages holding each of its pieces,
+//! which are then read to be sure.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::model::mail::MailEntry;
+use crate::parser::header;
+use crate::store::reader::MboxStore;
+
+use super::normalize::{self, fold};
+use super::query::SearchOperator;
+
+/// Inverted index over the messages of one mailbox, in index order.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InvertedIndex {
+    /// Number of messages indexed.
+    message_count: u64,
+    /// Tokens of the decoded body text, as the scan searches it.
+    body: Vocabulary,
+    /// Whole folded attachment filenames.
+    filenames: Vocabulary,
+    /// `name:token` for every token of every decoded header value.
+    headers: Vocabulary,
+    /// Messages that could not be read when the index was built. Lookups
+    /// never decide them, so the search reads (and skips) them as the scan
+    /// does.
+    unreadable: Vec<u32>,
+}
+
+/// Sorted terms with the messages holding each.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Vocabulary {
+    terms: Vec<String>,
+    /// Per term, ascending message numbers as varint-encoded deltas.
+    postings: Vec<Vec<u8>>,
+}
+
+/// What the index can tell about the messages matching a term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup {
+    /// Exactly these messages match (sorted).
+    Exact(Vec<u32>),
+    /// Only these messages can match (sorted); each still has to be read.
+    Candidates(Vec<u32>),
+    /// The index cannot narrow the term down.
+    Unknown,
+}
+
+impl Lookup {
+    /// Whether message `n` matches: `None` when it has to be read to know.
+    pub fn decide(&self, n: u32) -> Option<bool> {
+        match self {
+            Self::Exact(set) => Some(set.binary_search(&n).is_ok()),
+            Self::Candidates(set) if set.binary_search(&n).is_err() => Some(false),
+            Self::Candidates(_) | Self::Unknown => None,
+        }
+    }
+}
+
+impl InvertedIndex {
+    /// Index the messages of `entries`, read from the mailbox at `mbox_path`.
+    ///
+    /// `progress` receives `(processed, total)` messages and returns `false`
+    /// to cancel, in which case [`crate::error::MboxError::Cancelled`] is
+    /// returned.
+    pub fn build(
+        mbox_path: &Path,
+        entries: &[MailEntry],
+        progress: &dyn Fn(usize, usize) -> bool,
+    ) -> crate::error::Result<Self> {
+        let mut store = MboxStore::open(mbox_path)?;
+        let mut body = VocabularyBuilder::default();
+        let mut filenames = VocabularyBuilder::default();
+        let mut headers = VocabularyBuilder::default();
+        let mut unreadable = Vec::new();
+        let total = entries.len();
+
+        for (i, entry) in entries.iter().enumerate() {
+            if !progress(i, total) {
+                return Err(crate::error::MboxError::Cancelled);
+            }
+            let n = message_number(i);
+            let message = match store.get_message(entry) {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!(offset = entry.offset, error = %e, "Skipping message in full-text index");
+                    unreadable.push(n);
+                    continue;
+                }
+            };
+
+            let text = fold(message.text.as_deref().unwrap_or(""));
+            body.add(n, tokens(&text).map(str::to_string));
+            filenames.add(n, message.attachments.iter().map(|a| fold(&a.filename)));
+            let mut header_terms = Vec::new();
+            for (name, value) in header::unfold_headers(&message.raw_headers) {
+                let value = fold(&header::decode_encoded_words(&value));
+                header_terms.extend(tokens(&value).map(|t| format!("{name}:{t}")));
+            }
+            headers.add(n, header_terms);
+        }
+        let _ = progress(total, total);
+
+        Ok(Self {
+            message_count: total as u64,
+            body: body.finish(),
+            filenames: filenames.finish(),
+            headers: headers.finish(),
+            unreadable,
+        })
+    }
+
+    /// Number of messages indexed.
+    pub fn message_count(&self) -> u64 {
+        self.message_count
+    }
+
+    /// Number of distinct body tokens.
+    pub fn body_term_count(&self) -> usize {
+        self.body.terms.len()
+    }
+
+    /// Whether message `n` could not be read when the index was built.
+    pub fn is_unreadable(&self, n: u32) -> bool {
+        self.unreadable.binary_search(&n).is_ok()
+    }
+
+    /// Messages whose body text contains `needle` (folded).
+    pub fn body_lookup(&self, needle: &str) -> Lookup {
+        text_lookup(&self.body, needle, |term, piece| term.contains(piece))
+    }
+
+    /// Messages whose body text has words near every word of the fuzzy
+    /// `term`, with the scan's `~` semantics.
+    pub fn body_fuzzy_lookup(&self, term: &str, max_distance: usize) -> Lookup {
+        fuzzy_lookup(&self.body, term, max_distance, |t| Some(t))
+    }
+
+    /// Messages with an attachment whose filename matches `op`, with the
+    /// scan's `filename:` semantics. A regular expression may be
+    /// case-sensitive, and filenames are stored folded, so it is left to the
+    /// scan.
+    pub fn filename_lookup(&self, op: &SearchOperator) -> Lookup {
+        Lookup::Exact(match op {
+            SearchOperator::Contains(needle) => self.filenames.union(|f| f.contains(needle)),
+            SearchOperator::Exact(name) => self.filenames.union(|f| f == name),
+            SearchOperator::Fuzzy { term, max_distance } => self
+                .filenames
+                .union(|f| normalize::fuzzy_matches(f, term, *max_distance)),
+            SearchOperator::Regex(_) => return Lookup::Unknown,
+        })
+    }
+
+    /// Messages whose decoded `name` header (lowercase) contains `needle`
+    /// (folded) in any of its occurrences.
+    pub fn header_lookup(&self, name: &str, needle: &str) -> Lookup {
+        let prefix = format!("{name}:");
+        text_lookup(&self.headers, needle, |term, piece| {
+            term.strip_prefix(&prefix)
+                .is_some_and(|token| token.contains(piece))
+        })
+    }
+
+    /// Messages whose decoded `name` header has words near every word of
+    /// the fuzzy `term`. The index does not tell the occurrences of a header
+    /// apart, so a term of several words only narrows the messages down.
+    pub fn header_fuzzy_lookup(&self, name: &str, term: &str, max_distance: usize) -> Lookup {
+        let prefix = format!("{name}:");
+        match fuzzy_lookup(&self.headers, term, max_distance, |t| {
+            t.strip_prefix(&prefix)
+        }) {
+            Lookup::Exact(messages) if normalize::words(term).nth(1).is_some() => {
+                Lookup::Candidates(messages)
+            }
+            lookup => lookup,
+        }
+    }
+}
+
+/// The messages holding, for every word of the fuzzy `term`, a token near it;
+/// `token` extracts the token from a vocabulary term, `None` to skip it.
+fn fuzzy_lookup(
+    vocabulary: &Vocabulary,
+    term: &str,
+    max_distance: usize,
+    token: impl Fn(&str) -> Option<&str>,
+) -> Lookup {
+    let mut words = normalize::words(term).map(|(_, w)| w);
+    let Some(first) = words.next() else {
+        return Lookup::Unknown;
+    };
+    let near = |wanted: &str| {
+        vocabulary.union(|t| token(t).is_some_and(|t| normalize::is_near(t, wanted, max_distance)))
+    };
+    let mut messages = near(first);
+    for wanted in words {
+        let holding = near(wanted);
+        messages.retain(|n| holding.binary_search(n).is_ok());
+    }
+    Lookup::Exact(messages)
+}
+
+/// The [`Lookup`] of a substring `needle` over a vocabulary of tokens, where
+/// `matches(term, piece)` says whether a term holds an alphanumeric piece.
+fn text_lookup(
+    vocabulary: &Vocabulary,
+    needle: &str,
+    matches: impl Fn(&str, &str) -> bool,
+) -> Lookup {
+    let pieces: Vec<&str> = tokens(needle).collect();
+    if pieces.is_empty() {
+        return Lookup::Unknown;
+    }
+    if pieces.len() == 1 && pieces[0].len() == needle.len() {
+        return Lookup::Exact(vocabulary.union(|t| matches(t, pieces[0])));
+    }
+    let mut candidates = vocabulary.union(|t| matches(t, pieces[0]));
+    for piece in &pieces[1..] {
+        let holding = vocabulary.union(|t| matches(t, piece));
+        candidates.retain(|n| holding.binary_search(n).is_ok());
+    }
+    Lookup::Candidates(candidates)
+}
+
+/// The alphanumeric runs of `text`.
+fn tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+}
+
+/// Message number of the entry at position `i`.
+fn message_number(i: usize) -> u32 {
+    u32::try_from(i).unwrap_or(u32::MAX)
+}
+
+impl Vocabulary {
+    /// Sorted, deduplicated messages holding any term `keep` accepts.
+    fn union(&self, keep: impl Fn(&str) -> bool) -> Vec<u32> {
+        let mut messages: Vec<u32> = self
+            .terms
+            .iter()
+            .zip(&self.postings)
+            .filter(|(term, _)| keep(term))
+            .flat_map(|(_, postings)| decode_postings(postings))
+            .collect();
+        messages.sort_unstable();
+        messages.dedup();
+        messages
+    }
+}
+
+/// Accumulates a [`Vocabulary`] message by message, in ascending order.
+#[derive(Default)]
+struct VocabularyBuilder {
+    terms: HashMap<String, (u32, Vec<u8>)>,
+}
+
+impl VocabularyBuilder {
+    /// Record that message `n` holds `terms`; `n` must not be lower than any
+    /// message added before.
+    fn add(&mut self, n: u32, terms: impl IntoIterator<Item = String>) {
+        let unique: HashSet<String> = terms.into_iter().collect();
+        for term in unique {
+            let (last, postings) = self.terms.entry(term).or_default();
+            // The first delta is the message number itself.
+            let delta = if postings.is_empty() { n } else { n - *last };
+            push_varint(postings, delta);
+            *last = n;
+        }
+    }
+
+    fn finish(self) -> Vocabulary {
+        let mut terms: Vec<(String, Vec<u8>)> = self
+            .terms
+            .into_iter()
+            .map(|(term, (_, postings))| (term, postings))
+            .collect();
+        terms.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let (terms, postings) = terms.into_iter().unzip();
+        Vocabulary { terms, postings }
+    }
+}
+
+fn push_varint(buf: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Decode a postings list written by [`VocabularyBuilder::add`].
+fn decode_postings(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    let mut pos = 0;
+    let mut current: u32 = 0;
+    std::iter::from_fn(move || {
+        if pos >= bytes.len() {
+            return None;
+        }
+        let mut delta: u32 = 0;
+        let mut shift = 0;
+        while let Some(&byte) = bytes.get(pos) {
+            pos += 1;
+            delta |= u32::from(byte & 0x7F).checked_shl(shift).unwrap_or(0);
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        current = current.wrapping_add(delta);
+        Some(current)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::builder;
+    use crate::testing::temp_fixture;
+
+    #[test]
+    fn test_postings_roundtrip() {
+        let mut vocabulary = VocabularyBuilder::default();
+        let messages = [0u32, 1, 127, 128, 300, 70_000, 5_000_000];
+        for &n in &messages {
+            vocabulary.add(n, ["word".to_string(), "word".to_string()]);
+        }
+        let vocabulary = vocabulary.finish();
+        assert_eq!(vocabulary.union(|t| t == "word"), messages);
+    }
+
+    #[test]
+    fn test_lookups_over_fixture() {
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
+        let entries = builder::build_index(&mbox_path, true, None).unwrap();
+        let index = InvertedIndex::build(&mbox_path, &entries, &|_, _| true).unwrap();
+        assert_eq!(index.message_count(), entries.len() as u64);
+
+        // A single word, or part of one, is answered exactly.
+        let Lookup::Exact(hits) = index.body_lookup("especiales") else {
+            panic!("single word lookups are exact");
+        };
+        assert!(!hits.is_empty());
+        assert_eq!(index.body_lookup("speciale"), Lookup::Exact(hits.clone()));
+        assert_eq!(
+            index.body_lookup("zzzznotpresent"),
+            Lookup::Exact(Vec::new())
+        );
+
+        // A phrase only narrows the candidates down.
+        assert!(matches!(
+            index.body_lookup("caracteres especiales"),
+            Lookup::Candidates(c) if c == hits
+        ));
+        assert_eq!(index.body_lookup(" ... "), Lookup::Unknown);
+
+        // Headers are indexed per name.
+        let Lookup::Exact(hello) = index.header_lookup("subject", "hello") else {
+            panic!("single word lookups are exact");
+        };
+        assert!(!hello.is_empty());
+        assert_eq!(
+            index.header_lookup("from", "hello"),
+            Lookup::Exact(Vec::new())
+        );
+    }
+}
@@ -5,16 +5,19 @@
 
 use chrono::Datelike;
 
+use crate::model::address::EmailAddress;
 use crate::model::mail::MailEntry;
 
+use super::normalize::{self, fold};
 use super::query::{
-    DateFilter, SearchField, SearchOperator, SearchQuery, SearchTerm, SizeFilter, TermGroup,
+    self, Condition, DateFilter, MessageStatus, QueryTimezone, SearchField, SearchOperator,
+    SearchQuery, SearchTerm, SizeFilter,
 };
 
 /// Search the index metadata and return matching entry indices.
 ///
-/// Applies date/size/attachment filters first (cheapest), then text terms.
-/// Uses short-circuit evaluation for AND queries.
+/// The query expression is evaluated per entry with short-circuiting, so a
+/// failed date/size/attachment filter under an `AND` skips the text terms.
 ///
 /// `All` (free-text) terms are matched against metadata only (subject, from,
 /// to). To let them also match the message body, use
@@ -28,14 +31,15 @@ pub fn search_metadata(entries: &[MailEntry], query: &SearchQuery) -> Vec<usize>
         .collect()
 }
 
-/// Like [`search_metadata`], but groups that need the message body are left
-/// undecided here — [`super::fulltext`] settles them once it has read the
-/// body. Groups that can be decided from metadata alone, and the
-/// date/size/attachment filters, still apply, so this narrows the candidate
-/// set as much as metadata allows.
+/// Like [`search_metadata`], but conditions that need the message body are
+/// left undecided here — [`super::fulltext`] settles them once it has read
+/// the body. An entry is kept unless the conditions metadata can decide
+/// already rule it out, so this narrows the candidate set as much as
+/// metadata allows.
 ///
-/// A group needs the body when any of its terms is `body:`, `filename:`, or a
-/// free-text term (which matches metadata *or* body).
+/// A condition needs the body when it is a `body:` or `filename:` term, or a
+/// free-text term (which matches metadata *or* body) whose needle is not in
+/// the metadata.
 pub fn search_metadata_candidates(entries: &[MailEntry], query: &SearchQuery) -> Vec<usize> {
     entries
         .iter()
@@ -47,92 +51,86 @@ pub fn search_metadata_candidates(entries: &[MailEntry], query: &SearchQuery) ->
 
 /// Check whether a single entry matches the query.
 ///
-/// When `defer_body` is set, groups that need the message body are skipped —
-/// the caller is expected to settle them in the full-text pass.
+/// When `defer_body` is set, an entry that only the body could settle is
+/// kept — the caller is expected to settle it in the full-text pass.
 fn entry_matches(entry: &MailEntry, query: &SearchQuery, defer_body: bool) -> bool {
-    // 1. Date filters (cheapest to check), all of which must hold
-    if !query.date_filters.iter().all(|df| matches_date(entry, df)) {
-        return false;
-    }
-
-    // 2. Size filters, all of which must hold
-    if !query.size_filters.iter().all(|sf| matches_size(entry, sf)) {
-        return false;
-    }
-
-    // 3. Attachment filter
-    if let Some(want_att) = query.has_attachment {
-        if entry.has_attachments != want_att {
-            return false;
-        }
-    }
-
-    // 4. Term groups: AND between groups, OR inside each one.
-    //
-    //    A group that needs the body is either skipped (the full-text pass
-    //    will settle it) or, when there is no full-text pass, evaluated on
-    //    metadata alone — which is what its body:/filename: terms already did
-    //    before this existed.
-    query
-        .groups
-        .iter()
-        .all(|group| group_matches(entry, group, defer_body))
-}
-
-/// Whether an entry satisfies one OR-group.
-fn group_matches(entry: &MailEntry, group: &TermGroup, defer_body: bool) -> bool {
-    if defer_body && group.needs_body() {
+    let Some(expr) = &query.expr else {
         return true;
-    }
-    group
-        .terms
-        .iter()
-        .any(|term| term_matches_entry(entry, term))
+    };
+    let verdict = expr.evaluate(&mut |condition| match metadata_verdict(entry, condition) {
+        None if !defer_body => {
+            // No full-text pass: a free-text term is judged on metadata
+            // alone, and `body:`/`filename:`/`attachment:`/`header:` terms
+            // count as a match so metadata never rejects over what only the
+            // message could settle.
+            Some(!matches!(
+                condition,
+                Condition::Term(SearchTerm {
+                    field: SearchField::All,
+                    ..
+                })
+            ))
+        }
+        verdict => verdict,
+    });
+    verdict != Some(false)
 }
 
-/// Check if a text term matches an entry's metadata.
-///
-/// `Body` and `Filename` terms cannot be judged here and count as a match, so
-/// a metadata pass never rejects an entry over something only the body could
-/// settle. The full-text pass evaluates those for real.
+/// Decide a condition from an entry's metadata, or `None` when only the
+/// message body can settle it.
 ///
-/// Shared with [`super::fulltext`]: a group deferred to the body pass can hold
-/// metadata terms too, and those must be judged the same way in both passes.
-pub(crate) fn term_matches_entry(entry: &MailEntry, term: &SearchTerm) -> bool {
-    let raw_match = match term.field {
-        SearchField::All => all_matches_metadata(entry, &term.operator),
-        SearchField::From => {
-            matches_text(&entry.from.address, &term.operator)
-                || matches_text(&entry.from.display_name, &term.operator)
+/// Shared with [`super::fulltext`], which evaluates the same expression and
+/// only supplies the body side of the terms this leaves open.
+pub(crate) fn metadata_verdict(entry: &MailEntry, condition: &Condition) -> Option<bool> {
+    let term = match condition {
+        Condition::Date(filter) => return Some(matches_date(entry, filter, query::timezone())),
+        Condition::Size(filter) => return Some(compare(entry.length, filter)),
+        Condition::AttachmentSize(filter) => {
+            return Some(entry.attachments.iter().any(|a| compare(a.size, filter)))
+        }
+        Condition::AttachmentCount(filter) => {
+            return Some(compare(entry.attachments.len() as u64, filter))
         }
-        SearchField::To => entry.to.iter().any(|a| {
-            matches_text(&a.address, &term.operator)
-                || matches_text(&a.display_name, &term.operator)
-        }),
-        SearchField::Cc => entry.cc.iter().any(|a| {
-            matches_text(&a.address, &term.operator)
-                || matches_text(&a.display_name, &term.operator)
-        }),
-        SearchField::Subject => matches_text(&entry.subject, &term.operator),
-        SearchField::Label => entry.labels.iter().any(|l| matches_text(l, &term.operator)),
-        SearchField::MessageId => matches_text(&entry.message_id, &term.operator),
-        // Body and Filename are handled by fulltext search, not metadata
-        SearchField::Body | SearchField::Filename => true,
+        Condition::HasAttachment(want) => return Some(entry.has_attachments == *want),
+        Condition::Is(MessageStatus::Read) => return Some(entry.flags.seen),
+        Condition::Is(MessageStatus::Unread) => return Some(!entry.flags.seen),
+        Condition::Is(MessageStatus::Starred) => return Some(entry.flags.flagged),
+        Condition::Term(term) => term,
     };
-
-    if term.negated {
-        !raw_match
-    } else {
-        raw_match
+    let op = &term.operator;
+    match term.field {
+        // Matches metadata *or* body: a metadata hit settles it.
+        SearchField::All => all_matches_metadata(entry, op).then_some(true),
+        // The subject is kept decoded, exactly as `header:` sees it; other
+        // headers have to be read from the message.
+        SearchField::Header(ref name) if name == "subject" => {
+            Some(matches_text(&entry.subject, op))
+        }
+        SearchField::Body
+        | SearchField::Filename
+        | SearchField::Attachment
+        | SearchField::Header(_) => None,
+        SearchField::From => Some(matches_address(&entry.from, op)),
+        SearchField::To => Some(entry.to.iter().any(|a| matches_address(a, op))),
+        SearchField::Cc => Some(entry.cc.iter().any(|a| matches_address(a, op))),
+        SearchField::Bcc => Some(entry.bcc.iter().any(|a| matches_address(a, op))),
+        SearchField::ReplyTo => Some(entry.reply_to.iter().any(|a| matches_address(a, op))),
+        SearchField::DeliveredTo => Some(entry.delivered_to.iter().any(|a| matches_address(a, op))),
+        SearchField::Participant => Some(entry.participants().any(|a| matches_address(a, op))),
+        SearchField::Subject => Some(matches_text(&entry.subject, op)),
+        SearchField::Label => Some(entry.labels.iter().any(|l| matches_text(l, op))),
+        SearchField::Tag => Some(entry.tags.iter().any(|t| matches_text(t, op))),
+        SearchField::MessageId => Some(matches_text(&entry.message_id, op)),
+        SearchField::AttachmentType => Some(entry.attachments.iter().any(|a| {
+            matches_text(&a.content_type, op)
+                || a.extension().is_some_and(|ext| matches_text(&ext, op))
+        })),
     }
 }
 
 /// Whether a free-text (`All`) operator matches an entry's metadata, i.e. its
 /// subject, sender, or any recipient (address or display name).
-///
-/// Shared with [`super::fulltext`] so the full-text pass can apply the same
-/// "search everywhere" semantics to `All` terms (metadata **or** body).
-pub(crate) fn all_matches_metadata(entry: &MailEntry, op: &SearchOperator) -> bool {
+fn all_matches_metadata(entry: &MailEntry, op: &SearchOperator) -> bool {
     matches_text(&entry.subject, op)
         || matches_text(&entry.from.address, op)
         || matches_text(&entry.from.display_name, op)
@@ -142,27 +140,39 @@ pub(crate) fn all_matches_metadata(entry: &MailEntry, op: &SearchOperator) -> bo
             .any(|a| matches_text(&a.address, op) || matches_text(&a.display_name, op))
 }
 
-/// Case-insensitive text matching.
+/// Whether an address or its display name matches.
+fn matches_address(address: &EmailAddress, op: &SearchOperator) -> bool {
+    matches_text(&address.address, op) || matches_text(&address.display_name, op)
+}
+
+/// Accent- and case-insensitive text matching.
 ///
 /// Both `Contains` and `Exact` use substring matching. The distinction is
 /// purely lexical: `Exact` originates from a quoted phrase (so the entire
 /// phrase, spaces included, is treated as a single needle), while
 /// `Contains` is a single bareword. This mirrors the semantics of the
 /// fulltext search and matches what users expect from search engines.
+/// `Fuzzy` looks for words of the haystack near its own. `Regex` matches
+/// anywhere in the haystack, with the case sensitivity of its flags.
 pub(crate) fn matches_text(haystack: &str, op: &SearchOperator) -> bool {
-    let needle = match op {
-        SearchOperator::Contains(s) | SearchOperator::Exact(s) => s.as_str(),
-    };
-    contains_lower(haystack, needle)
+    match op {
+        SearchOperator::Contains(needle) | SearchOperator::Exact(needle) => {
+            contains_folded(haystack, needle)
+        }
+        SearchOperator::Fuzzy { term, max_distance } => {
+            normalize::fuzzy_matches(&fold(haystack), term, *max_distance)
+        }
+        SearchOperator::Regex(pattern) => pattern.is_match(haystack),
+    }
 }
 
-/// Case-insensitive substring check. The needle must already be lowercase
-/// (the query parser guarantees it).
+/// Accent- and case-insensitive substring check. The needle must already be
+/// folded (the query parser guarantees it).
 ///
 /// This runs once per term per entry across the whole index, so the common
 /// all-ASCII case is matched byte-wise without allocating; only haystacks
-/// with non-ASCII content pay for a `to_lowercase()`.
-fn contains_lower(haystack: &str, needle: &str) -> bool {
+/// with non-ASCII content pay for folding.
+fn contains_folded(haystack: &str, needle: &str) -> bool {
     if needle.is_empty() {
         return true;
     }
@@ -171,13 +181,14 @@ fn contains_lower(haystack: &str, needle: &str) -> bool {
         let n = needle.as_bytes();
         n.len() <= h.len() && h.windows(n.len()).any(|w| w.eq_ignore_ascii_case(n))
     } else {
-        haystack.to_lowercase().contains(needle)
+        fold(haystack).contains(needle)
     }
 }
 
-/// Check if entry's date matches the date filter.
-fn matches_date(entry: &MailEntry, filter: &DateFilter) -> bool {
-    let date = entry.date.date_naive();
+/// Check if entry's date matches the date filter, taking its calendar day
+/// in `timezone`.
+fn matches_date(entry: &MailEntry, filter: &DateFilter, timezone: QueryTimezone) -> bool {
+    let date = timezone.date_of(entry.date);
     match filter {
         DateFilter::Exact(d) => date == *d,
         DateFilter::Range(start, end) => date >= *start && date <= *end,
@@ -188,27 +199,35 @@ fn matches_date(entry: &MailEntry, filter: &DateFilter) -> bool {
         DateFilter::After(d) => date >= *d,
         DateFilter::Month(year, month) => date.year() == *year && date.month() == *month,
         DateFilter::Year(year) => date.year() == *year,
+        // Relative filters are resolved to instants, so they compare the
+        // exact time rather than the calendar day.
+        DateFilter::Newer(since) => entry.date >= *since,
+        DateFilter::Older(until) => entry.date < *until,
+        DateFilter::Within(start, end) => entry.date >= *start && entry.date < *end,
     }
 }
 
-/// Check if entry's size matches the size filter.
-fn matches_size(entry: &MailEntry, filter: &SizeFilter) -> bool {
+/// Check a size (message or attachment) or a count against a filter.
+fn compare(value: u64, filter: &SizeFilter) -> bool {
     match filter {
-        SizeFilter::GreaterThan(threshold) => entry.length > *threshold,
-        SizeFilter::LessThan(threshold) => entry.length < *threshold,
+        SizeFilter::GreaterThan(threshold) => value > *threshold,
+        SizeFilter::LessThan(threshold) => value < *threshold,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::address::EmailAddress;
+    use crate::model::attachment::AttachmentSummary;
     use crate::search::query::parse_query;
     use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
 
+    /// A message sent at 10:00 in the query timezone on `date_str`, so date
+    /// filters find it on that day wherever the tests run.
     fn make_entry(from: &str, subject: &str, date_str: &str) -> MailEntry {
         let date = chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
-            .map(|d| Utc.from_utc_datetime(&d.and_hms_opt(10, 0, 0).unwrap()))
+            .map(|d| query::timezone().to_utc(d.and_hms_opt(10, 0, 0).unwrap()))
             .unwrap_or(Utc::now());
         MailEntry {
             offset: 0,
@@ -218,21 +237,29 @@ mod tests {
                 display_name: String::new(),
                 address: from.to_string(),
             },
-            to: vec![EmailAddress {
+            to: vec![Arc::new(EmailAddress {
                 display_name: String::new(),
                 address: "recipient@example.com".to_string(),
-            }],
+            })],
             cc: vec![],
+            bcc: vec![],
+            reply_to: vec![],
+            sender: None,
+            delivered_to: vec![],
             subject: subject.to_string(),
             message_id: format!("<msg-{subject}@example.com>"),
             in_reply_to: None,
             references: vec![],
             has_attachments: false,
+            attachments: Vec::new(),
             content_type: "text/plain".to_string(),
             text_size: 500,
             labels: vec![],
+            tags: vec![],
             thread_id: None,
             sequence: 0,
+            file: None,
+            flags: Default::default(),
         }
     }
 
@@ -298,7 +325,7 @@ mod tests {
 
     #[test]
     fn test_or_group_combines_with_filters() {
-        // Filters are AND-ed on top of the groups, never OR-ed into them.
+        // `OR` binds tighter than the implicit `AND`, filters included.
         let entries = vec![
             make_entry("alice@example.com", "One", "2024-01-15"),
             make_entry("bob@example.com", "Two", "2023-01-16"),
@@ -320,6 +347,163 @@ mod tests {
         assert_eq!(search_metadata(&entries, &q), vec![0, 1]);
     }
 
+    #[test]
+    fn test_parentheses_group_an_or_inside_an_and() {
+        let entries = vec![
+            make_entry("alice@example.com", "Budget", "2024-01-15"),
+            make_entry("bob@example.com", "Holiday", "2024-01-16"),
+            make_entry("bob@example.com", "Budget", "2024-01-17"),
+        ];
+        // Without the parentheses `OR` would bind `alice` and `bob` only.
+        let q = parse_query("from:alice OR (from:bob subject:budget)");
+        assert_eq!(search_metadata(&entries, &q), vec![0, 2]);
+        let q = parse_query("-(from:alice OR subject:holiday)");
+        assert_eq!(search_metadata(&entries, &q), vec![2]);
+    }
+
+    #[test]
+    fn test_attachment_filters() {
+        let attachment = |filename: &str, content_type: &str, size| AttachmentSummary {
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            size,
+        };
+        let mut entries = vec![
+            make_entry("alice@example.com", "Scan", "2024-01-15"),
+            make_entry("bob@example.com", "Photos", "2024-01-16"),
+            make_entry("carol@example.com", "Nothing", "2024-01-17"),
+        ];
+        entries[0].attachments = vec![attachment("scan.pdf", "application/pdf", 6 << 20)];
+        entries[1].attachments = vec![
+            attachment("a.jpg", "image/jpeg", 100_000),
+            attachment("b.jpg", "image/jpeg", 200_000),
+            attachment("report.docx", "application/octet-stream", 30_000),
+            attachment("notes", "text/plain", 500),
+        ];
+
+        let search = |q: &str| search_metadata(&entries, &parse_query(q));
+        assert_eq!(search("attachment-type:pdf"), vec![0]);
+        assert_eq!(search("attachment-type:image"), vec![1]);
+        // The extension counts when the content type says nothing.
+        assert_eq!(search("attachment-type:docx"), vec![1]);
+        assert_eq!(search("attachment-type:/^image\\//"), vec![1]);
+        assert_eq!(search("attachment-size:>5mb"), vec![0]);
+        assert_eq!(search("attachment-size:<1kb"), vec![1]);
+        assert_eq!(search("attachments:>3"), vec![1]);
+        assert_eq!(search("attachments:<1"), vec![2]);
+        // Type and size are checked attachment by attachment in each filter,
+        // not together: some attachment is a PDF, some is over 100 KB.
+        assert_eq!(
+            search("attachment-type:pdf attachment-size:>100kb"),
+            vec![0]
+        );
+        assert_eq!(search("-attachment-type:jpeg attachments:>0"), vec![0]);
+    }
+
+    #[test]
+    fn test_recipient_fields() {
+        let address = |a: &str| Arc::new(EmailAddress::parse(a));
+        let mut entries = vec![
+            make_entry("alice@example.com", "Plan", "2024-01-15"),
+            make_entry("bob@example.com", "Report", "2024-01-16"),
+        ];
+        entries[0].bcc = vec![address("boss@example.com")];
+        entries[0].delivered_to = vec![address("me+work@example.com")];
+        entries[1].reply_to = vec![address("list@lists.example.com")];
+        entries[1].sender = Some(address("bounces@lists.example.com"));
+        entries[1].to = (0..50)
+            .map(|i| address(&format!("user{i}@example.com")))
+            .collect();
+
+        let search = |q: &str| search_metadata(&entries, &parse_query(q));
+        assert_eq!(search("bcc:boss"), vec![0]);
+        assert_eq!(search("delivered-to:me+work"), vec![0]);
+        assert_eq!(search("reply-to:lists.example.com"), vec![1]);
+        // Recipients past the fifth are searchable.
+        assert_eq!(search("to:user49"), vec![1]);
+        assert_eq!(search("participant:alice"), vec![0]);
+        assert_eq!(search("participant:bounces"), vec![1]);
+        assert_eq!(search("participant:boss"), vec![0]);
+        assert_eq!(search("participant:nobody"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_state_filters() {
+        let mut entries = vec![
+            make_entry("alice@example.com", "One", "2024-01-15"),
+            make_entry("bob@example.com", "Two", "2024-01-16"),
+        ];
+        entries[0].flags.seen = true;
+        entries[1].flags.flagged = true;
+        entries[1].tags = vec!["Review".to_string()];
+
+        let search = |q: &str| search_metadata(&entries, &parse_query(q));
+        assert_eq!(search("is:read"), vec![0]);
+        assert_eq!(search("is:unread"), vec![1]);
+        assert_eq!(search("is:starred"), vec![1]);
+        assert_eq!(search("tag:review"), vec![1]);
+        assert_eq!(search("-tag:review is:read"), vec![0]);
+    }
+
+    #[test]
+    fn test_read_state_comes_from_the_mailbox_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mail.mbox");
+        let message = |extra: &str, subject: &str| {
+            format!(
+                "From a@x Mon Jan 01 10:00:00 2024\nFrom: a@x\n{extra}\
+                 Date: Mon, 01 Jan 2024 10:00:00 +0000\nSubject: {subject}\n\nbody\n"
+            )
+        };
+        let mailbox = [
+            message("X-Gmail-Labels: Inbox,Important\n", "Takeout read"),
+            message("X-Gmail-Labels: Inbox,Unread,Starred\n", "Takeout unread"),
+            message("Status: RO\n", "Client read"),
+            message("Status: O\nX-Status: F\n", "Client unread"),
+            message("", "No state"),
+        ];
+        std::fs::write(&path, mailbox.join("\n")).unwrap();
+        let entries = crate::index::builder::build_index(&path, false, None).unwrap();
+
+        let search = |q: &str| search_metadata(&entries, &parse_query(q));
+        assert_eq!(search("is:read"), vec![0, 2, 4]);
+        assert_eq!(search("is:unread"), vec![1, 3]);
+        assert_eq!(search("is:starred"), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_filters_combine_with_or_and_not() {
+        let mut entries = vec![
+            make_entry("alice@example.com", "One", "2023-05-01"),
+            make_entry("bob@example.com", "Two", "2024-05-01"),
+            make_entry("carol@example.com", "Three", "2024-05-02"),
+        ];
+        entries[2].has_attachments = true;
+        let q = parse_query("date:2023 OR from:bob");
+        assert_eq!(search_metadata(&entries, &q), vec![0, 1]);
+        let q = parse_query("NOT date:2023 -has:attachment");
+        assert_eq!(search_metadata(&entries, &q), vec![1]);
+        let q = parse_query("(date:2023 OR has:attachment) size:<2000");
+        assert_eq!(search_metadata(&entries, &q), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_candidates_defer_only_what_the_body_can_change() {
+        let entries = vec![
+            make_entry("alice@example.com", "Budget", "2024-01-15"),
+            make_entry("bob@example.com", "Holiday", "2024-01-16"),
+        ];
+        // The subject settles the first entry; the second needs the body.
+        let q = parse_query("subject:budget OR body:budget");
+        assert_eq!(search_metadata_candidates(&entries, &q), vec![0, 1]);
+        // The date rules the first entry out whatever its body says.
+        let q = parse_query("body:budget date:2024-01-16");
+        assert_eq!(search_metadata_candidates(&entries, &q), vec![1]);
+        // A negated free-text hit in the metadata is settled without the body.
+        let q = parse_query("-budget");
+        assert_eq!(search_metadata_candidates(&entries, &q), vec![1]);
+    }
+
     #[test]
     fn test_after_is_inclusive_and_before_is_not() {
         // `after:X before:Y` is the half-open range [X, Y), so a full calendar
@@ -345,6 +529,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_relative_dates_compare_instants() {
+        use crate::search::query::{parse_query_at, QueryTimezone};
+
+        // Messages sent at 10:00 UTC.
+        let entries: Vec<MailEntry> = [(1, 1), (3, 25), (3, 31)]
+            .into_iter()
+            .map(|(month, day)| {
+                let mut entry = make_entry("a@example.com", "", "");
+                entry.date = Utc.with_ymd_and_hms(2024, month, day, 10, 0, 0).unwrap();
+                entry
+            })
+            .collect();
+        let now = Utc.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap();
+        let at = |q| parse_query_at(q, now, QueryTimezone::parse("utc").unwrap());
+        assert_eq!(search_metadata(&entries, &at("newer:7d")), vec![1, 2]);
+        assert_eq!(search_metadata(&entries, &at("older:1m")), vec![0]);
+        assert_eq!(search_metadata(&entries, &at("date:today")), vec![2]);
+        assert_eq!(
+            search_metadata(&entries, &at("date:this-year -date:today")),
+            vec![0, 1]
+        );
+        // At UTC-11 it is 01:00 on the 31st, and the last message was sent
+        // at 23:00 the day before.
+        let west = QueryTimezone::parse("-11:00").unwrap();
+        let today = parse_query_at("date:today", now, west);
+        assert!(search_metadata(&entries, &today).is_empty());
+        let yesterday = parse_query_at("date:yesterday", now, west);
+        assert_eq!(search_metadata(&entries, &yesterday), vec![2]);
+    }
+
     #[test]
     fn test_date_range_stays_inclusive_on_both_ends() {
         let entries = vec![
@@ -357,6 +572,33 @@ mod tests {
         assert_eq!(search_metadata(&entries, &q), vec![1, 2]);
     }
 
+    #[test]
+    fn test_absolute_dates_take_the_day_in_the_query_timezone() {
+        // 23:30 UTC on New Year's Eve is already 2024 at UTC+01:00.
+        let mut entry = make_entry("a@example.com", "Midnight", "2023-12-31");
+        entry.date = Utc.with_ymd_and_hms(2023, 12, 31, 23, 30, 0).unwrap();
+        let utc = QueryTimezone::parse("utc").unwrap();
+        let east = QueryTimezone::parse("+01:00").unwrap();
+        let day = |s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        let new_year = DateFilter::Exact(day("2024-01-01"));
+        assert!(!matches_date(&entry, &new_year, utc));
+        assert!(matches_date(&entry, &new_year, east));
+        let filters = [
+            DateFilter::Year(2024),
+            DateFilter::Month(2024, 1),
+            DateFilter::After(day("2024-01-01")),
+            DateFilter::Range(day("2024-01-01"), day("2024-01-31")),
+        ];
+        for filter in &filters {
+            assert!(!matches_date(&entry, filter, utc), "{filter:?}");
+            assert!(matches_date(&entry, filter, east), "{filter:?}");
+        }
+        let before = DateFilter::Before(day("2024-01-01"));
+        assert!(matches_date(&entry, &before, utc));
+        assert!(!matches_date(&entry, &before, east));
+    }
+
     #[test]
     fn test_negated_search() {
         let entries = vec![
@@ -449,17 +691,68 @@ mod tests {
     }
 
     #[test]
-    fn test_contains_lower_case_insensitive() {
+    fn test_regex_search_on_metadata() {
+        let entries = vec![
+            make_entry("billing@x.com", "Invoice INV-004211", "2024-01-15"),
+            make_entry("billing@x.com", "Invoice inv-12", "2024-01-16"),
+            make_entry("alice@x.com", "Lunch", "2024-01-17"),
+        ];
+        let q = parse_query(r"subject:/INV-\d{6}/");
+        assert_eq!(search_metadata(&entries, &q), vec![0]);
+        let q = parse_query(r"subject:/inv-\d+/i");
+        assert_eq!(search_metadata(&entries, &q), vec![0, 1]);
+        // Anchors apply to the whole field, and negation works as usual.
+        let q = parse_query(r"-from:/^billing@/");
+        assert_eq!(search_metadata(&entries, &q), vec![2]);
+        // A bare pattern searches subject, from and to.
+        let q = parse_query(r"/^alice/");
+        assert_eq!(search_metadata(&entries, &q), vec![2]);
+    }
+
+    #[test]
+    fn test_header_search_on_metadata() {
+        let entries = vec![
+            make_entry("alice@x.com", "Invoice", "2024-01-15"),
+            make_entry("bob@x.com", "Lunch", "2024-01-16"),
+        ];
+        // The subject is answered from the index.
+        let q = parse_query("header:Subject:invoice");
+        assert_eq!(search_metadata(&entries, &q), vec![0]);
+        assert_eq!(search_metadata_candidates(&entries, &q), vec![0]);
+        // Other headers are left to the message read.
+        let q = parse_query("header:List-Id:dev from:alice");
+        assert_eq!(search_metadata_candidates(&entries, &q), vec![0]);
+    }
+
+    #[test]
+    fn test_contains_folded_ignores_case_and_accents() {
         // ASCII fast path (no allocation)
-        assert!(contains_lower("Budget REPORT Q1", "report"));
-        assert!(contains_lower("alice@Example.COM", "example.com"));
-        assert!(!contains_lower("Budget Report", "meeting"));
-        assert!(contains_lower("anything", ""));
-        assert!(!contains_lower("ab", "abc"));
-        // Non-ASCII fallback keeps Unicode case folding
-        assert!(contains_lower("Reunión URGENTE", "reunión"));
-        assert!(contains_lower("CAFÉ con leche", "café"));
-        assert!(!contains_lower("Reunión", "cafe"));
+        assert!(contains_folded("Budget REPORT Q1", "report"));
+        assert!(contains_folded("alice@Example.COM", "example.com"));
+        assert!(!contains_folded("Budget Report", "meeting"));
+        assert!(contains_folded("anything", ""));
+        assert!(!contains_folded("ab", "abc"));
+        // Non-ASCII haystacks are folded: case and accents are ignored
+        assert!(contains_folded("Reunión URGENTE", "reunion"));
+        assert!(contains_folded("CAFÉ con leche", "cafe"));
+        assert!(contains_folded("Información", "informacion"));
+        assert!(!contains_folded("Reunión", "cafe"));
+    }
+
+    #[test]
+    fn test_fuzzy_terms_match_metadata() {
+        let entries = vec![make_entry(
+            "alice@example.com",
+            "Please recieve the invoice",
+            "2024-01-01",
+        )];
+        assert_eq!(
+            search_metadata(&entries, &parse_query("subject:~receive")),
+            vec![0]
+        );
+        assert_eq!(search_metadata(&entries, &parse_query("~invoise")), vec![0]);
+        assert!(search_metadata(&entries, &parse_query("~invoise~0")).is_empty());
+        assert!(search_metadata(&entries, &parse_query("from:~bob")).is_empty());
     }
 
     #[test]
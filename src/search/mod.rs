@@ -1,9 +1,14 @@
 //! Search engine: query parsing, metadata search, and full-text search.
 
+pub mod facets;
 pub mod fulltext;
+pub mod inverted;
 pub mod metadata;
+pub mod normalize;
 pub mod query;
+pub mod rank;
 
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use crate::model::mail::MailEntry;
@@ -13,10 +18,26 @@ use self::query::{parse_query, SearchQuery};
 /// Whether running this query requires reading message bodies from disk
 /// (the slow, cancelable path).
 ///
-/// True when an explicit `body:`/`filename:` term is present, or when a
-/// free-text (`All`) term is used — those search the body as well as metadata.
+/// True when a term targets a field only the message can answer (see
+/// [`query::SearchField::needs_message`]), or when a free-text (`All`) term
+/// is used — those search the body as well as metadata.
 pub fn needs_body_scan(query: &SearchQuery) -> bool {
-    query.groups.iter().any(|g| g.needs_body())
+    query.needs_body()
+}
+
+/// Resolve a reference to a saved search: `@name` stands for the query saved
+/// under `name` (see [`crate::config::Config::searches`]); any other query is
+/// returned as is.
+pub fn resolve_saved<'a>(
+    query: &'a str,
+    saved: &'a BTreeMap<String, String>,
+) -> crate::error::Result<&'a str> {
+    let Some(name) = query.trim().strip_prefix('@') else {
+        return Ok(query);
+    };
+    saved.get(name).map(String::as_str).ok_or_else(|| {
+        crate::error::MboxError::InvalidQuery(format!("no saved search named @{name}"))
+    })
 }
 
 /// High-level search: parse the query, search metadata, optionally run
@@ -24,13 +45,27 @@ pub fn needs_body_scan(query: &SearchQuery) -> bool {
 ///
 /// The `progress` callback is only invoked for full-text searches.
 /// It receives `(processed, total)` and returns `false` to cancel.
+///
+/// A regular expression that does not compile fails the search with
+/// [`crate::error::MboxError::InvalidQuery`].
 pub fn execute(
     mbox_path: &Path,
     entries: &[MailEntry],
     query_str: &str,
     progress: Option<&dyn Fn(usize, usize) -> bool>,
 ) -> crate::error::Result<(SearchQuery, Vec<usize>)> {
-    let query = parse_query(query_str);
+    execute_query(mbox_path, entries, parse_query(query_str), progress)
+}
+
+/// [`execute`] for a query already parsed, or read from JSON
+/// ([`SearchQuery::from_json`]).
+pub fn execute_query(
+    mbox_path: &Path,
+    entries: &[MailEntry],
+    query: SearchQuery,
+    progress: Option<&dyn Fn(usize, usize) -> bool>,
+) -> crate::error::Result<(SearchQuery, Vec<usize>)> {
+    query.check()?;
 
     if query.is_empty() {
         // Empty query — return all
@@ -42,8 +77,8 @@ pub fn execute(
     // message body — so they need the slow path just like `body:` does.
     let scan_bodies = needs_body_scan(&query);
 
-    // Phase 1: metadata search (fast). When bodies will be read, groups that
-    // need one are left undecided, so a term that only appears in the body is
+    // Phase 1: metadata search (fast). When bodies will be read, conditions
+    // that need one are left undecided, so a term that only appears in the body is
     // not filtered out before the body is ever read.
     let mut results = if scan_bodies {
         metadata::search_metadata_candidates(entries, &query)
@@ -53,9 +88,16 @@ pub fn execute(
 
     // Phase 2: full-text search if any term needs the body (body:/filename:
     // or a deferred Text term).
+    // A full-text index built by `mboxshell index --fulltext` answers most
+    // of it without reading the messages.
     if scan_bodies {
         let progress_fn = progress.unwrap_or(&|_, _| true);
-        results = fulltext::search_fulltext(mbox_path, entries, &results, &query, progress_fn)?;
+        results = match crate::index::builder::load_fulltext(mbox_path, entries.len()) {
+            Some(index) => {
+                fulltext::search_indexed(mbox_path, entries, &results, &query, &index, progress_fn)?
+            }
+            None => fulltext::search_fulltext(mbox_path, entries, &results, &query, progress_fn)?,
+        };
     }
 
     Ok((query, results))
@@ -64,18 +106,11 @@ pub fn execute(
 #[cfg(test)]
 mod tests {
     use crate::index::builder;
-    use std::path::{Path, PathBuf};
-
-    fn fixture(name: &str) -> PathBuf {
-        Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("tests")
-            .join("fixtures")
-            .join(name)
-    }
+    use crate::testing::temp_fixture;
 
     /// Collect the subjects of the entries returned by a search.
     fn search_subjects(query: &str) -> Vec<String> {
-        let mbox_path = fixture("simple.mbox");
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
         let entries = builder::build_index(&mbox_path, true, None).unwrap();
         let (_q, results) = super::execute(&mbox_path, &entries, query, None).unwrap();
         results
@@ -84,6 +119,42 @@ mod tests {
             .collect()
     }
 
+    #[test]
+    fn test_resolve_saved_search() {
+        let saved = std::collections::BTreeMap::from([(
+            "boss".to_string(),
+            "from:boss has:attachment".to_string(),
+        )]);
+        assert_eq!(
+            super::resolve_saved("@boss", &saved).unwrap(),
+            "from:boss has:attachment"
+        );
+        assert_eq!(
+            super::resolve_saved("from:@boss", &saved).unwrap(),
+            "from:@boss"
+        );
+        assert!(matches!(
+            super::resolve_saved("@nobody", &saved),
+            Err(crate::error::MboxError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn test_accents_and_typos() {
+        // Accent-insensitive in both directions, in metadata and body.
+        assert_eq!(search_subjects("cafe lena"), ["Café con leña"]);
+        assert_eq!(search_subjects("subject:CAFÉ"), ["Café con leña"]);
+        assert_eq!(search_subjects("body:aeiou"), ["Café con leña"]);
+        assert_eq!(search_subjects("from:garcika"), ["Café con leña"]);
+        // Fuzzy words, in the body or the subject.
+        assert_eq!(
+            search_subjects("~perspectve"),
+            ["Message with From in body"]
+        );
+        assert_eq!(search_subjects("subject:~meting"), ["Meeting tomorrow"]);
+        assert!(search_subjects("~perspectve~0").is_empty());
+    }
+
     #[test]
     fn test_free_text_matches_body() {
         // Regression for issues #4/#6: a bare "Text" term must find a word that
@@ -138,9 +209,9 @@ mod tests {
     }
 
     #[test]
-    fn test_two_body_groups_are_anded() {
-        // Both body words live in the same message; requiring them as separate
-        // groups must not turn into "either one".
+    fn test_two_body_terms_are_anded() {
+        // Only one of the body words exists; requiring both must not turn
+        // into "either one".
         assert_eq!(
             search_subjects("body:perspective body:zzzznotpresent").len(),
             0
@@ -162,6 +233,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parenthesized_body_and_metadata() {
+        // The group over a body word and a subject is negated as a whole.
+        let subjects = search_subjects("-(body:perspective OR subject:\"Meeting tomorrow\")");
+        assert_eq!(subjects.len(), 3);
+        assert!(!subjects.contains(&"Meeting tomorrow".to_string()));
+        assert!(!subjects.contains(&"Message with From in body".to_string()));
+    }
+
+    #[test]
+    fn test_regex_matches_body() {
+        let subjects = search_subjects(r"body:/\bpersp\w+ive\b/");
+        assert_eq!(subjects, vec!["Message with From in body".to_string()]);
+    }
+
+    #[test]
+    fn test_invalid_regex_fails_the_search() {
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
+        let entries = builder::build_index(&mbox_path, true, None).unwrap();
+        let err = super::execute(&mbox_path, &entries, "body:/(unclosed/", None).unwrap_err();
+        assert!(matches!(err, crate::error::MboxError::InvalidQuery(_)));
+    }
+
+    #[test]
+    fn test_header_field_reads_any_header() {
+        assert_eq!(
+            search_subjects("header:In-Reply-To:msg001"),
+            vec!["Re: Hello World".to_string()]
+        );
+        // Values are decoded before matching.
+        assert_eq!(
+            search_subjects(r#"header:From:"José Garcíka""#),
+            vec!["Café con leña".to_string()]
+        );
+        assert_eq!(
+            search_subjects("header:Content-Type:us-ascii"),
+            vec!["Meeting tomorrow".to_string()]
+        );
+        // An empty value asks for the header to be present.
+        assert_eq!(search_subjects("header:Cc:").len(), 1);
+        assert!(search_subjects("header:X-Mailer:").is_empty());
+    }
+
     #[test]
     fn test_free_text_no_match_anywhere() {
         let subjects = search_subjects("zzzznotfoundanywhere");
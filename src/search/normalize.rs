@@ -0,0 +1,129 @@
+//! Accent- and case-insensitive comparison, and fuzzy word matching.
+//!
+//! Search compares text *folded*: decomposed with Unicode compatibility
+//! decomposition (NFKD), stripped of combining marks and lowercased. So
+//! `información`, `informacion` and `INFORMACIÓN` are the same word, as are
+//! `ﬁle` and `file`. Needles are folded when the query is parsed (see
+//! [`super::query`]), haystacks when they are matched, and the full-text
+//! index stores folded tokens.
+//!
+//! A fuzzy term (`~recieve`) matches any word of the text within a few
+//! edits of it, a transposition of two neighbouring letters counting as one
+//! edit.
+
+use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+
+/// `text` folded: NFKD, without combining marks, lowercased.
+pub fn fold(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_ascii_lowercase();
+    }
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        push_folded(c, &mut folded);
+    }
+    folded
+}
+
+/// `text` folded, with the byte offset in `text` of every byte offset of the
+/// folded string (and of its end). Folding changes the length of characters
+/// (`é` loses its accent, `ﬁ` becomes two letters), so matches found in the
+/// folded text are mapped back to the original through it.
+pub fn fold_with_offsets(text: &str) -> (String, Vec<usize>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut origin = Vec::with_capacity(text.len() + 1);
+    for (at, c) in text.char_indices() {
+        let before = folded.len();
+        push_folded(c, &mut folded);
+        origin.resize(origin.len() + folded.len() - before, at);
+    }
+    origin.push(text.len());
+    (folded, origin)
+}
+
+/// Append the folded form of `c` to `out`.
+fn push_folded(c: char, out: &mut String) {
+    if c.is_ascii() {
+        out.push(c.to_ascii_lowercase());
+        return;
+    }
+    decompose_compatible(c, |d| {
+        if !is_combining_mark(d) {
+            out.extend(d.to_lowercase());
+        }
+    });
+}
+
+/// The words of `text` — maximal runs of alphanumeric characters — with
+/// their byte offsets.
+pub fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(move |w| (w.as_ptr() as usize - text.as_ptr() as usize, w))
+}
+
+/// Edits allowed by default for a fuzzy `word`: none for up to two letters,
+/// one for up to five, two beyond.
+pub fn default_distance(word: &str) -> usize {
+    match word.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// Whether `candidate` is within `max_distance` edits (insertions,
+/// deletions, substitutions and transpositions of adjacent characters) of
+/// `word`.
+pub fn is_near(candidate: &str, word: &str, max_distance: usize) -> bool {
+    candidate.chars().count().abs_diff(word.chars().count()) <= max_distance
+        && strsim::osa_distance(candidate, word) <= max_distance
+}
+
+/// Whether every word of the fuzzy `term` is near some word of `folded`
+/// (text already folded).
+pub fn fuzzy_matches(folded: &str, term: &str, max_distance: usize) -> bool {
+    words(term).all(|(_, wanted)| {
+        words(folded).any(|(_, candidate)| is_near(candidate, wanted, max_distance))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("Información"), "informacion");
+        assert_eq!(fold("INFORMACIÓN año Über"), "informacion ano uber");
+        assert_eq!(fold("ﬁle №5 ²"), "file no5 2");
+        assert_eq!(fold("日本語"), "日本語");
+
+        let (folded, origin) = fold_with_offsets("aÉb");
+        assert_eq!(folded, "aeb");
+        assert_eq!(origin, [0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_fuzzy() {
+        assert_eq!(default_distance("ok"), 0);
+        assert_eq!(default_distance("mesa"), 1);
+        assert_eq!(default_distance("recieve"), 2);
+
+        assert!(
+            is_near("receive", "recieve", 1),
+            "a transposition is one edit"
+        );
+        assert!(is_near("informe", "informa", 1));
+        assert!(!is_near("information", "informe", 2));
+
+        let text = fold("Please RECEIVE the attached informe.");
+        assert!(fuzzy_matches(&text, "recieve", 1));
+        assert!(fuzzy_matches(&text, "recieve atached", 1));
+        assert!(!fuzzy_matches(&text, "recieve unrelated", 2));
+        assert_eq!(
+            words("a-bc  d").collect::<Vec<_>>(),
+            [(0, "a"), (2, "bc"), (6, "d")]
+        );
+    }
+}
@@ -10,64 +10,252 @@
 //! - `from:user@example.com`
 //! - `to:dest@example.com`
 //! - `cc:copy@example.com`
+//! - `bcc:`, `reply-to:`, `delivered-to:` — the other recipient headers
+//! - `participant:user@example.com` — the sender or any recipient
 //! - `subject:invoice`
 //! - `body:important text`  (triggers full-text search)
 //! - `has:attachment` / `has:no-attachment`
 //! - `label:inbox`
+//! - `tag:review` — a tag the user gave the message (see
+//!   [`crate::index::state`])
+//! - `is:read` / `is:unread` / `is:starred`
 //! - `filename:report.pdf`
+//! - `attachment:invoice` — the text inside attachments: text files, PDFs,
+//!   and OOXML/ODF documents (see [`crate::parser::attachment_text`])
 //! - `id:<message-id@domain>`
+//! - `header:List-Id:dev.lists.example.com` — any header, by name
+//!   (`header:X-Spam-Flag:` with no value: the header is present)
 //!
 //! **Date filters**:
 //! - `date:2024-01-01` / `date:2024-01` / `date:2024`
 //! - `date:2024-01-01..2024-06-30`
 //! - `before:2024-06-01` / `after:2024-01-01`
+//! - `newer:7d` / `older:6m` — relative to now, in hours (`h`), days (`d`),
+//!   weeks (`w`), months (`m`) or years (`y`)
+//! - `date:today` / `date:yesterday` / `date:this-week` / `date:this-month` /
+//!   `date:this-year` — calendar periods in the configured timezone
+//!
+//! Relative dates are resolved when the query is parsed, against the current
+//! time in the timezone set with [`set_timezone`] (the system's local one by
+//! default), so a saved query keeps meaning "the last week". The absolute
+//! filters compare the day a message was sent on in that same timezone.
 //!
 //! **Size filters**:
 //! - `size:>1mb` / `size:<100kb`
 //!
+//! **Attachment filters**, answered from the index:
+//! - `attachment-type:pdf` — an attachment whose content type (`image`,
+//!   `application/pdf`) or filename extension (`docx`) contains the value
+//! - `attachment-size:>5mb` — an attachment over (or under) a size
+//! - `attachments:>3` / `attachments:<2` — the number of attachments
+//!
 //! **Operators**:
-//! - `term1 term2` — implicit AND
-//! - `term1 OR term2` — explicit OR
-//! - `-term` — NOT (exclude)
+//! - `term1 term2` or `term1 AND term2` — AND
+//! - `term1 OR term2` — OR
+//! - `-term` or `NOT term` — NOT (exclude)
+//! - `( … )` — grouping
 //! - `"exact phrase"` — quoted phrase
+//! - `/pattern/` or `/pattern/i` — regular expression, with any field
+//!   (`subject:/INV-\d{6}/`, `body:/\b\d{3}-\d{4}\b/i`, or bare for all)
+//! - `~word` — fuzzy: any word within a few typing mistakes of `word`
+//!   (`~recieve`, `subject:~informe`); `~word~N` allows exactly `N` edits
+//!
+//! Text is compared accent- and case-insensitively (see
+//! [`super::normalize`]): `informacion` finds `información`. Regular
+//! expressions are the exception; they see the text as it is.
 //!
-//! `OR` binds tighter than the implicit AND, so
-//! `from:alice OR from:bob subject:invoice` reads as
-//! `(from:alice OR from:bob) AND subject:invoice`. There are no parentheses:
-//! a query is a list of AND-ed groups, each group a list of OR-ed terms.
+//! Operators apply to every kind of condition — terms, date, size,
+//! attachment, `has:` and `is:` filters alike — at any depth: `(from:alice OR date:2023) -has:attachment`.
 //!
-//! `OR` only joins *terms*. Date, size and attachment filters are always
-//! AND-ed, so an `OR` next to one of them (`from:a OR has:attachment`) leaves
-//! the filter as a plain AND condition.
+//! `OR` binds tighter than `AND`, so `from:alice OR from:bob subject:invoice`
+//! reads as `(from:alice OR from:bob) AND subject:invoice`; parentheses
+//! override it: `from:alice OR (from:bob subject:invoice)`.
+//!
+//! Parsing never fails. An `OR` or `AND` with nothing to join is ignored, an
+//! unclosed `(` is closed at the end of the query, a `)` with no `(` is
+//! ignored, and a filter whose value does not parse (`date:soon`) is left out.
+//! A regular expression that does not compile is left out too, but recorded
+//! in [`SearchQuery::errors`] so the caller can report it instead of
+//! searching for something else than what was typed; [`SearchQuery::check`]
+//! turns it into an error.
+//!
+//! A parsed query serializes to JSON and back ([`SearchQuery::to_json`],
+//! [`SearchQuery::from_json`]), so scripts can build queries without the
+//! string syntax, and [`SearchQuery::explain`] shows how one was understood.
+
+use std::fmt::{self, Write as _};
+use std::sync::OnceLock;
 
-use chrono::NaiveDate;
+use chrono::{
+    DateTime, Datelike, Days, FixedOffset, Local, Months, NaiveDate, NaiveDateTime, Offset,
+    SecondsFormat, TimeZone, Utc,
+};
+use regex::{Regex, RegexBuilder};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::error::MboxError;
+
+use super::normalize::{self, default_distance, fold};
 
 /// Which field to search.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SearchField {
     /// Search in subject + from + to (default).
     All,
     From,
     To,
     Cc,
+    Bcc,
+    ReplyTo,
+    DeliveredTo,
+    /// `From`, `Sender` and every recipient header.
+    Participant,
     Subject,
     Body,
     Label,
+    /// A tag the user gave the message.
+    Tag,
     Filename,
+    /// The text extracted from the message's attachments.
+    Attachment,
+    /// The content type or filename extension of an attachment, from the
+    /// index.
+    AttachmentType,
     MessageId,
+    /// Any header, by lowercase name (`header:List-Id:…`). Matched against
+    /// each occurrence's unfolded, RFC 2047-decoded value.
+    Header(String),
 }
 
-/// How to match text.
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl SearchField {
+    /// Whether the field can only be judged by reading the message: `body:`,
+    /// `filename:`, `attachment:`, and `header:` for every header the index
+    /// does not keep verbatim — all but `Subject`.
+    pub fn needs_message(&self) -> bool {
+        match self {
+            Self::Body | Self::Filename | Self::Attachment => true,
+            Self::Header(name) => name != "subject",
+            _ => false,
+        }
+    }
+}
+
+/// How to match text. Needles are folded (see [`super::normalize::fold`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SearchOperator {
-    /// Case-insensitive substring match.
+    /// Accent- and case-insensitive substring match.
     Contains(String),
-    /// Exact quoted phrase (still case-insensitive).
+    /// Exact quoted phrase (still accent- and case-insensitive).
     Exact(String),
+    /// Words within `max_distance` edits of each word of `term` (`~term`).
+    Fuzzy { term: String, max_distance: usize },
+    /// Regular expression, matched against the original (not lowercased)
+    /// text.
+    Regex(Pattern),
+}
+
+/// A compiled `/…/` regular expression. Two patterns are equal when their
+/// source and flags are, and they serialize as those.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "PatternSource", try_from = "PatternSource")]
+pub struct Pattern {
+    source: String,
+    case_insensitive: bool,
+    regex: Regex,
+}
+
+impl Pattern {
+    /// Compile `source`; `case_insensitive` is the `i` flag.
+    pub fn new(source: &str, case_insensitive: bool) -> Result<Self, regex::Error> {
+        let regex = RegexBuilder::new(source)
+            .case_insensitive(case_insensitive)
+            .build()?;
+        Ok(Self {
+            source: source.to_string(),
+            case_insensitive,
+            regex,
+        })
+    }
+
+    /// The pattern as typed, without slashes and flags.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the pattern matches anywhere in `haystack`.
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.regex.is_match(haystack)
+    }
+
+    /// Byte ranges of the non-overlapping matches in `haystack`.
+    pub fn find_iter<'h>(
+        &'h self,
+        haystack: &'h str,
+    ) -> impl Iterator<Item = std::ops::Range<usize>> + 'h {
+        self.regex.find_iter(haystack).map(|m| m.range())
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source && self.case_insensitive == other.case_insensitive
+    }
+}
+
+impl Eq for Pattern {}
+
+/// The serialized form of a [`Pattern`]; it is compiled when read.
+#[derive(Serialize, Deserialize)]
+struct PatternSource {
+    source: String,
+    #[serde(default)]
+    case_insensitive: bool,
+}
+
+impl From<Pattern> for PatternSource {
+    fn from(pattern: Pattern) -> Self {
+        Self {
+            source: pattern.source,
+            case_insensitive: pattern.case_insensitive,
+        }
+    }
+}
+
+impl TryFrom<PatternSource> for Pattern {
+    type Error = InvalidPattern;
+
+    fn try_from(value: PatternSource) -> Result<Self, InvalidPattern> {
+        Pattern::new(&value.source, value.case_insensitive).map_err(|e| InvalidPattern {
+            reason: regex_error_reason(&e),
+            pattern: value.source,
+        })
+    }
+}
+
+/// A regular expression in a query that does not compile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvalidPattern {
+    /// The pattern as typed, without slashes and flags.
+    pub pattern: String,
+    /// Why it does not compile, on one line.
+    pub reason: String,
+}
+
+impl std::fmt::Display for InvalidPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid regular expression /{}/: {}",
+            self.pattern, self.reason
+        )
+    }
 }
 
 /// Date range filter.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DateFilter {
     /// Single day.
     Exact(NaiveDate),
@@ -81,253 +269,894 @@ pub enum DateFilter {
     Month(i32, u32),
     /// All days in a year.
     Year(i32),
+    /// At or after an instant (`newer:`).
+    Newer(DateTime<Utc>),
+    /// Strictly before an instant (`older:`).
+    Older(DateTime<Utc>),
+    /// From the first instant (inclusive) to the second (exclusive): a
+    /// calendar period such as `date:today`, resolved in the query timezone.
+    Within(DateTime<Utc>, DateTime<Utc>),
 }
 
-/// Size comparison filter.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Timezone relative dates are resolved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueryTimezone {
+    /// The system's local timezone, daylight saving time included.
+    #[default]
+    Local,
+    /// A fixed offset from UTC.
+    Fixed(FixedOffset),
+}
+
+static QUERY_TIMEZONE: OnceLock<QueryTimezone> = OnceLock::new();
+
+/// Set the timezone dates are resolved in. Call once at startup.
+/// If already set, this is a no-op.
+pub fn set_timezone(timezone: QueryTimezone) {
+    let _ = QUERY_TIMEZONE.set(timezone);
+}
+
+/// The timezone set with [`set_timezone`], or the default.
+pub(crate) fn timezone() -> QueryTimezone {
+    QUERY_TIMEZONE.get().copied().unwrap_or_default()
+}
+
+impl QueryTimezone {
+    /// Parse `local`, `utc` or an offset (`+02:00`, `-0500`, `+9`). `None`
+    /// for anything else.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("local") {
+            return Some(Self::Local);
+        }
+        if value.eq_ignore_ascii_case("utc") || value.eq_ignore_ascii_case("z") {
+            return Some(Self::Fixed(Utc.fix()));
+        }
+        let (sign, rest) = match value.as_bytes().first()? {
+            b'+' => (1, &value[1..]),
+            b'-' => (-1, &value[1..]),
+            _ => return None,
+        };
+        let (hours, minutes) = match rest.split_once(':') {
+            Some((h, m)) => (h, m),
+            None if rest.len() == 4 => rest.split_at(2),
+            None => (rest, "0"),
+        };
+        let hours: i32 = hours.parse().ok()?;
+        let minutes: i32 = minutes.parse().ok()?;
+        if !(0..60).contains(&minutes) {
+            return None;
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(Self::Fixed)
+    }
+
+    /// The calendar day `instant` falls on in this timezone.
+    pub(crate) fn date_of(self, instant: DateTime<Utc>) -> NaiveDate {
+        self.now(instant).date()
+    }
+
+    /// The current date and time in this timezone.
+    fn now(self, now: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Self::Local => now.with_timezone(&Local).naive_local(),
+            Self::Fixed(offset) => now.with_timezone(&offset).naive_local(),
+        }
+    }
+
+    /// The instant a wall-clock time in this timezone denotes. A time skipped
+    /// by a daylight-saving change resolves to the hour after it.
+    pub(crate) fn to_utc(self, local: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            Self::Fixed(offset) => (local - offset).and_utc(),
+            Self::Local => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .or_else(|| {
+                    Local
+                        .from_local_datetime(&(local + chrono::Duration::hours(1)))
+                        .earliest()
+                })
+                .map_or_else(|| local.and_utc(), |t| t.to_utc()),
+        }
+    }
+}
+
+/// The moment and timezone relative dates are resolved against.
+#[derive(Debug, Clone, Copy)]
+struct Clock {
+    now: DateTime<Utc>,
+    timezone: QueryTimezone,
+}
+
+/// Size (or count) comparison filter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SizeFilter {
     GreaterThan(u64),
     LessThan(u64),
 }
 
-/// A single search term.
-#[derive(Debug, Clone)]
+/// A single search term. Negation is a [`QueryExpr::Not`] around it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SearchTerm {
     pub field: SearchField,
     pub operator: SearchOperator,
-    pub negated: bool,
 }
 
-/// One or more terms joined by `OR`. An entry satisfies the group when it
-/// matches **any** of them.
+impl SearchTerm {
+    /// Whether the term has to read the message body.
+    ///
+    /// `All` (free-text) counts: it matches metadata *or* body, so it cannot
+    /// be settled without the body unless it already matched.
+    pub fn needs_body(&self) -> bool {
+        self.field == SearchField::All || self.field.needs_message()
+    }
+}
+
+/// A leaf of a query: something a single message either satisfies or not.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// A text term.
+    Term(SearchTerm),
+    /// `date:`, `before:` or `after:`.
+    Date(DateFilter),
+    /// `size:`.
+    Size(SizeFilter),
+    /// `attachment-size:`: some attachment of that size.
+    AttachmentSize(SizeFilter),
+    /// `attachments:`: the number of attachments.
+    AttachmentCount(SizeFilter),
+    /// `has:attachment` (`true`) or `has:no-attachment` (`false`).
+    HasAttachment(bool),
+    /// `is:read`, `is:unread` or `is:starred`.
+    Is(MessageStatus),
+}
+
+/// A message state `is:` tests: the mailbox's own flags with the user's
+/// changes laid over them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageStatus {
+    Read,
+    Unread,
+    Starred,
+}
+
+/// A boolean query expression.
 ///
-/// A group of one is the common case — a term with no `OR` beside it.
-#[derive(Debug, Clone)]
-pub struct TermGroup {
-    pub terms: Vec<SearchTerm>,
+/// In JSON, `{"and": [...]}`, `{"or": [...]}` and `{"not": ...}` around
+/// conditions, which appear as they are: `{"term": ...}`, `{"date": ...}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryExpr {
+    /// Holds when the inner expression does not.
+    Not(Box<QueryExpr>),
+    /// Holds when every operand does.
+    And(Vec<QueryExpr>),
+    /// Holds when any operand does.
+    Or(Vec<QueryExpr>),
+    /// A single condition.
+    #[serde(untagged)]
+    Condition(Condition),
 }
 
-impl TermGroup {
-    /// Whether any term in the group has to read the message body.
+impl QueryExpr {
+    /// Evaluate the expression, with `condition` deciding each leaf.
     ///
-    /// `All` (free-text) counts: it matches metadata *or* body, so the group
-    /// cannot be settled without the body unless it already matched.
-    pub fn needs_body(&self) -> bool {
-        self.terms.iter().any(|t| {
-            matches!(
-                t.field,
-                SearchField::Body | SearchField::Filename | SearchField::All
-            )
-        })
+    /// Three-valued: `condition` returns `None` for a leaf it cannot decide
+    /// yet (a body term during the metadata pass), and the result is `None`
+    /// only when the known leaves do not settle it — `x AND <unknown>` is
+    /// false as soon as `x` is, `x OR <unknown>` true as soon as `x` is.
+    /// Operands are evaluated in order and short-circuit.
+    pub fn evaluate(&self, condition: &mut dyn FnMut(&Condition) -> Option<bool>) -> Option<bool> {
+        match self {
+            Self::Condition(c) => condition(c),
+            Self::Not(inner) => inner.evaluate(condition).map(|v| !v),
+            Self::And(operands) => {
+                let mut result = Some(true);
+                for operand in operands {
+                    match operand.evaluate(condition) {
+                        Some(false) => return Some(false),
+                        Some(true) => {}
+                        None => result = None,
+                    }
+                }
+                result
+            }
+            Self::Or(operands) => {
+                let mut result = Some(false);
+                for operand in operands {
+                    match operand.evaluate(condition) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => result = None,
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    /// Every condition of the expression, in query order.
+    pub fn conditions(&self) -> Vec<&Condition> {
+        let mut out = Vec::new();
+        self.collect_conditions(&mut out);
+        out
+    }
+
+    fn collect_conditions<'a>(&'a self, out: &mut Vec<&'a Condition>) {
+        match self {
+            Self::Condition(c) => out.push(c),
+            Self::Not(inner) => inner.collect_conditions(out),
+            Self::And(operands) | Self::Or(operands) => {
+                for operand in operands {
+                    operand.collect_conditions(out);
+                }
+            }
+        }
+    }
+
+    /// Fold every needle and lowercase every header name, as the parser
+    /// leaves them.
+    fn normalize(&mut self) {
+        match self {
+            Self::Condition(Condition::Term(term)) => {
+                if let SearchField::Header(name) = &mut term.field {
+                    *name = name.to_lowercase();
+                }
+                match &mut term.operator {
+                    SearchOperator::Contains(needle) | SearchOperator::Exact(needle) => {
+                        *needle = fold(needle);
+                    }
+                    SearchOperator::Fuzzy { term, .. } => *term = fold(term),
+                    SearchOperator::Regex(_) => {}
+                }
+            }
+            Self::Condition(_) => {}
+            Self::Not(inner) => inner.normalize(),
+            Self::And(operands) | Self::Or(operands) => {
+                operands.iter_mut().for_each(Self::normalize);
+            }
+        }
+    }
+
+    /// Write the expression as an indented tree, `depth` levels in.
+    fn write_tree(&self, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        let operands = match self {
+            Self::Condition(c) => {
+                let _ = writeln!(out, "{indent}{c}");
+                return;
+            }
+            Self::Not(inner) => {
+                let _ = writeln!(out, "{indent}NOT");
+                return inner.write_tree(depth + 1, out);
+            }
+            Self::And(operands) => {
+                let _ = writeln!(out, "{indent}AND");
+                operands
+            }
+            Self::Or(operands) => {
+                let _ = writeln!(out, "{indent}OR");
+                operands
+            }
+        };
+        for operand in operands {
+            operand.write_tree(depth + 1, out);
+        }
+    }
+}
+
+/// Read by hand rather than derived: an untagged condition would report any
+/// mistake inside it as "did not match any variant".
+impl<'de> Deserialize<'de> for QueryExpr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        enum Operator {
+            Not(Box<QueryExpr>),
+            And(Vec<QueryExpr>),
+            Or(Vec<QueryExpr>),
+        }
+
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let is_operator = value.as_object().is_some_and(|object| {
+            object.len() == 1
+                && matches!(
+                    object.keys().next().map(String::as_str),
+                    Some("not" | "and" | "or")
+                )
+        });
+        if !is_operator {
+            return Condition::deserialize(value)
+                .map(Self::Condition)
+                .map_err(de::Error::custom);
+        }
+        Ok(
+            match Operator::deserialize(value).map_err(de::Error::custom)? {
+                Operator::Not(inner) => Self::Not(inner),
+                Operator::And(operands) => Self::And(operands),
+                Operator::Or(operands) => Self::Or(operands),
+            },
+        )
+    }
+}
+
+/// A condition in query syntax, as it was understood: needles folded, fuzzy
+/// terms with their number of edits, relative dates resolved to instants
+/// (`newer:`/`older:` followed by one) and sizes in bytes.
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Term(term) => write!(f, "{term}"),
+            Self::Date(filter) => write!(f, "{filter}"),
+            Self::Size(SizeFilter::GreaterThan(n)) => write!(f, "size:>{n}"),
+            Self::Size(SizeFilter::LessThan(n)) => write!(f, "size:<{n}"),
+            Self::AttachmentSize(SizeFilter::GreaterThan(n)) => {
+                write!(f, "attachment-size:>{n}")
+            }
+            Self::AttachmentSize(SizeFilter::LessThan(n)) => write!(f, "attachment-size:<{n}"),
+            Self::AttachmentCount(SizeFilter::GreaterThan(n)) => write!(f, "attachments:>{n}"),
+            Self::AttachmentCount(SizeFilter::LessThan(n)) => write!(f, "attachments:<{n}"),
+            Self::HasAttachment(true) => f.write_str("has:attachment"),
+            Self::HasAttachment(false) => f.write_str("has:no-attachment"),
+            Self::Is(MessageStatus::Read) => f.write_str("is:read"),
+            Self::Is(MessageStatus::Unread) => f.write_str("is:unread"),
+            Self::Is(MessageStatus::Starred) => f.write_str("is:starred"),
+        }
+    }
+}
+
+impl fmt::Display for SearchTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            SearchField::All => {}
+            SearchField::From => f.write_str("from:")?,
+            SearchField::To => f.write_str("to:")?,
+            SearchField::Cc => f.write_str("cc:")?,
+            SearchField::Bcc => f.write_str("bcc:")?,
+            SearchField::ReplyTo => f.write_str("reply-to:")?,
+            SearchField::DeliveredTo => f.write_str("delivered-to:")?,
+            SearchField::Participant => f.write_str("participant:")?,
+            SearchField::Subject => f.write_str("subject:")?,
+            SearchField::Body => f.write_str("body:")?,
+            SearchField::Label => f.write_str("label:")?,
+            SearchField::Tag => f.write_str("tag:")?,
+            SearchField::Filename => f.write_str("filename:")?,
+            SearchField::Attachment => f.write_str("attachment:")?,
+            SearchField::AttachmentType => f.write_str("attachment-type:")?,
+            SearchField::MessageId => f.write_str("id:")?,
+            SearchField::Header(name) => write!(f, "header:{name}:")?,
+        }
+        match &self.operator {
+            SearchOperator::Contains(needle) => f.write_str(needle),
+            SearchOperator::Exact(phrase) => write!(f, "\"{phrase}\""),
+            SearchOperator::Fuzzy { term, max_distance } if term.contains(' ') => {
+                write!(f, "~\"{term}\"~{max_distance}")
+            }
+            SearchOperator::Fuzzy { term, max_distance } => write!(f, "~{term}~{max_distance}"),
+            SearchOperator::Regex(pattern) => {
+                let flags = if pattern.case_insensitive { "i" } else { "" };
+                write!(f, "/{}/{flags}", pattern.source)
+            }
+        }
+    }
+}
+
+impl fmt::Display for DateFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let instant = |t: &DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Secs, true);
+        match self {
+            Self::Exact(day) => write!(f, "date:{day}"),
+            Self::Range(from, to) => write!(f, "date:{from}..{to}"),
+            Self::Before(day) => write!(f, "before:{day}"),
+            Self::After(day) => write!(f, "after:{day}"),
+            Self::Month(year, month) => write!(f, "date:{year}-{month:02}"),
+            Self::Year(year) => write!(f, "date:{year}"),
+            Self::Newer(t) => write!(f, "newer:{}", instant(t)),
+            Self::Older(t) => write!(f, "older:{}", instant(t)),
+            Self::Within(from, to) => write!(f, "newer:{} older:{}", instant(from), instant(to)),
+        }
     }
 }
 
 /// A fully parsed search query.
-///
-/// The term groups are AND-ed together and each group is OR-ed internally, so
-/// `a OR b c` is `(a OR b) AND c`. Date, size and attachment filters apply on
-/// top of all of them.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchQuery {
-    /// Term groups, AND-ed together.
-    pub groups: Vec<TermGroup>,
-    /// Date filters, AND-ed together, so `after:X before:Y` is a range.
-    pub date_filters: Vec<DateFilter>,
-    /// Size filters, AND-ed together, so `size:>1mb size:<10mb` is a band.
-    pub size_filters: Vec<SizeFilter>,
-    /// Explicit attachment filter: `Some(true)` for has:attachment,
-    /// `Some(false)` for has:no-attachment, `None` if unspecified.
-    pub has_attachment: Option<bool>,
-    /// Whether any term targets the Body or Filename field (requires
-    /// full-text search).
+    /// The query expression; `None` for an empty query, which matches
+    /// everything.
+    pub expr: Option<QueryExpr>,
+    /// Whether any term targets the Body, Filename or Attachment field, or a
+    /// header the index does not keep (requires full-text search).
+    #[serde(default)]
     pub needs_fulltext: bool,
+    /// Regular expressions that did not compile, and were left out of
+    /// [`expr`](Self::expr).
+    #[serde(default)]
+    pub errors: Vec<InvalidPattern>,
 }
 
 impl SearchQuery {
-    /// Every term of every group, flattened.
+    /// Every term of the query, in query order.
     pub fn all_terms(&self) -> impl Iterator<Item = &SearchTerm> {
-        self.groups.iter().flat_map(|g| g.terms.iter())
+        self.conditions().into_iter().filter_map(|c| match c {
+            Condition::Term(term) => Some(term),
+            _ => None,
+        })
+    }
+
+    /// Every condition of the query, in query order.
+    pub fn conditions(&self) -> Vec<&Condition> {
+        self.expr
+            .as_ref()
+            .map(QueryExpr::conditions)
+            .unwrap_or_default()
+    }
+
+    /// Whether any term has to read the message body (see
+    /// [`SearchTerm::needs_body`]).
+    pub fn needs_body(&self) -> bool {
+        self.all_terms().any(SearchTerm::needs_body)
     }
 
     /// Whether the query carries no terms and no filters.
     pub fn is_empty(&self) -> bool {
-        self.groups.is_empty()
-            && self.date_filters.is_empty()
-            && self.size_filters.is_empty()
-            && self.has_attachment.is_none()
+        self.expr.is_none()
     }
+
+    /// Fail with the first regular expression that did not compile, so a
+    /// typo is reported rather than searched without.
+    pub fn check(&self) -> crate::error::Result<()> {
+        match self.errors.first() {
+            Some(error) => Err(MboxError::InvalidQuery(error.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// The query as a JSON document, the form [`from_json`](Self::from_json)
+    /// reads.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a query serializes")
+    }
+
+    /// Read a query from a JSON document, as [`to_json`](Self::to_json)
+    /// writes it. Only `expr` is required. Needles are folded and header
+    /// names lowercased, as the parser leaves them, and `needs_fulltext` is
+    /// worked out again. A document that does not describe a query, or
+    /// holds a regular expression that does not compile, fails with
+    /// [`MboxError::InvalidQuery`].
+    pub fn from_json(json: &str) -> crate::error::Result<Self> {
+        let mut query: Self = serde_json::from_str(json)
+            .map_err(|e| MboxError::InvalidQuery(format!("invalid JSON query: {e}")))?;
+        if let Some(expr) = &mut query.expr {
+            expr.normalize();
+        }
+        query.needs_fulltext = needs_fulltext(query.expr.as_ref());
+        Ok(query)
+    }
+
+    /// The expression as an indented tree, one node per line: `AND`, `OR`
+    /// and `NOT` with their operands below them, and each condition in query
+    /// syntax as it was understood (see [`Condition`]'s `Display`). Empty
+    /// for an empty query.
+    pub fn explain(&self) -> String {
+        let mut out = String::new();
+        if let Some(expr) = &self.expr {
+            expr.write_tree(0, &mut out);
+        }
+        out
+    }
+}
+
+/// Whether any term of `expr` can only be judged by reading the message.
+fn needs_fulltext(expr: Option<&QueryExpr>) -> bool {
+    expr.is_some_and(|e| {
+        e.conditions()
+            .iter()
+            .any(|c| matches!(c, Condition::Term(term) if term.field.needs_message()))
+    })
 }
 
 /// Parse a query string into a structured [`SearchQuery`].
 ///
 /// Never fails — unrecognized syntax is treated as a plain text search.
+/// Relative dates are resolved against the current time.
 pub fn parse_query(input: &str) -> SearchQuery {
-    let input = input.trim();
-
-    let mut groups: Vec<TermGroup> = Vec::new();
-    let mut date_filters = Vec::new();
-    let mut size_filters = Vec::new();
-    let mut has_attachment = None;
-    let mut needs_fulltext = false;
-    // Set by an `OR` token: the next term joins the group before it instead of
-    // opening one of its own.
-    let mut join_previous = false;
-
-    let tokens = tokenize(input);
-
-    // Collect a term into the current group — the previous one right after an
-    // `OR`, a fresh one otherwise.
-    macro_rules! push_term {
-        ($term:expr) => {{
-            let term = $term;
-            match groups.last_mut() {
-                Some(group) if join_previous => group.terms.push(term),
-                _ => groups.push(TermGroup { terms: vec![term] }),
-            }
-        }};
+    parse_query_at(input, Utc::now(), timezone())
+}
+
+/// [`parse_query`], with relative dates resolved against `now` in
+/// `timezone`.
+pub fn parse_query_at(input: &str, now: DateTime<Utc>, timezone: QueryTimezone) -> SearchQuery {
+    let tokens = tokenize(input.trim());
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        errors: Vec::new(),
+        clock: Clock { now, timezone },
+    };
+    let mut operands = Vec::new();
+    while parser.pos < tokens.len() {
+        // A `)` that closes nothing is skipped here, at the top level.
+        if let Some(expr) = parser.and_expr() {
+            operands.push(expr);
+        }
+        parser.pos += 1;
+    }
+    let expr = join(operands, QueryExpr::And);
+    SearchQuery {
+        needs_fulltext: needs_fulltext(expr.as_ref()),
+        expr,
+        errors: parser.errors,
+    }
+}
+
+/// A lexical token of a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+}
+
+/// Recursive-descent parser over the tokens of a query:
+///
+/// ```text
+/// and_expr := or_expr ( [AND] or_expr )*
+/// or_expr  := unary ( OR unary )*
+/// unary    := ( NOT | - ) unary | "(" and_expr ")" | condition
+/// ```
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    errors: Vec<InvalidPattern>,
+    clock: Clock,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
     }
 
-    for token in &tokens {
-        if token == "OR" {
-            // A dangling `OR` — leading, trailing, or next to a filter rather
-            // than a term — has nothing to join and is ignored.
-            join_previous = true;
-            continue;
+    /// Operands joined by `AND` (explicit or implicit), up to a `)` or the end.
+    fn and_expr(&mut self) -> Option<QueryExpr> {
+        let mut operands = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) => break,
+                // Dangling `AND`/`OR`: nothing on its left to join.
+                Some(Token::And | Token::Or) => self.pos += 1,
+                Some(_) => operands.extend(self.or_expr()),
+            }
         }
+        join(operands, QueryExpr::And)
+    }
 
-        let (negated, token) = if let Some(stripped) = token.strip_prefix('-') {
-            (true, stripped)
-        } else {
-            (false, token.as_str())
-        };
+    /// Operands joined by `OR`.
+    fn or_expr(&mut self) -> Option<QueryExpr> {
+        let mut operands: Vec<QueryExpr> = self.unary().into_iter().collect();
+        while self.peek() == Some(&Token::Or) {
+            while self.peek() == Some(&Token::Or) {
+                self.pos += 1;
+            }
+            match self.peek() {
+                Some(Token::Word(_) | Token::Open | Token::Not) => operands.extend(self.unary()),
+                // A trailing `OR`, or one before `)`/`AND`, joins nothing.
+                _ => break,
+            }
+        }
+        join(operands, QueryExpr::Or)
+    }
 
-        // Field:value pairs
-        if let Some(value) = token.strip_prefix("from:") {
-            push_term!(SearchTerm {
-                field: SearchField::From,
-                operator: make_operator(value),
-                negated,
-            });
-        } else if let Some(value) = token.strip_prefix("to:") {
-            push_term!(SearchTerm {
-                field: SearchField::To,
-                operator: make_operator(value),
-                negated,
-            });
-        } else if let Some(value) = token.strip_prefix("cc:") {
-            push_term!(SearchTerm {
-                field: SearchField::Cc,
-                operator: make_operator(value),
-                negated,
-            });
-        } else if let Some(value) = token.strip_prefix("subject:") {
-            push_term!(SearchTerm {
-                field: SearchField::Subject,
-                operator: make_operator(value),
-                negated,
-            });
-        } else if let Some(value) = token.strip_prefix("body:") {
-            needs_fulltext = true;
-            push_term!(SearchTerm {
-                field: SearchField::Body,
-                operator: make_operator(value),
-                negated,
-            });
-        } else if let Some(value) = token.strip_prefix("label:") {
-            push_term!(SearchTerm {
-                field: SearchField::Label,
-                operator: make_operator(value),
-                negated,
-            });
-        } else if let Some(value) = token.strip_prefix("filename:") {
-            needs_fulltext = true;
-            push_term!(SearchTerm {
-                field: SearchField::Filename,
-                operator: make_operator(value),
-                negated,
-            });
-        } else if let Some(value) = token.strip_prefix("id:") {
-            push_term!(SearchTerm {
-                field: SearchField::MessageId,
-                operator: make_operator(value),
-                negated,
-            });
-        } else if let Some(value) = token.strip_prefix("has:") {
-            match value {
-                "attachment" | "attachments" => has_attachment = Some(!negated),
-                "no-attachment" | "no-attachments" => has_attachment = Some(negated),
-                _ => {}
+    /// A negation, a parenthesized group or a single condition. `None` when
+    /// it holds nothing (`()`, a filter that does not parse).
+    fn unary(&mut self) -> Option<QueryExpr> {
+        let token = self.peek()?.clone();
+        self.pos += 1;
+        match token {
+            Token::Not => {
+                let inner = self.unary()?;
+                Some(QueryExpr::Not(Box::new(inner)))
             }
-        } else if let Some(value) = token.strip_prefix("date:") {
-            // Filters accumulate instead of replacing each other, so
-            // `after:X before:Y` is a range rather than just whichever came
-            // last, and a nonsensical combination returns nothing rather than
-            // silently dropping half of what was typed.
-            date_filters.extend(parse_date_filter(value));
-        } else if let Some(value) = token.strip_prefix("before:") {
-            date_filters.extend(parse_naive_date(value).map(DateFilter::Before));
-        } else if let Some(value) = token.strip_prefix("after:") {
-            date_filters.extend(parse_naive_date(value).map(DateFilter::After));
-        } else if let Some(value) = token.strip_prefix("size:") {
-            size_filters.extend(parse_size_filter(value));
-        } else {
-            // Plain text — search All fields
-            push_term!(SearchTerm {
-                field: SearchField::All,
-                operator: make_operator(token),
-                negated,
-            });
+            Token::Open => {
+                let inner = self.and_expr();
+                // Consume the `)`; a missing one is implied at the end.
+                if self.peek() == Some(&Token::Close) {
+                    self.pos += 1;
+                }
+                inner
+            }
+            Token::Word(word) => match parse_condition(&word, self.clock) {
+                Ok(condition) => condition.map(QueryExpr::Condition),
+                Err(error) => {
+                    self.errors.push(error);
+                    None
+                }
+            },
+            // Callers only get here on a token that can start an operand.
+            Token::Close | Token::And | Token::Or => None,
         }
+    }
+}
 
-        join_previous = false;
+/// A single operand as is, several joined by `make`, none as `None`.
+fn join(mut operands: Vec<QueryExpr>, make: fn(Vec<QueryExpr>) -> QueryExpr) -> Option<QueryExpr> {
+    match operands.len() {
+        0 => None,
+        1 => operands.pop(),
+        _ => Some(make(operands)),
     }
+}
 
-    SearchQuery {
-        groups,
-        date_filters,
-        size_filters,
-        has_attachment,
-        needs_fulltext,
+/// Parse one `field:value` (or bare word) into a condition. `Ok(None)` when
+/// a filter value does not parse, an error when a regular expression does
+/// not compile.
+fn parse_condition(token: &str, clock: Clock) -> Result<Option<Condition>, InvalidPattern> {
+    let term = |field, value| {
+        Ok(Some(Condition::Term(SearchTerm {
+            field,
+            operator: make_operator(value)?,
+        })))
+    };
+
+    if let Some(value) = token.strip_prefix("from:") {
+        term(SearchField::From, value)
+    } else if let Some(value) = token.strip_prefix("to:") {
+        term(SearchField::To, value)
+    } else if let Some(value) = token.strip_prefix("cc:") {
+        term(SearchField::Cc, value)
+    } else if let Some(value) = token.strip_prefix("bcc:") {
+        term(SearchField::Bcc, value)
+    } else if let Some(value) = token.strip_prefix("reply-to:") {
+        term(SearchField::ReplyTo, value)
+    } else if let Some(value) = token.strip_prefix("delivered-to:") {
+        term(SearchField::DeliveredTo, value)
+    } else if let Some(value) = token.strip_prefix("participant:") {
+        term(SearchField::Participant, value)
+    } else if let Some(value) = token.strip_prefix("subject:") {
+        term(SearchField::Subject, value)
+    } else if let Some(value) = token.strip_prefix("body:") {
+        term(SearchField::Body, value)
+    } else if let Some(value) = token.strip_prefix("label:") {
+        term(SearchField::Label, value)
+    } else if let Some(value) = token.strip_prefix("tag:") {
+        term(SearchField::Tag, value)
+    } else if let Some(value) = token.strip_prefix("filename:") {
+        term(SearchField::Filename, value)
+    } else if let Some(value) = token.strip_prefix("attachment:") {
+        term(SearchField::Attachment, value)
+    } else if let Some(value) = token.strip_prefix("attachment-type:") {
+        term(SearchField::AttachmentType, value)
+    } else if let Some(value) = token.strip_prefix("id:") {
+        term(SearchField::MessageId, value)
+    } else if let Some((name, value)) = token
+        .strip_prefix("header:")
+        .and_then(|rest| rest.split_once(':'))
+        .filter(|(name, _)| !name.is_empty())
+    {
+        term(SearchField::Header(name.to_lowercase()), value)
+    } else if let Some(value) = token.strip_prefix("has:") {
+        Ok(match value {
+            "attachment" | "attachments" => Some(Condition::HasAttachment(true)),
+            "no-attachment" | "no-attachments" => Some(Condition::HasAttachment(false)),
+            _ => None,
+        })
+    } else if let Some(value) = token.strip_prefix("is:") {
+        Ok(match value {
+            "read" => Some(Condition::Is(MessageStatus::Read)),
+            "unread" => Some(Condition::Is(MessageStatus::Unread)),
+            "starred" => Some(Condition::Is(MessageStatus::Starred)),
+            _ => None,
+        })
+    } else if let Some(value) = token.strip_prefix("date:") {
+        Ok(parse_date_filter(value)
+            .or_else(|| parse_calendar_period(value, clock))
+            .map(Condition::Date))
+    } else if let Some(value) = token.strip_prefix("before:") {
+        Ok(parse_naive_date(value).map(|d| Condition::Date(DateFilter::Before(d))))
+    } else if let Some(value) = token.strip_prefix("after:") {
+        Ok(parse_naive_date(value).map(|d| Condition::Date(DateFilter::After(d))))
+    } else if let Some(value) = token.strip_prefix("newer:") {
+        Ok(parse_age(value, clock).map(|t| Condition::Date(DateFilter::Newer(t))))
+    } else if let Some(value) = token.strip_prefix("older:") {
+        Ok(parse_age(value, clock).map(|t| Condition::Date(DateFilter::Older(t))))
+    } else if let Some(value) = token.strip_prefix("size:") {
+        Ok(parse_size_filter(value).map(Condition::Size))
+    } else if let Some(value) = token.strip_prefix("attachment-size:") {
+        Ok(parse_size_filter(value).map(Condition::AttachmentSize))
+    } else if let Some(value) = token.strip_prefix("attachments:") {
+        Ok(parse_count_filter(value).map(Condition::AttachmentCount))
+    } else {
+        // Plain text — search All fields
+        term(SearchField::All, token)
     }
 }
 
-/// Build an operator from a value string (quoted → Exact, otherwise → Contains).
-fn make_operator(value: &str) -> SearchOperator {
+/// Build an operator from a value string (`/…/` → Regex, `~…` → Fuzzy,
+/// quoted → Exact, otherwise → Contains).
+fn make_operator(value: &str) -> Result<SearchOperator, InvalidPattern> {
+    if let Some((source, case_insensitive)) = split_regex(value) {
+        return Pattern::new(source, case_insensitive)
+            .map(SearchOperator::Regex)
+            .map_err(|e| InvalidPattern {
+                pattern: source.to_string(),
+                reason: regex_error_reason(&e),
+            });
+    }
+    if let Some(fuzzy) = parse_fuzzy(value) {
+        return Ok(fuzzy);
+    }
     let unquoted = value
         .strip_prefix('"')
         .and_then(|s| s.strip_suffix('"'))
         .unwrap_or(value);
     if value.starts_with('"') && value.ends_with('"') {
-        SearchOperator::Exact(unquoted.to_lowercase())
+        Ok(SearchOperator::Exact(fold(unquoted)))
     } else {
-        SearchOperator::Contains(unquoted.to_lowercase())
+        Ok(SearchOperator::Contains(fold(unquoted)))
     }
 }
 
-/// Tokenize input respecting quoted strings.
-fn tokenize(input: &str) -> Vec<String> {
-    let mut tokens = Vec::new();
+/// Parse `~word` (default distance for its length) or `~word~N`. `None`
+/// when there is no word after the `~`.
+fn parse_fuzzy(value: &str) -> Option<SearchOperator> {
+    let rest = value.strip_prefix('~')?;
+    let (word, max_distance) = match rest.rsplit_once('~') {
+        Some((word, n)) if n.len() == 1 => (word, n.parse().ok()?),
+        _ => (rest, default_distance(rest)),
+    };
+    let word = word
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(word);
+    let term = fold(word);
+    let has_words = normalize::words(&term).next().is_some();
+    has_words.then_some(SearchOperator::Fuzzy { term, max_distance })
+}
+
+/// Split `/pattern/` or `/pattern/i` into the pattern and whether it is
+/// case-insensitive. `None` for anything else — `/usr/bin` is a path, not a
+/// pattern with flags `bin`.
+fn split_regex(value: &str) -> Option<(&str, bool)> {
+    let rest = value.strip_prefix('/')?;
+    let end = regex_end(rest)?;
+    let (source, flags) = (&rest[..end], &rest[end + 1..]);
+    if source.is_empty() {
+        return None;
+    }
+    match flags {
+        "" => Some((source, false)),
+        "i" => Some((source, true)),
+        _ => None,
+    }
+}
+
+/// Byte position of the `/` closing a pattern, skipping `\/` escapes.
+fn regex_end(pattern: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, ch) in pattern.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '/' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The gist of a regex compile error on one line. The syntax errors of the
+/// `regex` crate span several lines (the pattern, a caret, the reason); the
+/// reason is the last.
+fn regex_error_reason(error: &regex::Error) -> String {
+    let text = error.to_string();
+    let last = text
+        .lines()
+        .rev()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("");
+    last.trim().trim_start_matches("error: ").to_string()
+}
+
+/// Tokenize input respecting quoted strings and `/…/` patterns.
+///
+/// `(` opens a group at the start of a word and `)` closes one at its end
+/// while a group is open, so `(a OR b)` splits but `foo(bar)` outside any
+/// group, or inside quotes, stays one word. A leading `-` before a word or
+/// group is `NOT`; `AND`, `OR` and `NOT` are operators only in capitals.
+/// A `/` opening a value (`/…/`, `subject:/…/`) keeps whitespace and quotes
+/// up to the closing `/` in the same word.
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut words: Vec<String> = Vec::new();
     let mut current = String::new();
     let mut in_quotes = false;
+    let mut in_regex = false;
+    let mut escaped = false;
 
     for ch in input.chars() {
-        if ch == '"' {
+        if in_regex {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '/' => in_regex = false,
+                _ => {}
+            }
+            current.push(ch);
+        } else if ch == '/' && !in_quotes && opens_value(&current) {
+            in_regex = true;
+            current.push(ch);
+        } else if ch == '"' {
             in_quotes = !in_quotes;
             current.push(ch);
         } else if ch.is_whitespace() && !in_quotes {
             if !current.is_empty() {
-                tokens.push(std::mem::take(&mut current));
+                words.push(std::mem::take(&mut current));
             }
         } else {
             current.push(ch);
         }
     }
     if !current.is_empty() {
-        tokens.push(current);
+        words.push(current);
+    }
+
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    for word in words {
+        let mut rest = word.as_str();
+        loop {
+            if let Some(r) = rest.strip_prefix('(') {
+                tokens.push(Token::Open);
+                depth += 1;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix('-').filter(|r| r.starts_with('(')) {
+                tokens.push(Token::Not);
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        let mut closes = 0;
+        while depth > closes && rest.ends_with(')') && !ends_inside_quotes(rest) {
+            rest = &rest[..rest.len() - 1];
+            closes += 1;
+        }
+        match rest {
+            "" => {}
+            "AND" => tokens.push(Token::And),
+            "OR" => tokens.push(Token::Or),
+            "NOT" => tokens.push(Token::Not),
+            _ => match rest.strip_prefix('-') {
+                Some(negated) if !negated.is_empty() => {
+                    tokens.push(Token::Not);
+                    tokens.push(Token::Word(negated.to_string()));
+                }
+                _ => tokens.push(Token::Word(rest.to_string())),
+            },
+        }
+        for _ in 0..closes {
+            tokens.push(Token::Close);
+            depth -= 1;
+        }
     }
     tokens
 }
 
+/// Whether a `/` after `word` would start a value: at the start of a word
+/// (after any `(` and `-`) or right after a `field:`.
+fn opens_value(word: &str) -> bool {
+    word.ends_with(':') || word.chars().all(|c| c == '(' || c == '-')
+}
+
+/// Whether the last character of `word` is inside a quoted string.
+fn ends_inside_quotes(word: &str) -> bool {
+    word.matches('"').count() % 2 == 1
+}
+
 /// Parse a date filter value like `2024-01-01`, `2024-01`, `2024`,
 /// or a range `2024-01-01..2024-06-30` (partial dates accepted in ranges).
 fn parse_date_filter(value: &str) -> Option<DateFilter> {
@@ -363,6 +1192,61 @@ fn parse_date_filter(value: &str) -> Option<DateFilter> {
     None
 }
 
+/// Resolve `today`, `yesterday`, `this-week` (from Monday), `this-month` or
+/// `this-year` to the period's bounds in the clock's timezone.
+fn parse_calendar_period(value: &str, clock: Clock) -> Option<DateFilter> {
+    let today = clock.timezone.now(clock.now).date();
+    let (start, end) = match value.to_ascii_lowercase().as_str() {
+        "today" => (today, today.checked_add_days(Days::new(1))?),
+        "yesterday" => (today.checked_sub_days(Days::new(1))?, today),
+        "this-week" => {
+            let monday =
+                today.checked_sub_days(Days::new(today.weekday().num_days_from_monday().into()))?;
+            (monday, monday.checked_add_days(Days::new(7))?)
+        }
+        "this-month" => {
+            let first = today.with_day(1)?;
+            (first, first.checked_add_months(Months::new(1))?)
+        }
+        "this-year" => {
+            let first = NaiveDate::from_ymd_opt(today.year(), 1, 1)?;
+            (first, first.checked_add_months(Months::new(12))?)
+        }
+        _ => return None,
+    };
+    let midnight = |d: NaiveDate| clock.timezone.to_utc(d.and_time(chrono::NaiveTime::MIN));
+    Some(DateFilter::Within(midnight(start), midnight(end)))
+}
+
+/// Resolve an age like `7d`, `12h`, `2w`, `6m` or `1y` to the instant that
+/// long before the clock's now. Months and years are calendar ones, counted
+/// on the wall clock of the clock's timezone.
+fn parse_age(value: &str, clock: Clock) -> Option<DateTime<Utc>> {
+    let (split, unit) = value.char_indices().last()?;
+    let unit = unit.to_ascii_lowercase();
+    let amount: u32 = value[..split].parse().ok()?;
+    let now = clock.now;
+    match unit {
+        'h' => now.checked_sub_signed(chrono::Duration::hours(amount.into())),
+        'd' => now.checked_sub_signed(chrono::Duration::days(amount.into())),
+        'w' => now.checked_sub_signed(chrono::Duration::weeks(amount.into())),
+        'm' | 'y' => {
+            let months = if unit == 'y' {
+                amount.checked_mul(12)?
+            } else {
+                amount
+            };
+            let local = clock.timezone.now(now);
+            Some(
+                clock
+                    .timezone
+                    .to_utc(local.checked_sub_months(Months::new(months))?),
+            )
+        }
+        _ => None,
+    }
+}
+
 /// Parse a date string like `2024-01-04`.
 fn parse_naive_date(s: &str) -> Option<NaiveDate> {
     NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
@@ -421,26 +1305,44 @@ fn parse_size_filter(value: &str) -> Option<SizeFilter> {
         None => (false, value.strip_prefix('<')?),
     };
 
-    let rest_lower = rest.to_lowercase();
-    let (num_str, multiplier) = if let Some(n) = rest_lower.strip_suffix("gb") {
+    let bytes = parse_byte_size(rest)?;
+    if cmp {
+        Some(SizeFilter::GreaterThan(bytes))
+    } else {
+        Some(SizeFilter::LessThan(bytes))
+    }
+}
+
+/// Parse a byte size like `100kb`, `2GB` or `512`: a whole number with an
+/// optional `b`, `kb`, `mb` or `gb` suffix (powers of 1024).
+pub(crate) fn parse_byte_size(value: &str) -> Option<u64> {
+    let lower = value.to_lowercase();
+    let (num_str, multiplier) = if let Some(n) = lower.strip_suffix("gb") {
         (n, 1024 * 1024 * 1024)
-    } else if let Some(n) = rest_lower.strip_suffix("mb") {
+    } else if let Some(n) = lower.strip_suffix("mb") {
         (n, 1024 * 1024)
-    } else if let Some(n) = rest_lower.strip_suffix("kb") {
+    } else if let Some(n) = lower.strip_suffix("kb") {
         (n, 1024)
-    } else if let Some(n) = rest_lower.strip_suffix('b') {
+    } else if let Some(n) = lower.strip_suffix('b') {
         (n, 1u64)
     } else {
-        (rest_lower.as_str(), 1u64)
+        (lower.as_str(), 1u64)
     };
 
     let num: u64 = num_str.parse().ok()?;
-    let bytes = num.checked_mul(multiplier)?;
+    num.checked_mul(multiplier)
+}
 
-    if cmp {
-        Some(SizeFilter::GreaterThan(bytes))
+/// Parse `>N` or `<N`, a plain count.
+fn parse_count_filter(value: &str) -> Option<SizeFilter> {
+    if let Some(n) = value.strip_prefix('>') {
+        n.parse().ok().map(SizeFilter::GreaterThan)
     } else {
-        Some(SizeFilter::LessThan(bytes))
+        value
+            .strip_prefix('<')?
+            .parse()
+            .ok()
+            .map(SizeFilter::LessThan)
     }
 }
 
@@ -448,30 +1350,64 @@ fn parse_size_filter(value: &str) -> Option<SizeFilter> {
 mod tests {
     use super::*;
 
-    /// Every term of the query, flattened — most assertions here predate
-    /// grouping and only care about what was parsed, not how it was grouped.
+    /// Every term of the query, flattened — most assertions here only care
+    /// about what was parsed, not how it was nested.
     fn terms(q: &SearchQuery) -> Vec<&SearchTerm> {
         q.all_terms().collect()
     }
 
+    /// Every date filter of the query, flattened.
+    fn dates(q: &SearchQuery) -> Vec<&DateFilter> {
+        q.conditions()
+            .into_iter()
+            .filter_map(|c| match c {
+                Condition::Date(d) => Some(d),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Every size filter of the query, flattened.
+    fn sizes(q: &SearchQuery) -> Vec<&SizeFilter> {
+        q.conditions()
+            .into_iter()
+            .filter_map(|c| match c {
+                Condition::Size(s) => Some(s),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// The single date filter of a query that has exactly one.
     fn only_date(q: &SearchQuery) -> &DateFilter {
-        assert_eq!(q.date_filters.len(), 1, "expected exactly one date filter");
-        &q.date_filters[0]
+        let dates = dates(q);
+        assert_eq!(dates.len(), 1, "expected exactly one date filter");
+        dates[0]
     }
 
     /// The single size filter of a query that has exactly one.
     fn only_size(q: &SearchQuery) -> &SizeFilter {
-        assert_eq!(q.size_filters.len(), 1, "expected exactly one size filter");
-        &q.size_filters[0]
+        let sizes = sizes(q);
+        assert_eq!(sizes.len(), 1, "expected exactly one size filter");
+        sizes[0]
+    }
+
+    /// A leaf term, for building expected expressions.
+    fn term(field: SearchField, needle: &str) -> QueryExpr {
+        QueryExpr::Condition(Condition::Term(SearchTerm {
+            field,
+            operator: SearchOperator::Contains(needle.to_string()),
+        }))
+    }
+
+    fn not(expr: QueryExpr) -> QueryExpr {
+        QueryExpr::Not(Box::new(expr))
     }
 
     #[test]
     fn test_parse_simple_query() {
         let q = parse_query("hello");
-        assert_eq!(terms(&q).len(), 1);
-        assert_eq!(terms(&q)[0].field, SearchField::All);
-        assert!(!terms(&q)[0].negated);
+        assert_eq!(q.expr, Some(term(SearchField::All, "hello")));
         assert!(!q.needs_fulltext);
     }
 
@@ -480,9 +1416,14 @@ mod tests {
         // The Search Filters "Text" field emits its value verbatim, so a
         // multi-word value tokenizes into one free-text term per word, ANDed.
         let q = parse_query("multi word search");
-        assert_eq!(terms(&q).len(), 3);
-        assert!(terms(&q).iter().all(|t| t.field == SearchField::All));
-        assert_eq!(q.groups.len(), 3, "no OR, so each term is its own group");
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::And(vec![
+                term(SearchField::All, "multi"),
+                term(SearchField::All, "word"),
+                term(SearchField::All, "search"),
+            ]))
+        );
     }
 
     #[test]
@@ -496,22 +1437,27 @@ mod tests {
     #[test]
     fn test_parse_negation() {
         let q = parse_query("-subject:spam");
-        assert_eq!(terms(&q).len(), 1);
-        assert!(terms(&q)[0].negated);
-        assert_eq!(terms(&q)[0].field, SearchField::Subject);
+        assert_eq!(q.expr, Some(not(term(SearchField::Subject, "spam"))));
+        assert_eq!(q.expr, parse_query("NOT subject:spam").expr);
     }
 
     #[test]
     fn test_parse_has_attachment() {
         let q = parse_query("has:attachment");
-        assert_eq!(q.has_attachment, Some(true));
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::Condition(Condition::HasAttachment(true)))
+        );
         assert!(terms(&q).is_empty());
     }
 
     #[test]
     fn test_parse_has_no_attachment() {
         let q = parse_query("has:no-attachment");
-        assert_eq!(q.has_attachment, Some(false));
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::Condition(Condition::HasAttachment(false)))
+        );
     }
 
     #[test]
@@ -568,20 +1514,101 @@ mod tests {
         ));
     }
 
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    /// The single date filter of `input` parsed at `now` in UTC+2.
+    fn relative(input: &str, now: &str) -> DateFilter {
+        let tz = QueryTimezone::parse("+02:00").unwrap();
+        only_date(&parse_query_at(input, utc(now), tz)).clone()
+    }
+
+    #[test]
+    fn test_parse_relative_ages() {
+        let now = "2024-03-31T12:00:00Z";
+        assert_eq!(
+            relative("newer:7d", now),
+            DateFilter::Newer(utc("2024-03-24T12:00:00Z"))
+        );
+        assert_eq!(
+            relative("older:12h", now),
+            DateFilter::Older(utc("2024-03-31T00:00:00Z"))
+        );
+        assert_eq!(
+            relative("newer:2w", now),
+            DateFilter::Newer(utc("2024-03-17T12:00:00Z"))
+        );
+        // Calendar months, clamped to the end of a shorter month.
+        assert_eq!(
+            relative("older:1m", now),
+            DateFilter::Older(utc("2024-02-29T12:00:00Z"))
+        );
+        assert_eq!(
+            relative("newer:1y", now),
+            DateFilter::Newer(utc("2023-03-31T12:00:00Z"))
+        );
+        for bad in ["newer:7", "newer:d", "older:7x", "newer:-1d", "newer:7é"] {
+            let tz = QueryTimezone::default();
+            assert!(parse_query_at(bad, utc(now), tz).is_empty(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_parse_calendar_periods() {
+        // 23:30 UTC on Sunday 31 March is already Monday 1 April at UTC+2.
+        let now = "2024-03-31T23:30:00Z";
+        assert_eq!(
+            relative("date:today", now),
+            DateFilter::Within(utc("2024-03-31T22:00:00Z"), utc("2024-04-01T22:00:00Z"))
+        );
+        assert_eq!(
+            relative("date:yesterday", now),
+            DateFilter::Within(utc("2024-03-30T22:00:00Z"), utc("2024-03-31T22:00:00Z"))
+        );
+        assert_eq!(
+            relative("date:this-week", now),
+            DateFilter::Within(utc("2024-03-31T22:00:00Z"), utc("2024-04-07T22:00:00Z"))
+        );
+        assert_eq!(
+            relative("date:this-month", now),
+            DateFilter::Within(utc("2024-03-31T22:00:00Z"), utc("2024-04-30T22:00:00Z"))
+        );
+        assert_eq!(
+            relative("date:This-Year", now),
+            DateFilter::Within(utc("2023-12-31T22:00:00Z"), utc("2024-12-31T22:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(QueryTimezone::parse("local"), Some(QueryTimezone::Local));
+        let east = |secs| Some(QueryTimezone::Fixed(FixedOffset::east_opt(secs).unwrap()));
+        assert_eq!(QueryTimezone::parse("UTC"), east(0));
+        assert_eq!(QueryTimezone::parse("+02:00"), east(7200));
+        assert_eq!(QueryTimezone::parse("-0530"), east(-19800));
+        assert_eq!(QueryTimezone::parse("+9"), east(32400));
+        for bad in ["", "Europe/Madrid", "+25:00", "+02:75", "2"] {
+            assert_eq!(QueryTimezone::parse(bad), None, "{bad}");
+        }
+    }
+
     #[test]
     fn test_date_filters_accumulate() {
         // `after:` and `before:` together describe a range. Only the last one
         // used to survive, so half of what the user typed was dropped.
         let q = parse_query("after:2024-01-01 before:2025-01-01");
-        assert_eq!(q.date_filters.len(), 2);
-        assert!(matches!(q.date_filters[0], DateFilter::After(_)));
-        assert!(matches!(q.date_filters[1], DateFilter::Before(_)));
+        let dates = dates(&q);
+        assert_eq!(dates.len(), 2);
+        assert!(matches!(dates[0], DateFilter::After(_)));
+        assert!(matches!(dates[1], DateFilter::Before(_)));
+        assert!(matches!(q.expr, Some(QueryExpr::And(_))));
     }
 
     #[test]
     fn test_size_filters_accumulate() {
         let q = parse_query("size:>1mb size:<10mb");
-        assert_eq!(q.size_filters.len(), 2);
+        assert_eq!(sizes(&q).len(), 2);
     }
 
     #[test]
@@ -614,7 +1641,38 @@ mod tests {
         // A value that overflows u64 when multiplied by the unit must yield no
         // size filter instead of panicking (debug) or wrapping (release).
         let q = parse_query("size:>99999999999gb");
-        assert!(q.size_filters.is_empty());
+        assert!(sizes(&q).is_empty());
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_parse_attachment_filters() {
+        let q = parse_query("attachment-type:PDF attachment-size:>5mb attachments:>3");
+        assert_eq!(
+            q.conditions(),
+            [
+                &Condition::Term(SearchTerm {
+                    field: SearchField::AttachmentType,
+                    operator: SearchOperator::Contains("pdf".into()),
+                }),
+                &Condition::AttachmentSize(SizeFilter::GreaterThan(5 * 1024 * 1024)),
+                &Condition::AttachmentCount(SizeFilter::GreaterThan(3)),
+            ]
+        );
+        assert!(!q.needs_fulltext, "answered from the index");
+        assert_eq!(
+            q.explain(),
+            "AND\n\
+             \x20 attachment-type:pdf\n\
+             \x20 attachment-size:>5242880\n\
+             \x20 attachments:>3\n"
+        );
+        assert_eq!(
+            parse_query("attachments:<2").conditions(),
+            [&Condition::AttachmentCount(SizeFilter::LessThan(2))]
+        );
+        // A count has no unit, and a filter that does not parse is left out.
+        assert!(parse_query("attachments:>2mb attachments:3 attachment-size:big").is_empty());
     }
 
     #[test]
@@ -627,8 +1685,13 @@ mod tests {
     #[test]
     fn test_parse_or_query() {
         let q = parse_query("from:alice OR from:bob");
-        assert_eq!(q.groups.len(), 1, "OR keeps both terms in one group");
-        assert_eq!(q.groups[0].terms.len(), 2);
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::Or(vec![
+                term(SearchField::From, "alice"),
+                term(SearchField::From, "bob"),
+            ]))
+        );
     }
 
     #[test]
@@ -636,67 +1699,208 @@ mod tests {
         // `(from:alice OR from:bob) AND subject:invoice` — the whole query used
         // to become an OR, so anything with a matching subject came back too.
         let q = parse_query("from:alice OR from:bob subject:invoice");
-        assert_eq!(q.groups.len(), 2);
-        assert_eq!(q.groups[0].terms.len(), 2);
-        assert_eq!(q.groups[0].terms[0].field, SearchField::From);
-        assert_eq!(q.groups[0].terms[1].field, SearchField::From);
-        assert_eq!(q.groups[1].terms.len(), 1);
-        assert_eq!(q.groups[1].terms[0].field, SearchField::Subject);
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::And(vec![
+                QueryExpr::Or(vec![
+                    term(SearchField::From, "alice"),
+                    term(SearchField::From, "bob"),
+                ]),
+                term(SearchField::Subject, "invoice"),
+            ]))
+        );
+        // An explicit AND reads the same as the implicit one.
+        assert_eq!(
+            q.expr,
+            parse_query("from:alice OR from:bob AND subject:invoice").expr
+        );
     }
 
     #[test]
-    fn test_or_chain_stays_in_one_group() {
+    fn test_or_chain_is_flat() {
         let q = parse_query("a OR b OR c");
-        assert_eq!(q.groups.len(), 1);
-        assert_eq!(q.groups[0].terms.len(), 3);
+        assert!(matches!(q.expr, Some(QueryExpr::Or(ref ops)) if ops.len() == 3));
+    }
+
+    #[test]
+    fn test_parentheses_override_precedence() {
+        let q = parse_query("from:alice OR (from:bob subject:invoice)");
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::Or(vec![
+                term(SearchField::From, "alice"),
+                QueryExpr::And(vec![
+                    term(SearchField::From, "bob"),
+                    term(SearchField::Subject, "invoice"),
+                ]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_nested_groups_and_negation() {
+        let q = parse_query("-(from:alice OR (subject:a NOT subject:b)) label:x");
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::And(vec![
+                not(QueryExpr::Or(vec![
+                    term(SearchField::From, "alice"),
+                    QueryExpr::And(vec![
+                        term(SearchField::Subject, "a"),
+                        not(term(SearchField::Subject, "b")),
+                    ]),
+                ])),
+                term(SearchField::Label, "x"),
+            ]))
+        );
+        // Spacing around the parentheses does not matter.
+        assert_eq!(
+            q.expr,
+            parse_query("NOT ( from:alice OR ( subject:a NOT subject:b ) ) label:x").expr
+        );
+        assert_eq!(
+            q.expr,
+            parse_query("NOT (from:alice OR (subject:a NOT subject:b)) label:x").expr
+        );
+    }
+
+    #[test]
+    fn test_filters_inside_or_and_not() {
+        let q = parse_query("(date:2023 OR has:attachment) -size:>1mb");
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::And(vec![
+                QueryExpr::Or(vec![
+                    QueryExpr::Condition(Condition::Date(DateFilter::Year(2023))),
+                    QueryExpr::Condition(Condition::HasAttachment(true)),
+                ]),
+                not(QueryExpr::Condition(Condition::Size(
+                    SizeFilter::GreaterThan(1024 * 1024)
+                ))),
+            ]))
+        );
     }
 
     #[test]
-    fn test_and_only_query_is_one_group_per_term() {
-        let q = parse_query("from:alice subject:invoice");
-        assert_eq!(q.groups.len(), 2);
-        assert!(q.groups.iter().all(|g| g.terms.len() == 1));
+    fn test_or_next_to_a_filter_joins_it() {
+        let q = parse_query("date:2024 OR from:alice");
+        assert_eq!(
+            q.expr,
+            Some(QueryExpr::Or(vec![
+                QueryExpr::Condition(Condition::Date(DateFilter::Year(2024))),
+                term(SearchField::From, "alice"),
+            ]))
+        );
     }
 
     #[test]
-    fn test_dangling_or_is_ignored() {
-        // Leading, trailing and doubled `OR` have nothing to join.
+    fn test_dangling_operators_are_ignored() {
+        // Leading, trailing and doubled `OR`/`AND` have nothing to join.
         for input in [
             "OR from:alice",
             "from:alice OR",
-            "from:alice OR OR from:bob",
+            "AND from:alice",
+            "from:alice AND",
+            "from:alice NOT",
         ] {
-            let q = parse_query(input);
-            assert!(!q.groups.is_empty(), "{input} parsed to nothing");
-            assert!(
-                q.all_terms().all(|t| t.field == SearchField::From),
-                "{input} produced a stray term"
+            assert_eq!(
+                parse_query(input).expr,
+                Some(term(SearchField::From, "alice")),
+                "{input}"
             );
         }
         // The doubled OR still joins the two it sits between.
-        assert_eq!(parse_query("from:alice OR OR from:bob").groups.len(), 1);
+        assert!(matches!(
+            parse_query("from:alice OR OR from:bob").expr,
+            Some(QueryExpr::Or(ref ops)) if ops.len() == 2
+        ));
+        assert!(matches!(
+            parse_query("from:alice OR AND from:bob").expr,
+            Some(QueryExpr::And(ref ops)) if ops.len() == 2
+        ));
     }
 
     #[test]
-    fn test_or_next_to_a_filter_falls_back_to_and() {
-        // `OR` joins terms, not filters: the date filter stays an AND
-        // condition and the term next to it opens its own group.
-        let q = parse_query("date:2024 OR from:alice");
-        assert_eq!(q.date_filters.len(), 1);
-        assert_eq!(q.groups.len(), 1);
-        assert_eq!(q.groups[0].terms.len(), 1);
+    fn test_unbalanced_parentheses() {
+        // A missing `)` is implied at the end.
+        assert_eq!(
+            parse_query("(from:alice OR from:bob").expr,
+            parse_query("(from:alice OR from:bob)").expr
+        );
+        // A `)` with nothing to close stays part of the word.
+        assert_eq!(
+            parse_query("smile:)").expr,
+            Some(term(SearchField::All, "smile:)"))
+        );
+        // Empty groups hold nothing.
+        assert!(parse_query("()").is_empty());
+        assert_eq!(
+            parse_query("() from:alice").expr,
+            Some(term(SearchField::From, "alice"))
+        );
+    }
+
+    #[test]
+    fn test_parentheses_inside_words_and_quotes() {
+        assert_eq!(
+            parse_query("subject:f(x)").expr,
+            Some(term(SearchField::Subject, "f(x)"))
+        );
+        let q = parse_query("(subject:\"a (b)\")");
+        assert_eq!(terms(&q)[0].operator, SearchOperator::Exact("a (b)".into()));
     }
 
     #[test]
-    fn test_group_needs_body() {
-        assert!(parse_query("body:hello").groups[0].needs_body());
-        assert!(parse_query("filename:report.pdf").groups[0].needs_body());
+    fn test_lowercase_operators_are_words() {
+        let q = parse_query("rock or roll");
+        assert_eq!(terms(&q).len(), 3);
+        assert!(matches!(q.expr, Some(QueryExpr::And(_))));
+    }
+
+    #[test]
+    fn test_evaluate_is_three_valued() {
+        let q = parse_query("(subject:a OR body:b) -from:c");
+        // Body undecided, but the subject already satisfies the OR.
+        let mut leaf = |c: &Condition| match c {
+            Condition::Term(t) if t.field == SearchField::Body => None,
+            Condition::Term(t) => Some(t.field == SearchField::Subject),
+            _ => unreachable!(),
+        };
+        assert_eq!(q.expr.as_ref().unwrap().evaluate(&mut leaf), Some(true));
+
+        // Without the subject, only the body can settle it.
+        let mut leaf = |c: &Condition| match c {
+            Condition::Term(t) if t.field == SearchField::Body => None,
+            Condition::Term(_) => Some(false),
+            _ => unreachable!(),
+        };
+        assert_eq!(q.expr.as_ref().unwrap().evaluate(&mut leaf), None);
+
+        // A false operand settles an AND regardless of the unknown one.
+        let q = parse_query("body:b from:c");
+        let mut leaf = |c: &Condition| match c {
+            Condition::Term(t) if t.field == SearchField::Body => None,
+            _ => Some(false),
+        };
+        assert_eq!(q.expr.as_ref().unwrap().evaluate(&mut leaf), Some(false));
+    }
+
+    #[test]
+    fn test_query_needs_body() {
+        assert!(parse_query("body:hello").needs_body());
+        assert!(parse_query("filename:report.pdf").needs_body());
+        assert!(parse_query("attachment:invoice").needs_body());
+        assert_eq!(
+            terms(&parse_query("attachment:\"total due\""))[0].field,
+            SearchField::Attachment
+        );
         // Free-text searches metadata *or* body, so it needs the body too.
-        assert!(parse_query("hello").groups[0].needs_body());
-        assert!(!parse_query("subject:hello").groups[0].needs_body());
-        // A mixed group needs the body because one of its terms does.
-        assert!(parse_query("subject:hello OR body:hello").groups[0].needs_body());
+        assert!(parse_query("hello").needs_body());
+        assert!(!parse_query("subject:hello").needs_body());
+        assert!(!parse_query("subject:hello date:2024").needs_body());
+        assert!(parse_query("subject:hello OR -(date:2024 body:hello)").needs_body());
     }
+
     #[test]
     fn test_parse_quoted_phrase() {
         let q = parse_query("subject:\"hello world\"");
@@ -708,22 +1912,278 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_needles_are_folded() {
+        let q = parse_query("Información subject:\"AÑO Nuevo\"");
+        let ops: Vec<&SearchOperator> = terms(&q).iter().map(|t| &t.operator).collect();
+        assert_eq!(ops[0], &SearchOperator::Contains("informacion".into()));
+        assert_eq!(ops[1], &SearchOperator::Exact("ano nuevo".into()));
+    }
+
+    #[test]
+    fn test_parse_fuzzy() {
+        let fuzzy = |term: &str, max_distance| SearchOperator::Fuzzy {
+            term: term.into(),
+            max_distance,
+        };
+        let q = parse_query("~Recieve subject:~mesa body:~informe~1 ~ab -~\"Canción\"~0");
+        let ops: Vec<&SearchOperator> = terms(&q).iter().map(|t| &t.operator).collect();
+        assert_eq!(ops[0], &fuzzy("recieve", 2));
+        assert_eq!(ops[1], &fuzzy("mesa", 1));
+        assert_eq!(ops[2], &fuzzy("informe", 1));
+        assert_eq!(ops[3], &fuzzy("ab", 0));
+        assert_eq!(ops[4], &fuzzy("cancion", 0));
+        assert_eq!(terms(&q)[1].field, SearchField::Subject);
+
+        // A `~` with no word after it is plain text.
+        let q = parse_query("~ ~~");
+        assert!(terms(&q)
+            .iter()
+            .all(|t| matches!(t.operator, SearchOperator::Contains(_))));
+    }
+
+    #[test]
+    fn test_parse_regex() {
+        let q = parse_query(r"subject:/INV-\d{6}/");
+        let SearchOperator::Regex(ref pattern) = terms(&q)[0].operator else {
+            panic!("expected Regex operator");
+        };
+        assert_eq!(pattern.as_str(), r"INV-\d{6}");
+        assert!(pattern.is_match("Re: INV-004211 overdue"));
+        // Case-sensitive unless flagged.
+        assert!(!pattern.is_match("inv-004211"));
+        assert!(q.errors.is_empty());
+        assert!(q.check().is_ok());
+
+        let q = parse_query(r"body:/\b\d{3}-\d{4}\b/i");
+        assert_eq!(terms(&q)[0].field, SearchField::Body);
+        assert!(q.needs_fulltext);
+        let SearchOperator::Regex(ref pattern) = terms(&q)[0].operator else {
+            panic!("expected Regex operator");
+        };
+        assert!(pattern.is_match("call 555-1234"));
+    }
+
+    #[test]
+    fn test_regex_keeps_spaces_slashes_and_parentheses() {
+        let q = parse_query(r"(/foo bar\/baz (x|y)/i OR from:alice) -subject:/a b/");
+        let patterns: Vec<&str> = q
+            .all_terms()
+            .filter_map(|t| match &t.operator {
+                SearchOperator::Regex(p) => Some(p.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(patterns, vec![r"foo bar\/baz (x|y)", "a b"]);
+        assert!(matches!(q.expr, Some(QueryExpr::And(ref ops)) if ops.len() == 2));
+    }
+
+    #[test]
+    fn test_slashes_that_are_not_patterns_stay_text() {
+        for input in ["/usr/bin", "and/or", "//", "a/b/"] {
+            let q = parse_query(input);
+            assert!(
+                matches!(terms(&q)[0].operator, SearchOperator::Contains(_)),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_regex_is_reported() {
+        let q = parse_query(r"subject:/INV-\d{6/ from:alice");
+        // The broken term is left out and the rest still parses.
+        assert_eq!(q.expr, Some(term(SearchField::From, "alice")));
+        assert_eq!(q.errors.len(), 1);
+        assert_eq!(q.errors[0].pattern, r"INV-\d{6");
+        assert!(!q.errors[0].reason.contains('\n'));
+        let message = q.check().unwrap_err().to_string();
+        assert!(message.contains(r"/INV-\d{6/"), "{message}");
+    }
+
+    #[test]
+    fn test_parse_header_field() {
+        let q = parse_query("header:List-Id:dev.lists.example.com");
+        assert_eq!(
+            q.expr,
+            Some(term(
+                SearchField::Header("list-id".into()),
+                "dev.lists.example.com"
+            ))
+        );
+        assert!(q.needs_fulltext);
+        assert!(q.needs_body());
+
+        // Quotes, patterns and an empty value (the header is present).
+        let q = parse_query(
+            r#"header:X-Mailer:"Apple Mail" header:Received:/from \w+\.google/ header:X-Spam-Flag:"#,
+        );
+        let ops: Vec<&SearchOperator> = q.all_terms().map(|t| &t.operator).collect();
+        assert_eq!(ops[0], &SearchOperator::Exact("apple mail".into()));
+        assert!(matches!(ops[1], SearchOperator::Regex(_)));
+        assert_eq!(ops[2], &SearchOperator::Contains(String::new()));
+
+        // The subject is kept in the index, so it needs no message read.
+        let q = parse_query("header:Subject:invoice");
+        assert!(!q.needs_fulltext);
+        assert!(!q.needs_body());
+
+        // Without a name it is plain text.
+        assert_eq!(terms(&parse_query("header::x"))[0].field, SearchField::All);
+        assert_eq!(terms(&parse_query("header:x"))[0].field, SearchField::All);
+    }
+
+    #[test]
+    fn test_parse_recipient_fields() {
+        let q = parse_query("bcc:boss reply-to:list delivered-to:me participant:alice");
+        let fields: Vec<&SearchField> = q.all_terms().map(|t| &t.field).collect();
+        assert_eq!(
+            fields,
+            [
+                &SearchField::Bcc,
+                &SearchField::ReplyTo,
+                &SearchField::DeliveredTo,
+                &SearchField::Participant
+            ]
+        );
+        assert!(!q.needs_body(), "recipients are kept in the index");
+        let shown: Vec<String> = q.all_terms().map(|t| t.to_string()).collect();
+        assert_eq!(
+            shown,
+            [
+                "bcc:boss",
+                "reply-to:list",
+                "delivered-to:me",
+                "participant:alice"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_state_filters() {
+        let q = parse_query("is:unread tag:review is:starred -is:read");
+        assert_eq!(terms(&q).len(), 1);
+        assert_eq!(terms(&q)[0].field, SearchField::Tag);
+        let conditions = q.conditions();
+        assert!(conditions.contains(&&Condition::Is(MessageStatus::Unread)));
+        assert!(conditions.contains(&&Condition::Is(MessageStatus::Starred)));
+        assert!(!q.needs_body());
+        assert_eq!(terms(&q)[0].to_string(), "tag:review");
+        assert_eq!(
+            Condition::Is(MessageStatus::Starred).to_string(),
+            "is:starred"
+        );
+        assert_eq!(
+            serde_json::to_value(Condition::Is(MessageStatus::Unread)).unwrap(),
+            serde_json::json!({ "is": "unread" })
+        );
+        // An unknown state is searched as text.
+        assert!(parse_query("is:pinned").conditions().is_empty());
+    }
+
     #[test]
     fn test_parse_combined_query() {
         let q = parse_query("from:user1 subject:budget date:2024-01..2024-06 has:attachment");
         assert_eq!(terms(&q).len(), 2);
         assert_eq!(terms(&q)[0].field, SearchField::From);
         assert_eq!(terms(&q)[1].field, SearchField::Subject);
-        assert_eq!(q.date_filters.len(), 1);
-        assert_eq!(q.has_attachment, Some(true));
+        assert_eq!(dates(&q).len(), 1);
+        assert!(q.conditions().contains(&&Condition::HasAttachment(true)));
+    }
+
+    #[test]
+    fn test_query_json_round_trip() {
+        let q = parse_query_at(
+            r#"(from:alice OR ~recieve) -has:attachment body:/INV-\d+/i header:List-Id:dev subject:"q1 report" date:2024-01 size:>1mb newer:7d"#,
+            utc("2024-06-15T12:00:00Z"),
+            QueryTimezone::Fixed(Utc.fix()),
+        );
+        let read = SearchQuery::from_json(&q.to_json()).unwrap();
+        assert_eq!(read.expr, q.expr);
+        assert!(read.needs_fulltext);
+
+        let json: serde_json::Value = serde_json::from_str(&q.to_json()).unwrap();
+        assert_eq!(
+            json["expr"]["and"][2],
+            serde_json::json!({"term": {"field": "body", "operator": {"regex": {
+                "source": "INV-\\d+", "case_insensitive": true
+            }}}})
+        );
+        assert_eq!(
+            json["expr"]["and"][5],
+            serde_json::json!({"date": {"month": [2024, 1]}})
+        );
+    }
+
+    #[test]
+    fn test_query_from_json() {
+        // Written by hand: needles are folded and header names lowercased
+        // as the parser would leave them; the rest is optional.
+        let q = SearchQuery::from_json(
+            r#"{"expr": {"or": [
+                {"term": {"field": "subject", "operator": {"exact": "Información"}}},
+                {"not": {"term": {"field": {"header": "X-Mailer"}, "operator": {"contains": "Outlook"}}}}
+            ]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            q.expr,
+            parse_query(r#"subject:"información" OR -header:X-Mailer:outlook"#).expr
+        );
+        assert!(q.needs_fulltext);
+        assert!(q.errors.is_empty());
+
+        assert!(SearchQuery::from_json(r#"{"expr": null}"#)
+            .unwrap()
+            .is_empty());
+        for bad in [
+            "not json",
+            r#"{"expr": {"term": {"field": "nowhere", "operator": {"contains": "x"}}}}"#,
+            r#"{"expr": {"term": {"field": "body", "operator": {"regex": {"source": "("}}}}}"#,
+        ] {
+            assert!(
+                matches!(SearchQuery::from_json(bad), Err(MboxError::InvalidQuery(_))),
+                "{bad}"
+            );
+        }
+        let err = SearchQuery::from_json(
+            r#"{"expr": {"and": [{"term": {"field": "body", "operator": {"regex": {"source": "("}}}}]}}"#,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("invalid regular expression /(/"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let q = parse_query_at(
+            r#"from:José (subject:"Hola Mundo" OR ~recieve~1) -has:attachment older:1d size:<10kb"#,
+            utc("2024-06-15T12:00:00Z"),
+            QueryTimezone::Fixed(Utc.fix()),
+        );
+        assert_eq!(
+            q.explain(),
+            "AND\n\
+             \x20 from:jose\n\
+             \x20 OR\n\
+             \x20   subject:\"hola mundo\"\n\
+             \x20   ~recieve~1\n\
+             \x20 NOT\n\
+             \x20   has:attachment\n\
+             \x20 older:2024-06-14T12:00:00Z\n\
+             \x20 size:<10240\n"
+        );
+        assert_eq!(parse_query("body:/a b/i").explain(), "body:/a b/i\n");
+        assert_eq!(parse_query("").explain(), "");
     }
 
     #[test]
     fn test_parse_empty_query() {
         let q = parse_query("");
-        assert!(terms(&q).is_empty());
-        assert!(q.date_filters.is_empty());
-        assert!(q.size_filters.is_empty());
-        assert!(q.has_attachment.is_none());
+        assert!(q.is_empty());
+        assert!(q.conditions().is_empty());
+        assert!(parse_query("   ").is_empty());
     }
 }
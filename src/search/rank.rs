@@ -0,0 +1,465 @@
+//! Relevance ranking of search results, with snippets of the body matches.
+//!
+//! [`super::execute`] returns the matching messages in index order. [`rank`]
+//! orders them by how well they match instead: every term the query looks
+//! for (terms under a `NOT` only filter) scores by how often it occurs in
+//! each field, a hit in the subject or the sender counting more than one in
+//! the body, and the total is boosted for recent messages. Repeated hits
+//! count less and less (`1 + ln tf`), so a long message quoting a word fifty
+//! times does not bury a short one about it.
+//!
+//! Recency is measured from the newest of the results, not from today, so an
+//! archive from years ago ranks the same whenever it is searched.
+//!
+//! Messages are read when the query has free-text or `body:` terms, to count
+//! body hits and cut [`Snippet`]s around them.
+
+use std::ops::Range;
+use std::path::Path;
+
+use tracing::debug;
+
+use crate::model::address::SharedAddress;
+use crate::model::mail::MailEntry;
+use crate::store::reader::MboxStore;
+
+use super::normalize::{self, fold_with_offsets, is_near};
+use super::query::{Condition, QueryExpr, SearchField, SearchOperator, SearchQuery, SearchTerm};
+
+/// Weight of a hit in the subject.
+const SUBJECT_BOOST: f64 = 3.0;
+/// Weight of a hit in the sender's address or name.
+const FROM_BOOST: f64 = 2.0;
+/// Weight of a hit in a recipient, label or the body.
+const OTHER_BOOST: f64 = 1.0;
+/// How much a message sent at the same time as the newest result gains over
+/// a very old one: its score is multiplied by up to `1 + RECENCY_BOOST`.
+const RECENCY_BOOST: f64 = 0.5;
+/// Age, in days before the newest result, at which the recency boost halves.
+const RECENCY_HALF_LIFE_DAYS: f64 = 365.0;
+/// Characters of context kept on each side of a body match.
+const SNIPPET_CONTEXT: usize = 40;
+/// Longest a snippet grows, in bytes, by taking in nearby matches.
+const MAX_SNIPPET_LEN: usize = 4 * SNIPPET_CONTEXT;
+/// Snippets kept per message.
+const MAX_SNIPPETS: usize = 3;
+
+/// A search result with its relevance.
+#[derive(Debug, Clone)]
+pub struct RankedHit {
+    /// Index of the entry in the mailbox index.
+    pub index: usize,
+    /// Relevance score; only meaningful compared to other hits of the same
+    /// search.
+    pub score: f64,
+    /// Passages of the body around the matches, in body order.
+    pub snippets: Vec<Snippet>,
+}
+
+/// A passage of a message body around one or more matches, on one line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// The passage, whitespace collapsed; starts or ends with `…` where the
+    /// body was cut.
+    pub text: String,
+    /// Byte ranges of [`text`](Self::text) that matched, in order.
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl Snippet {
+    /// The highlight ranges as character offsets, for consumers that do not
+    /// index strings by UTF-8 byte (JSON output).
+    pub fn char_highlights(&self) -> Vec<Range<usize>> {
+        let chars = |byte: usize| self.text[..byte].chars().count();
+        self.highlights
+            .iter()
+            .map(|r| chars(r.start)..chars(r.end))
+            .collect()
+    }
+}
+
+/// Rank `results` (entry indices, as returned by [`super::execute`] for
+/// `query`), best match first; ties keep index order.
+///
+/// `progress` receives `(processed, total)` messages while bodies are read
+/// and returns `false` to cancel, in which case
+/// [`crate::error::MboxError::Cancelled`] is returned. A message that cannot
+/// be read is ranked on its metadata alone.
+pub fn rank(
+    mbox_path: &Path,
+    entries: &[MailEntry],
+    query: &SearchQuery,
+    results: &[usize],
+    progress: &dyn Fn(usize, usize) -> bool,
+) -> crate::error::Result<Vec<RankedHit>> {
+    let mut terms = Vec::new();
+    if let Some(expr) = &query.expr {
+        positive_terms(expr, false, &mut terms);
+    }
+    let body_terms: Vec<&SearchTerm> = terms
+        .iter()
+        .copied()
+        .filter(|t| matches!(t.field, SearchField::All | SearchField::Body))
+        .collect();
+
+    let newest = results.iter().map(|&i| entries[i].date).max();
+    let mut store = if body_terms.is_empty() {
+        None
+    } else {
+        Some(MboxStore::open(mbox_path)?)
+    };
+
+    let total = results.len();
+    let mut hits = Vec::with_capacity(total);
+    for (i, &idx) in results.iter().enumerate() {
+        let entry = &entries[idx];
+        let mut score: f64 = terms.iter().map(|t| metadata_score(entry, t)).sum();
+        let mut snippets = Vec::new();
+
+        if let Some(store) = &mut store {
+            if !progress(i, total) {
+                return Err(crate::error::MboxError::Cancelled);
+            }
+            match store.get_message(entry) {
+                Ok(body) => {
+                    let text = body.text.as_deref().unwrap_or("");
+                    let mut matches = Vec::new();
+                    for term in &body_terms {
+                        let found = find_matches(text, &term.operator);
+                        score += OTHER_BOOST * damped(found.len());
+                        matches.extend(found);
+                    }
+                    snippets = snippets_around(text, matches);
+                }
+                Err(e) => {
+                    debug!(offset = entry.offset, error = %e, "Ranking message without its body");
+                }
+            }
+        }
+
+        // A query with no terms (only filters) ranks by date alone.
+        if terms.is_empty() {
+            score = 1.0;
+        }
+        if let Some(newest) = newest {
+            let age_days = (newest - entry.date).num_seconds().max(0) as f64 / 86_400.0;
+            score *= 1.0 + RECENCY_BOOST * 0.5f64.powf(age_days / RECENCY_HALF_LIFE_DAYS);
+        }
+
+        hits.push(RankedHit {
+            index: idx,
+            score,
+            snippets,
+        });
+    }
+    if store.is_some() {
+        let _ = progress(total, total);
+    }
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(hits)
+}
+
+/// Collect the terms of `expr` a matching message is meant to contain: those
+/// under an even number of `NOT`s.
+fn positive_terms<'a>(expr: &'a QueryExpr, negated: bool, out: &mut Vec<&'a SearchTerm>) {
+    match expr {
+        QueryExpr::Condition(Condition::Term(term)) if !negated => out.push(term),
+        QueryExpr::Condition(_) => {}
+        QueryExpr::Not(inner) => positive_terms(inner, !negated, out),
+        QueryExpr::And(operands) | QueryExpr::Or(operands) => {
+            for operand in operands {
+                positive_terms(operand, negated, out);
+            }
+        }
+    }
+}
+
+/// Score of a term in the indexed fields of an entry. Terms on fields the
+/// index does not hold (body, attachment names, other headers) score here
+/// only as far as they are free-text; the body is scored by the caller.
+fn metadata_score(entry: &MailEntry, term: &SearchTerm) -> f64 {
+    let op = &term.operator;
+    let hits = |text: &str| find_matches(text, op).len();
+    let from = || hits(&entry.from.address) + hits(&entry.from.display_name);
+    let recipients = |list: &[SharedAddress]| {
+        list.iter()
+            .map(|a| hits(&a.address) + hits(&a.display_name))
+            .sum::<usize>()
+    };
+    match &term.field {
+        SearchField::All => {
+            SUBJECT_BOOST * damped(hits(&entry.subject))
+                + FROM_BOOST * damped(from())
+                + OTHER_BOOST * damped(recipients(&entry.to))
+        }
+        SearchField::Subject => SUBJECT_BOOST * damped(hits(&entry.subject)),
+        SearchField::From => FROM_BOOST * damped(from()),
+        SearchField::To => OTHER_BOOST * damped(recipients(&entry.to)),
+        SearchField::Cc => OTHER_BOOST * damped(recipients(&entry.cc)),
+        SearchField::Bcc => OTHER_BOOST * damped(recipients(&entry.bcc)),
+        SearchField::ReplyTo => OTHER_BOOST * damped(recipients(&entry.reply_to)),
+        SearchField::DeliveredTo => OTHER_BOOST * damped(recipients(&entry.delivered_to)),
+        SearchField::Participant => {
+            let everyone_else = entry
+                .recipients()
+                .map(|a| hits(&a.address) + hits(&a.display_name))
+                .sum();
+            FROM_BOOST * damped(from()) + OTHER_BOOST * damped(everyone_else)
+        }
+        SearchField::Label => OTHER_BOOST * damped(entry.labels.iter().map(|l| hits(l)).sum()),
+        SearchField::Tag => OTHER_BOOST * damped(entry.tags.iter().map(|t| hits(t)).sum()),
+        SearchField::Header(name) if name == "subject" => {
+            SUBJECT_BOOST * damped(hits(&entry.subject))
+        }
+        SearchField::Body
+        | SearchField::Filename
+        | SearchField::Attachment
+        | SearchField::AttachmentType
+        | SearchField::MessageId
+        | SearchField::Header(_) => 0.0,
+    }
+}
+
+/// `1 + ln tf` for a term found `tf` times, 0 when it is not found.
+fn damped(tf: usize) -> f64 {
+    if tf == 0 {
+        0.0
+    } else {
+        1.0 + (tf as f64).ln()
+    }
+}
+
+/// Byte ranges of `text` matching `op`, in order and not overlapping.
+/// Substring needles and fuzzy words (already folded) are found in the
+/// folded text, as the search matched them.
+fn find_matches(text: &str, op: &SearchOperator) -> Vec<Range<usize>> {
+    match op {
+        SearchOperator::Contains(needle) | SearchOperator::Exact(needle) => {
+            if needle.is_empty() {
+                return Vec::new();
+            }
+            let (folded, origin) = fold_with_offsets(text);
+            folded
+                .match_indices(needle.as_str())
+                .map(|(at, m)| origin[at]..origin[at + m.len()])
+                .filter(|r| !r.is_empty())
+                .collect()
+        }
+        SearchOperator::Fuzzy { term, max_distance } => {
+            let (folded, origin) = fold_with_offsets(text);
+            normalize::words(&folded)
+                .filter(|(_, word)| {
+                    normalize::words(term).any(|(_, wanted)| is_near(word, wanted, *max_distance))
+                })
+                .map(|(at, word)| origin[at]..origin[at + word.len()])
+                .filter(|r| !r.is_empty())
+                .collect()
+        }
+        SearchOperator::Regex(pattern) => pattern.find_iter(text).collect(),
+    }
+}
+
+/// Cut up to [`MAX_SNIPPETS`] snippets of `text` around `matches`, merging
+/// matches whose context overlaps into one snippet.
+fn snippets_around(text: &str, mut matches: Vec<Range<usize>>) -> Vec<Snippet> {
+    matches.sort_by_key(|r| (r.start, r.end));
+    // Drop matches overlapping an earlier one (two terms hitting the same
+    // word).
+    let mut kept: Vec<Range<usize>> = Vec::with_capacity(matches.len());
+    for m in matches {
+        match kept.last_mut() {
+            Some(last) if m.start < last.end => last.end = last.end.max(m.end),
+            _ => kept.push(m),
+        }
+    }
+
+    // A match whose context overlaps the previous snippet joins it, as long
+    // as that stays under MAX_SNIPPET_LEN; otherwise it starts a new one,
+    // never repeating text already shown.
+    let mut windows: Vec<(Range<usize>, Vec<Range<usize>>)> = Vec::new();
+    for m in kept {
+        let window = context_start(text, m.start)..context_end(text, m.end);
+        let shown = windows.last().map_or(0, |(last, _)| last.end);
+        if let Some((last, inside)) = windows.last_mut() {
+            if m.end <= last.end {
+                inside.push(m);
+                continue;
+            }
+            if window.start <= last.end && window.end - last.start <= MAX_SNIPPET_LEN {
+                last.end = window.end;
+                inside.push(m);
+                continue;
+            }
+        }
+        if windows.len() == MAX_SNIPPETS {
+            break;
+        }
+        windows.push((window.start.max(shown).min(m.start)..window.end, vec![m]));
+    }
+
+    windows
+        .into_iter()
+        .map(|(window, inside)| build_snippet(text, window, &inside))
+        .collect()
+}
+
+/// Start of the context before a match at `at`: [`SNIPPET_CONTEXT`]
+/// characters back, moved forward to the start of a word.
+fn context_start(text: &str, at: usize) -> usize {
+    let Some((start, _)) = text[..at].char_indices().rev().nth(SNIPPET_CONTEXT - 1) else {
+        return 0;
+    };
+    match text[start..at].find(char::is_whitespace) {
+        Some(space) => start + space,
+        None => start,
+    }
+}
+
+/// End of the context after a match ending at `at`: [`SNIPPET_CONTEXT`]
+/// characters on, moved back to the end of a word.
+fn context_end(text: &str, at: usize) -> usize {
+    let Some((end, _)) = text[at..].char_indices().nth(SNIPPET_CONTEXT) else {
+        return text.len();
+    };
+    let end = at + end;
+    match text[at..end].rfind(char::is_whitespace) {
+        Some(space) => at + space,
+        None => end,
+    }
+}
+
+/// The snippet of `text[window]`, with `matches` (inside the window)
+/// highlighted. Runs of whitespace and control characters become one space.
+fn build_snippet(text: &str, window: Range<usize>, matches: &[Range<usize>]) -> Snippet {
+    let mut out = String::new();
+    let mut highlights = Vec::with_capacity(matches.len());
+    // Leading whitespace is dropped.
+    let mut after_space = true;
+    let mut push = |out: &mut String, piece: &str| {
+        for c in piece.chars() {
+            if c.is_whitespace() || c.is_control() {
+                if !after_space {
+                    out.push(' ');
+                    after_space = true;
+                }
+            } else {
+                out.push(c);
+                after_space = false;
+            }
+        }
+    };
+
+    if window.start > 0 {
+        out.push('…');
+    }
+    let mut pos = window.start;
+    for m in matches {
+        push(&mut out, &text[pos..m.start]);
+        let start = out.len();
+        push(&mut out, &text[m.start..m.end]);
+        highlights.push(start..out.len());
+        pos = m.end;
+    }
+    push(&mut out, &text[pos..window.end]);
+
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    if window.end < text.len() {
+        out.push('…');
+    }
+    Snippet {
+        text: out,
+        highlights,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::builder;
+    use crate::search::query::parse_query;
+    use crate::testing::temp_fixture;
+
+    /// Rank the results of `query` over simple.mbox, as subjects.
+    fn ranked(query: &str) -> Vec<(String, RankedHit)> {
+        let (_dir, mbox_path) = temp_fixture("simple.mbox");
+        let entries = builder::build_index(&mbox_path, true, None).unwrap();
+        let (parsed, results) = crate::search::execute(&mbox_path, &entries, query, None).unwrap();
+        rank(&mbox_path, &entries, &parsed, &results, &|_, _| true)
+            .unwrap()
+            .into_iter()
+            .map(|hit| (entries[hit.index].subject.clone(), hit))
+            .collect()
+    }
+
+    #[test]
+    fn test_subject_hits_rank_above_body_hits() {
+        let hits = ranked("message");
+        let subjects: Vec<&str> = hits.iter().map(|(s, _)| s.as_str()).collect();
+        // Subject and body beat body alone.
+        assert_eq!(
+            subjects,
+            ["Message with From in body", "Hello World"],
+            "{hits:?}"
+        );
+        assert!(hits[0].1.score > hits[1].1.score);
+    }
+
+    #[test]
+    fn test_filters_alone_rank_newest_first() {
+        let subjects: Vec<String> = ranked("date:2024").into_iter().map(|(s, _)| s).collect();
+        assert_eq!(
+            subjects,
+            [
+                "Meeting tomorrow",
+                "Message with From in body",
+                "Café con leña",
+                "Re: Hello World",
+                "Hello World",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snippets_highlight_body_matches() {
+        let hits = ranked("body:perspective");
+        let snippet = &hits[0].1.snippets[0];
+        assert_eq!(
+            snippet.text,
+            "…message has a tricky line: From the perspective of the user, this should not be a…"
+        );
+        assert_eq!(snippet.highlights.len(), 1);
+        assert_eq!(&snippet.text[snippet.highlights[0].clone()], "perspective");
+
+        // Case-insensitive, and mapped back through lowercasing.
+        let hits = ranked("body:ÁÉÍ");
+        let snippet = &hits[0].1.snippets[0];
+        assert_eq!(&snippet.text[snippet.highlights[0].clone()], "áéí");
+        assert_eq!(snippet.char_highlights(), vec![35..38]);
+
+        // Fuzzy words highlight the word they matched.
+        let hits = ranked("~perspectve");
+        let snippet = &hits[0].1.snippets[0];
+        assert_eq!(&snippet.text[snippet.highlights[0].clone()], "perspective");
+
+        // Excluded words are not highlighted, metadata-only queries have none.
+        assert_eq!(
+            ranked("confirm -body:perspective")[0].1.snippets[0].highlights,
+            vec![7..14]
+        );
+        assert!(ranked("from:user1")[0].1.snippets.is_empty());
+    }
+
+    #[test]
+    fn test_snippets_are_merged_and_capped() {
+        let text = format!("{} end", "word filler ".repeat(60));
+        let query = parse_query("word");
+        let term = query.all_terms().next().unwrap();
+        let snippets = snippets_around(&text, find_matches(&text, &term.operator));
+        assert_eq!(snippets.len(), MAX_SNIPPETS);
+        // Matches close together share a snippet.
+        assert!(snippets[0].highlights.len() > 1);
+        assert!(snippets[0].text.starts_with("word") && snippets[0].text.ends_with('…'));
+    }
+}
@@ -0,0 +1,330 @@
+//! gzip decoding with resumable checkpoints.
+//!
+//! The deflate stream is inflated with `miniz_oxide`'s low-level decoder into
+//! a 32 KB ring buffer, which doubles as the history window. When recording,
+//! the decoder stops at every deflate block boundary; there the bit offset
+//! into the current input byte and the window are all the state needed to
+//! resume, so that is what a checkpoint stores (the window deflated, to keep
+//! the table small). Multi-member files (`cat a.gz b.gz`, `bgzip`) are read
+//! member by member, and a member start is a checkpoint with no state.
+//!
+//! The same decoder reads the raw deflate streams of zip members
+//! ([`Framing::Deflate`]), which have no gzip header or trailer.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+use miniz_oxide::inflate::core::inflate_flags::{
+    TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY,
+};
+use miniz_oxide::inflate::core::{decompress, BlockBoundaryState, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+use tracing::warn;
+
+use super::{Checkpoint, Counting, Recorder};
+
+/// Size of the deflate history window, and of the output ring buffer.
+const WINDOW_SIZE: usize = 32 * 1024;
+const WINDOW_MASK: u64 = WINDOW_SIZE as u64 - 1;
+
+/// gzip header flag bits (RFC 1952).
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// What surrounds the deflate data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Framing {
+    /// One or more gzip members, each with a header and a trailer.
+    Gzip,
+    /// A single bare deflate stream (a zip member), whose length and
+    /// checksum are known to the caller.
+    Deflate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Expecting a member header (or the end of the file).
+    Header,
+    /// Inside a member's deflate stream.
+    Inflate,
+    /// The deflate stream ended; the 8-byte trailer follows.
+    Trailer,
+    Done,
+}
+
+pub(super) struct GzipReader {
+    input: Counting<BufReader<File>>,
+    framing: Framing,
+    decoder: Box<DecompressorOxide>,
+    window: Box<[u8; WINDOW_SIZE]>,
+    phase: Phase,
+    /// Decompressed bytes produced so far (including `pending`).
+    produced: u64,
+    /// Decoded bytes in `window` not yet handed to the caller.
+    pending_start: usize,
+    pending_len: usize,
+    /// Running CRC and length of the current member, when decoding started
+    /// at the member's beginning and the trailer can therefore be verified.
+    member_check: Option<(crc32fast::Hasher, u32)>,
+    recorder: Option<Recorder>,
+}
+
+impl GzipReader {
+    pub(super) fn new(input: Counting<BufReader<File>>, recorder: Option<Recorder>) -> Self {
+        Self {
+            input,
+            framing: Framing::Gzip,
+            decoder: Box::default(),
+            window: Box::new([0; WINDOW_SIZE]),
+            phase: Phase::Header,
+            produced: 0,
+            pending_start: 0,
+            pending_len: 0,
+            member_check: None,
+            recorder,
+        }
+    }
+
+    /// A bare deflate stream whose first byte is at `offset` in the content.
+    pub(super) fn deflate(
+        input: Counting<BufReader<File>>,
+        offset: u64,
+        recorder: Option<Recorder>,
+    ) -> Self {
+        let mut reader = Self::new(input, recorder);
+        reader.framing = Framing::Deflate;
+        reader.phase = Phase::Inflate;
+        reader.produced = offset;
+        reader
+    }
+
+    /// Resume at `checkpoint`; `input` is positioned at its source offset.
+    pub(super) fn resume(
+        input: Counting<BufReader<File>>,
+        checkpoint: &Checkpoint,
+        framing: Framing,
+    ) -> io::Result<Self> {
+        let mut reader = match framing {
+            Framing::Gzip => Self::new(input, None),
+            Framing::Deflate => Self::deflate(input, 0, None),
+        };
+        reader.produced = checkpoint.offset;
+        if checkpoint.state.is_empty() {
+            return Ok(reader);
+        }
+
+        let [num_bits, bit_buf, packed @ ..] = checkpoint.state.as_slice() else {
+            return Err(invalid_data("truncated gzip checkpoint"));
+        };
+        let window = miniz_oxide::inflate::decompress_to_vec(packed)
+            .map_err(|_| invalid_data("corrupt gzip checkpoint window"))?;
+        if window.len() != WINDOW_SIZE || *num_bits > 7 {
+            return Err(invalid_data("corrupt gzip checkpoint"));
+        }
+        reader.window.copy_from_slice(&window);
+        reader.decoder = Box::new(DecompressorOxide::from_block_boundary_state(
+            &BlockBoundaryState {
+                num_bits: *num_bits,
+                bit_buf: *bit_buf,
+                ..Default::default()
+            },
+        ));
+        reader.phase = Phase::Inflate;
+        Ok(reader)
+    }
+
+    pub(super) fn position(&self) -> u64 {
+        self.produced - self.pending_len as u64
+    }
+
+    pub(super) fn source_position(&self) -> u64 {
+        self.input.position
+    }
+
+    pub(super) fn take_recorder(&mut self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+
+    /// Record a checkpoint at the current output position if one is due.
+    fn offer_checkpoint(&mut self, at_member_start: bool) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        if !recorder.wants(self.produced) {
+            return;
+        }
+        let state = if at_member_start {
+            Vec::new()
+        } else {
+            let Some(boundary) = self.decoder.block_boundary_state() else {
+                return;
+            };
+            let mut state = vec![boundary.num_bits, boundary.bit_buf];
+            state.extend(miniz_oxide::deflate::compress_to_vec(&self.window[..], 1));
+            state
+        };
+        recorder.push(Checkpoint {
+            offset: self.produced,
+            source_offset: self.input.position,
+            state,
+        });
+    }
+
+    /// Parse a member header. Returns `false` at the end of the file.
+    fn read_member_header(&mut self) -> io::Result<bool> {
+        let first = match self.input.fill_buf()?.first() {
+            None => return Ok(false),
+            Some(&b) => b,
+        };
+        // Some tools pad the last member with zeros to a block size.
+        if first == 0 {
+            return Ok(false);
+        }
+        self.offer_checkpoint(true);
+
+        let mut fixed = [0u8; 10];
+        self.input.read_exact(&mut fixed).map_err(truncated)?;
+        if fixed[..2] != [0x1f, 0x8b] {
+            warn!(
+                offset = self.input.position - 10,
+                "Ignoring trailing data after the last gzip member"
+            );
+            return Ok(false);
+        }
+        if fixed[2] != 8 {
+            return Err(invalid_data("unsupported gzip compression method"));
+        }
+        let flags = fixed[3];
+        if flags & FEXTRA != 0 {
+            let mut len = [0u8; 2];
+            self.input.read_exact(&mut len).map_err(truncated)?;
+            let len = u64::from(u16::from_le_bytes(len));
+            let skipped = io::copy(&mut (&mut self.input).take(len), &mut io::sink())?;
+            if skipped < len {
+                return Err(truncated(io::ErrorKind::UnexpectedEof.into()));
+            }
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                let mut field = Vec::new();
+                self.input.read_until(0, &mut field)?;
+                if field.last() != Some(&0) {
+                    return Err(truncated(io::ErrorKind::UnexpectedEof.into()));
+                }
+            }
+        }
+        if flags & FHCRC != 0 {
+            let mut crc = [0u8; 2];
+            self.input.read_exact(&mut crc).map_err(truncated)?;
+        }
+
+        *self.decoder = DecompressorOxide::default();
+        self.member_check = Some((crc32fast::Hasher::new(), 0));
+        Ok(true)
+    }
+
+    /// Read and verify the member trailer (CRC-32 and length mod 2^32).
+    fn read_trailer(&mut self) -> io::Result<()> {
+        let mut trailer = [0u8; 8];
+        self.input.read_exact(&mut trailer).map_err(truncated)?;
+        if let Some((hasher, len)) = self.member_check.take() {
+            let crc = u32::from_le_bytes(trailer[..4].try_into().expect("4 bytes"));
+            let isize = u32::from_le_bytes(trailer[4..].try_into().expect("4 bytes"));
+            if hasher.finalize() != crc || len != isize {
+                return Err(invalid_data("gzip member checksum mismatch"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the decoder once, leaving any output in `pending`.
+    fn inflate(&mut self) -> io::Result<()> {
+        let input = self.input.fill_buf()?;
+        let mut flags = 0;
+        if !input.is_empty() {
+            flags |= TINFL_FLAG_HAS_MORE_INPUT;
+        }
+        if self.recorder.is_some() {
+            flags |= TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY;
+        }
+        let out_pos = (self.produced & WINDOW_MASK) as usize;
+        let (status, consumed, written) = decompress(
+            &mut self.decoder,
+            input,
+            &mut self.window[..],
+            out_pos,
+            flags,
+        );
+        self.input.consume(consumed);
+        self.pending_start = out_pos;
+        self.pending_len = written;
+        self.produced += written as u64;
+
+        match status {
+            TINFLStatus::Done => self.phase = Phase::Trailer,
+            TINFLStatus::BlockBoundary => self.offer_checkpoint(false),
+            TINFLStatus::NeedsMoreInput | TINFLStatus::HasMoreOutput => {}
+            TINFLStatus::FailedCannotMakeProgress => {
+                return Err(truncated(io::ErrorKind::UnexpectedEof.into()))
+            }
+            _ => return Err(invalid_data("corrupt gzip data")),
+        }
+        Ok(())
+    }
+}
+
+impl Read for GzipReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.pending_len > 0 {
+                let n = buf.len().min(self.pending_len);
+                let out = &self.window[self.pending_start..self.pending_start + n];
+                buf[..n].copy_from_slice(out);
+                if let Some((hasher, len)) = &mut self.member_check {
+                    hasher.update(out);
+                    *len = len.wrapping_add(n as u32);
+                }
+                self.pending_start += n;
+                self.pending_len -= n;
+                return Ok(n);
+            }
+            match self.phase {
+                Phase::Header => {
+                    self.phase = if self.read_member_header()? {
+                        Phase::Inflate
+                    } else {
+                        Phase::Done
+                    };
+                }
+                Phase::Inflate => self.inflate()?,
+                Phase::Trailer => {
+                    self.phase = match self.framing {
+                        Framing::Gzip => {
+                            self.read_trailer()?;
+                            Phase::Header
+                        }
+                        Framing::Deflate => Phase::Done,
+                    };
+                }
+                Phase::Done => return Ok(0),
+            }
+        }
+    }
+}
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+fn truncated(e: io::Error) -> io::Error {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "gzip stream is truncated")
+    } else {
+        e
+    }
+}
@@ -0,0 +1,864 @@
+//! Compressed mailbox sources: gzip, zstd and xz files, and zip archives,
+//! read in place.
+//!
+//! A compressed MBOX is indexed exactly like a plain one, except that every
+//! offset in the index addresses the *decompressed* stream. Reading a message
+//! from the middle of a 50 GB `.mbox.gz` would otherwise mean decompressing
+//! everything before it, so the sequential scan that builds the index also
+//! records a [`CheckpointTable`]: every few megabytes of output, the state a
+//! decoder needs to resume from that point. [`SeekableSource`] uses the table
+//! to decode only from the nearest checkpoint before a message.
+//!
+//! What a checkpoint holds depends on the codec:
+//!
+//! - **gzip** — the deflate bit position at a block boundary plus the 32 KB
+//!   history window (the `zran` technique), so any single-member file gets
+//!   checkpoints. Member starts need no state.
+//! - **zstd** — frame starts only. `zstd` writes one frame for a whole file
+//!   unless asked otherwise; files written by `pzstd` or `zstd --rsyncable`-style
+//!   tools have many frames and are fully seekable.
+//! - **xz** — the start of the file only. Reads after the first one continue
+//!   from the last position when moving forward, which is what message
+//!   listing and export do, but jumping backwards decodes from the start.
+//! - **zip** — the content is the concatenation of the archive's mailbox
+//!   members (see [`zip`]). Member starts are checkpoints, stored members are
+//!   seekable anywhere, and deflated ones get gzip-style checkpoints.
+//!
+//! A `.tar` (or `.tgz`, `.tar.zst`, …) needs nothing here: its mailboxes are
+//! read at their offsets in the (decompressed) tar stream, and [`tar`] parses
+//! the member headers while indexing.
+
+mod gzip;
+pub mod tar;
+mod xz;
+pub mod zip;
+mod zstd;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MboxError, Result};
+
+/// Minimum decompressed distance between two recorded checkpoints. Reading a
+/// message decodes at most this much data it does not need.
+pub const CHECKPOINT_SPACING: u64 = 8 * 1024 * 1024;
+
+/// Buffer size for the compressed input.
+const INPUT_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Compression format of a mailbox file, detected from its magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+    Xz,
+    /// A zip archive, whose content is its mailbox members.
+    Zip,
+}
+
+impl Codec {
+    /// Detect the codec of the file at `path` from its first bytes.
+    ///
+    /// Returns `None` for anything that is not a regular file starting with a
+    /// gzip, zstd, xz or zip signature, including plain MBOX files and directories.
+    /// The extension is not consulted: a `.mbox` that was gzipped in place
+    /// still opens, and a `.gz` that is really plain text is read as is.
+    pub fn detect(path: &Path) -> Result<Option<Self>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let file = File::open(path).map_err(|e| MboxError::io(path, e))?;
+        let mut magic = Vec::with_capacity(6);
+        file.take(6)
+            .read_to_end(&mut magic)
+            .map_err(|e| MboxError::io(path, e))?;
+        Ok(Self::from_magic(&magic))
+    }
+
+    fn from_magic(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if magic.starts_with(b"PK\x03\x04") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Short lowercase name, as used on the command line and in logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+            Self::Xz => "xz",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+/// A point in a compressed stream where decoding can resume.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Offset in the decompressed stream.
+    pub offset: u64,
+    /// Offset in the compressed file where decoding resumes.
+    pub source_offset: u64,
+    /// Codec-specific decoder state. Empty at the start of a gzip member or
+    /// zstd frame, where a fresh decoder is enough.
+    pub state: Vec<u8>,
+}
+
+impl Checkpoint {
+    /// The start of the file, which every table contains.
+    pub fn start() -> Self {
+        Self {
+            offset: 0,
+            source_offset: 0,
+            state: Vec::new(),
+        }
+    }
+}
+
+/// Resume points for a compressed mailbox, recorded while it is indexed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointTable {
+    /// Codec the table was recorded for.
+    pub codec: Codec,
+    /// Total decompressed size: the bound index offsets are checked against.
+    pub content_size: u64,
+    /// Checkpoints in increasing offset order, starting at offset 0.
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl CheckpointTable {
+    /// Whether the table is usable: non-empty, starting at 0, sorted, and
+    /// within the decompressed size.
+    pub fn is_well_formed(&self) -> bool {
+        self.checkpoints.first().is_some_and(|c| c.offset == 0)
+            && self
+                .checkpoints
+                .windows(2)
+                .all(|w| w[0].offset < w[1].offset && w[0].source_offset <= w[1].source_offset)
+            && self
+                .checkpoints
+                .last()
+                .is_some_and(|c| c.offset <= self.content_size)
+    }
+}
+
+/// Whether a file in an archive, named by its path inside the archive, is a
+/// mailbox: `*.mbox`, or Apple Mail's `Name.mbox/mbox`. macOS resource forks
+/// (`__MACOSX/`, `._*`) and other dotfiles are not.
+pub fn is_mailbox_member(name: &str) -> bool {
+    if name.ends_with('/') || name.starts_with("__MACOSX/") {
+        return false;
+    }
+    let mut parts = name.rsplit('/');
+    let file = parts.next().unwrap_or(name);
+    if file.starts_with('.') {
+        return false;
+    }
+    let is_mbox = |n: &str| n.len() > 5 && n.to_ascii_lowercase().ends_with(".mbox");
+    is_mbox(file) || (file == "mbox" && parts.next().is_some_and(is_mbox))
+}
+
+/// Collects checkpoints during a scan, at least `spacing` bytes apart.
+struct Recorder {
+    spacing: u64,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl Recorder {
+    fn new(spacing: u64) -> Self {
+        Self {
+            spacing,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Whether a checkpoint at decompressed `offset` should be recorded.
+    fn wants(&self, offset: u64) -> bool {
+        match self.checkpoints.last() {
+            None => true,
+            Some(last) => offset >= last.offset + self.spacing,
+        }
+    }
+
+    fn push(&mut self, checkpoint: Checkpoint) {
+        self.checkpoints.push(checkpoint);
+    }
+}
+
+/// Counts the bytes consumed from the underlying reader, so decoders can
+/// report exact positions in the compressed file.
+struct Counting<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R> Counting<R> {
+    fn new(inner: R, position: u64) -> Self {
+        Self { inner, position }
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Counting<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.position += amt as u64;
+    }
+}
+
+/// Buffered compressed input positioned at `source_offset`.
+fn open_input(path: &Path, source_offset: u64) -> io::Result<Counting<BufReader<File>>> {
+    let mut file = File::open(path)?;
+    if source_offset > 0 {
+        file.seek(SeekFrom::Start(source_offset))?;
+    }
+    Ok(Counting::new(
+        BufReader::with_capacity(INPUT_BUFFER_SIZE, file),
+        source_offset,
+    ))
+}
+
+/// Sequential reader over a mailbox file that yields its decompressed bytes.
+///
+/// A plain file is passed through unchanged, so callers do not need a
+/// separate code path for uncompressed mailboxes.
+pub struct SourceReader {
+    inner: Inner,
+    finished: bool,
+}
+
+enum Inner {
+    Plain(Counting<File>),
+    Gzip(Box<gzip::GzipReader>),
+    Zstd(Box<zstd::ZstdReader>),
+    Xz(Box<xz::XzReader>),
+    Zip(Box<zip::ZipReader>),
+}
+
+impl SourceReader {
+    /// Open the mailbox at `path`, detecting its codec.
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, None)
+    }
+
+    /// Like [`open`](Self::open), but also record a [`CheckpointTable`] while
+    /// reading. Retrieve it with [`take_checkpoints`](Self::take_checkpoints)
+    /// once the stream has been read to the end.
+    pub fn open_recording(path: &Path) -> Result<Self> {
+        Self::open_with(path, Some(CHECKPOINT_SPACING))
+    }
+
+    fn open_with(path: &Path, spacing: Option<u64>) -> Result<Self> {
+        let codec = Codec::detect(path)?;
+        let map_err = |e| MboxError::io(path, e);
+        let inner = match codec {
+            None => Inner::Plain(Counting::new(File::open(path).map_err(map_err)?, 0)),
+            Some(Codec::Zip) => Inner::Zip(Box::new(
+                zip::ZipReader::new(path, spacing.map(Recorder::new)).map_err(map_err)?,
+            )),
+            Some(codec) => {
+                let input = open_input(path, 0).map_err(map_err)?;
+                let recorder = spacing.map(Recorder::new);
+                match codec {
+                    Codec::Gzip => Inner::Gzip(Box::new(gzip::GzipReader::new(input, recorder))),
+                    Codec::Zstd => Inner::Zstd(Box::new(zstd::ZstdReader::new(input, recorder))),
+                    Codec::Xz => Inner::Xz(Box::new(xz::XzReader::new(input, recorder))),
+                    Codec::Zip => unreachable!("handled above"),
+                }
+            }
+        };
+        Ok(Self {
+            inner,
+            finished: false,
+        })
+    }
+
+    /// Open a compressed mailbox positioned at `checkpoint`.
+    pub fn resume(path: &Path, codec: Codec, checkpoint: &Checkpoint) -> Result<Self> {
+        let map_err = |e| MboxError::io(path, e);
+        if codec == Codec::Zip {
+            return Ok(Self {
+                inner: Inner::Zip(Box::new(
+                    zip::ZipReader::resume(path, checkpoint).map_err(map_err)?,
+                )),
+                finished: false,
+            });
+        }
+        let input = open_input(path, checkpoint.source_offset).map_err(map_err)?;
+        let inner = match codec {
+            Codec::Gzip => Inner::Gzip(Box::new(
+                gzip::GzipReader::resume(input, checkpoint, gzip::Framing::Gzip)
+                    .map_err(map_err)?,
+            )),
+            Codec::Zstd => Inner::Zstd(Box::new(zstd::ZstdReader::resume(input, checkpoint))),
+            Codec::Xz => {
+                // xz tables only ever hold the start of the file.
+                if checkpoint.offset != 0 {
+                    return Err(map_err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "xz sources can only be decoded from the start",
+                    )));
+                }
+                Inner::Xz(Box::new(xz::XzReader::new(input, None)))
+            }
+            Codec::Zip => unreachable!("handled above"),
+        };
+        Ok(Self {
+            inner,
+            finished: false,
+        })
+    }
+
+    /// Codec of the source, or `None` for a plain file.
+    pub fn codec(&self) -> Option<Codec> {
+        match self.inner {
+            Inner::Plain(_) => None,
+            Inner::Gzip(_) => Some(Codec::Gzip),
+            Inner::Zstd(_) => Some(Codec::Zstd),
+            Inner::Xz(_) => Some(Codec::Xz),
+            Inner::Zip(_) => Some(Codec::Zip),
+        }
+    }
+
+    /// Offset in the decompressed stream of the next byte to be read.
+    pub fn position(&self) -> u64 {
+        match &self.inner {
+            Inner::Plain(r) => r.position,
+            Inner::Gzip(r) => r.position(),
+            Inner::Zstd(r) => r.position(),
+            Inner::Xz(r) => r.position(),
+            Inner::Zip(r) => r.position(),
+        }
+    }
+
+    /// How far into the underlying file reading has got. For progress bars,
+    /// which measure against the size of the file on disk.
+    pub fn source_position(&self) -> u64 {
+        match &self.inner {
+            Inner::Plain(r) => r.position,
+            Inner::Gzip(r) => r.source_position(),
+            Inner::Zstd(r) => r.source_position(),
+            Inner::Xz(r) => r.source_position(),
+            Inner::Zip(r) => r.source_position(),
+        }
+    }
+
+    /// The checkpoints recorded by a reader from
+    /// [`open_recording`](Self::open_recording).
+    ///
+    /// `None` for a plain file, for a reader that was not recording, and when
+    /// the stream was not read to the end (the content size is unknown then).
+    pub fn take_checkpoints(&mut self) -> Option<CheckpointTable> {
+        if !self.finished {
+            return None;
+        }
+        let content_size = self.position();
+        let codec = self.codec()?;
+        let checkpoints = match &mut self.inner {
+            Inner::Plain(_) => return None,
+            Inner::Gzip(r) => r.take_recorder(),
+            Inner::Zstd(r) => r.take_recorder(),
+            Inner::Xz(r) => r.take_recorder(),
+            Inner::Zip(r) => r.take_recorder(),
+        }?
+        .checkpoints;
+        Some(CheckpointTable {
+            codec,
+            content_size,
+            checkpoints,
+        })
+    }
+
+    /// Skip `n` bytes of content, returning how many there were. Seeks where
+    /// the content is stored as is (a plain file, a stored zip member) and
+    /// decodes and discards it otherwise.
+    pub fn skip(&mut self, n: u64) -> io::Result<u64> {
+        let skipped = match &mut self.inner {
+            Inner::Plain(r) => {
+                let target = r
+                    .inner
+                    .metadata()?
+                    .len()
+                    .max(r.position)
+                    .min(r.position + n);
+                r.inner.seek(SeekFrom::Start(target))?;
+                let skipped = target - r.position;
+                r.position = target;
+                skipped
+            }
+            Inner::Zip(r) => r.skip(n)?,
+            _ => io::copy(&mut self.by_ref().take(n), &mut io::sink())?,
+        };
+        if skipped < n {
+            self.finished = true;
+        }
+        Ok(skipped)
+    }
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match &mut self.inner {
+            Inner::Plain(r) => r.read(buf)?,
+            Inner::Gzip(r) => r.read(buf)?,
+            Inner::Zstd(r) => r.read(buf)?,
+            Inner::Xz(r) => r.read(buf)?,
+            Inner::Zip(r) => r.read(buf)?,
+        };
+        if n == 0 && !buf.is_empty() {
+            self.finished = true;
+        }
+        Ok(n)
+    }
+}
+
+/// Random access to the decompressed content of a compressed mailbox.
+///
+/// Each read resumes from the nearest checkpoint at or before the requested
+/// offset, or keeps going with the previous decoder when the read lies ahead
+/// of it — reading messages in file order never decodes anything twice.
+pub struct SeekableSource {
+    path: PathBuf,
+    codec: Codec,
+    checkpoints: Vec<Checkpoint>,
+    cursor: Option<SourceReader>,
+}
+
+impl SeekableSource {
+    /// Random access to `path` through `checkpoints`, which must be sorted
+    /// and start at offset 0 (see [`CheckpointTable::is_well_formed`]).
+    pub fn new(path: impl Into<PathBuf>, codec: Codec, checkpoints: Vec<Checkpoint>) -> Self {
+        let checkpoints = if checkpoints.is_empty() {
+            vec![Checkpoint::start()]
+        } else {
+            checkpoints
+        };
+        Self {
+            path: path.into(),
+            codec,
+            checkpoints,
+            cursor: None,
+        }
+    }
+
+    /// Fill `buf` with the decompressed bytes starting at `offset`.
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let result = self.read_at_inner(offset, buf);
+        if result.is_err() {
+            // A failed decoder is in an unknown state; start afresh next time.
+            self.cursor = None;
+        }
+        result
+    }
+
+    fn read_at_inner(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let index = self
+            .checkpoints
+            .partition_point(|c| c.offset <= offset)
+            .saturating_sub(1);
+        let checkpoint = &self.checkpoints[index];
+
+        let reusable = self
+            .cursor
+            .as_ref()
+            .is_some_and(|c| (checkpoint.offset..=offset).contains(&c.position()));
+        if !reusable {
+            self.cursor = Some(SourceReader::resume(&self.path, self.codec, checkpoint)?);
+        }
+        let cursor = self.cursor.as_mut().expect("cursor was just set");
+
+        let skip = offset - cursor.position();
+        let map_err = |e| MboxError::io(&self.path, e);
+        if cursor.skip(skip).map_err(map_err)? < skip {
+            return Err(map_err(io::ErrorKind::UnexpectedEof.into()));
+        }
+        cursor.read_exact(buf).map_err(map_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mailbox-like text that does not compress to nothing, so the deflate
+    /// stream has many blocks.
+    fn sample_content(len: usize) -> Vec<u8> {
+        let words = [
+            "invoice",
+            "meeting",
+            "From",
+            "quarterly",
+            "re:",
+            "attached",
+            "thanks",
+        ];
+        let mut out = Vec::with_capacity(len + 64);
+        let mut seed: u32 = 7;
+        while out.len() < len {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            out.extend_from_slice(words[(seed >> 16) as usize % words.len()].as_bytes());
+            out.extend_from_slice(format!(" {} ", seed % 9973).as_bytes());
+            if seed % 11 == 0 {
+                out.push(b'\n');
+            }
+        }
+        out.truncate(len);
+        out
+    }
+
+    /// A single gzip member holding `data`.
+    fn gzip_member(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+        out.extend(miniz_oxide::deflate::compress_to_vec(data, 6));
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(data);
+        out.extend(crc.finalize().to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        out
+    }
+
+    /// A zip archive of `(name, data, deflate)` members.
+    fn zip_archive(members: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for &(name, data, deflate) in members {
+            let body = if deflate {
+                miniz_oxide::deflate::compress_to_vec(data, 6)
+            } else {
+                data.to_vec()
+            };
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(data);
+            let mut fields = Vec::new();
+            fields.extend(if deflate { [8u8, 0] } else { [0, 0] });
+            fields.extend([0; 4]); // time, date
+            fields.extend(crc.finalize().to_le_bytes());
+            fields.extend((body.len() as u32).to_le_bytes());
+            fields.extend((data.len() as u32).to_le_bytes());
+            fields.extend((name.len() as u16).to_le_bytes());
+
+            central.extend([0x50, 0x4b, 1, 2, 20, 0, 20, 0, 0, 0]);
+            central.extend(&fields);
+            central.extend([0; 12]); // extra, comment, disk, attributes
+            central.extend((out.len() as u32).to_le_bytes());
+            central.extend(name.as_bytes());
+
+            // A local extra field the central directory does not have.
+            out.extend([0x50, 0x4b, 3, 4, 20, 0, 0, 0]);
+            out.extend(&fields);
+            out.extend([4, 0]);
+            out.extend(name.as_bytes());
+            out.extend([0xfe, 0xca, 0, 0]);
+            out.extend(body);
+        }
+        let cd_offset = out.len() as u32;
+        out.extend(&central);
+        out.extend([0x50, 0x4b, 5, 6, 0, 0, 0, 0]);
+        out.extend((members.len() as u16).to_le_bytes());
+        out.extend((members.len() as u16).to_le_bytes());
+        out.extend((central.len() as u32).to_le_bytes());
+        out.extend(cd_offset.to_le_bytes());
+        out.extend([0, 0]);
+        out
+    }
+
+    fn write_temp(dir: &tempfile::TempDir, name: &str, data: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// Read `path` to the end while recording every `spacing` bytes.
+    fn record(path: &Path, spacing: u64) -> (Vec<u8>, CheckpointTable) {
+        let mut reader = SourceReader::open_with(path, Some(spacing)).unwrap();
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        (out, reader.take_checkpoints().expect("table after EOF"))
+    }
+
+    /// Random reads, forwards and backwards, must match the original.
+    fn assert_random_access(path: &Path, table: CheckpointTable, original: &[u8]) {
+        let mut source = SeekableSource::new(path, table.codec, table.checkpoints);
+        let len = original.len();
+        for &(offset, n) in &[
+            (len / 2, 5000),
+            (len / 10, 300),
+            (len - 1000, 1000),
+            (0, 64),
+            (len / 3, 70_000),
+            (len / 3 + 80_000, 10),
+        ] {
+            let mut buf = vec![0u8; n];
+            source.read_at(offset as u64, &mut buf).unwrap();
+            assert_eq!(buf, &original[offset..offset + n], "read at {offset}");
+        }
+        let mut buf = [0u8; 1];
+        assert!(source.read_at(len as u64, &mut buf).is_err());
+    }
+
+    #[test]
+    fn test_gzip_checkpoints_resume_mid_member() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = sample_content(1_500_000);
+        let path = write_temp(&dir, "mail.mbox.gz", &gzip_member(&content));
+
+        let (decoded, table) = record(&path, 64 * 1024);
+        assert_eq!(decoded, content);
+        assert_eq!(table.codec, Codec::Gzip);
+        assert_eq!(table.content_size, content.len() as u64);
+        assert!(table.is_well_formed());
+        assert!(table.checkpoints.len() > 5);
+        assert!(table.checkpoints[1..].iter().all(|c| !c.state.is_empty()));
+
+        assert_random_access(&path, table, &content);
+    }
+
+    #[test]
+    fn test_gzip_multi_member() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = sample_content(300_000);
+        let (a, b) = content.split_at(120_000);
+        let mut data = gzip_member(a);
+        data.extend(gzip_member(b));
+        let path = write_temp(&dir, "mail.mbox.gz", &data);
+
+        let (decoded, table) = record(&path, 100_000);
+        assert_eq!(decoded, content);
+        // The second member starts past the spacing: a stateless checkpoint.
+        assert!(table
+            .checkpoints
+            .iter()
+            .any(|c| c.offset == 120_000 && c.state.is_empty()));
+        assert_random_access(&path, table, &content);
+    }
+
+    #[test]
+    fn test_gzip_corruption_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = sample_content(50_000);
+        let mut data = gzip_member(&content);
+
+        let truncated = write_temp(&dir, "truncated.gz", &data[..data.len() / 2]);
+        let mut out = Vec::new();
+        let err = SourceReader::open(&truncated)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let crc_at = data.len() - 8;
+        data[crc_at] ^= 0xff;
+        let bad_crc = write_temp(&dir, "bad-crc.gz", &data);
+        let err = SourceReader::open(&bad_crc)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_zstd_frames_are_checkpoints() {
+        use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+        let dir = tempfile::tempdir().unwrap();
+        let content = sample_content(400_000);
+        let mut data = Vec::new();
+        for (i, chunk) in content.chunks(100_000).enumerate() {
+            if i == 2 {
+                // A skippable frame between data frames.
+                data.extend([0x50, 0x2a, 0x4d, 0x18, 3, 0, 0, 0, 1, 2, 3]);
+            }
+            data.extend(compress_to_vec(chunk, CompressionLevel::Fastest));
+        }
+        let path = write_temp(&dir, "mail.mbox.zst", &data);
+
+        let (decoded, table) = record(&path, 1);
+        assert_eq!(decoded, content);
+        let offsets: Vec<u64> = table.checkpoints.iter().map(|c| c.offset).collect();
+        assert_eq!(offsets, vec![0, 100_000, 200_000, 300_000]);
+        assert_random_access(&path, table, &content);
+    }
+
+    #[test]
+    fn test_xz_reads_from_start_only() {
+        use lzma_rust2::{XzOptions, XzWriter};
+
+        let dir = tempfile::tempdir().unwrap();
+        let content = sample_content(200_000);
+        let mut writer = XzWriter::new(Vec::new(), XzOptions::with_preset(1)).unwrap();
+        io::Write::write_all(&mut writer, &content).unwrap();
+        let path = write_temp(&dir, "mail.mbox.xz", &writer.finish().unwrap());
+
+        let (decoded, table) = record(&path, 1);
+        assert_eq!(decoded, content);
+        assert_eq!(table.checkpoints, vec![Checkpoint::start()]);
+        assert_random_access(&path, table, &content);
+    }
+
+    #[test]
+    fn test_zip_concatenates_mailbox_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = sample_content(900_000);
+        let (a, rest) = content.split_at(300_000);
+        let (b, c) = rest.split_at(250_000);
+        let data = zip_archive(&[
+            ("Takeout/Mail/Inbox.mbox", a, true),
+            ("Takeout/Drive/notes.txt", b"not a mailbox", false),
+            ("Takeout/Mail/Sent.mbox", b, false),
+            ("Takeout/Mail/Empty.mbox", b"", false),
+            ("Takeout/Mail/Archive.mbox", c, true),
+        ]);
+        let path = write_temp(&dir, "takeout.zip", &data);
+
+        let (decoded, table) = record(&path, 64 * 1024);
+        assert_eq!(decoded, content);
+        assert_eq!(table.codec, Codec::Zip);
+        assert!(table.is_well_formed());
+        // Every member start is a stateless checkpoint, whatever the spacing.
+        for start in [0, 300_000, 550_000] {
+            assert!(table
+                .checkpoints
+                .iter()
+                .any(|c| c.offset == start && c.state.is_empty()));
+        }
+        assert!(table.checkpoints.iter().any(|c| !c.state.is_empty()));
+        assert_random_access(&path, table, &content);
+
+        // Without checkpoints, stored members are still reached by seeking.
+        let mut source = SeekableSource::new(&path, Codec::Zip, Vec::new());
+        let mut buf = vec![0u8; 1000];
+        source.read_at(400_000, &mut buf).unwrap();
+        assert_eq!(buf, &content[400_000..401_000]);
+    }
+
+    #[test]
+    fn test_zip_member_corruption_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = sample_content(20_000);
+        let mut data = zip_archive(&[("a.mbox", &content, false)]);
+        // Flip a byte of the stored data: the CRC no longer matches.
+        data[100] ^= 0xff;
+        let path = write_temp(&dir, "bad.zip", &data);
+        let err = SourceReader::open(&path)
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_zip_members_in_memory() {
+        let content = sample_content(5_000);
+        let data = zip_archive(&[
+            ("word/document.xml", &content, true),
+            ("word/styles.xml", b"<styles/>", false),
+            ("[Content_Types].xml", b"<Types/>", false),
+        ]);
+        let members = zip::read_members(&data, |name| name.starts_with("word/"), 10_000).unwrap();
+        assert_eq!(
+            members,
+            [
+                ("word/document.xml".to_string(), content.clone()),
+                ("word/styles.xml".to_string(), b"<styles/>".to_vec()),
+            ]
+        );
+
+        // Over the limit, the members that do not fit are left out.
+        let members = zip::read_members(&data, |_| true, 100).unwrap();
+        let names: Vec<_> = members.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["word/styles.xml", "[Content_Types].xml"]);
+
+        let mut bad = data.clone();
+        bad[60] ^= 0xff;
+        assert!(zip::read_members(&bad, |_| true, 10_000).is_err());
+        assert!(zip::read_members(b"not a zip", |_| true, 10_000).is_err());
+    }
+
+    #[test]
+    fn test_is_mailbox_member() {
+        assert!(is_mailbox_member(
+            "Takeout/Mail/All mail Including Spam and Trash.mbox"
+        ));
+        assert!(is_mailbox_member(
+            "Takeout/Groups/team@googlegroups.com/topics.MBOX"
+        ));
+        assert!(is_mailbox_member("Export/Inbox.mbox/mbox"));
+        assert!(!is_mailbox_member("Export/Inbox.mbox/"));
+        assert!(!is_mailbox_member("Export/Inbox.mbox/Info.plist"));
+        assert!(!is_mailbox_member("Export/mbox"));
+        assert!(!is_mailbox_member("__MACOSX/Export/._Inbox.mbox"));
+        assert!(!is_mailbox_member("Takeout/.mbox"));
+        assert!(!is_mailbox_member("Takeout/Drive/archive.mbox.txt"));
+    }
+
+    #[test]
+    fn test_codec_from_magic() {
+        assert_eq!(Codec::from_magic(&[0x1f, 0x8b, 8, 0]), Some(Codec::Gzip));
+        assert_eq!(
+            Codec::from_magic(&[0x28, 0xb5, 0x2f, 0xfd, 0x24]),
+            Some(Codec::Zstd)
+        );
+        assert_eq!(
+            Codec::from_magic(&[0xfd, b'7', b'z', b'X', b'Z', 0]),
+            Some(Codec::Xz)
+        );
+        assert_eq!(Codec::from_magic(b"PK\x03\x04\x14\0"), Some(Codec::Zip));
+        assert_eq!(Codec::from_magic(b"From a@b"), None);
+        assert_eq!(Codec::from_magic(&[0x1f]), None);
+    }
+
+    #[test]
+    fn test_checkpoint_table_well_formed() {
+        let cp = |offset, source_offset| Checkpoint {
+            offset,
+            source_offset,
+            state: Vec::new(),
+        };
+        let mut table = CheckpointTable {
+            codec: Codec::Zstd,
+            content_size: 100,
+            checkpoints: vec![cp(0, 0), cp(50, 20)],
+        };
+        assert!(table.is_well_formed());
+
+        table.checkpoints = vec![cp(10, 0)];
+        assert!(!table.is_well_formed(), "must start at offset 0");
+        table.checkpoints = vec![cp(0, 0), cp(50, 20), cp(40, 30)];
+        assert!(!table.is_well_formed(), "must be sorted");
+        table.checkpoints = vec![cp(0, 0), cp(150, 20)];
+        assert!(!table.is_well_formed(), "must stay within the content");
+        table.checkpoints.clear();
+        assert!(!table.is_well_formed());
+    }
+
+    #[test]
+    fn test_recorder_spacing() {
+        let mut recorder = Recorder::new(100);
+        assert!(recorder.wants(0));
+        recorder.push(Checkpoint::start());
+        assert!(!recorder.wants(99));
+        assert!(recorder.wants(100));
+    }
+}
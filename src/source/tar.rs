@@ -0,0 +1,310 @@
+//! Tar member headers, for Takeout archives exported as `.tgz`.
+//!
+//! Only what is needed to walk the archive: each member's name, size and
+//! whether it is a regular file. The formats in use are covered — POSIX
+//! ustar (with its name prefix), GNU long names (`L` records) and PAX
+//! extended headers (`path` and `size`, which Takeout needs for members over
+//! 8 GB), plus the GNU base-256 encoding of large sizes.
+
+use std::io::{self, Read};
+
+/// Size of a tar block: headers are one block, data is padded to a multiple.
+pub const BLOCK_SIZE: u64 = 512;
+
+/// Largest GNU long name or PAX header read into memory.
+const MAX_META_LEN: u64 = 1024 * 1024;
+
+/// A member of a tar archive. Its data follows the header, padded to
+/// [`BLOCK_SIZE`] (see [`padding`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TarMember {
+    /// Path inside the archive, with `/` separators.
+    pub name: String,
+    /// Size of the member's data.
+    pub size: u64,
+    /// Whether the member is a regular file (not a directory, link, …).
+    pub is_file: bool,
+}
+
+/// Whether `block`, the first block of a stream, is a ustar or GNU tar
+/// header. Old v7 archives without the magic are not recognised.
+pub fn is_tar(block: &[u8]) -> bool {
+    block.len() >= BLOCK_SIZE as usize && block[257..262] == *b"ustar" && checksum_ok(block)
+}
+
+/// Padding after `size` bytes of member data.
+pub fn padding(size: u64) -> u64 {
+    (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+/// Read the next member header from `reader`, positioned at a header block.
+///
+/// GNU long-name and PAX records are consumed here and applied to the member
+/// they describe. Returns `None` at the end-of-archive marker or at the end
+/// of the stream.
+pub fn next_member<R: Read>(reader: &mut R) -> io::Result<Option<TarMember>> {
+    let mut long_name: Option<String> = None;
+    let mut pax_size: Option<u64> = None;
+    loop {
+        let mut block = [0u8; BLOCK_SIZE as usize];
+        match read_block(reader, &mut block)? {
+            false => return Ok(None),
+            true if block.iter().all(|&b| b == 0) => return Ok(None),
+            true => {}
+        }
+        if !checksum_ok(&block) {
+            return Err(invalid_data("corrupt tar header"));
+        }
+        let size = parse_size(&block[124..136])?;
+        match block[156] {
+            b'L' => {
+                let data = read_meta(reader, size)?;
+                let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                long_name = Some(String::from_utf8_lossy(&data[..end]).into_owned());
+            }
+            b'x' => {
+                let data = read_meta(reader, size)?;
+                for (key, value) in pax_records(&data) {
+                    match key {
+                        "path" => long_name = Some(value.to_string()),
+                        "size" => {
+                            pax_size = Some(
+                                value
+                                    .parse()
+                                    .map_err(|_| invalid_data("corrupt tar PAX size"))?,
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            // Global PAX headers and other metadata-only records.
+            b'g' | b'K' => {
+                read_meta(reader, size)?;
+            }
+            kind => {
+                let name = long_name.unwrap_or_else(|| header_name(&block));
+                return Ok(Some(TarMember {
+                    name,
+                    size: pax_size.unwrap_or(size),
+                    is_file: matches!(kind, b'0' | 0 | b'7'),
+                }));
+            }
+        }
+    }
+}
+
+/// Fill `block`; `false` at a clean end of the stream.
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < block.len() {
+        match reader.read(&mut block[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(truncated()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Read the data of a metadata record, and its padding.
+fn read_meta<R: Read>(reader: &mut R, size: u64) -> io::Result<Vec<u8>> {
+    if size > MAX_META_LEN {
+        return Err(invalid_data("tar metadata record is too large"));
+    }
+    let mut data = vec![0u8; (size + padding(size)) as usize];
+    reader.read_exact(&mut data).map_err(|_| truncated())?;
+    data.truncate(size as usize);
+    Ok(data)
+}
+
+/// `prefix/name` from a ustar header.
+fn header_name(block: &[u8]) -> String {
+    let name = c_string(&block[..100]);
+    let prefix = c_string(&block[345..500]);
+    if prefix.is_empty() {
+        name
+    } else {
+        format!("{prefix}/{name}")
+    }
+}
+
+fn c_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// A numeric field: octal ASCII, or big-endian base-256 when the high bit of
+/// the first byte is set (GNU, for sizes of 8 GB and more).
+fn parse_size(field: &[u8]) -> io::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        if field[0] & 0x40 != 0 || field[1..field.len() - 8].iter().any(|&b| b != 0) {
+            return Err(invalid_data("tar size out of range"));
+        }
+        let tail = &field[field.len() - 8..];
+        return Ok(u64::from_be_bytes(tail.try_into().expect("8 bytes")));
+    }
+    let digits = field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|&&b| (b'0'..=b'7').contains(&b));
+    let mut value: u64 = 0;
+    for &d in digits {
+        value = value
+            .checked_mul(8)
+            .map(|v| v + u64::from(d - b'0'))
+            .ok_or_else(|| invalid_data("tar size out of range"))?;
+    }
+    Ok(value)
+}
+
+/// The header checksum: the byte sum with the checksum field read as spaces.
+fn checksum_ok(block: &[u8]) -> bool {
+    let Ok(stored) = parse_size(&block[148..156]) else {
+        return false;
+    };
+    let sum: u64 = block[..BLOCK_SIZE as usize]
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                u64::from(b' ')
+            } else {
+                u64::from(b)
+            }
+        })
+        .sum();
+    sum == stored
+}
+
+/// `(key, value)` pairs of a PAX header: records of the form
+/// `"<length> <key>=<value>\n"`.
+fn pax_records(data: &[u8]) -> Vec<(&str, &str)> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let Some(len) = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|l| l.parse::<usize>().ok())
+            .filter(|&l| l > space + 1 && l <= rest.len())
+        else {
+            break;
+        };
+        let record = &rest[space + 1..len];
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Ok(record) = std::str::from_utf8(record) {
+            if let Some((key, value)) = record.split_once('=') {
+                records.push((key, value));
+            }
+        }
+        rest = &rest[len..];
+    }
+    records
+}
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "tar archive is truncated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ustar header block with a valid checksum.
+    fn header(name: &str, prefix: &str, size: u64, kind: u8) -> Vec<u8> {
+        let mut block = vec![0u8; BLOCK_SIZE as usize];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        block[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+        block[156] = kind;
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[263..265].copy_from_slice(b"00");
+        block[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        block[148..156].fill(b' ');
+        let sum: u32 = block.iter().map(|&b| u32::from(b)).sum();
+        block[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+        block
+    }
+
+    fn with_data(mut block: Vec<u8>, data: &[u8]) -> Vec<u8> {
+        block.extend(data);
+        block.resize(block.len() + padding(data.len() as u64) as usize, 0);
+        block
+    }
+
+    #[test]
+    fn test_ustar_prefix_and_kinds() {
+        let mut archive = header("Mail", "Takeout", 0, b'5');
+        archive.extend(header("All mail.mbox", "Takeout/Mail", 1234, b'0'));
+        assert!(is_tar(&archive));
+
+        let mut reader = archive.as_slice();
+        let dir = next_member(&mut reader).unwrap().unwrap();
+        assert_eq!(dir.name, "Takeout/Mail");
+        assert!(!dir.is_file);
+        let file = next_member(&mut reader).unwrap().unwrap();
+        assert_eq!(file.name, "Takeout/Mail/All mail.mbox");
+        assert_eq!(file.size, 1234);
+        assert!(file.is_file);
+        assert_eq!(next_member(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_gnu_long_name_and_pax() {
+        let long = format!("Takeout/{}/Inbox.mbox", "x".repeat(150));
+        let mut name = long.clone().into_bytes();
+        name.push(0);
+        let mut archive = with_data(header("././@LongLink", "", name.len() as u64, b'L'), &name);
+        archive.extend(header("truncated-name", "", 10, b'0'));
+        archive.extend([0u8; 512]);
+
+        let pax = "31 path=Takeout/Mail/Sent.mbox\n20 size=12884901888\n";
+        archive.extend(with_data(
+            header("PaxHeader", "", pax.len() as u64, b'x'),
+            pax.as_bytes(),
+        ));
+        archive.extend(header("Sent.mbox", "", 0, b'0'));
+
+        let mut reader = archive.as_slice();
+        let first = next_member(&mut reader).unwrap().unwrap();
+        assert_eq!(first.name, long);
+        assert_eq!(first.size, 10);
+        let mut data = [0u8; 512];
+        reader.read_exact(&mut data).unwrap();
+
+        let second = next_member(&mut reader).unwrap().unwrap();
+        assert_eq!(second.name, "Takeout/Mail/Sent.mbox");
+        assert_eq!(second.size, 12 << 30);
+        // The end of the stream without a marker is also the end.
+        assert_eq!(next_member(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_base256_size_and_bad_checksum() {
+        let mut field = [0u8; 12];
+        field[0] = 0x80;
+        field[4..].copy_from_slice(&(20u64 << 30).to_be_bytes());
+        assert_eq!(parse_size(&field).unwrap(), 20 << 30);
+
+        let mut block = header("a.mbox", "", 1, b'0');
+        block[0] = b'b';
+        assert!(!is_tar(&block));
+        assert_eq!(
+            next_member(&mut block.as_slice()).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_padding() {
+        assert_eq!(padding(0), 0);
+        assert_eq!(padding(1), 511);
+        assert_eq!(padding(512), 0);
+        assert_eq!(padding(1000), 24);
+    }
+}
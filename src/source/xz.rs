@@ -0,0 +1,52 @@
+//! xz decoding.
+//!
+//! LZMA2 state cannot be captured at an arbitrary point the way a deflate
+//! block boundary can, and the block index at the end of an `.xz` file is
+//! only useful for files written with several blocks (`xz -T`), so only the
+//! start of the file is recorded. See the module docs of [`super`].
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+use super::{Checkpoint, Counting, Recorder};
+
+pub(super) struct XzReader {
+    inner: lzma_rust2::XzReader<Counting<BufReader<File>>>,
+    position: u64,
+    recorder: Option<Recorder>,
+}
+
+impl XzReader {
+    pub(super) fn new(input: Counting<BufReader<File>>, recorder: Option<Recorder>) -> Self {
+        let recorder = recorder.map(|mut r| {
+            r.push(Checkpoint::start());
+            r
+        });
+        Self {
+            // Concatenated streams (`cat a.xz b.xz`) are one mailbox.
+            inner: lzma_rust2::XzReader::new(input, true),
+            position: 0,
+            recorder,
+        }
+    }
+
+    pub(super) fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(super) fn source_position(&self) -> u64 {
+        self.inner.inner().position
+    }
+
+    pub(super) fn take_recorder(&mut self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+}
+
+impl Read for XzReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
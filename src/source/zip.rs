@@ -0,0 +1,683 @@
+//! Mailboxes inside a `.zip` archive (Google Takeout's default format).
+//!
+//! The content of a zip source is the concatenation of its mailbox members
+//! ([`super::is_mailbox_member`]) in the order they are stored in the file.
+//! Takeout stores them either as is or deflated. A stored member is read and
+//! skipped with plain seeks; a deflated one goes through the gzip decoder in
+//! raw-deflate mode, so it gets the same block-boundary checkpoints as a
+//! `.gz`. Every member start is a checkpoint with no state.
+//!
+//! Only the central directory is trusted for names and sizes (local headers
+//! may defer them to a data descriptor), including the ZIP64 fields Takeout
+//! writes for archives over 4 GB.
+//!
+//! [`read_members`] reads a small archive held in memory instead: the OOXML
+//! and ODF documents whose text attachment search extracts.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use super::gzip::{Framing, GzipReader};
+use super::{is_mailbox_member, open_input, Checkpoint, Counting, Recorder};
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const EOCD_SIG: u32 = 0x0605_4b50;
+const ZIP64_EOCD_SIG: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIG: u32 = 0x0706_4b50;
+
+const LOCAL_HEADER_LEN: u64 = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const EOCD_LEN: usize = 22;
+const ZIP64_LOCATOR_LEN: u64 = 20;
+const ZIP64_EOCD_LEN: usize = 56;
+/// The end of central directory record is followed by a comment of up to
+/// 64 KB, so it is searched for in this many bytes at the end of the file.
+const EOCD_SEARCH_LEN: u64 = EOCD_LEN as u64 + u16::MAX as u64;
+
+/// Header ID of the ZIP64 extended information extra field.
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+/// General purpose flag: the entry is encrypted.
+const FLAG_ENCRYPTED: u16 = 0x0001;
+
+/// How a member's data is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Stored,
+    Deflated,
+}
+
+/// A member of a zip archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipMember {
+    /// Path inside the archive, with `/` separators.
+    pub name: String,
+    /// Offset of the member's first byte in the concatenated content.
+    pub offset: u64,
+    /// Uncompressed size.
+    pub size: u64,
+    method: Method,
+    crc32: u32,
+    /// Offset of the member's local header in the archive.
+    header_offset: u64,
+}
+
+/// List the mailbox members of the zip archive at `path`, in file order.
+///
+/// Encrypted members and compression methods other than stored and deflate
+/// are skipped with a warning, as are empty ones.
+pub fn list_mailboxes(path: &Path) -> io::Result<Vec<ZipMember>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let (cd_offset, cd_size) = find_central_directory(&mut file, len)?;
+    if cd_offset.checked_add(cd_size).is_none_or(|end| end > len) {
+        return Err(invalid_data("zip central directory lies outside the file"));
+    }
+    let cd_len =
+        usize::try_from(cd_size).map_err(|_| invalid_data("zip central directory is too large"))?;
+    let mut cd = vec![0u8; cd_len];
+    file.seek(SeekFrom::Start(cd_offset))?;
+    file.read_exact(&mut cd)?;
+
+    let mut members = parse_central_directory(&cd, is_mailbox_member)?;
+    members.retain(|m| m.size > 0);
+    members.sort_by_key(|m| m.header_offset);
+    let mut offset = 0;
+    for member in &mut members {
+        member.offset = offset;
+        offset += member.size;
+    }
+    Ok(members)
+}
+
+/// Read the members of the zip archive `data` whose name is `wanted`, in
+/// file order, as `(name, content)`. Members that would take the content
+/// read past `limit` bytes in total are left out.
+pub fn read_members(
+    data: &[u8],
+    wanted: impl Fn(&str) -> bool,
+    limit: usize,
+) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let (cd_offset, cd_size) =
+        find_central_directory(&mut io::Cursor::new(data), data.len() as u64)?;
+    let cd = usize::try_from(cd_offset)
+        .ok()
+        .zip(usize::try_from(cd_size).ok())
+        .and_then(|(offset, size)| data.get(offset..offset.checked_add(size)?))
+        .ok_or_else(|| invalid_data("zip central directory lies outside the file"))?;
+
+    let mut members = parse_central_directory(cd, wanted)?;
+    members.sort_by_key(|m| m.header_offset);
+    let mut budget = limit;
+    let mut contents = Vec::new();
+    for member in members {
+        let Some(size) = usize::try_from(member.size)
+            .ok()
+            .filter(|&size| size <= budget)
+        else {
+            continue;
+        };
+        budget -= size;
+        let content = read_in_memory(data, &member, size)?;
+        contents.push((member.name, content));
+    }
+    Ok(contents)
+}
+
+/// The content of `member` of the in-memory archive `data`, `size` bytes.
+fn read_in_memory(data: &[u8], member: &ZipMember, size: usize) -> io::Result<Vec<u8>> {
+    let header_len = LOCAL_HEADER_LEN as usize;
+    let header = usize::try_from(member.header_offset)
+        .ok()
+        .and_then(|offset| data.get(offset..)?.get(..header_len))
+        .filter(|header| le32(header) == LOCAL_HEADER_SIG)
+        .ok_or_else(|| invalid_data("corrupt zip local header"))?;
+    let start = member.header_offset as usize
+        + header_len
+        + usize::from(le16(&header[26..]))
+        + usize::from(le16(&header[28..]));
+    let packed = data
+        .get(start..)
+        .ok_or_else(|| truncated(io::ErrorKind::UnexpectedEof.into()))?;
+    let content = match member.method {
+        Method::Stored => packed
+            .get(..size)
+            .ok_or_else(|| truncated(io::ErrorKind::UnexpectedEof.into()))?
+            .to_vec(),
+        Method::Deflated => miniz_oxide::inflate::decompress_to_vec_with_limit(packed, size)
+            .map_err(|_| invalid_data("corrupt deflated zip member"))?,
+    };
+    if content.len() != size {
+        return Err(invalid_data("zip member is shorter than its recorded size"));
+    }
+    if crc32fast::hash(&content) != member.crc32 {
+        return Err(invalid_data("zip member checksum mismatch"));
+    }
+    Ok(content)
+}
+
+/// Locate the central directory: `(offset, size)`.
+fn find_central_directory(file: &mut (impl Read + Seek), len: u64) -> io::Result<(u64, u64)> {
+    let tail_len = len.min(EOCD_SEARCH_LEN);
+    let tail_start = len - tail_len;
+    let mut tail = vec![0u8; tail_len as usize];
+    file.seek(SeekFrom::Start(tail_start))?;
+    file.read_exact(&mut tail)?;
+
+    // The last signature whose comment length matches the end of the file.
+    let eocd_at = (0..tail.len().saturating_sub(EOCD_LEN - 1))
+        .rev()
+        .find(|&i| {
+            le32(&tail[i..]) == EOCD_SIG
+                && i + EOCD_LEN + usize::from(le16(&tail[i + 20..])) == tail.len()
+        })
+        .ok_or_else(|| invalid_data("not a zip archive (no end of central directory)"))?;
+    let eocd = &tail[eocd_at..];
+    let mut cd_size = u64::from(le32(&eocd[12..]));
+    let mut cd_offset = u64::from(le32(&eocd[16..]));
+
+    if cd_size == u64::from(u32::MAX) || cd_offset == u64::from(u32::MAX) {
+        let eocd_pos = tail_start + eocd_at as u64;
+        let locator_pos = eocd_pos
+            .checked_sub(ZIP64_LOCATOR_LEN)
+            .ok_or_else(|| invalid_data("missing ZIP64 end of central directory locator"))?;
+        let mut locator = [0u8; ZIP64_LOCATOR_LEN as usize];
+        file.seek(SeekFrom::Start(locator_pos))?;
+        file.read_exact(&mut locator)?;
+        if le32(&locator) != ZIP64_LOCATOR_SIG {
+            return Err(invalid_data(
+                "missing ZIP64 end of central directory locator",
+            ));
+        }
+        let mut record = [0u8; ZIP64_EOCD_LEN];
+        file.seek(SeekFrom::Start(le64(&locator[8..])))?;
+        file.read_exact(&mut record)?;
+        if le32(&record) != ZIP64_EOCD_SIG {
+            return Err(invalid_data("corrupt ZIP64 end of central directory"));
+        }
+        cd_size = le64(&record[40..]);
+        cd_offset = le64(&record[48..]);
+    }
+    Ok((cd_offset, cd_size))
+}
+
+/// Parse every central directory entry whose name is `wanted`. Offsets into
+/// the concatenated content are assigned by the caller.
+fn parse_central_directory(
+    mut cd: &[u8],
+    wanted: impl Fn(&str) -> bool,
+) -> io::Result<Vec<ZipMember>> {
+    let mut members = Vec::new();
+    while cd.len() >= 4 && le32(cd) == CENTRAL_HEADER_SIG {
+        if cd.len() < CENTRAL_HEADER_LEN {
+            return Err(corrupt_directory());
+        }
+        let flags = le16(&cd[8..]);
+        let method = le16(&cd[10..]);
+        let crc32 = le32(&cd[16..]);
+        let name_len = usize::from(le16(&cd[28..]));
+        let extra_len = usize::from(le16(&cd[30..]));
+        let comment_len = usize::from(le16(&cd[32..]));
+        let entry_len = CENTRAL_HEADER_LEN + name_len + extra_len + comment_len;
+        if cd.len() < entry_len {
+            return Err(corrupt_directory());
+        }
+        let raw_name = &cd[CENTRAL_HEADER_LEN..CENTRAL_HEADER_LEN + name_len];
+        let extra = &cd[CENTRAL_HEADER_LEN + name_len..CENTRAL_HEADER_LEN + name_len + extra_len];
+
+        // Takeout writes UTF-8 names whether or not it sets the UTF-8 flag;
+        // the CP437 names of old archives are only lossy for non-ASCII.
+        let name = String::from_utf8_lossy(raw_name).into_owned();
+        if wanted(&name) {
+            let sizes = Zip64Fields {
+                size: u64::from(le32(&cd[24..])),
+                compressed_size: u64::from(le32(&cd[20..])),
+                header_offset: u64::from(le32(&cd[42..])),
+            }
+            .with_extra(extra)?;
+            let method = match method {
+                0 => Some(Method::Stored),
+                8 => Some(Method::Deflated),
+                _ => None,
+            };
+            match method {
+                _ if flags & FLAG_ENCRYPTED != 0 => {
+                    warn!(member = %name, "Skipping encrypted member of zip archive");
+                }
+                None => {
+                    warn!(member = %name, "Skipping zip member with unsupported compression");
+                }
+                Some(method) => members.push(ZipMember {
+                    name,
+                    offset: 0,
+                    size: sizes.size,
+                    method,
+                    crc32,
+                    header_offset: sizes.header_offset,
+                }),
+            }
+        }
+        cd = &cd[entry_len..];
+    }
+    Ok(members)
+}
+
+/// The central directory fields that ZIP64 can widen.
+#[derive(Debug, PartialEq, Eq)]
+struct Zip64Fields {
+    size: u64,
+    compressed_size: u64,
+    header_offset: u64,
+}
+
+impl Zip64Fields {
+    /// Replace the fields saturated at `u32::MAX` with their 64-bit values
+    /// from the ZIP64 extra field, which lists only those, in this order.
+    fn with_extra(mut self, mut extra: &[u8]) -> io::Result<Self> {
+        let saturated = u64::from(u32::MAX);
+        if ![self.size, self.compressed_size, self.header_offset].contains(&saturated) {
+            return Ok(self);
+        }
+        while extra.len() >= 4 {
+            let id = le16(extra);
+            let len = usize::from(le16(&extra[2..]));
+            let data = extra.get(4..4 + len).ok_or_else(corrupt_directory)?;
+            if id == ZIP64_EXTRA_ID {
+                let mut values = data.chunks_exact(8).map(le64);
+                for field in [
+                    &mut self.size,
+                    &mut self.compressed_size,
+                    &mut self.header_offset,
+                ] {
+                    if *field == saturated {
+                        *field = values.next().ok_or_else(corrupt_directory)?;
+                    }
+                }
+                return Ok(self);
+            }
+            extra = &extra[4 + len..];
+        }
+        Err(invalid_data("zip entry is missing its ZIP64 sizes"))
+    }
+}
+
+/// Sequential reader over the concatenated mailbox members of a zip archive.
+pub(super) struct ZipReader {
+    path: PathBuf,
+    members: Vec<ZipMember>,
+    /// Index of the member being read (or to be opened next).
+    current: usize,
+    body: Option<Body>,
+    position: u64,
+    /// Input position of the last member read, once it is closed.
+    source_position: u64,
+    recorder: Option<Recorder>,
+}
+
+/// The member being read.
+struct Body {
+    data: Data,
+    /// Bytes of the member not yet read.
+    remaining: u64,
+    /// Running CRC-32, when the member was read from its first byte.
+    check: Option<crc32fast::Hasher>,
+}
+
+enum Data {
+    Stored(Counting<BufReader<File>>),
+    Deflated(Box<GzipReader>),
+}
+
+impl ZipReader {
+    pub(super) fn new(path: &Path, recorder: Option<Recorder>) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            members: list_mailboxes(path)?,
+            current: 0,
+            body: None,
+            position: 0,
+            source_position: 0,
+            recorder,
+        })
+    }
+
+    /// Resume at `checkpoint`: a member start, any offset of a stored member,
+    /// or a block boundary inside a deflated one.
+    pub(super) fn resume(path: &Path, checkpoint: &Checkpoint) -> io::Result<Self> {
+        let mut reader = Self::new(path, None)?;
+        reader.position = checkpoint.offset;
+        reader.current = reader
+            .members
+            .partition_point(|m| m.offset + m.size <= checkpoint.offset);
+        let Some(member) = reader.members.get(reader.current) else {
+            return Ok(reader);
+        };
+        let within = checkpoint.offset - member.offset;
+        if checkpoint.state.is_empty() {
+            reader.open_member(within)?;
+            return Ok(reader);
+        }
+        if member.method != Method::Deflated {
+            return Err(invalid_data("corrupt zip checkpoint"));
+        }
+        let input = open_input(path, checkpoint.source_offset)?;
+        let decoder = GzipReader::resume(input, checkpoint, Framing::Deflate)?;
+        reader.body = Some(Body {
+            data: Data::Deflated(Box::new(decoder)),
+            remaining: member.size - within,
+            check: None,
+        });
+        Ok(reader)
+    }
+
+    pub(super) fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(super) fn source_position(&self) -> u64 {
+        match &self.body {
+            Some(Body {
+                data: Data::Stored(input),
+                ..
+            }) => input.position,
+            Some(Body {
+                data: Data::Deflated(decoder),
+                ..
+            }) => decoder.source_position(),
+            None => self.source_position,
+        }
+    }
+
+    pub(super) fn take_recorder(&mut self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+
+    /// Skip `n` bytes of content, seeking through stored members.
+    pub(super) fn skip(&mut self, n: u64) -> io::Result<u64> {
+        let mut skipped = 0;
+        let mut scratch = Vec::new();
+        while skipped < n {
+            if let Some(body) = &mut self.body {
+                if let Data::Stored(input) = &mut body.data {
+                    if body.remaining > 0 {
+                        let step = body.remaining.min(n - skipped);
+                        // Both bounded by the member size, which fits the file.
+                        input.inner.seek_relative(step as i64)?;
+                        input.position += step;
+                        body.remaining -= step;
+                        body.check = None;
+                        self.position += step;
+                        skipped += step;
+                        continue;
+                    }
+                }
+            }
+            if scratch.is_empty() {
+                scratch = vec![0u8; 64 * 1024];
+            }
+            let want = (n - skipped).min(scratch.len() as u64) as usize;
+            let got = self.read(&mut scratch[..want])?;
+            if got == 0 {
+                break;
+            }
+            skipped += got as u64;
+        }
+        Ok(skipped)
+    }
+
+    /// Open the current member, positioned `within` bytes into its content.
+    fn open_member(&mut self, within: u64) -> io::Result<()> {
+        let member = &self.members[self.current];
+        let data_offset = self.data_offset(member)?;
+        if within == 0 {
+            if let Some(recorder) = &mut self.recorder {
+                // Recorded regardless of spacing: resuming at a member start
+                // is free, and decoding across a member boundary is not.
+                if recorder
+                    .checkpoints
+                    .last()
+                    .is_none_or(|c| c.offset < member.offset)
+                {
+                    recorder.push(Checkpoint {
+                        offset: member.offset,
+                        source_offset: data_offset,
+                        state: Vec::new(),
+                    });
+                }
+            }
+        }
+        let data = match member.method {
+            Method::Stored => Data::Stored(open_input(&self.path, data_offset + within)?),
+            Method::Deflated => {
+                if within != 0 {
+                    return Err(invalid_data("corrupt zip checkpoint"));
+                }
+                let input = open_input(&self.path, data_offset)?;
+                Data::Deflated(Box::new(GzipReader::deflate(
+                    input,
+                    member.offset,
+                    self.recorder.take(),
+                )))
+            }
+        };
+        self.body = Some(Body {
+            data,
+            remaining: member.size - within,
+            check: (within == 0).then(crc32fast::Hasher::new),
+        });
+        Ok(())
+    }
+
+    /// Where a member's data starts: after its local header, whose name and
+    /// extra field lengths may differ from the central directory's.
+    fn data_offset(&self, member: &ZipMember) -> io::Result<u64> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(member.header_offset))?;
+        let mut header = [0u8; LOCAL_HEADER_LEN as usize];
+        file.read_exact(&mut header).map_err(truncated)?;
+        if le32(&header) != LOCAL_HEADER_SIG {
+            return Err(invalid_data("corrupt zip local header"));
+        }
+        Ok(member.header_offset
+            + LOCAL_HEADER_LEN
+            + u64::from(le16(&header[26..]))
+            + u64::from(le16(&header[28..])))
+    }
+
+    /// Verify and close the current member once all of it has been read.
+    fn finish_member(&mut self) -> io::Result<()> {
+        let mut body = self.body.take().expect("a member is open");
+        self.source_position = match &mut body.data {
+            Data::Stored(input) => input.position,
+            Data::Deflated(decoder) => {
+                if decoder.read(&mut [0u8; 1])? != 0 {
+                    return Err(invalid_data("zip member is longer than its recorded size"));
+                }
+                self.recorder = decoder.take_recorder();
+                decoder.source_position()
+            }
+        };
+        if let Some(check) = body.check {
+            if check.finalize() != self.members[self.current].crc32 {
+                return Err(invalid_data("zip member checksum mismatch"));
+            }
+        }
+        self.current += 1;
+        Ok(())
+    }
+}
+
+impl Read for ZipReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let Some(body) = &mut self.body else {
+                if self.current >= self.members.len() {
+                    return Ok(0);
+                }
+                self.open_member(0)?;
+                continue;
+            };
+            if body.remaining == 0 {
+                self.finish_member()?;
+                continue;
+            }
+            let want = buf
+                .len()
+                .min(usize::try_from(body.remaining).unwrap_or(usize::MAX));
+            let n = match &mut body.data {
+                Data::Stored(input) => input.read(&mut buf[..want])?,
+                Data::Deflated(decoder) => decoder.read(&mut buf[..want])?,
+            };
+            if n == 0 {
+                return Err(match body.data {
+                    Data::Stored(_) => truncated(io::ErrorKind::UnexpectedEof.into()),
+                    Data::Deflated(_) => {
+                        invalid_data("zip member is shorter than its recorded size")
+                    }
+                });
+            }
+            if let Some(check) = &mut body.check {
+                check.update(&buf[..n]);
+            }
+            body.remaining -= n as u64;
+            self.position += n as u64;
+            return Ok(n);
+        }
+    }
+}
+
+fn le16(b: &[u8]) -> u16 {
+    u16::from_le_bytes([b[0], b[1]])
+}
+
+fn le32(b: &[u8]) -> u32 {
+    u32::from_le_bytes(b[..4].try_into().expect("4 bytes"))
+}
+
+fn le64(b: &[u8]) -> u64 {
+    u64::from_le_bytes(b[..8].try_into().expect("8 bytes"))
+}
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+fn corrupt_directory() -> io::Error {
+    invalid_data("corrupt zip central directory")
+}
+
+fn truncated(e: io::Error) -> io::Error {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "zip archive is truncated")
+    } else {
+        e
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn central_entry(
+        name: &str,
+        method: u16,
+        flags: u16,
+        sizes: [u32; 3],
+        extra: &[u8],
+    ) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(CENTRAL_HEADER_SIG.to_le_bytes());
+        out.extend([20, 0, 20, 0]);
+        out.extend(flags.to_le_bytes());
+        out.extend(method.to_le_bytes());
+        out.extend([0; 4]); // time, date
+        out.extend(0xdead_beef_u32.to_le_bytes());
+        out.extend(sizes[1].to_le_bytes()); // compressed
+        out.extend(sizes[0].to_le_bytes()); // uncompressed
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend((extra.len() as u16).to_le_bytes());
+        out.extend([0; 10]); // comment length, disk, attributes
+        out.extend(sizes[2].to_le_bytes());
+        out.extend(name.as_bytes());
+        out.extend(extra);
+        out
+    }
+
+    #[test]
+    fn test_central_directory_filters_mailboxes() {
+        let mut cd = Vec::new();
+        cd.extend(central_entry(
+            "Takeout/Mail/All mail.mbox",
+            8,
+            0,
+            [100, 40, 0],
+            &[],
+        ));
+        cd.extend(central_entry(
+            "Takeout/Drive/photo.jpg",
+            0,
+            0,
+            [100, 100, 80],
+            &[],
+        ));
+        cd.extend(central_entry(
+            "Takeout/Mail/Secret.mbox",
+            8,
+            1,
+            [10, 10, 200],
+            &[],
+        ));
+        cd.extend(central_entry(
+            "Takeout/Mail/Odd.mbox",
+            12,
+            0,
+            [10, 10, 300],
+            &[],
+        ));
+        let members = parse_central_directory(&cd, is_mailbox_member).unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].name, "Takeout/Mail/All mail.mbox");
+        assert_eq!(members[0].method, Method::Deflated);
+        assert_eq!(members[0].size, 100);
+        assert_eq!(members[0].crc32, 0xdead_beef);
+    }
+
+    #[test]
+    fn test_zip64_extra_field() {
+        // Size and header offset saturated; the extra field carries both, in
+        // order, after an unrelated field.
+        let mut extra = vec![0x55, 0x54, 1, 0, 0];
+        extra.extend(ZIP64_EXTRA_ID.to_le_bytes());
+        extra.extend(16u16.to_le_bytes());
+        extra.extend((6u64 << 30).to_le_bytes());
+        extra.extend((5u64 << 32).to_le_bytes());
+        let fields = Zip64Fields {
+            size: u64::from(u32::MAX),
+            compressed_size: 1000,
+            header_offset: u64::from(u32::MAX),
+        }
+        .with_extra(&extra)
+        .unwrap();
+        assert_eq!(
+            fields,
+            Zip64Fields {
+                size: 6 << 30,
+                compressed_size: 1000,
+                header_offset: 5 << 32,
+            }
+        );
+
+        let missing = Zip64Fields {
+            size: u64::from(u32::MAX),
+            compressed_size: 1000,
+            header_offset: 0,
+        };
+        assert!(missing.with_extra(&[]).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! zstd decoding with a checkpoint at each frame start.
+//!
+//! Frames are independent, so resuming needs nothing but the frame's
+//! position in the file. Skippable frames (used by `pzstd` and others for
+//! metadata) are stepped over.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+use ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+use ruzstd::decoding::{BlockDecodingStrategy, FrameDecoder};
+
+use super::{Checkpoint, Counting, Recorder};
+
+pub(super) struct ZstdReader {
+    input: Counting<BufReader<File>>,
+    decoder: FrameDecoder,
+    in_frame: bool,
+    done: bool,
+    position: u64,
+    recorder: Option<Recorder>,
+}
+
+impl ZstdReader {
+    pub(super) fn new(input: Counting<BufReader<File>>, recorder: Option<Recorder>) -> Self {
+        Self {
+            input,
+            decoder: FrameDecoder::new(),
+            in_frame: false,
+            done: false,
+            position: 0,
+            recorder,
+        }
+    }
+
+    /// Resume at the frame start recorded in `checkpoint`.
+    pub(super) fn resume(input: Counting<BufReader<File>>, checkpoint: &Checkpoint) -> Self {
+        let mut reader = Self::new(input, None);
+        reader.position = checkpoint.offset;
+        reader
+    }
+
+    pub(super) fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(super) fn source_position(&self) -> u64 {
+        self.input.position
+    }
+
+    pub(super) fn take_recorder(&mut self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+
+    /// Start the next frame. Returns `false` at the end of the file.
+    fn next_frame(&mut self) -> io::Result<bool> {
+        loop {
+            if self.input.fill_buf()?.is_empty() {
+                return Ok(false);
+            }
+            if let Some(recorder) = &mut self.recorder {
+                if recorder.wants(self.position) {
+                    recorder.push(Checkpoint {
+                        offset: self.position,
+                        source_offset: self.input.position,
+                        state: Vec::new(),
+                    });
+                }
+            }
+            match self.decoder.reset(&mut self.input) {
+                Ok(()) => return Ok(true),
+                Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame {
+                    length,
+                    ..
+                })) => {
+                    let length = u64::from(length);
+                    let skipped = io::copy(&mut (&mut self.input).take(length), &mut io::sink())?;
+                    if skipped < length {
+                        return Err(truncated());
+                    }
+                }
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            }
+        }
+    }
+
+    /// Check the content checksum of a finished frame, when it has one.
+    fn verify_frame(&self) -> io::Result<()> {
+        match (
+            self.decoder.get_checksum_from_data(),
+            self.decoder.get_calculated_checksum(),
+        ) {
+            (Some(expected), Some(actual)) if expected != actual => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "zstd frame checksum mismatch",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Read for ZstdReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.in_frame {
+                while self.decoder.can_collect() < buf.len() && !self.decoder.is_finished() {
+                    let wanted = buf.len() - self.decoder.can_collect();
+                    self.decoder
+                        .decode_blocks(&mut self.input, BlockDecodingStrategy::UptoBytes(wanted))
+                        .map_err(|e| match e {
+                            FrameDecoderError::FailedToReadBlockHeader(_)
+                            | FrameDecoderError::FailedToReadChecksum(_) => truncated(),
+                            e => io::Error::new(io::ErrorKind::InvalidData, e),
+                        })?;
+                }
+                let n = self.decoder.read(buf)?;
+                if n > 0 {
+                    self.position += n as u64;
+                    return Ok(n);
+                }
+                self.verify_frame()?;
+                self.in_frame = false;
+            }
+            if self.done {
+                return Ok(0);
+            }
+            if self.next_frame()? {
+                self.in_frame = true;
+            } else {
+                self.done = true;
+            }
+        }
+    }
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "zstd stream is truncated")
+}
@@ -1,4 +1,7 @@
-//! MBOX store: reads individual messages by offset with LRU caching.
+//! MBOX store: reads individual messages by offset (or by file, for a
+//! directory mailbox) with LRU caching. Compressed mailboxes are read through
+//! the checkpoint table recorded when they were indexed. Messages read from
+//! an MBOX come back with the `From ` quoting of its dialect undone.
 
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
@@ -10,9 +13,14 @@ use lru::LruCache;
 use tracing::debug;
 
 use crate::error::{MboxError, Result};
+use crate::index::builder;
 use crate::model::attachment::AttachmentMeta;
 use crate::model::mail::{MailBody, MailEntry};
+use crate::parser::dialect::MboxDialect;
+use crate::parser::mbox::MboxParser;
+use crate::parser::message_dir::{self, DirFormat};
 use crate::parser::mime;
+use crate::source::{Checkpoint, Codec, SeekableSource};
 
 /// Default number of decoded messages to keep in the LRU cache.
 const DEFAULT_CACHE_SIZE: usize = 50;
@@ -28,24 +36,58 @@ const DEFAULT_CACHE_SIZE: usize = 50;
 /// potentially multi-MB `MailBody` out of the cache on every access.
 pub struct MboxStore {
     path: PathBuf,
-    file: File,
+    backend: Backend,
+    dialect: MboxDialect,
     cache: LruCache<u64, Rc<MailBody>>,
 }
 
+/// Where message bytes come from.
+enum Backend {
+    /// A single MBOX file, read at each entry's offset.
+    Mbox(File),
+    /// A gzip/zstd/xz MBOX: offsets address the decompressed content.
+    Compressed(SeekableSource),
+    /// A Maildir or `.eml` folder: each entry names its own file relative
+    /// to the root.
+    Files(DirFormat),
+}
+
 impl MboxStore {
-    /// Open an MBOX file for random-access reading.
+    /// Open an MBOX file (or a Maildir / `.eml` folder) for random-access reading.
+    ///
+    /// A compressed MBOX should be indexed first: without its checkpoint
+    /// table every read going backwards decodes from the start of the file.
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
-        let file = File::open(&path).map_err(|e| MboxError::io(&path, e))?;
+        let mut dialect = MboxDialect::default();
+        let backend = if let Some(format) = message_dir::detect(&path) {
+            Backend::Files(format)
+        } else if let Some(codec) = Codec::detect(&path)? {
+            let checkpoints = builder::load_checkpoints(&path)
+                .map(|t| t.checkpoints)
+                .unwrap_or_else(|| vec![Checkpoint::start()]);
+            dialect = MboxParser::new(&path)?.dialect();
+            Backend::Compressed(SeekableSource::new(&path, codec, checkpoints))
+        } else {
+            dialect = MboxParser::new(&path)?.dialect();
+            Backend::Mbox(File::open(&path).map_err(|e| MboxError::io(&path, e))?)
+        };
         let cache_size =
             NonZeroUsize::new(DEFAULT_CACHE_SIZE).expect("DEFAULT_CACHE_SIZE is non-zero");
         Ok(Self {
             path,
-            file,
+            backend,
+            dialect,
             cache: LruCache::new(cache_size),
         })
     }
 
+    /// Dialect of the mailbox (mboxo for a Maildir or `.eml` folder, whose
+    /// messages are not quoted).
+    pub fn dialect(&self) -> MboxDialect {
+        self.dialect
+    }
+
     /// Read and decode a message, returning a shared handle to the cached body.
     ///
     /// The returned [`Rc`] is a cheap refcount bump, not a deep copy; it keeps
@@ -60,7 +102,8 @@ impl MboxStore {
         Ok(body)
     }
 
-    /// Read the raw bytes of a message (not cached).
+    /// Read the raw bytes of a message (not cached): as the mailbox holds
+    /// them, `From ` line included, except for the quoting of its dialect.
     pub fn get_raw_message(&mut self, entry: &MailEntry) -> Result<Vec<u8>> {
         self.read_raw(entry)
     }
@@ -75,16 +118,24 @@ impl MboxStore {
         mime::extract_attachment(&raw, attachment)
     }
 
-    /// Low-level: seek to offset and read `length` bytes.
+    /// Extract the text of a message's attachments (see
+    /// [`mime::extract_attachment_texts`]).
+    pub fn get_attachment_texts(&mut self, entry: &MailEntry) -> Result<Vec<String>> {
+        let raw = self.read_raw(entry)?;
+        mime::extract_attachment_texts(&raw)
+    }
+
+    /// Low-level: seek to offset and read `length` bytes, then undo the
+    /// dialect's quoting.
     fn read_raw(&mut self, entry: &MailEntry) -> Result<Vec<u8>> {
         debug!(
             offset = entry.offset,
             length = entry.length,
             "Reading message from MBOX"
         );
-        self.file
-            .seek(SeekFrom::Start(entry.offset))
-            .map_err(|e| MboxError::io(&self.path, e))?;
+        if let Backend::Files(format) = self.backend {
+            return self.read_message_file(entry, format);
+        }
         // Explicit conversion: on 32-bit targets a length above usize::MAX
         // would silently truncate with `as`, under-allocating the buffer.
         let length = usize::try_from(entry.length).map_err(|_| MboxError::ParseError {
@@ -92,9 +143,34 @@ impl MboxStore {
             reason: format!("message length {} exceeds addressable memory", entry.length),
         })?;
         let mut buf = vec![0u8; length];
-        self.file
-            .read_exact(&mut buf)
-            .map_err(|e| MboxError::io(&self.path, e))?;
-        Ok(buf)
+        match &mut self.backend {
+            Backend::Mbox(file) => {
+                file.seek(SeekFrom::Start(entry.offset))
+                    .map_err(|e| MboxError::io(&self.path, e))?;
+                file.read_exact(&mut buf)
+                    .map_err(|e| MboxError::io(&self.path, e))?;
+            }
+            Backend::Compressed(source) => source.read_at(entry.offset, &mut buf)?,
+            Backend::Files(_) => unreachable!("handled above"),
+        }
+        match self.dialect.unescape(&buf) {
+            std::borrow::Cow::Owned(unquoted) => Ok(unquoted),
+            std::borrow::Cow::Borrowed(_) => Ok(buf),
+        }
+    }
+
+    /// Read the whole file of a one-message-per-file entry.
+    ///
+    /// The file is read as it is now rather than trusting `entry.length`: a
+    /// Maildir message renamed by another client (flag change) is found by
+    /// its unique name and read in full.
+    fn read_message_file(&self, entry: &MailEntry, format: DirFormat) -> Result<Vec<u8>> {
+        let rel = entry.file.as_deref().ok_or_else(|| MboxError::ParseError {
+            offset: entry.offset,
+            reason: "index entry has no message file".to_string(),
+        })?;
+        let path = message_dir::locate_message(&self.path, format, rel)
+            .ok_or_else(|| MboxError::FileNotFound(self.path.join(rel)))?;
+        std::fs::read(&path).map_err(|e| MboxError::io(&path, e))
     }
 }
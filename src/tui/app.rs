@@ -9,7 +9,9 @@ use std::sync::Arc;
 
 use crate::i18n;
 use crate::index::builder;
+use crate::index::state::StateOverlay;
 use crate::model::mail::{MailBody, MailEntry};
+use crate::search::facets::Facets;
 use crate::store::reader::MboxStore;
 use crate::tui::threading;
 
@@ -164,9 +166,41 @@ pub const SIZE_OPTIONS: &[(&str, &str)] = &[
     ("< 10 KB", "size:<10kb"),
 ];
 
+/// Values listed per facet in the facet panel.
+pub const FACET_PANEL_VALUES: usize = 8;
+
 /// Maximum number of entries kept in search history.
 const MAX_SEARCH_HISTORY: usize = 20;
 
+/// A query saved under a name, listed in the sidebar as a virtual folder.
+#[derive(Debug, Clone)]
+pub struct SavedSearch {
+    /// Name it is saved under (`@name` on the command line).
+    pub name: String,
+    /// The query itself.
+    pub query: String,
+    /// Number of matching messages, or `None` while unknown: a query that
+    /// reads message bodies is only counted once it has been opened.
+    pub count: Option<usize>,
+}
+
+/// One row of the sidebar, in display order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SidebarEntry {
+    /// "All Messages".
+    All,
+    /// A label, by index into `App::all_labels`.
+    Label(usize),
+    /// "Unread": `is:unread`.
+    Unread,
+    /// "Starred": `is:starred`.
+    Starred,
+    /// A user tag, by index into `App::all_tags`.
+    Tag(usize),
+    /// A saved search, by index into `App::saved_searches`.
+    SavedSearch(usize),
+}
+
 /// Complete TUI state.
 /// A single case-insensitive match found in the message body by the
 /// interactive in-body search. Coordinates are body-relative: `line` is the
@@ -203,6 +237,10 @@ pub struct App {
     pub message_scroll_offset: usize,
     /// Set of offsets for "marked" messages (toggled with Space).
     pub marked: HashSet<u64>,
+    /// Read, starred and tag states the user set (see
+    /// [`crate::index::state`]). `None` when the overlay file could not be
+    /// read: changes are then refused rather than written over it.
+    pub state: Option<StateOverlay>,
 
     // ── UI state ──────────────────────────────
     /// Active panel.
@@ -244,6 +282,27 @@ pub struct App {
     pub sidebar_selected: usize,
     /// The active label filter (None = show all, Some = filter by label).
     pub active_label_filter: Option<String>,
+    /// Number of unread messages.
+    pub unread_count: usize,
+    /// Number of starred messages.
+    pub starred_count: usize,
+    /// All user tags in use, sorted alphabetically.
+    pub all_tags: Vec<String>,
+    /// Number of messages per tag (parallel to `all_tags`).
+    pub tag_counts: Vec<usize>,
+    /// Is the tag prompt open and capturing input?
+    pub tag_prompt_active: bool,
+    /// Tag typed into the tag prompt.
+    pub tag_prompt_input: String,
+    /// Saved searches listed below the labels, sorted by name.
+    pub saved_searches: Vec<SavedSearch>,
+    /// Config file saved searches are written to (`None` keeps them in
+    /// memory only).
+    pub saved_searches_file: Option<PathBuf>,
+    /// Is the "save search as" prompt open and capturing input?
+    pub save_search_active: bool,
+    /// Name typed into the "save search as" prompt.
+    pub save_search_name: String,
 
     // ── Search ────────────────────────────────
     /// Is the search bar active (accepting input)?
@@ -288,6 +347,14 @@ pub struct App {
     /// Whether the "search within previous results" checkbox is checked.
     pub filter_within_results: bool,
 
+    // ── Facet panel ───────────────────────────
+    /// Whether the facet panel is visible.
+    pub show_facets: bool,
+    /// Facets of the messages visible when the panel was opened.
+    pub facets: Option<Facets>,
+    /// Selected value in the panel (index into [`App::facet_rows`]).
+    pub facet_selected: usize,
+
     // ── Search history ───────────────────────
     /// Recent search queries, most recent first.
     pub search_history: Vec<String>,
@@ -379,6 +446,7 @@ impl App {
         let has_labels = !all_labels.is_empty();
 
         let entries = Arc::new(entries);
+        let state = StateOverlay::load(&mbox_path);
 
         let mut app = Self {
             mbox_path,
@@ -389,6 +457,7 @@ impl App {
             list_scroll_offset: 0,
             message_scroll_offset: 0,
             marked: HashSet::new(),
+            state: None,
             focus: PanelFocus::MailList,
             layout: LayoutMode::HorizontalSplit,
             show_help: false,
@@ -406,6 +475,16 @@ impl App {
             label_counts,
             sidebar_selected: 0,
             active_label_filter: None,
+            unread_count: 0,
+            starred_count: 0,
+            all_tags: Vec::new(),
+            tag_counts: Vec::new(),
+            tag_prompt_active: false,
+            tag_prompt_input: String::new(),
+            saved_searches: Vec::new(),
+            saved_searches_file: None,
+            save_search_active: false,
+            save_search_name: String::new(),
             search_active: false,
             search_query: String::new(),
             search_dirty_since: None,
@@ -424,6 +503,9 @@ impl App {
             filter_has_attachment: false,
             filter_label_selected: 0,
             filter_within_results: false,
+            show_facets: false,
+            facets: None,
+            facet_selected: 0,
             search_history: Vec::new(),
             search_history_index: None,
             search_draft: String::new(),
@@ -445,6 +527,18 @@ impl App {
             pending_html_view: None,
         };
 
+        match state {
+            Ok(state) => app.state = Some(state),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to read message states");
+                app.set_status(&format!("{}: {e}", i18n::tui_state_error()));
+            }
+        }
+        app.refresh_state_counts();
+        if !app.all_tags.is_empty() {
+            app.show_sidebar = true;
+        }
+
         // Sort by date descending and load first message
         app.apply_sort();
         if !app.visible_indices.is_empty() {
@@ -768,6 +862,10 @@ impl App {
         }
 
         let query = crate::search::query::parse_query(&self.search_query);
+        if let Err(e) = query.check() {
+            self.set_status(&format!("{}: {e}", i18n::tui_search_error()));
+            return;
+        }
 
         if crate::search::needs_body_scan(&query) {
             self.spawn_search_job(restrict);
@@ -900,6 +998,13 @@ impl App {
     fn apply_search_results(&mut self, mut results: Vec<usize>, restrict: Option<HashSet<usize>>) {
         if let Some(prev) = &restrict {
             results.retain(|i| prev.contains(i));
+        } else if let Some(saved) = self
+            .saved_searches
+            .iter_mut()
+            .find(|s| s.query == self.search_query)
+        {
+            // The whole mailbox was searched: keep the sidebar count live.
+            saved.count = Some(results.len());
         }
         self.search_results = results.clone();
         self.visible_indices = results;
@@ -982,8 +1087,10 @@ impl App {
 
         let query = crate::search::query::parse_query(&self.search_query);
 
-        // Skip incremental filtering if full-text is needed (too slow)
-        if query.needs_fulltext {
+        // Skip incremental filtering if full-text is needed (too slow), or
+        // while a pattern being typed does not compile yet — the error is
+        // reported when the search is submitted.
+        if query.needs_fulltext || !query.errors.is_empty() {
             return;
         }
 
@@ -1111,6 +1218,417 @@ impl App {
     }
 }
 
+impl App {
+    /// Open the facet panel over the visible messages.
+    pub fn open_facets(&mut self) {
+        self.show_facets = true;
+        self.facet_selected = 0;
+        self.facets = Some(crate::search::facets::compute(
+            &self.entries,
+            &self.visible_indices,
+        ));
+    }
+
+    /// Close the facet panel.
+    pub fn close_facets(&mut self) {
+        self.show_facets = false;
+        self.facets = None;
+    }
+
+    /// The selectable rows of the facet panel: `(facet, value)` index pairs,
+    /// the first [`FACET_PANEL_VALUES`] values of each facet.
+    pub fn facet_rows(&self) -> Vec<(usize, usize)> {
+        let Some(facets) = &self.facets else {
+            return Vec::new();
+        };
+        facets
+            .facets
+            .iter()
+            .enumerate()
+            .flat_map(|(f, facet)| {
+                (0..facet.values.len().min(FACET_PANEL_VALUES)).map(move |v| (f, v))
+            })
+            .collect()
+    }
+
+    /// Narrow the list to the messages with the selected facet value. The
+    /// matching term is appended to the query when the syntax has one, so
+    /// the narrowed search can be run again or saved.
+    pub fn narrow_to_facet(&mut self) {
+        let Some(&(f, v)) = self.facet_rows().get(self.facet_selected) else {
+            return;
+        };
+        let Some(facet) = self.facets.as_ref().map(|facets| &facets.facets[f]) else {
+            return;
+        };
+        let value = &facet.values[v];
+        let messages = value.messages.clone();
+        if let Some(term) = facet.kind.narrowing_term(&value.value) {
+            self.search_query = if self.search_query.is_empty() {
+                term
+            } else {
+                format!("{} {term}", self.search_query)
+            };
+            self.push_search_history(&self.search_query.clone());
+        }
+        self.close_facets();
+
+        self.cancel_search();
+        let scope = self.visible_indices.iter().copied().collect();
+        self.apply_search_results(messages, Some(scope));
+    }
+}
+
+impl App {
+    /// Whether the sidebar has anything to list: labels, the message states
+    /// of a non-empty mailbox, or saved searches.
+    pub fn has_sidebar_entries(&self) -> bool {
+        !self.entries.is_empty() || !self.saved_searches.is_empty()
+    }
+
+    /// Number of "Unread" and "Starred" rows: none for an empty mailbox.
+    fn state_rows(&self) -> usize {
+        if self.entries.is_empty() {
+            0
+        } else {
+            2
+        }
+    }
+
+    /// Number of rows in the sidebar.
+    pub fn sidebar_len(&self) -> usize {
+        1 + self.all_labels.len()
+            + self.state_rows()
+            + self.all_tags.len()
+            + self.saved_searches.len()
+    }
+
+    /// The sidebar row at `idx`, if there is one.
+    pub fn sidebar_entry(&self, idx: usize) -> Option<SidebarEntry> {
+        let labels = self.all_labels.len();
+        let states = self.state_rows();
+        let tags = self.all_tags.len();
+        match idx {
+            0 => Some(SidebarEntry::All),
+            i if i <= labels => Some(SidebarEntry::Label(i - 1)),
+            i if i <= labels + states => Some(if i == labels + 1 {
+                SidebarEntry::Unread
+            } else {
+                SidebarEntry::Starred
+            }),
+            i if i <= labels + states + tags => Some(SidebarEntry::Tag(i - 1 - labels - states)),
+            i if i <= labels + states + tags + self.saved_searches.len() => {
+                Some(SidebarEntry::SavedSearch(i - 1 - labels - states - tags))
+            }
+            _ => None,
+        }
+    }
+
+    /// The query a message-state row of the sidebar runs.
+    pub fn sidebar_query(&self, entry: SidebarEntry) -> Option<String> {
+        match entry {
+            SidebarEntry::Unread => Some("is:unread".to_string()),
+            SidebarEntry::Starred => Some("is:starred".to_string()),
+            SidebarEntry::Tag(i) => self
+                .all_tags
+                .get(i)
+                .map(|tag| format!("tag:{}", quote_if_needed(tag))),
+            SidebarEntry::All | SidebarEntry::Label(_) | SidebarEntry::SavedSearch(_) => None,
+        }
+    }
+
+    /// Whether the results of sidebar row `entry`'s query are on screen.
+    pub fn sidebar_query_active(&self, entry: SidebarEntry) -> bool {
+        self.active_label_filter.is_none()
+            && !self.filter_within_results
+            && self.sidebar_query(entry).as_ref() == Some(&self.search_query)
+    }
+
+    /// Run the query of a message-state row of the sidebar over the whole
+    /// mailbox.
+    pub fn open_sidebar_query(&mut self, entry: SidebarEntry) {
+        if let Some(query) = self.sidebar_query(entry) {
+            self.search_whole_mailbox(query);
+        }
+    }
+
+    /// Load saved searches (name → query) into the sidebar, counting the
+    /// matches of those the index alone can answer.
+    pub fn set_saved_searches(&mut self, searches: &BTreeMap<String, String>) {
+        self.saved_searches = searches
+            .iter()
+            .map(|(name, query)| SavedSearch {
+                name: name.clone(),
+                query: query.clone(),
+                count: self.metadata_count(query),
+            })
+            .collect();
+        if !self.saved_searches.is_empty() {
+            self.show_sidebar = true;
+        }
+    }
+
+    /// Number of messages matching `query`, when it can be told from the
+    /// index without reading any message.
+    fn metadata_count(&self, query: &str) -> Option<usize> {
+        let query = crate::search::query::parse_query(query);
+        if query.check().is_err() || crate::search::needs_body_scan(&query) {
+            return None;
+        }
+        Some(crate::search::metadata::search_metadata(&self.entries, &query).len())
+    }
+
+    /// The saved search whose results are on screen, if any.
+    pub fn active_saved_search(&self) -> Option<usize> {
+        if self.active_label_filter.is_some()
+            || self.filter_within_results
+            || self.search_query.is_empty()
+        {
+            return None;
+        }
+        self.saved_searches
+            .iter()
+            .position(|s| s.query == self.search_query)
+    }
+
+    /// Run saved search `i` over the whole mailbox.
+    pub fn open_saved_search(&mut self, i: usize) {
+        let Some(query) = self.saved_searches.get(i).map(|s| s.query.clone()) else {
+            return;
+        };
+        self.search_whole_mailbox(query);
+    }
+
+    /// Run `query` over the whole mailbox, leaving any label filter or
+    /// "within previous results" scope.
+    fn search_whole_mailbox(&mut self, query: String) {
+        self.active_label_filter = None;
+        self.filter_within_results = false;
+        self.push_search_history(&query);
+        self.search_query = query;
+        self.execute_search();
+    }
+
+    /// Save the current search as `name` (a leading `@` is dropped),
+    /// replacing any saved search of that name.
+    pub fn save_current_search(&mut self, name: &str) {
+        let name = name.trim().trim_start_matches('@').trim();
+        if name.is_empty() {
+            return;
+        }
+        if self.search_query.is_empty() {
+            self.set_status(i18n::tui_no_search_to_save());
+            return;
+        }
+        let query = self.search_query.clone();
+        if let Some(path) = &self.saved_searches_file {
+            if let Err(e) = crate::config::store_saved_search(path, name, Some(&query)) {
+                tracing::warn!(error = %e, "Failed to save search");
+                self.set_status(&format!("{}: {e}", i18n::tui_saved_search_error()));
+                return;
+            }
+        }
+
+        // Results of this very query over the whole mailbox are on screen.
+        let shown = (self.active_label_filter.is_none()
+            && !self.filter_within_results
+            && !self.search_in_progress())
+        .then_some(self.visible_indices.len());
+        let saved = SavedSearch {
+            name: name.to_string(),
+            count: self.metadata_count(&query).or(shown),
+            query,
+        };
+        match self
+            .saved_searches
+            .binary_search_by(|s| s.name.as_str().cmp(name))
+        {
+            Ok(i) => self.saved_searches[i] = saved,
+            Err(i) => self.saved_searches.insert(i, saved),
+        }
+        self.show_sidebar = true;
+        self.set_status(&format!("{} @{name}", i18n::tui_search_saved()));
+    }
+
+    /// Delete saved search `i`.
+    pub fn delete_saved_search(&mut self, i: usize) {
+        let Some(name) = self.saved_searches.get(i).map(|s| s.name.clone()) else {
+            return;
+        };
+        if let Some(path) = &self.saved_searches_file {
+            if let Err(e) = crate::config::store_saved_search(path, &name, None) {
+                tracing::warn!(error = %e, "Failed to delete saved search");
+                self.set_status(&format!("{}: {e}", i18n::tui_saved_search_error()));
+                return;
+            }
+        }
+        self.saved_searches.remove(i);
+        self.sidebar_selected = self
+            .sidebar_selected
+            .min(self.sidebar_len().saturating_sub(1));
+        self.set_status(&format!("{} @{name}", i18n::tui_search_deleted()));
+    }
+}
+
+impl App {
+    /// The messages a state change applies to: the marked ones if any,
+    /// otherwise the selected one.
+    fn state_targets(&self) -> Vec<usize> {
+        if self.marked.is_empty() {
+            return self
+                .visible_indices
+                .get(self.selected)
+                .copied()
+                .into_iter()
+                .collect();
+        }
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| self.marked.contains(&e.offset))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Mark the targets read, or unread when they all are read already.
+    pub fn toggle_read(&mut self) {
+        let targets = self.state_targets();
+        let read = !targets.iter().all(|&i| self.entries[i].flags.seen);
+        if self.change_state(&targets, |state, entry| state.set_read(entry, read)) {
+            self.set_status(if read {
+                i18n::tui_marked_read()
+            } else {
+                i18n::tui_marked_unread()
+            });
+        }
+    }
+
+    /// Star the targets, or unstar them when they all are starred already.
+    pub fn toggle_starred(&mut self) {
+        let targets = self.state_targets();
+        let starred = !targets.iter().all(|&i| self.entries[i].flags.flagged);
+        if self.change_state(&targets, |state, entry| state.set_starred(entry, starred)) {
+            self.set_status(if starred {
+                i18n::tui_starred()
+            } else {
+                i18n::tui_unstarred()
+            });
+        }
+    }
+
+    /// Tag the targets with `tag`, or untag them when they all have it.
+    pub fn toggle_tag(&mut self, tag: &str) {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return;
+        }
+        let targets = self.state_targets();
+        let present = !targets
+            .iter()
+            .all(|&i| self.entries[i].tags.iter().any(|t| t == tag));
+        if self.change_state(&targets, |state, entry| state.set_tag(entry, tag, present)) {
+            let done = if present {
+                i18n::tui_tag_added()
+            } else {
+                i18n::tui_tag_removed()
+            };
+            self.set_status(&format!("{done} #{tag}"));
+        }
+    }
+
+    /// Mark the selected message read, as opening it does.
+    pub fn mark_selected_read(&mut self) {
+        let Some(&i) = self.visible_indices.get(self.selected) else {
+            return;
+        };
+        if !self.entries[i].flags.seen && self.state.is_some() {
+            self.change_state(&[i], |state, entry| state.set_read(entry, true));
+        }
+    }
+
+    /// Apply `change` to every target through the state overlay, then save
+    /// it. Returns whether anything was changed.
+    fn change_state(
+        &mut self,
+        targets: &[usize],
+        change: impl Fn(&mut StateOverlay, &mut MailEntry),
+    ) -> bool {
+        if targets.is_empty() {
+            return false;
+        }
+        let Some(state) = self.state.as_mut() else {
+            self.set_status(i18n::tui_state_unavailable());
+            return false;
+        };
+
+        let entries = Arc::make_mut(&mut self.entries);
+        let mut changed: std::collections::HashMap<String, usize> = Default::default();
+        for &i in targets {
+            change(state, &mut entries[i]);
+            if !entries[i].message_id.is_empty() {
+                changed.insert(entries[i].message_id.clone(), i);
+            }
+        }
+        // Other copies of a message (same Message-ID) share its state.
+        let copies: Vec<(usize, usize)> = entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| {
+                changed
+                    .get(&e.message_id)
+                    .filter(|&&source| source != i)
+                    .map(|&source| (i, source))
+            })
+            .collect();
+        for (copy, source) in copies {
+            let (flags, tags) = (entries[source].flags, entries[source].tags.clone());
+            entries[copy].flags.seen = flags.seen;
+            entries[copy].flags.flagged = flags.flagged;
+            entries[copy].tags = tags;
+        }
+
+        if let Err(e) = state.save(&self.mbox_path) {
+            tracing::warn!(error = %e, "Failed to save message states");
+            self.set_status(&format!("{}: {e}", i18n::tui_state_error()));
+        }
+        self.refresh_state_counts();
+        self.render_cache = None;
+        true
+    }
+
+    /// Recount unread, starred and tagged messages for the sidebar.
+    fn refresh_state_counts(&mut self) {
+        let mut tags: BTreeMap<&str, usize> = BTreeMap::new();
+        let (mut unread, mut starred) = (0, 0);
+        for entry in self.entries.iter() {
+            unread += usize::from(!entry.flags.seen);
+            starred += usize::from(entry.flags.flagged);
+            for tag in &entry.tags {
+                *tags.entry(tag).or_insert(0) += 1;
+            }
+        }
+        self.all_tags = tags.keys().map(|t| t.to_string()).collect();
+        self.tag_counts = tags.into_values().collect();
+        self.unread_count = unread;
+        self.starred_count = starred;
+        // Saved searches may filter on the states too (`is:unread`,
+        // `tag:review`). Those that need the message keep their last count.
+        let counts: Vec<Option<usize>> = self
+            .saved_searches
+            .iter()
+            .map(|saved| self.metadata_count(&saved.query))
+            .collect();
+        for (saved, count) in self.saved_searches.iter_mut().zip(counts) {
+            if count.is_some() {
+                saved.count = count;
+            }
+        }
+        self.sidebar_selected = self
+            .sidebar_selected
+            .min(self.sidebar_len().saturating_sub(1));
+    }
+}
+
 impl App {
     /// Open the interactive in-body search prompt, starting from a blank query.
     pub fn body_search_open(&mut self) {
@@ -1236,13 +1754,7 @@ fn quote_if_needed(value: &str) -> String {
 #[cfg(test)]
 mod body_search_tests {
     use super::{find_matches_ci, App, BodyMatch};
-    use std::path::PathBuf;
-
-    fn fixture(name: &str) -> PathBuf {
-        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("tests/fixtures")
-            .join(name)
-    }
+    use crate::testing::temp_fixture;
 
     #[test]
     fn find_matches_basic_and_case_insensitive() {
@@ -1282,7 +1794,8 @@ mod body_search_tests {
     #[test]
     fn external_html_view_sanitizes_script() {
         use super::MailBody;
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.current_body = Some(std::rc::Rc::new(MailBody {
             text: Some("plain".to_string()),
             html: Some(
@@ -1308,7 +1821,8 @@ mod body_search_tests {
 
     #[test]
     fn next_and_prev_wrap_around() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.body_search_matches = vec![
             BodyMatch {
                 line: 0,
@@ -1345,7 +1859,8 @@ mod body_search_tests {
 
     #[test]
     fn next_prev_on_empty_matches_do_not_request_recenter() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.body_search_matches.clear();
         app.body_search_recenter = false;
         app.body_search_next();
@@ -1358,7 +1873,8 @@ mod body_search_tests {
 
     #[test]
     fn clear_resets_all_state() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.body_search_active = true;
         app.body_search_query = "x".to_string();
         app.body_search_matches = vec![BodyMatch {
@@ -1402,16 +1918,9 @@ mod build_query_tests {
 #[cfg(test)]
 mod async_search_tests {
     use super::App;
-    use std::path::{Path, PathBuf};
+    use crate::testing::temp_fixture;
     use std::time::{Duration, Instant};
 
-    fn fixture(name: &str) -> PathBuf {
-        Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("tests")
-            .join("fixtures")
-            .join(name)
-    }
-
     /// Drive an in-flight background search to completion (bounded wait).
     fn drain_search(app: &mut App) {
         let deadline = Instant::now() + Duration::from_secs(5);
@@ -1427,7 +1936,8 @@ mod async_search_tests {
 
     #[test]
     fn multiword_body_search_runs_in_background_and_applies_results() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.search_query = "perspective message".to_string();
         app.execute_search();
 
@@ -1449,7 +1959,8 @@ mod async_search_tests {
 
     #[test]
     fn metadata_only_search_runs_inline() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.search_query = "from:user1".to_string();
         app.execute_search();
         assert!(
@@ -1460,7 +1971,8 @@ mod async_search_tests {
 
     #[test]
     fn cancelling_a_search_clears_the_job() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.search_query = "perspective".to_string();
         app.execute_search();
         assert!(app.search_in_progress());
@@ -1474,7 +1986,8 @@ mod async_search_tests {
     #[test]
     fn search_respects_active_label_filter_scope() {
         use std::sync::Arc;
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
 
         // Tag the first two entries with a label and activate the label filter,
         // mirroring what apply_label_filter does in the TUI.
@@ -1506,7 +2019,8 @@ mod async_search_tests {
     #[test]
     fn empty_query_respects_active_label_filter_scope() {
         use std::sync::Arc;
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
 
         {
             let entries = Arc::make_mut(&mut app.entries);
@@ -1528,7 +2042,8 @@ mod async_search_tests {
     /// the previously matched messages, instead of searching the whole index.
     #[test]
     fn within_results_persists_and_scopes_body_search() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
 
         // Step 1: a subject search narrows the view to the "Hello World" thread.
         app.search_query = "subject:Hello".to_string();
@@ -1571,7 +2086,8 @@ mod async_search_tests {
     /// within-results mode so it cannot silently apply to a stale result set.
     #[test]
     fn within_results_clears_on_label_filter_change() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.filter_within_results = true;
         app.apply_label_filter(None);
         assert!(
@@ -1585,7 +2101,8 @@ mod async_search_tests {
     /// proves the body was not cloned out of the LRU on selection.
     #[test]
     fn load_body_is_shared_not_deep_copied() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         assert!(!app.visible_indices.is_empty(), "fixture has messages");
         app.select_message(0);
         let body = app
@@ -1605,7 +2122,8 @@ mod async_search_tests {
     #[test]
     fn incremental_search_single_pass() {
         use std::sync::Arc;
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
 
         // No label filter: results are exactly the metadata matches.
         app.search_query = "from:user1".to_string();
@@ -1640,7 +2158,8 @@ mod async_search_tests {
     /// `cancel_pending_incremental_search` discards it.
     #[test]
     fn incremental_search_is_debounced_and_cancelable() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.search_query = "from:user1".to_string();
 
         app.mark_search_dirty();
@@ -1659,4 +2178,132 @@ mod async_search_tests {
         // A poll with nothing pending is a no-op.
         app.poll_incremental_search();
     }
+
+    #[test]
+    fn saved_searches_are_listed_and_counted() {
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
+        let searches = std::collections::BTreeMap::from([
+            ("hello".to_string(), "subject:hello".to_string()),
+            ("spanish".to_string(), "body:especiales".to_string()),
+            ("to do".to_string(), "is:unread OR tag:todo".to_string()),
+        ]);
+        app.set_saved_searches(&searches);
+        assert!(app.show_sidebar && app.has_sidebar_entries());
+        // All, Unread, Starred, then the saved searches.
+        assert_eq!(app.sidebar_len(), 6);
+        assert_eq!(
+            app.sidebar_entry(5),
+            Some(super::SidebarEntry::SavedSearch(2))
+        );
+        assert_eq!(app.sidebar_entry(6), None);
+
+        // Metadata-only searches are counted up front; body searches once run.
+        assert_eq!(app.saved_searches[0].count, Some(2));
+        assert_eq!(app.saved_searches[1].count, None);
+        app.open_saved_search(1);
+        drain_search(&mut app);
+        assert_eq!(app.saved_searches[1].count, Some(1));
+        assert_eq!(app.visible_indices.len(), 1);
+        assert_eq!(app.active_saved_search(), Some(1));
+
+        // Choosing a label leaves the saved search.
+        app.apply_label_filter(None);
+        assert_eq!(app.active_saved_search(), None);
+
+        // Counts follow the states the searches filter on.
+        assert_eq!(app.saved_searches[2].count, Some(0));
+        app.toggle_read();
+        assert_eq!(app.saved_searches[2].count, Some(1));
+        app.toggle_tag("todo");
+        app.toggle_read();
+        assert_eq!(app.saved_searches[2].count, Some(1));
+        app.toggle_tag("todo");
+        assert_eq!(app.saved_searches[2].count, Some(0));
+        assert_eq!(app.saved_searches[1].count, Some(1));
+    }
+
+    #[test]
+    fn message_states_are_toggled_counted_and_saved() {
+        use super::SidebarEntry;
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox.clone(), true).expect("open copy");
+        // The fixture has no status headers, so every message starts read.
+        assert_eq!((app.unread_count, app.starred_count), (0, 0));
+
+        app.toggle_read();
+        app.toggle_starred();
+        app.toggle_tag(" review ");
+        assert_eq!(app.unread_count, 1);
+        assert_eq!(app.starred_count, 1);
+        assert_eq!(app.all_tags, ["review"]);
+        assert!(crate::index::builder::state_path_for(&mbox).exists());
+
+        // All, Unread, Starred, #review.
+        assert_eq!(app.sidebar_entry(3), Some(SidebarEntry::Tag(0)));
+        app.open_sidebar_query(SidebarEntry::Tag(0));
+        drain_search(&mut app);
+        assert_eq!(app.visible_indices.len(), 1);
+
+        // The states come back with the mailbox.
+        let reopened = App::new(mbox, true).expect("reopen copy");
+        assert_eq!(reopened.unread_count, 1);
+        assert_eq!(reopened.starred_count, 1);
+        assert_eq!(reopened.all_tags, ["review"]);
+
+        app.toggle_tag("review");
+        assert!(app.all_tags.is_empty());
+    }
+
+    #[test]
+    fn saving_and_deleting_a_search_updates_the_config_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = tmp.path().join("config.toml");
+        let saved = || -> crate::config::Config {
+            toml::from_str(&std::fs::read_to_string(&config).unwrap()).unwrap()
+        };
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
+        app.saved_searches_file = Some(config.clone());
+
+        app.save_current_search("nothing");
+        assert!(
+            app.saved_searches.is_empty(),
+            "an empty search is not saved"
+        );
+
+        app.search_query = "from:user1".to_string();
+        app.execute_search();
+        app.save_current_search("@mine ");
+        assert_eq!(app.saved_searches.len(), 1);
+        assert_eq!(app.saved_searches[0].name, "mine");
+        assert_eq!(app.saved_searches[0].count, Some(app.visible_indices.len()));
+        assert_eq!(saved().searches["mine"], "from:user1");
+
+        app.delete_saved_search(0);
+        assert!(app.saved_searches.is_empty());
+        assert!(saved().searches.is_empty());
+    }
+
+    #[test]
+    fn facet_panel_narrows_the_results_to_a_value() {
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
+        app.search_query = "subject:hello".to_string();
+        app.execute_search();
+        assert_eq!(app.visible_indices.len(), 2);
+
+        app.open_facets();
+        let facets = app.facets.as_ref().expect("facets computed");
+        assert_eq!(facets.total, 2);
+
+        // The first row is the most frequent sender.
+        let sender = &facets.facets[0].values[0];
+        let (address, messages) = (sender.value.clone(), sender.messages.clone());
+        app.facet_selected = 0;
+        app.narrow_to_facet();
+        assert!(!app.show_facets && app.facets.is_none());
+        assert_eq!(app.visible_indices, messages);
+        assert_eq!(app.search_query, format!("subject:hello from:{address}"));
+    }
 }
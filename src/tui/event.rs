@@ -13,7 +13,9 @@ use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use crate::i18n;
 
-use super::app::{App, LayoutMode, PanelFocus, SearchFilterField, SortColumn, SIZE_OPTIONS};
+use super::app::{
+    App, LayoutMode, PanelFocus, SearchFilterField, SidebarEntry, SortColumn, SIZE_OPTIONS,
+};
 
 /// Process a key event and update the application state.
 pub fn handle_key_event(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
@@ -27,6 +29,16 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
         return handle_body_search_input(app, key);
     }
 
+    // ── "Save search as" prompt (captures all keys) ───────
+    if app.save_search_active {
+        return handle_save_search_input(app, key);
+    }
+
+    // ── Tag prompt (captures all keys) ────────────────────
+    if app.tag_prompt_active {
+        return handle_tag_prompt_input(app, key);
+    }
+
     // ── Popup handling (captures all keys) ────────────────
     if app.show_help {
         match key.code {
@@ -48,6 +60,10 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
         return handle_search_filter_popup(app, key);
     }
 
+    if app.show_facets {
+        return handle_facet_popup(app, key);
+    }
+
     // ── Cancel an in-flight background search with Esc ────
     if app.search_in_progress() && key.code == KeyCode::Esc {
         app.cancel_search();
@@ -120,7 +136,7 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
 
 /// Cycle focus to the next (or previous) panel.
 fn next_focus(app: &App, forward: bool) -> PanelFocus {
-    let has_sidebar = app.show_sidebar && !app.all_labels.is_empty();
+    let has_sidebar = app.show_sidebar && app.has_sidebar_entries();
 
     if forward {
         match app.focus {
@@ -153,7 +169,7 @@ fn next_focus(app: &App, forward: bool) -> PanelFocus {
 
 /// Handle the L key: toggle sidebar visibility and focus.
 fn handle_sidebar_toggle(app: &mut App) {
-    if app.all_labels.is_empty() {
+    if !app.has_sidebar_entries() {
         app.set_status(i18n::tui_no_labels());
         return;
     }
@@ -233,6 +249,7 @@ fn handle_mail_list_keys(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
             // In ListOnly the view renders fullscreen on focus change,
             // so keep the layout and just move focus.
             app.focus = PanelFocus::MailView;
+            app.mark_selected_read();
         }
         KeyCode::Char(' ') => app.toggle_mark(),
         KeyCode::Char('*') => {
@@ -255,6 +272,11 @@ fn handle_mail_list_keys(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
             }
         }
 
+        // ── Message states (marked messages, or the selected one) ─
+        KeyCode::Char('u') => app.toggle_read(),
+        KeyCode::Char('x') => app.toggle_starred(),
+        KeyCode::Char('T') => open_tag_prompt(app),
+
         // ── Sorting ──────────────────────────────────────────
         KeyCode::Char('s') => {
             let next = match app.sort_column {
@@ -300,6 +322,17 @@ fn handle_mail_list_keys(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
             app.show_search_filter = true;
         }
         KeyCode::Char('t') => app.toggle_threads(),
+        // b breaks the visible messages down by sender, label, date, ...
+        KeyCode::Char('b') => app.open_facets(),
+        // w names the current search and saves it to the sidebar.
+        KeyCode::Char('w') => {
+            if app.search_query.is_empty() {
+                app.set_status(i18n::tui_no_search_to_save());
+            } else {
+                app.save_search_name.clear();
+                app.save_search_active = true;
+            }
+        }
 
         // ── Search navigation ────────────────────────────────
         KeyCode::Char('n') => {
@@ -381,6 +414,9 @@ fn handle_mail_view_keys(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
             app.export_selected = 0;
             app.show_export = true;
         }
+        KeyCode::Char('u') => app.toggle_read(),
+        KeyCode::Char('x') => app.toggle_starred(),
+        KeyCode::Char('T') => open_tag_prompt(app),
         KeyCode::Char('q') => {
             app.should_quit = true;
         }
@@ -389,6 +425,61 @@ fn handle_mail_view_keys(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Open the tag prompt, unless there is no message to tag.
+fn open_tag_prompt(app: &mut App) {
+    if app.marked.is_empty() && app.current_entry().is_none() {
+        return;
+    }
+    app.tag_prompt_input.clear();
+    app.tag_prompt_active = true;
+}
+
+/// Key handling while the tag prompt is open: Enter toggles the typed tag on
+/// the marked messages (or the selected one), Esc abandons it.
+fn handle_tag_prompt_input(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
+    match key.code {
+        KeyCode::Esc => {
+            app.tag_prompt_active = false;
+        }
+        KeyCode::Enter => {
+            app.tag_prompt_active = false;
+            let tag = std::mem::take(&mut app.tag_prompt_input);
+            app.toggle_tag(tag.trim_start_matches('#'));
+        }
+        KeyCode::Backspace => {
+            app.tag_prompt_input.pop();
+        }
+        KeyCode::Char(c) => {
+            app.tag_prompt_input.push(c);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Key handling while the "save search as" prompt is open: Enter saves the
+/// current search under the typed name, Esc abandons it.
+fn handle_save_search_input(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
+    match key.code {
+        KeyCode::Esc => {
+            app.save_search_active = false;
+        }
+        KeyCode::Enter => {
+            app.save_search_active = false;
+            let name = std::mem::take(&mut app.save_search_name);
+            app.save_current_search(&name);
+        }
+        KeyCode::Backspace => {
+            app.save_search_name.pop();
+        }
+        KeyCode::Char(c) => {
+            app.save_search_name.push(c);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
 /// Key handling while the in-body search prompt is open and capturing input.
 ///
 /// Typing refines the query with live highlighting; Enter confirms (closing the
@@ -415,9 +506,9 @@ fn handle_body_search_input(app: &mut App, key: KeyEvent) -> anyhow::Result<()>
     Ok(())
 }
 
-/// Key handling when the sidebar (labels) panel has focus.
+/// Key handling when the sidebar (labels and saved searches) has focus.
 fn handle_sidebar_keys(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
-    let total = 1 + app.all_labels.len(); // "All Messages" + labels
+    let total = app.sidebar_len();
     match key.code {
         KeyCode::Char('j') | KeyCode::Down => {
             if app.sidebar_selected + 1 < total {
@@ -436,14 +527,30 @@ fn handle_sidebar_keys(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
             app.sidebar_selected = total.saturating_sub(1);
         }
         KeyCode::Enter => {
-            // Apply label filter and move focus to mail list
-            if app.sidebar_selected == 0 {
-                app.apply_label_filter(None);
-            } else if let Some(label) = app.all_labels.get(app.sidebar_selected - 1) {
-                app.apply_label_filter(Some(label.clone()));
+            // Apply the label filter or saved search and move focus to the
+            // mail list
+            match app.sidebar_entry(app.sidebar_selected) {
+                Some(SidebarEntry::All) => app.apply_label_filter(None),
+                Some(SidebarEntry::Label(i)) => {
+                    let label = app.all_labels[i].clone();
+                    app.apply_label_filter(Some(label));
+                }
+                Some(SidebarEntry::SavedSearch(i)) => app.open_saved_search(i),
+                Some(
+                    entry @ (SidebarEntry::Unread | SidebarEntry::Starred | SidebarEntry::Tag(_)),
+                ) => app.open_sidebar_query(entry),
+                None => {}
             }
             app.focus = PanelFocus::MailList;
         }
+        KeyCode::Char('d') | KeyCode::Delete => {
+            if let Some(SidebarEntry::SavedSearch(i)) = app.sidebar_entry(app.sidebar_selected) {
+                app.delete_saved_search(i);
+                if !app.has_sidebar_entries() {
+                    app.focus = PanelFocus::MailList;
+                }
+            }
+        }
         KeyCode::Esc => {
             // Leave sidebar, go back to mail list
             app.focus = PanelFocus::MailList;
@@ -522,6 +629,28 @@ fn handle_attachment_popup(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Key handling when the facet panel is open.
+fn handle_facet_popup(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
+    let count = app.facet_rows().len();
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('b') => app.close_facets(),
+        KeyCode::Char('j') | KeyCode::Down => {
+            if app.facet_selected + 1 < count {
+                app.facet_selected += 1;
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.facet_selected = app.facet_selected.saturating_sub(1);
+        }
+        KeyCode::Char('g') | KeyCode::Home => app.facet_selected = 0,
+        KeyCode::Char('G') | KeyCode::End => app.facet_selected = count.saturating_sub(1),
+        KeyCode::Enter => app.narrow_to_facet(),
+        _ => {}
+    }
+    Ok(())
+}
+
 /// Key handling when the export popup is open.
 fn handle_export_popup(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
     let option_count = crate::tui::widgets::export_popup::EXPORT_OPTION_COUNT;
@@ -994,20 +1123,15 @@ fn handle_search_filter_popup(app: &mut App, key: KeyEvent) -> anyhow::Result<()
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::temp_fixture;
     use crate::tui::app::App;
-    use std::path::PathBuf;
-
-    fn fixture(name: &str) -> PathBuf {
-        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("tests/fixtures")
-            .join(name)
-    }
 
     /// Regression for #20: exporting with several messages marked must write
     /// one file per marked message for HTML and TXT (not just the current one).
     #[test]
     fn export_html_and_txt_cover_all_marked_messages() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         assert!(
             app.entries.len() >= 2,
             "fixture needs at least two messages"
@@ -1038,7 +1162,8 @@ mod tests {
     #[test]
     fn mark_all_star_toggles_only_the_visible_set() {
         use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         assert!(app.entries.len() >= 3, "fixture needs 3+ messages");
         let outside = app.entries[0].offset;
         app.marked.insert(outside); // marked, but outside the filtered view below
@@ -27,7 +27,7 @@ use crate::i18n;
 /// Run the TUI application. Blocks until the user quits.
 pub fn run_tui(mbox_path: PathBuf, force_reindex: bool) -> anyhow::Result<()> {
     // Show progress bar BEFORE entering alternate screen so the user sees it
-    let file_size = std::fs::metadata(&mbox_path)?.len();
+    let file_size = crate::index::builder::mailbox_size(&mbox_path)?;
     let pb = ProgressBar::new(file_size);
     pb.set_style(
         ProgressStyle::default_bar()
@@ -39,10 +39,12 @@ pub fn run_tui(mbox_path: PathBuf, force_reindex: bool) -> anyhow::Result<()> {
             .progress_chars("#>-"),
     );
 
-    let app = App::new_with_progress(mbox_path, force_reindex, &|current, total| {
+    let mut app = App::new_with_progress(mbox_path, force_reindex, &|current, total| {
         pb.set_length(total);
         pb.set_position(current);
     })?;
+    app.saved_searches_file = crate::config::config_file_path();
+    app.set_saved_searches(&crate::config::load_config().searches);
 
     pb.finish_and_clear();
 
@@ -421,16 +421,24 @@ mod tests {
             },
             to: Vec::new(),
             cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: Vec::new(),
+            sender: None,
+            delivered_to: Vec::new(),
             subject: subject.to_string(),
             message_id: message_id.to_string(),
             in_reply_to: in_reply_to.map(String::from),
             references: references.into_iter().map(String::from).collect(),
             has_attachments: false,
+            attachments: Vec::new(),
             content_type: "text/plain".to_string(),
             text_size: 100,
             labels: Vec::new(),
+            tags: Vec::new(),
             thread_id: None,
             sequence: idx,
+            file: None,
+            flags: Default::default(),
         }
     }
 
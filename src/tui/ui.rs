@@ -24,7 +24,7 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     widgets::header_bar::render(frame, app, vertical[0]);
 
     // Content area with optional sidebar
-    let content_area = if app.show_sidebar && !app.all_labels.is_empty() {
+    let content_area = if app.show_sidebar && app.has_sidebar_entries() {
         let h_split = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Length(24), Constraint::Min(30)])
@@ -69,6 +69,10 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     // the body being searched, so it does not appear here.
     if app.search_active {
         widgets::search_bar::render(frame, app, vertical[2]);
+    } else if app.save_search_active {
+        widgets::save_search_bar::render(frame, app, vertical[2]);
+    } else if app.tag_prompt_active {
+        widgets::tag_bar::render(frame, app, vertical[2]);
     } else {
         widgets::status_bar::render(frame, app, vertical[2]);
     }
@@ -86,4 +90,7 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     if app.show_search_filter {
         widgets::search_popup::render(frame, app);
     }
+    if app.show_facets {
+        widgets::facet_popup::render(frame, app);
+    }
 }
@@ -0,0 +1,89 @@
+//! Facet panel: the visible messages counted by sender, domain, label,
+//! date, thread and attachment type.
+
+use ratatui::layout::{Constraint, Rect};
+use ratatui::widgets::{Block, Borders, Cell, Clear, Row, Table};
+use ratatui::Frame;
+
+use crate::i18n;
+use crate::tui::app::{App, FACET_PANEL_VALUES};
+use crate::tui::text::sanitize_line;
+use crate::tui::theme::current_theme;
+
+/// Render the facet panel centered on screen.
+pub fn render(frame: &mut Frame, app: &App) {
+    let Some(facets) = &app.facets else {
+        return;
+    };
+    let theme = current_theme();
+    let area = centered_rect(60, 70, frame.area());
+
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(theme.popup_title)
+        .title(i18n::tui_facets_title())
+        .style(theme.popup);
+
+    // One heading row per facet, then its first values; `selected_line` is
+    // the line of the selected value, to keep it in view.
+    let mut rows: Vec<Row> = Vec::new();
+    let mut selected_line = 0;
+    let mut value_row = 0;
+    for facet in &facets.facets {
+        rows.push(Row::new(vec![
+            Cell::from(""),
+            Cell::from(i18n::facet_title(facet.kind)).style(theme.popup_title),
+        ]));
+        for value in facet.values.iter().take(FACET_PANEL_VALUES) {
+            let selected = value_row == app.facet_selected;
+            if selected {
+                selected_line = rows.len();
+            }
+            let style = if selected {
+                theme.list_selected
+            } else {
+                theme.popup
+            };
+            rows.push(Row::new(vec![
+                Cell::from(format!("{:>6}", value.count)).style(style),
+                Cell::from(sanitize_line(&value.value).into_owned()).style(style),
+            ]));
+            value_row += 1;
+        }
+        let hidden = facet.values.len().saturating_sub(FACET_PANEL_VALUES);
+        if hidden > 0 {
+            rows.push(Row::new(vec![
+                Cell::from(""),
+                Cell::from(format!("\u{2026} {hidden} {}", i18n::facet_more()))
+                    .style(theme.help_dim),
+            ]));
+        }
+    }
+
+    // Borders take two lines and the footer two more.
+    let height = usize::from(area.height.saturating_sub(4));
+    let skip = (selected_line + 1).saturating_sub(height);
+    let mut visible: Vec<Row> = rows.into_iter().skip(skip).take(height).collect();
+    visible.push(Row::new(vec![Cell::from(""), Cell::from("")]));
+    visible.push(Row::new(vec![
+        Cell::from(""),
+        Cell::from(i18n::tui_facets_footer()).style(theme.status_bar),
+    ]));
+
+    let table = Table::new(visible, [Constraint::Length(6), Constraint::Min(20)])
+        .block(block)
+        .column_spacing(2);
+
+    frame.render_widget(table, area);
+}
+
+/// Calculate a centered rectangle.
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = area.height * percent_y / 100;
+    let x = area.x + (area.width.saturating_sub(width)) / 2;
+    let y = area.y + (area.height.saturating_sub(height)) / 2;
+    Rect::new(x, y, width, height)
+}
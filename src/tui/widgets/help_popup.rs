@@ -170,6 +170,18 @@ fn build_lines<'a>(
                 key: "*",
                 desc: i18n::tui_help_mark_all(),
             },
+            Shortcut {
+                key: "u",
+                desc: i18n::tui_help_toggle_read(),
+            },
+            Shortcut {
+                key: "x",
+                desc: i18n::tui_help_toggle_star(),
+            },
+            Shortcut {
+                key: "T",
+                desc: i18n::tui_help_tag(),
+            },
             Shortcut {
                 key: "s",
                 desc: i18n::tui_help_cycle_sort(),
@@ -206,19 +218,31 @@ fn build_lines<'a>(
                 key: "n / N",
                 desc: i18n::tui_help_next_prev_result(),
             },
+            Shortcut {
+                key: "w",
+                desc: i18n::tui_help_save_search(),
+            },
+            Shortcut {
+                key: "b",
+                desc: i18n::tui_help_facets(),
+            },
         ],
         cols,
         col_width,
         theme,
     );
     lines.push(Line::from(Span::styled(
-        "    from: to: subject: body: label: date: size: has:attachment",
+        "    from: to: subject: body: label: tag: date: size: has:attachment is:unread",
         theme.help_dim,
     )));
     lines.push(Line::from(Span::styled(
         format!("    {}", i18n::tui_help_search_history()),
         theme.help_dim,
     )));
+    lines.push(Line::from(Span::styled(
+        format!("    {}", i18n::tui_help_saved_searches()),
+        theme.help_dim,
+    )));
     lines.push(Line::from(""));
 
     // ── Layout & General ──────────────────────────
@@ -1,6 +1,7 @@
 //! Mail list widget — virtual-scrolling table of messages.
 
 use ratatui::layout::{Constraint, Rect};
+use ratatui::style::Modifier;
 use ratatui::widgets::{Block, Borders, Row, Table};
 use ratatui::Frame;
 use unicode_width::UnicodeWidthStr;
@@ -39,15 +40,17 @@ pub fn render(frame: &mut Frame, app: &mut App, area: Rect) {
 
     // Column widths
     let mark_w = 2u16;
+    let state_w = 2u16;
     let date_w = 17u16;
     let size_w = 8u16;
     let att_w = 2u16;
     let from_w = 20u16.min(inner.width / 4);
-    let fixed = mark_w + date_w + from_w + size_w + att_w + 5; // 5 for padding
+    let fixed = mark_w + state_w + date_w + from_w + size_w + att_w + 6; // 6 for padding
     let subject_w = inner.width.saturating_sub(fixed);
 
     let constraints = [
         Constraint::Length(mark_w),
+        Constraint::Length(state_w),
         Constraint::Length(date_w),
         Constraint::Length(from_w),
         Constraint::Min(subject_w),
@@ -79,6 +82,7 @@ pub fn render(frame: &mut Frame, app: &mut App, area: Rect) {
 
     let header = Row::new(vec![
         " ".to_string(),
+        String::new(),
         h_date,
         h_from,
         h_subject,
@@ -100,6 +104,12 @@ pub fn render(frame: &mut Frame, app: &mut App, area: Rect) {
             let is_marked = app.marked.contains(&entry.offset);
 
             let mark = if is_marked { "*" } else { " " };
+            // Unread dot, then star.
+            let state = format!(
+                "{}{}",
+                if entry.flags.seen { ' ' } else { '\u{25cf}' },
+                if entry.flags.flagged { '\u{2605}' } else { ' ' }
+            );
             let date = entry.date.format("%Y-%m-%d %H:%M").to_string();
 
             let from_display = if entry.from.display_name.is_empty() {
@@ -124,10 +134,14 @@ pub fn render(frame: &mut Frame, app: &mut App, area: Rect) {
             } else {
                 String::new()
             };
+            let tags: String = entry.tags.iter().map(|t| format!("[{t}] ")).collect();
             let avail_subj = (subject_w as usize).saturating_sub(indent.len());
             let subject_truncated = format!(
                 "{indent}{}",
-                truncate_str(&sanitize_line(&entry.subject), avail_subj)
+                truncate_str(
+                    &sanitize_line(&format!("{tags}{}", entry.subject)),
+                    avail_subj
+                )
             );
 
             let size = humansize::format_size(entry.length, humansize::BINARY);
@@ -140,9 +154,15 @@ pub fn render(frame: &mut Frame, app: &mut App, area: Rect) {
             } else {
                 theme.list_normal
             };
+            let style = if entry.flags.seen {
+                style
+            } else {
+                style.add_modifier(Modifier::BOLD)
+            };
 
             Row::new(vec![
                 mark.to_string(),
+                state,
                 date,
                 from_truncated,
                 subject_truncated,
@@ -7,6 +7,7 @@ use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
 use ratatui::Frame;
 
 use crate::i18n;
+use crate::model::address::SharedAddress;
 use crate::tui::app::{App, BodyMatch, PanelFocus};
 use crate::tui::text::sanitize_line;
 use crate::tui::theme::current_theme;
@@ -398,12 +399,7 @@ fn build_lines(
             ]));
 
             if !entry.to.is_empty() {
-                let to_str = entry
-                    .to
-                    .iter()
-                    .map(|a| a.display())
-                    .collect::<Vec<_>>()
-                    .join(", ");
+                let to_str = address_line(&entry.to);
                 lines.push(Line::from(vec![
                     Span::styled(i18n::tui_header_to(), theme.message_header_label),
                     Span::styled(
@@ -414,12 +410,7 @@ fn build_lines(
             }
 
             if !entry.cc.is_empty() {
-                let cc_str = entry
-                    .cc
-                    .iter()
-                    .map(|a| a.display())
-                    .collect::<Vec<_>>()
-                    .join(", ");
+                let cc_str = address_line(&entry.cc);
                 lines.push(Line::from(vec![
                     Span::styled(i18n::tui_header_cc(), theme.message_header_label),
                     Span::styled(
@@ -436,6 +427,22 @@ fn build_lines(
                     theme.message_header_value,
                 ),
             ]));
+
+            if !entry.tags.is_empty() {
+                let tags_str = entry
+                    .tags
+                    .iter()
+                    .map(|t| format!("#{t}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                lines.push(Line::from(vec![
+                    Span::styled(i18n::tui_header_tags(), theme.message_header_label),
+                    Span::styled(
+                        sanitize_line(&tags_str).into_owned(),
+                        theme.message_header_value,
+                    ),
+                ]));
+            }
         }
 
         // Separator
@@ -497,19 +504,34 @@ fn build_lines(
     (lines, body_line_start)
 }
 
+/// Recipients shown in the header block; the rest are counted. The index
+/// keeps whole lists, and a mass mailing would push the body off screen.
+const MAX_SHOWN_RECIPIENTS: usize = 10;
+
+/// `list` joined for the header block, cut at [`MAX_SHOWN_RECIPIENTS`].
+fn address_line(list: &[SharedAddress]) -> String {
+    let mut line = list
+        .iter()
+        .take(MAX_SHOWN_RECIPIENTS)
+        .map(|a| a.display())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if list.len() > MAX_SHOWN_RECIPIENTS {
+        line.push_str(&format!(
+            ", \u{2026} (+{})",
+            list.len() - MAX_SHOWN_RECIPIENTS
+        ));
+    }
+    line
+}
+
 #[cfg(test)]
 mod render_tests {
     use crate::model::mail::MailBody;
+    use crate::testing::temp_fixture;
     use crate::tui::app::{App, LayoutMode, PanelFocus};
     use ratatui::backend::TestBackend;
     use ratatui::Terminal;
-    use std::path::PathBuf;
-
-    fn fixture(name: &str) -> PathBuf {
-        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("tests/fixtures")
-            .join(name)
-    }
 
     /// Flatten each terminal row into a string for substring assertions.
     fn rendered_rows(term: &Terminal<TestBackend>) -> String {
@@ -528,7 +550,8 @@ mod render_tests {
     /// so the wrapped rows piled up and the match stayed off-screen.
     #[test]
     fn in_body_match_is_scrolled_into_view_despite_wrapping() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
 
         // 40 long lines (each wraps into several rows) then a unique needle that
         // fits on one line so it stays contiguous in the rendered buffer.
@@ -568,7 +591,8 @@ mod render_tests {
     /// render pipeline (notably across the 0.30 upgrade).
     #[test]
     fn full_ui_renders_across_layouts_and_popups() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         assert!(!app.entries.is_empty(), "fixture has messages");
 
         for layout in [
@@ -663,7 +687,8 @@ mod render_tests {
     /// (a hit), and toggling a view mode changes the key (a rebuild).
     #[test]
     fn render_populates_and_reuses_cache() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         app.layout = LayoutMode::HorizontalSplit;
         app.focus = PanelFocus::MailView;
 
@@ -693,7 +718,8 @@ mod render_tests {
     /// must render that message, not the previous one's cached lines.
     #[test]
     fn render_cache_invalidates_on_message_change() {
-        let mut app = App::new(fixture("simple.mbox"), true).expect("open fixture");
+        let (_dir, mbox) = temp_fixture("simple.mbox");
+        let mut app = App::new(mbox, true).expect("open fixture");
         assert!(app.visible_indices.len() >= 2, "fixture has >= 2 messages");
         app.layout = LayoutMode::HorizontalSplit;
         app.focus = PanelFocus::MailView;
@@ -3,11 +3,14 @@
 pub mod attachment_popup;
 pub mod body_search_bar;
 pub mod export_popup;
+pub mod facet_popup;
 pub mod header_bar;
 pub mod help_popup;
 pub mod mail_list;
 pub mod mail_view;
+pub mod save_search_bar;
 pub mod search_bar;
 pub mod search_popup;
 pub mod sidebar;
 pub mod status_bar;
+pub mod tag_bar;
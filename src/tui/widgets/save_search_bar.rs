@@ -0,0 +1,29 @@
+//! "Save search as" prompt shown at the bottom while naming the current
+//! search, in place of the status bar.
+
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::Frame;
+
+use crate::i18n;
+use crate::tui::app::App;
+use crate::tui::theme::current_theme;
+
+/// Render the name prompt followed by the query being saved.
+pub fn render(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = current_theme();
+
+    let spans: Vec<Span<'static>> = vec![
+        Span::styled(
+            format!(" {}", i18n::tui_save_search_prompt()),
+            theme.search_prompt,
+        ),
+        Span::styled(app.save_search_name.clone(), theme.message_body),
+        Span::styled("_", theme.search_prompt), // cursor indicator
+        Span::styled(format!("  = {}", app.search_query), theme.help_dim),
+    ];
+
+    let bar = Paragraph::new(Line::from(spans)).style(theme.status_bar);
+    frame.render_widget(bar, area);
+}
@@ -1,4 +1,5 @@
-//! Sidebar widget showing labels/folders for filtering messages.
+//! Sidebar widget showing labels/folders, message states and tags, and
+//! saved searches for filtering messages.
 
 use ratatui::layout::Rect;
 use ratatui::style::Modifier;
@@ -7,7 +8,7 @@ use ratatui::widgets::{Block, Borders, Paragraph};
 use ratatui::Frame;
 
 use crate::i18n;
-use crate::tui::app::{App, PanelFocus};
+use crate::tui::app::{App, PanelFocus, SidebarEntry};
 use crate::tui::theme::current_theme;
 
 /// Render the label sidebar panel.
@@ -36,10 +37,34 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     let max_width = inner.width as usize;
     let mut lines: Vec<Line> = Vec::new();
 
+    // Message states and user tags, listed after the labels
+    let first_state = 1 + app.all_labels.len();
+    let mut states = Vec::new();
+    if app.sidebar_entry(first_state) == Some(SidebarEntry::Unread) {
+        states.push((
+            SidebarEntry::Unread,
+            i18n::tui_unread().to_string(),
+            app.unread_count,
+        ));
+        states.push((
+            SidebarEntry::Starred,
+            i18n::tui_starred_title().to_string(),
+            app.starred_count,
+        ));
+    }
+    for (i, tag) in app.all_tags.iter().enumerate() {
+        states.push((SidebarEntry::Tag(i), format!("#{tag}"), app.tag_counts[i]));
+    }
+
     // "All Messages" entry (index 0 in sidebar)
     let all_count = app.entries.len();
     let is_selected = app.sidebar_selected == 0;
-    let is_active = app.active_label_filter.is_none();
+    let active_saved = app.active_saved_search();
+    let is_active = app.active_label_filter.is_none()
+        && active_saved.is_none()
+        && !states
+            .iter()
+            .any(|(entry, ..)| app.sidebar_query_active(*entry));
     let all_label = truncate_sidebar_entry(i18n::tui_all_messages(), all_count, max_width);
 
     let style = if is_selected && is_focused {
@@ -83,13 +108,68 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
         lines.push(Line::from(Span::styled(entry_text, style)));
     }
 
+    // Message states and tags, after their own separator
+    if !states.is_empty() && !app.all_labels.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "\u{2500}".repeat(max_width.min(40)),
+            theme.border,
+        )));
+    }
+    for (i, (entry, name, count)) in states.iter().enumerate() {
+        let is_selected = app.sidebar_selected == first_state + i;
+        let is_active = app.sidebar_query_active(*entry);
+        let entry_text = truncate_sidebar_entry(name, *count, max_width);
+
+        let style = if is_selected && is_focused {
+            theme.sidebar_selected
+        } else if is_active {
+            theme.sidebar_selected.remove_modifier(Modifier::BOLD)
+        } else {
+            theme.sidebar
+        };
+
+        lines.push(Line::from(Span::styled(entry_text, style)));
+    }
+
+    // Saved searches, after their own separator
+    if !app.saved_searches.is_empty() && (!app.all_labels.is_empty() || !states.is_empty()) {
+        lines.push(Line::from(Span::styled(
+            "\u{2500}".repeat(max_width.min(40)),
+            theme.border,
+        )));
+    }
+    let first_saved = first_state + states.len();
+    for (i, saved) in app.saved_searches.iter().enumerate() {
+        let is_selected = app.sidebar_selected == first_saved + i;
+        let is_active = active_saved == Some(i);
+        let count = saved
+            .count
+            .map_or_else(|| " (?)".to_string(), |n| format!(" ({n})"));
+        let entry_text = pad_sidebar_entry(&format!("@{}", saved.name), &count, max_width);
+
+        let style = if is_selected && is_focused {
+            theme.sidebar_selected
+        } else if is_active {
+            theme.sidebar_selected.remove_modifier(Modifier::BOLD)
+        } else {
+            theme.sidebar
+        };
+
+        lines.push(Line::from(Span::styled(entry_text, style)));
+    }
+
     let paragraph = Paragraph::new(lines);
     frame.render_widget(paragraph, inner);
 }
 
 /// Format a sidebar entry as "Label Name  (123)" truncated to fit.
 fn truncate_sidebar_entry(label: &str, count: usize, max_width: usize) -> String {
-    let count_str = format!(" ({count})");
+    pad_sidebar_entry(label, &format!(" ({count})"), max_width)
+}
+
+/// Format a sidebar entry as `label` padded (or truncated) so that
+/// `count_str` ends at `max_width`.
+fn pad_sidebar_entry(label: &str, count_str: &str, max_width: usize) -> String {
     let avail = max_width.saturating_sub(count_str.len());
     if label.len() <= avail {
         format!(" {label}{}{count_str}", " ".repeat(avail - label.len()))
@@ -6,7 +6,7 @@ use ratatui::widgets::Paragraph;
 use ratatui::Frame;
 
 use crate::i18n;
-use crate::tui::app::{App, PanelFocus};
+use crate::tui::app::{App, PanelFocus, SidebarEntry};
 use crate::tui::theme::current_theme;
 
 /// Version string shown at the right edge of the status bar.
@@ -59,7 +59,10 @@ fn build_hints(app: &App) -> Vec<(&'static str, &'static str)> {
         PanelFocus::Sidebar => {
             hints.push(("j/k", i18n::tui_hint_nav()));
             hints.push(("Enter", i18n::tui_hint_select()));
-            if !app.all_labels.is_empty() {
+            if let Some(SidebarEntry::SavedSearch(_)) = app.sidebar_entry(app.sidebar_selected) {
+                hints.push(("d", i18n::tui_hint_delete()));
+            }
+            if app.has_sidebar_entries() {
                 hints.push(("l", i18n::tui_hint_labels()));
             }
             hints.push(("Esc", i18n::tui_hint_back()));
@@ -78,7 +81,10 @@ fn build_hints(app: &App) -> Vec<(&'static str, &'static str)> {
             hints.push(("e", i18n::tui_hint_export()));
             hints.push(("a", i18n::tui_hint_attach()));
             hints.push(("t", i18n::tui_hint_thread()));
-            if !app.all_labels.is_empty() {
+            if !app.search_query.is_empty() {
+                hints.push(("w", i18n::tui_hint_save()));
+            }
+            if app.has_sidebar_entries() {
                 hints.push(("l", i18n::tui_hint_labels()));
             }
             hints.push(("Tab", i18n::tui_hint_panel()));
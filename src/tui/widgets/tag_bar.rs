@@ -0,0 +1,25 @@
+//! Tag prompt shown at the bottom while typing a tag for the marked (or
+//! selected) messages, in place of the status bar.
+
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::Frame;
+
+use crate::i18n;
+use crate::tui::app::App;
+use crate::tui::theme::current_theme;
+
+/// Render the tag prompt.
+pub fn render(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = current_theme();
+
+    let spans: Vec<Span<'static>> = vec![
+        Span::styled(format!(" {}", i18n::tui_tag_prompt()), theme.search_prompt),
+        Span::styled(app.tag_prompt_input.clone(), theme.message_body),
+        Span::styled("_", theme.search_prompt), // cursor indicator
+    ];
+
+    let bar = Paragraph::new(Line::from(spans)).style(theme.status_bar);
+    frame.render_widget(bar, area);
+}
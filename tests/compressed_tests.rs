@@ -0,0 +1,150 @@
+//! Integration tests for compressed MBOX sources (`.mbox.gz`, `.mbox.zst`,
+//! `.mbox.xz`): indexed in place, with offsets into the decompressed content
+//! and messages read back through the checkpoint table.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use mboxshell::index::builder;
+use mboxshell::store::reader::MboxStore;
+
+fn mbox_content() -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..40 {
+        data.extend_from_slice(
+            format!(
+                "From sender{i}@example.com Mon Jan  1 10:00:00 2024\n\
+                 From: Sender {i} <sender{i}@example.com>\n\
+                 To: archive@example.com\n\
+                 Subject: Message number {i}\n\
+                 Date: Mon, 1 Jan 2024 10:{:02}:00 +0000\n\
+                 Message-ID: <msg{i}@example.com>\n\n",
+                i % 60
+            )
+            .as_bytes(),
+        );
+        let marker = if i == 27 { "needle" } else { "hay" };
+        for line in 0..50 {
+            data.extend_from_slice(
+                format!("Body line {line} of message {i}: {marker}\n").as_bytes(),
+            );
+        }
+        data.push(b'\n');
+    }
+    data
+}
+
+fn gzip_member(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    out.extend(miniz_oxide::deflate::compress_to_vec(data, 6));
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(data);
+    out.extend(crc.finalize().to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Write the plain mailbox and compressed copies of it into `dir`.
+fn write_sources(dir: &Path) -> (PathBuf, Vec<PathBuf>) {
+    let content = mbox_content();
+    let plain = dir.join("mail.mbox");
+    std::fs::write(&plain, &content).unwrap();
+
+    // Two gzip members, as produced by appending to a .gz file.
+    let gz = dir.join("mail.mbox.gz");
+    let (a, b) = content.split_at(content.len() / 3);
+    let mut data = gzip_member(a);
+    data.extend(gzip_member(b));
+    std::fs::write(&gz, data).unwrap();
+
+    let zst = dir.join("mail.mbox.zst");
+    std::fs::write(
+        &zst,
+        ruzstd::encoding::compress_to_vec(
+            content.as_slice(),
+            ruzstd::encoding::CompressionLevel::Fastest,
+        ),
+    )
+    .unwrap();
+
+    let xz = dir.join("mail.mbox.xz");
+    let mut writer = lzma_rust2::XzWriter::new(
+        std::fs::File::create(&xz).unwrap(),
+        lzma_rust2::XzOptions::with_preset(1),
+    )
+    .unwrap();
+    writer.write_all(&content).unwrap();
+    writer.finish().unwrap();
+
+    (plain, vec![gz, zst, xz])
+}
+
+#[test]
+fn test_compressed_index_matches_plain() {
+    let dir = tempfile::tempdir().unwrap();
+    let (plain, compressed) = write_sources(dir.path());
+    let expected = builder::build_index(&plain, true, None).unwrap();
+    assert_eq!(expected.len(), 40);
+
+    for path in &compressed {
+        let entries = builder::build_index(path, true, None).unwrap();
+        assert_eq!(entries.len(), expected.len(), "{}", path.display());
+        for (got, want) in entries.iter().zip(&expected) {
+            assert_eq!(got.offset, want.offset);
+            assert_eq!(got.length, want.length);
+            assert_eq!(got.subject, want.subject);
+        }
+        assert!(builder::checkpoint_path_for(path).exists());
+        assert!(builder::load_index(path).unwrap().is_some());
+    }
+}
+
+#[test]
+fn test_compressed_store_reads_messages_in_any_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let (plain, compressed) = write_sources(dir.path());
+    let expected = builder::build_index(&plain, true, None).unwrap();
+    let mut plain_store = MboxStore::open(&plain).unwrap();
+
+    for path in &compressed {
+        let entries = builder::build_index(path, true, None).unwrap();
+        let mut store = MboxStore::open(path).unwrap();
+        for i in [31, 2, 39, 0, 17, 18] {
+            assert_eq!(
+                store.get_raw_message(&entries[i]).unwrap(),
+                plain_store.get_raw_message(&expected[i]).unwrap(),
+                "message {i} of {}",
+                path.display()
+            );
+        }
+        let body = store.get_message(&entries[5]).unwrap();
+        assert!(body.text.as_deref().unwrap_or("").contains("of message 5"));
+    }
+}
+
+#[test]
+fn test_compressed_body_search() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, compressed) = write_sources(dir.path());
+    for path in &compressed {
+        let entries = builder::build_index(path, true, None).unwrap();
+        let (_, results) = mboxshell::search::execute(path, &entries, "body:needle", None).unwrap();
+        assert_eq!(results, vec![27], "{}", path.display());
+    }
+}
+
+#[test]
+fn test_missing_checkpoints_invalidate_compressed_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, compressed) = write_sources(dir.path());
+    let gz = &compressed[0];
+    builder::build_index(gz, true, None).unwrap();
+    assert!(builder::load_index(gz).unwrap().is_some());
+
+    // Without the decompressed size from the checkpoint file the index
+    // offsets cannot be bounds-checked, so the index is rebuilt.
+    std::fs::remove_file(builder::checkpoint_path_for(gz)).unwrap();
+    assert!(builder::load_index(gz).unwrap().is_none());
+    assert_eq!(builder::build_index(gz, false, None).unwrap().len(), 40);
+    assert!(builder::load_checkpoints(gz).is_some());
+}
@@ -0,0 +1,64 @@
+//! Integration tests for folders of loose `.eml` files opened as one virtual
+//! mailbox (evidence folders, exports from other clients).
+
+use std::path::Path;
+
+use mboxshell::index::builder;
+use mboxshell::store::reader::MboxStore;
+
+fn write_eml(root: &Path, rel: &str, subject: &str, body: &str) {
+    let path = root.join(rel);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let data = format!(
+        "From: Carol <carol@example.com>\r\nTo: dave@example.com\r\nSubject: {subject}\r\n\
+         Date: Tue, 2 Jan 2024 09:00:00 +0000\r\nMessage-ID: <{subject}@example.com>\r\n\r\n{body}\r\n"
+    );
+    std::fs::write(path, data).unwrap();
+}
+
+fn make_folder() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("evidence");
+    write_eml(&root, "001.eml", "invoice", "payment due");
+    write_eml(
+        &root,
+        "custodian-a/002.eml",
+        "contract",
+        "signed copy attached",
+    );
+    write_eml(&root, "custodian-b/003.EML", "minutes", "meeting notes");
+    std::fs::write(root.join("README.txt"), "not a message").unwrap();
+    dir
+}
+
+#[test]
+fn test_eml_folder_index_is_persisted_and_reloaded() {
+    let dir = make_folder();
+    let root = dir.path().join("evidence");
+
+    let entries = builder::build_index(&root, true, None).unwrap();
+    let subjects: Vec<&str> = entries.iter().map(|e| e.subject.as_str()).collect();
+    assert_eq!(subjects, vec!["invoice", "contract", "minutes"]);
+    assert_eq!(entries[1].labels, vec!["custodian-a".to_string()]);
+
+    assert!(builder::load_index(&root).unwrap().is_some());
+
+    // A new file invalidates the persisted index.
+    write_eml(&root, "004.eml", "late", "arrived later");
+    assert!(builder::load_index(&root).unwrap().is_none());
+    assert_eq!(builder::build_index(&root, false, None).unwrap().len(), 4);
+}
+
+#[test]
+fn test_eml_folder_store_and_search() {
+    let dir = make_folder();
+    let root = dir.path().join("evidence");
+    let entries = builder::build_index(&root, true, None).unwrap();
+
+    let mut store = MboxStore::open(&root).unwrap();
+    let body = store.get_message(&entries[2]).unwrap();
+    assert!(body.text.as_deref().unwrap_or("").contains("meeting notes"));
+
+    let (_, results) = mboxshell::search::execute(&root, &entries, "body:signed", None).unwrap();
+    assert_eq!(results, vec![1]);
+}
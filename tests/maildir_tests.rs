@@ -0,0 +1,121 @@
+//! Integration tests for Maildir sources: a `cur/`/`new/`/`tmp/` tree opened
+//! as one mailbox by the index builder, the store, search and export.
+
+use std::path::Path;
+
+use mboxshell::export::mbox::export_mbox;
+use mboxshell::index::builder;
+use mboxshell::store::reader::MboxStore;
+
+/// `rel` as written on disk: Windows does not allow `:` in file names, so the
+/// tests use the `!` form of the Maildir info separator there.
+fn on_disk(rel: &str) -> String {
+    if cfg!(windows) {
+        rel.replace(':', "!")
+    } else {
+        rel.to_string()
+    }
+}
+
+fn write_message(root: &Path, rel: &str, subject: &str, body: &str) {
+    let path = root.join(on_disk(rel));
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let data = format!(
+        "From: Alice <alice@example.com>\r\nTo: bob@example.com\r\nSubject: {subject}\r\n\
+         Date: Mon, 1 Jan 2024 10:00:00 +0000\r\nMessage-ID: <{subject}@example.com>\r\n\r\n{body}\r\n"
+    );
+    std::fs::write(path, data).unwrap();
+}
+
+fn make_maildir() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("Maildir");
+    for sub in ["cur", "new", "tmp"] {
+        std::fs::create_dir_all(root.join(sub)).unwrap();
+    }
+    write_message(&root, "cur/1704103200.M1P1.host:2,S", "alpha", "first body");
+    write_message(
+        &root,
+        "cur/1704103300.M2P1.host:2,RS",
+        "beta",
+        "needle here",
+    );
+    write_message(&root, "new/1704103400.M3P1.host", "gamma", "third body");
+    dir
+}
+
+#[test]
+fn test_maildir_index_is_persisted_and_reloaded() {
+    let dir = make_maildir();
+    let root = dir.path().join("Maildir");
+
+    let entries = builder::build_index(&root, true, None).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(entries[1].flags.replied);
+
+    let loaded = builder::load_index(&root).unwrap().expect("index reloads");
+    assert_eq!(loaded.len(), 3);
+    assert_eq!(
+        builder::mailbox_size(&root).unwrap(),
+        entries.iter().map(|e| e.length).sum::<u64>()
+    );
+}
+
+#[test]
+fn test_maildir_flag_rename_invalidates_index() {
+    let dir = make_maildir();
+    let root = dir.path().join("Maildir");
+    builder::build_index(&root, true, None).unwrap();
+
+    // Another client marks the new message as seen.
+    std::fs::rename(
+        root.join("new/1704103400.M3P1.host"),
+        root.join(on_disk("cur/1704103400.M3P1.host:2,S")),
+    )
+    .unwrap();
+    assert!(builder::load_index(&root).unwrap().is_none());
+
+    let entries = builder::build_index(&root, false, None).unwrap();
+    assert!(entries.iter().all(|e| e.flags.seen));
+}
+
+#[test]
+fn test_maildir_store_reads_renamed_message() {
+    let dir = make_maildir();
+    let root = dir.path().join("Maildir");
+    let entries = builder::build_index(&root, true, None).unwrap();
+
+    // Renamed after indexing: the store still finds it by its unique name.
+    std::fs::rename(
+        root.join(on_disk("cur/1704103300.M2P1.host:2,RS")),
+        root.join(on_disk("cur/1704103300.M2P1.host:2,FRS")),
+    )
+    .unwrap();
+    let mut store = MboxStore::open(&root).unwrap();
+    let body = store.get_message(&entries[1]).unwrap();
+    assert!(body.text.as_deref().unwrap_or("").contains("needle here"));
+}
+
+#[test]
+fn test_maildir_search_and_export() {
+    let dir = make_maildir();
+    let root = dir.path().join("Maildir");
+    let entries = builder::build_index(&root, true, None).unwrap();
+
+    let (_, results) = mboxshell::search::execute(&root, &entries, "needle", None).unwrap();
+    assert_eq!(results, vec![1]);
+
+    let out = dir.path().join("out.mbox");
+    let mut store = MboxStore::open(&root).unwrap();
+    let selected: Vec<&_> = entries.iter().collect();
+    let dialect = store.dialect();
+    assert_eq!(
+        export_mbox(&mut store, &selected, &out, dialect, &|_, _| {}).unwrap(),
+        3
+    );
+
+    // The exported file is a regular MBOX with the same messages.
+    let reexported = builder::build_index(&out, true, None).unwrap();
+    let subjects: Vec<&str> = reexported.iter().map(|e| e.subject.as_str()).collect();
+    assert_eq!(subjects, vec!["alpha", "beta", "gamma"]);
+}
@@ -14,6 +14,15 @@ fn fixture(name: &str) -> std::path::PathBuf {
         .join(name)
 }
 
+/// `testing::temp_fixture` of the unit tests, which integration tests
+/// cannot reach: a copy of the fixture `name` in a temporary directory.
+fn temp_fixture(name: &str) -> (tempfile::TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(name);
+    std::fs::copy(fixture(name), &path).unwrap();
+    (dir, path)
+}
+
 // ─── Test 1: Parse simple.mbox → exactly 5 messages ─────────────────
 
 #[test]
@@ -36,7 +45,8 @@ fn test_parse_simple_mbox_count() {
 
 #[test]
 fn test_parse_simple_mbox_first_message() {
-    let entries = builder::build_index(&fixture("simple.mbox"), true, None).unwrap();
+    let (_dir, mbox_path) = temp_fixture("simple.mbox");
+    let entries = builder::build_index(&mbox_path, true, None).unwrap();
     assert!(!entries.is_empty());
     let first = &entries[0];
     assert_eq!(first.subject, "Hello World");
@@ -49,7 +59,8 @@ fn test_parse_simple_mbox_first_message() {
 
 #[test]
 fn test_parse_encoded_words() {
-    let entries = builder::build_index(&fixture("simple.mbox"), true, None).unwrap();
+    let (_dir, mbox_path) = temp_fixture("simple.mbox");
+    let entries = builder::build_index(&mbox_path, true, None).unwrap();
     assert!(entries.len() >= 3);
     let third = &entries[2];
     // From: =?UTF-8?B?Sm9zw6kgR2FyY8Ota2E=?= → "José Garcíka"
@@ -77,7 +88,8 @@ fn test_parse_encoded_words() {
 fn test_from_escaping_in_body() {
     // The fourth message has ">From " in its body.
     // This should NOT split it into two messages.
-    let entries = builder::build_index(&fixture("simple.mbox"), true, None).unwrap();
+    let (_dir, mbox_path) = temp_fixture("simple.mbox");
+    let entries = builder::build_index(&mbox_path, true, None).unwrap();
     assert_eq!(
         entries.len(),
         5,
@@ -88,7 +100,7 @@ fn test_from_escaping_in_body() {
     assert_eq!(fourth.subject, "Message with From in body");
 
     // Verify the body contains the >From line
-    let mut store = MboxStore::open(fixture("simple.mbox")).unwrap();
+    let mut store = MboxStore::open(&mbox_path).unwrap();
     let body = store.get_message(fourth).unwrap();
     let text = body.text.as_deref().unwrap_or("");
     assert!(
@@ -189,7 +201,8 @@ fn test_index_invalidation() {
 
 #[test]
 fn test_charset_decoding() {
-    let entries = builder::build_index(&fixture("encoded_words.mbox"), true, None).unwrap();
+    let (_dir, mbox_path) = temp_fixture("encoded_words.mbox");
+    let entries = builder::build_index(&mbox_path, true, None).unwrap();
     assert_eq!(entries.len(), 3);
 
     // ISO-8859-1: François, Résumé du projet
@@ -223,10 +236,11 @@ fn test_charset_decoding() {
 
 #[test]
 fn test_read_message_by_offset() {
-    let entries = builder::build_index(&fixture("simple.mbox"), true, None).unwrap();
+    let (_dir, mbox_path) = temp_fixture("simple.mbox");
+    let entries = builder::build_index(&mbox_path, true, None).unwrap();
     assert!(entries.len() >= 3);
 
-    let mut store = MboxStore::open(fixture("simple.mbox")).unwrap();
+    let mut store = MboxStore::open(&mbox_path).unwrap();
     let body = store.get_message(&entries[2]).unwrap();
     let text = body.text.as_deref().unwrap_or("");
     assert!(
@@ -272,7 +286,8 @@ fn test_date_parsing_formats() {
 
 #[test]
 fn test_threading_headers() {
-    let entries = builder::build_index(&fixture("simple.mbox"), true, None).unwrap();
+    let (_dir, mbox_path) = temp_fixture("simple.mbox");
+    let entries = builder::build_index(&mbox_path, true, None).unwrap();
     assert!(entries.len() >= 2);
 
     let second = &entries[1];
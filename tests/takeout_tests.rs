@@ -0,0 +1,232 @@
+//! Integration tests for Google Takeout archives (`.zip`, `.tgz`, `.tar`)
+//! opened in place: every mailbox member is indexed, labelled with its
+//! display name, and read back without extracting the archive.
+
+use std::path::{Path, PathBuf};
+
+use mboxshell::index::builder;
+use mboxshell::store::reader::MboxStore;
+
+/// A small mailbox whose messages are tagged with `tag`.
+fn mailbox(tag: &str, count: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..count {
+        data.extend_from_slice(
+            format!(
+                "From {tag}{i}@example.com Mon Jan  1 10:00:00 2024\n\
+                 From: {tag} {i} <{tag}{i}@example.com>\n\
+                 Subject: {tag} message {i}\n\
+                 Date: Mon, 1 Jan 2024 10:{i:02}:00 +0000\n\
+                 Message-ID: <{tag}{i}@example.com>\n\n"
+            )
+            .as_bytes(),
+        );
+        for line in 0..200 {
+            data.extend_from_slice(format!("Line {line} of {tag} message {i}\n").as_bytes());
+        }
+        if tag == "group" && i == 3 {
+            data.extend_from_slice(b"the needle is here\n");
+        }
+        data.push(b'\n');
+    }
+    data
+}
+
+const MAIL: &str = "Takeout/Mail/All mail Including Spam and Trash.mbox";
+const GROUP: &str = "Takeout/Groups/team@googlegroups.com/topics.mbox";
+
+/// The archive members: two mailboxes with a non-mailbox file between them.
+fn members() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        (MAIL, mailbox("mail", 30)),
+        (
+            "Takeout/Drive/notes.txt",
+            b"From nobody, not a mailbox\n".repeat(500),
+        ),
+        (GROUP, mailbox("group", 12)),
+    ]
+}
+
+/// A zip archive: the first member deflated, the others stored.
+fn zip_archive(members: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (i, (name, data)) in members.iter().enumerate() {
+        let deflate = i == 0;
+        let body = if deflate {
+            miniz_oxide::deflate::compress_to_vec(data, 6)
+        } else {
+            data.clone()
+        };
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(data);
+        let mut fields = Vec::new();
+        fields.extend(if deflate { [8u8, 0] } else { [0, 0] });
+        fields.extend([0; 4]);
+        fields.extend(crc.finalize().to_le_bytes());
+        fields.extend((body.len() as u32).to_le_bytes());
+        fields.extend((data.len() as u32).to_le_bytes());
+        fields.extend((name.len() as u16).to_le_bytes());
+
+        central.extend([0x50, 0x4b, 1, 2, 20, 0, 20, 0, 0, 8]);
+        central.extend(&fields);
+        central.extend([0; 12]);
+        central.extend((out.len() as u32).to_le_bytes());
+        central.extend(name.as_bytes());
+
+        out.extend([0x50, 0x4b, 3, 4, 20, 0, 0, 8]);
+        out.extend(&fields);
+        out.extend([0, 0]);
+        out.extend(name.as_bytes());
+        out.extend(body);
+    }
+    let cd_offset = out.len() as u32;
+    out.extend(&central);
+    out.extend([0x50, 0x4b, 5, 6, 0, 0, 0, 0]);
+    out.extend((members.len() as u16).to_le_bytes());
+    out.extend((members.len() as u16).to_le_bytes());
+    out.extend((central.len() as u32).to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out.extend([0, 0]);
+    out
+}
+
+/// A ustar header block.
+fn tar_header(name: &str, size: usize, kind: u8) -> Vec<u8> {
+    let mut block = vec![0u8; 512];
+    block[..name.len()].copy_from_slice(name.as_bytes());
+    block[100..107].copy_from_slice(b"0000644");
+    block[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+    block[156] = kind;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+    block[148..156].fill(b' ');
+    let sum: u32 = block.iter().map(|&b| u32::from(b)).sum();
+    block[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+    block
+}
+
+/// A tar archive with a directory entry for `Takeout/`.
+fn tar_archive(members: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut out = tar_header("Takeout/", 0, b'5');
+    for (name, data) in members {
+        out.extend(tar_header(name, data.len(), b'0'));
+        out.extend(data);
+        out.resize(out.len().div_ceil(512) * 512, 0);
+    }
+    out.extend([0u8; 1024]);
+    out
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    out.extend(miniz_oxide::deflate::compress_to_vec(data, 6));
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(data);
+    out.extend(crc.finalize().to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+fn write_archives(dir: &Path) -> Vec<PathBuf> {
+    let members = members();
+    let tar = tar_archive(&members);
+    let archives = [
+        ("takeout.zip", zip_archive(&members)),
+        ("takeout.tgz", gzip(&tar)),
+        ("takeout.tar", tar),
+    ];
+    archives
+        .into_iter()
+        .map(|(name, data)| {
+            let path = dir.join(name);
+            std::fs::write(&path, data).unwrap();
+            path
+        })
+        .collect()
+}
+
+#[test]
+fn test_archive_mailboxes_are_indexed_and_labelled() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in write_archives(dir.path()) {
+        let entries = builder::build_index(&path, true, None).unwrap();
+        assert_eq!(entries.len(), 42, "{}", path.display());
+
+        let mail = &entries[0];
+        assert_eq!(mail.subject, "mail message 0");
+        assert_eq!(mail.file.as_deref(), Some(MAIL));
+        assert!(mail
+            .labels
+            .contains(&"All mail Including Spam and Trash.mbox".to_string()));
+
+        let group = &entries[30];
+        assert_eq!(group.subject, "group message 0");
+        assert_eq!(group.file.as_deref(), Some(GROUP));
+        assert!(group.labels.contains(&"team@googlegroups.com".to_string()));
+
+        let sequences: Vec<u64> = entries.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, (0..42).collect::<Vec<_>>());
+
+        // The saved index is valid for the archive as it is.
+        let loaded = builder::load_index(&path).unwrap().expect("index reloads");
+        assert_eq!(loaded.len(), 42);
+    }
+}
+
+#[test]
+fn test_archive_messages_read_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let mail = mailbox("mail", 30);
+    let group = mailbox("group", 12);
+    for path in write_archives(dir.path()) {
+        let entries = builder::build_index(&path, true, None).unwrap();
+        let mut store = MboxStore::open(&path).unwrap();
+        for i in [35, 2, 41, 29, 30, 0] {
+            let entry = &entries[i];
+            let raw = store.get_raw_message(entry).unwrap();
+            let (source, first) = if i < 30 { (&mail, 0) } else { (&group, 30) };
+            // Messages are laid out back to back in each source mailbox.
+            let start: u64 = entries[first..i].iter().map(|e| e.length).sum();
+            let start = start as usize;
+            assert_eq!(
+                raw,
+                &source[start..start + entry.length as usize],
+                "message {i} of {}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_archive_body_search() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in write_archives(dir.path()) {
+        let entries = builder::build_index(&path, true, None).unwrap();
+        let (_, results) =
+            mboxshell::search::execute(&path, &entries, "body:needle", None).unwrap();
+        assert_eq!(results, vec![33], "{}", path.display());
+    }
+}
+
+#[test]
+fn test_plain_mbox_is_not_an_archive() {
+    use mboxshell::parser::archive;
+
+    let dir = tempfile::tempdir().unwrap();
+    let plain = dir.path().join("mail.mbox");
+    std::fs::write(&plain, mailbox("mail", 2)).unwrap();
+    assert_eq!(archive::detect(&plain).unwrap(), None);
+    let gz = dir.path().join("mail.mbox.gz");
+    std::fs::write(&gz, gzip(&mailbox("mail", 2))).unwrap();
+    assert_eq!(archive::detect(&gz).unwrap(), None);
+
+    for (path, kind) in write_archives(dir.path()).iter().zip([
+        archive::ArchiveKind::Zip,
+        archive::ArchiveKind::Tar,
+        archive::ArchiveKind::Tar,
+    ]) {
+        assert_eq!(archive::detect(path).unwrap(), Some(kind));
+    }
+}